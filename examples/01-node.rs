@@ -15,7 +15,7 @@
  *   limitations under the License.
  */
 
-use colored::Colorize;
+use hello_ockam::{print_title, print_topology, NodeRole, Topology};
 use ockam::{node, Context, Result};
 
 /// From: <https://docs.ockam.io/reference/libraries/rust/nodes>
@@ -25,30 +25,16 @@ use ockam::{node, Context, Result};
 async fn main(ctx: Context) -> Result<()> {
     print_title("Run a node & stop it right away");
 
-    println!("{}", HELP_TEXT.green());
-
     // Create a node with default implementations
     let mut node = node(ctx);
 
+    // Draw what is running on the node.
+    let mut topology = Topology::new();
+    topology
+        .node("Node 1", NodeRole::Initiator)
+        .context(node.context());
+    print_topology(node.context(), &topology).await?;
+
     // Stop the node as soon as it starts.
     node.stop().await
 }
-
-fn print_title(title: &str) {
-    let padding = "=".repeat(title.len());
-    println!("{}", padding.black().on_bright_white());
-    println!("{}", title.black().on_bright_white());
-    println!("{}", padding.black().on_bright_white());
-}
-
-#[rustfmt::skip]
-const HELP_TEXT: &str =r#"
-┌──────────────────────┐
-│  Node 1              │
-├──────────────────────┤
-│  ┌────────────────┐  │
-│  │ Address:       │  │
-│  │ 'app'          │  │
-│  └────────────────┘  │
-└──────────────────────┘
-"#;
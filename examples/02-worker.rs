@@ -16,7 +16,7 @@
  */
 
 use colored::Colorize;
-use hello_ockam::Echoer;
+use hello_ockam::{print_title, print_topology, Echoer, NodeRole, Topology};
 use ockam::{node, Context, Result};
 
 /// From: <https://docs.ockam.io/reference/libraries/rust/nodes#echoer-worker>
@@ -29,14 +29,20 @@ async fn main(ctx: Context) -> Result<()> {
         "Run a node with default context 'app' & 'echoer' worker → send a message → stop the node",
     );
 
-    println!("{}", HELP_TEXT.green());
-
     // Create a node with default implementations
     let mut node = node(ctx);
 
     // Start a worker, of type Echoer, at address "echoer"
     node.start_worker("echoer", Echoer).await?;
 
+    // Draw what is running on the node.
+    let mut topology = Topology::new();
+    topology
+        .node("Node 1", NodeRole::Initiator)
+        .context(node.context())
+        .worker("echoer");
+    print_topology(node.context(), &topology).await?;
+
     // Send a message to the worker at address "echoer".
     let msg = "Hello Ockam!";
     let output_msg = format!("App Sending: '{0}'", msg.red());
@@ -51,26 +57,3 @@ async fn main(ctx: Context) -> Result<()> {
     // Stop all workers, stop the node, cleanup and return.
     node.stop().await
 }
-
-fn print_title(title: &str) {
-    let padding = "=".repeat(title.len());
-    println!("{}", padding.black().on_bright_white());
-    println!("{}", title.black().on_bright_white());
-    println!("{}", padding.black().on_bright_white());
-}
-
-#[rustfmt::skip]
-const HELP_TEXT: &str =r#"
-┌──────────────────────┐
-│  Node 1              │
-├──────────────────────┤
-│  ┌────────────────┐  │
-│  │ Address:       │  │
-│  │ 'app'          │  │
-│  └─┬────────────▲─┘  │
-│  ┌─▼────────────┴─┐  │
-│  │ Address:       │  │
-│  │ 'echoer'       │  │
-│  └────────────────┘  │
-└──────────────────────┘
-"#;
@@ -16,7 +16,7 @@
  */
 
 use colored::Colorize;
-use hello_ockam::{Echoer, Hopper};
+use hello_ockam::{print_title, print_topology, Echoer, Hopper, NodeRole, Topology};
 use ockam::{node, route, Context, Result};
 
 /// From: <https://docs.ockam.io/reference/libraries/rust/routing#routing-over-many-hops>
@@ -26,8 +26,6 @@ use ockam::{node, route, Context, Result};
 async fn main(ctx: Context) -> Result<()> {
     print_title("Run a node w/ 'app', 'echoer' and 'hopper1', 'hopper2', 'hopper3' workers → send a message over 3 hops -> stop the node");
 
-    println!("{}", HELP_TEXT.green());
-
     // Create a node with default implementations
     let mut node = node(ctx);
 
@@ -39,6 +37,17 @@ async fn main(ctx: Context) -> Result<()> {
     node.start_worker("hopper2", Hopper).await?;
     node.start_worker("hopper3", Hopper).await?;
 
+    // Draw what is running on the node.
+    let mut topology = Topology::new();
+    topology
+        .node("Node 1", NodeRole::Initiator)
+        .context(node.context())
+        .worker("hopper1")
+        .worker("hopper2")
+        .worker("hopper3")
+        .worker("echoer");
+    print_topology(node.context(), &topology).await?;
+
     // Send a message to the echoer worker via the "hopper1", "hopper2", and "hopper3" workers
     let route = route!["hopper1", "hopper2", "hopper3", "echoer"];
     let route_msg = format!("{:?}", route);
@@ -59,30 +68,3 @@ async fn main(ctx: Context) -> Result<()> {
     // Stop all workers, stop the node, cleanup and return.
     node.stop().await
 }
-
-fn print_title(title: &str) {
-    let padding = "=".repeat(title.len());
-    println!("{}", padding.black().on_bright_white());
-    println!("{}", title.black().on_bright_white());
-    println!("{}", padding.black().on_bright_white());
-}
-
-#[rustfmt::skip]
-const HELP_TEXT: &str =r#"
-┌───────────────────────┐
-│  Node 1               │
-├───────────────────────┤
-│  ┌────────────────┐   │
-│  │ Address:       │   │
-│  │ 'app'          │   │
-│  └─┬────────────▲─┘   │
-│  ┌─▼────────────┴─┐   │
-│  │ Address:       │   │
-│  │ 'hopper1..3'   │x3 │
-│  └─┬────────────▲─┘   │
-│  ┌─▼────────────┴─┐   │
-│  │ Address:       │   │
-│  │ 'echoer'       │   │
-│  └────────────────┘   │
-└───────────────────────┘
-"#;
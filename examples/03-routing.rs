@@ -16,7 +16,7 @@
  */
 
 use colored::Colorize;
-use hello_ockam::{Echoer, Hopper};
+use hello_ockam::{print_title, print_topology, Echoer, Hopper, NodeRole, Topology};
 use ockam::{node, route, Context, Result};
 
 /// From: <https://docs.ockam.io/reference/libraries/rust/routing#app-worker>
@@ -26,8 +26,6 @@ use ockam::{node, route, Context, Result};
 async fn main(ctx: Context) -> Result<()> {
     print_title("Run a node w/ 'app', 'echoer' and 'hopper1' workers → send a message over a hop -> stop the node");
 
-    println!("{}", HELP_TEXT.green());
-
     // Create a node with default implementations
     let mut node = node(ctx);
 
@@ -37,6 +35,15 @@ async fn main(ctx: Context) -> Result<()> {
     // Start a worker, of type Hopper, at address "hopper1"
     node.start_worker("hopper1", Hopper).await?;
 
+    // Draw what is running on the node.
+    let mut topology = Topology::new();
+    topology
+        .node("Node 1", NodeRole::Initiator)
+        .context(node.context())
+        .worker("hopper1")
+        .worker("echoer");
+    print_topology(node.context(), &topology).await?;
+
     // Send a message to the worker at address "echoer",
     // via the worker at address "hopper1"
     let route = route!["hopper1", "echoer"];
@@ -58,30 +65,3 @@ async fn main(ctx: Context) -> Result<()> {
     // Stop all workers, stop the node, cleanup and return.
     node.stop().await
 }
-
-fn print_title(title: &str) {
-    let padding = "=".repeat(title.len());
-    println!("{}", padding.black().on_bright_white());
-    println!("{}", title.black().on_bright_white());
-    println!("{}", padding.black().on_bright_white());
-}
-
-#[rustfmt::skip]
-const HELP_TEXT: &str =r#"
-┌──────────────────────┐
-│  Node 1              │
-├──────────────────────┤
-│  ┌────────────────┐  │
-│  │ Address:       │  │
-│  │ 'app'          │  │
-│  └─┬────────────▲─┘  │
-│  ┌─▼────────────┴─┐  │
-│  │ Address:       │  │
-│  │ 'hopper1'      │  │
-│  └─┬────────────▲─┘  │
-│  ┌─▼────────────┴─┐  │
-│  │ Address:       │  │
-│  │ 'echoer'       │  │
-│  └────────────────┘  │
-└──────────────────────┘
-"#;
@@ -16,7 +16,10 @@
  */
 
 use colored::Colorize;
-use hello_ockam::Echoer;
+use hello_ockam::{
+    print_title, print_topology, Echoer, NodeResult, NodeRole, NodeScope, Presenter, ResultExt,
+    Topology,
+};
 use ockam::{
     node, route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
     TcpTransportExtension,
@@ -25,13 +28,16 @@ use ockam::{
 /// From: <https://docs.ockam.io/reference/libraries/rust/routing#transport>
 #[ockam::node]
 async fn main(ctx: Context) -> Result<()> {
-    println!("{}", HELP_TEXT.green());
-
     let ctx_clone = ctx.async_try_clone().await?;
+    let mut topology = Topology::new();
 
-    let mut node_responder = create_responder_node(ctx).await.unwrap();
+    let mut node_responder = create_responder_node(ctx, &mut topology)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
 
-    create_initiator_node(ctx_clone).await.unwrap();
+    create_initiator_node(ctx_clone, &mut topology)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
 
     node_responder.stop().await.ok();
 
@@ -43,49 +49,84 @@ async fn main(ctx: Context) -> Result<()> {
 /// examples/04-routing-over-transport-responder.rs
 /// This node starts a tcp listener and an echoer worker.
 /// It then runs forever waiting for messages.
-async fn create_responder_node(ctx: Context) -> Result<ockam::Node> {
+async fn create_responder_node(ctx: Context, topology: &mut Topology) -> NodeResult<ockam::Node> {
     print_title(
         "Create node_responder that runs tcp listener on 4000 and echoer worker → wait for messages until stopped",
     );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
 
     // Create a node with default implementations
     let node = node(ctx);
 
     // Initialize the TCP Transport
-    let tcp_transport = node.create_tcp_transport().await?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
 
     // Create an echoer worker
-    node.start_worker("echoer", Echoer).await?;
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
 
     // Create a TCP listener and wait for incoming connections.
     let listener = tcp_transport
         .listen("127.0.0.1:4000", TcpListenerOptions::new())
-        .await?;
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
 
     // Allow access to the Echoer via TCP connections from the TCP listener
     node.flow_controls()
         .add_consumer("echoer", listener.flow_control_id());
+    presenter.println("🎙️ echoer is reachable via tcp listener on 127.0.0.1:4000");
+
+    topology
+        .node("node_responder", NodeRole::Responder)
+        .tcp_transport(&tcp_transport)
+        .worker("echoer");
 
     Ok(node)
 }
 
 /// examples/04-routing-over-transport-initiator.rs
 /// This node routes a message, to a worker on a different node, over the tcp transport.
-async fn create_initiator_node(ctx: Context) -> Result<()> {
+async fn create_initiator_node(ctx: Context, topology: &mut Topology) -> NodeResult<()> {
     print_title(
         "Create node_initiator that routes a message, over the TCP transport, to a worker on a different node → stop",
     );
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
 
     // Create a node with default implementations
     let mut node = node(ctx);
 
     // Initialize the TCP Transport.
-    let tcp_transport = node.create_tcp_transport().await?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
 
     // Create a TCP connection to a different node.
     let connection_to_responder = tcp_transport
         .connect("localhost:4000", TcpConnectionOptions::new())
-        .await?;
+        .await
+        .during(&scope, "connect to localhost:4000")?;
+
+    // The connection is listed in the transport's registry once it has started.
+    node.context()
+        .wait_for(connection_to_responder.sender_address().clone())
+        .await
+        .during(&scope, "wait for the connection to localhost:4000")?;
+
+    // Draw the nodes that are now running.
+    topology
+        .node("node_initiator", NodeRole::Initiator)
+        .context(node.context())
+        .tcp_transport(&tcp_transport);
+    print_topology(node.context(), topology)
+        .await
+        .during(&scope, "draw the topology")?;
 
     // Send a message to the "echoer" worker on a different node, over a tcp transport.
     // Wait to receive a reply and print it.
@@ -94,47 +135,22 @@ async fn create_initiator_node(ctx: Context) -> Result<()> {
     let route_msg = format!("{:?}", route);
     let reply = node
         .send_and_receive::<String>(route, msg.to_string())
-        .await?;
+        .await
+        .during(
+            &scope,
+            format!("send '{}' and receive a reply over {}", msg, route_msg),
+        )?;
 
     let lines = [
-        "node_initiator →".to_string(),
+        "→".to_string(),
         format!("    sending: {}", msg.green()),
         format!("    over route: '{}'", route_msg.blue()),
         format!("    and received: '{}'", reply.purple()), // Should print "👈 echo back:  Hello Ockam!"
     ];
-    lines
-        .iter()
-        .for_each(|line| println!("{}", line.black().on_white()));
+    presenter.println(lines.join("\n"));
 
     // Stop all workers, stop the node, cleanup and return.
-    node.stop().await?;
+    node.stop().await.during(&scope, "stop the node")?;
 
     Ok(())
 }
-
-fn print_title(title: &str) {
-    let padding = "=".repeat(title.len());
-    println!("{}", padding.black().on_bright_white());
-    println!("{}", title.black().on_bright_white());
-    println!("{}", padding.black().on_bright_white());
-}
-
-#[rustfmt::skip]
-const HELP_TEXT: &str =r#"
-┌──────────────────────┐
-│node_initiator        │
-├──────────────────────┤
-│ ┌──────────────────┐ │
-│ │Address:          │ │  ┌──────────────────────┐
-│ │'app'             │ │  │node_responder        │
-│ └──┬────────────▲──┘ │  ├──────────────────────┤
-│ ┌──▼────────────┴──┐ │  │ ┌──────────────────┐ │
-│ │TCP transport     └─┼──┼─►TCP transport     │ │
-│ │connect to 4000   ◄─┼──┼─┐listening on 4000 │ │
-│ └──────────────────┘ │  │ └──┬────────────▲──┘ │
-└──────────────────────┘  │ ┌──▼────────────┴──┐ │
-                          │ │Address:          │ │
-                          │ │'echoer'          │ │
-                          │ └──────────────────┘ │
-                          └──────────────────────┘
-"#;
@@ -16,69 +16,111 @@
  */
 
 use colored::Colorize;
-use hello_ockam::{Echoer, Forwarder};
+use hello_ockam::{
+    emit_event, export_diagrams_from_env, print_title, print_topology, wait_for_signal, Dashboard,
+    Echoer, Event, FlowRecorder, Forwarder, NodeResult, NodeRole, NodeScope, Presenter, ResultExt,
+    ShutdownCoordinator, Topology,
+};
 use ockam::{
     node, route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
     TcpTransportExtension,
 };
-use tokio::spawn;
 
 /// From: <https://docs.ockam.io/reference/libraries/rust/routing#routing-over-two-transport-hops>
 #[ockam::node]
 async fn main(ctx: Context) -> Result<()> {
     let ctx_clone = ctx.async_try_clone().await?;
     let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+    let mut topology = Topology::new();
 
-    let mut node_responder = spawn(async move { create_responder_node(ctx).await.unwrap() })
-        .await
-        .unwrap();
+    // Record the messages that flow between the nodes, the coordinator prints them as a
+    // sequence diagram when it shuts down.
+    FlowRecorder::global().enable();
 
-    let mut node_middle = spawn(async move { create_middle_node(ctx_clone).await.unwrap() })
+    // Show the nodes in a terminal UI instead of on stdout when TUI is set.
+    let dashboard = Dashboard::from_env();
+
+    let _node_responder = create_responder_node(ctx, &mut topology, &dashboard)
         .await
-        .unwrap();
+        .unwrap_or_else(|error| error.report_and_exit());
+    dashboard.show_topology(&topology);
 
-    spawn(async move {
-        create_initiator_node(ctx_clone_2).await.unwrap();
-    })
-    .await
-    .ok();
+    let _node_middle = create_middle_node(ctx_clone, &mut topology, &dashboard)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+    dashboard.show_topology(&topology);
 
-    node_responder.stop().await.ok();
-    node_middle.stop().await.ok();
+    // Ctrl-C while the initiator is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_initiator_node(ctx_clone_2, &mut topology, &dashboard) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+        _ = dashboard.quit_requested() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+    dashboard.show_topology(&topology);
+    dashboard.finish().await;
 
     println!(
         "{}",
-        "App finished, stopping responder & middle nodes".red()
+        "App finished, stopping initiator, middle & responder nodes".red()
     );
 
+    // Stop the initiator first, then the middle node, then the responder.
+    coordinator.register_topology(&topology);
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
     Ok(())
 }
 
 /// examples/04-routing-over-transport-two-hops-responder.rs
 /// This node starts a tcp listener and an echoer worker.
 /// It then runs forever waiting for messages.
-async fn create_responder_node(ctx: Context) -> Result<ockam::Node> {
+async fn create_responder_node(
+    ctx: Context,
+    topology: &mut Topology,
+    dashboard: &Dashboard,
+) -> NodeResult<ockam::Node> {
     print_title(
         "Create a node that runs tcp listener on 4000 and echoer worker → wait for messages until stopped",
     );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
 
     // Create a node with default implementations
     let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
 
     // Initialize the TCP Transport
-    let tcp_transport = node.create_tcp_transport().await?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
 
     // Create an echoer worker
-    node.start_worker("echoer", Echoer).await?;
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
 
     // Create a TCP listener and wait for incoming connections.
     let listener = tcp_transport
         .listen("127.0.0.1:4000", TcpListenerOptions::new())
-        .await?;
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(&scope.node_name, Event::tcp_listener_bound(listener.socket_address()));
 
     // Allow access to the Echoer via TCP connections from the TCP listener
     node.flow_controls()
         .add_consumer("echoer", listener.flow_control_id());
+    presenter.println("🎙️ echoer is reachable via tcp listener on 127.0.0.1:4000");
+    dashboard.add_flow_control("node_responder", "tcp listener 127.0.0.1:4000 → echoer");
+
+    topology
+        .node("node_responder", NodeRole::Responder)
+        .tcp_transport(&tcp_transport)
+        .worker("echoer");
 
     Ok(node)
 }
@@ -88,19 +130,30 @@ async fn create_responder_node(ctx: Context) -> Result<ockam::Node> {
 /// Starts a forwarder worker to forward messages to 127.0.0.1:4000.
 /// Starts a tcp listener at 127.0.0.1:3000.
 /// It then runs forever waiting to route messages.
-async fn create_middle_node(ctx: Context) -> Result<ockam::Node> {
+async fn create_middle_node(
+    ctx: Context,
+    topology: &mut Topology,
+    dashboard: &Dashboard,
+) -> NodeResult<ockam::Node> {
     print_title("Create a middle (forwarder) node that listens on 3000 and forwards to 4000 → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
 
     // Create a node with default implementations
     let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
 
     // Initialize the TCP Transport
-    let tcp_transport = node.create_tcp_transport().await?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
 
     // Create a TCP connection to the responder node.
     let connection_to_responder = tcp_transport
         .connect("127.0.0.1:4000", TcpConnectionOptions::new())
-        .await?;
+        .await
+        .during(&scope, "connect to 127.0.0.1:4000")?;
 
     // Create a Forwarder worker
     node.start_worker(
@@ -109,61 +162,122 @@ async fn create_middle_node(ctx: Context) -> Result<ockam::Node> {
             address: connection_to_responder.into(),
         },
     )
-    .await?;
+    .await
+    .during(&scope, "start worker 'forward_to_responder'")?;
 
     // Create a TCP listener and wait for incoming connections.
     let listener = tcp_transport
         .listen("127.0.0.1:3000", TcpListenerOptions::new())
-        .await?;
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(&scope.node_name, Event::tcp_listener_bound(listener.socket_address()));
 
     // Allow access to the Forwarder via TCP connections from the TCP listener
     node.flow_controls()
         .add_consumer("forward_to_responder", listener.flow_control_id());
+    presenter.println("👉 forward_to_responder forwards from 127.0.0.1:3000 to 127.0.0.1:4000");
+    dashboard.add_flow_control(
+        "node_middle",
+        "tcp listener 127.0.0.1:3000 → forward_to_responder",
+    );
+
+    topology
+        .node("node_middle", NodeRole::Forwarder)
+        .tcp_transport(&tcp_transport)
+        .worker("forward_to_responder");
 
     Ok(node)
 }
 
 /// examples/04-routing-over-transport-two-hops-initiator.rs
 /// This node routes a message, to a worker on a different node, over two tcp transport hops.
-async fn create_initiator_node(ctx: Context) -> Result<()> {
+async fn create_initiator_node(
+    ctx: Context,
+    topology: &mut Topology,
+    dashboard: &Dashboard,
+) -> NodeResult<()> {
     print_title(
         "Create a node that routes a message, over two TCP transport hops, to a worker on a different node → stop",
     );
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
 
     // Create a node with default implementations
-    let mut node = node(ctx);
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
 
     // Initialize the TCP Transport
-    let tcp_transport = node.create_tcp_transport().await?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
 
     // Create a TCP connection to the middle node.
     let connection_to_middle_node = tcp_transport
         .connect("localhost:3000", TcpConnectionOptions::new())
-        .await?;
+        .await
+        .during(&scope, "connect to localhost:3000")?;
+
+    // The connection is listed in the transport's registry once it has started.
+    node.context()
+        .wait_for(connection_to_middle_node.sender_address().clone())
+        .await
+        .during(&scope, "wait for the connection to localhost:3000")?;
+
+    // Draw the nodes that are now running.
+    topology
+        .node("node_initiator", NodeRole::Initiator)
+        .context(node.context())
+        .tcp_transport(&tcp_transport);
+    print_topology(node.context(), topology)
+        .await
+        .during(&scope, "draw the topology")?;
+    dashboard.show_topology(topology);
 
     // Send a message to the "echoer" worker, on a different node, over two tcp hops.
     // Wait to receive a reply and print it.
     let route = route![connection_to_middle_node, "forward_to_responder", "echoer"];
     let route_msg = format!("{:?}", route);
     let msg = "Hello Ockam!";
+    let app_address = node.context().address();
+    FlowRecorder::global().record_send(&app_address, &route, msg);
+    emit_event(&scope.node_name, Event::message_sent(&route, msg));
     let reply = node
-        .send_and_receive::<String>(route, msg.to_string())
-        .await?;
+        .send_and_receive::<String>(route.clone(), msg.to_string())
+        .await
+        .during(
+            &scope,
+            format!("send '{}' and receive a reply over {}", msg, route_msg),
+        )?;
     let output_msg = format!(
         "App Sending: '{0}', over route: '{1}', and received: '{2}'",
         msg.red(),
         route_msg.green(),
         reply.yellow() // Should print "👈 echo back:  Hello Ockam!"
     );
-    println!("{}", output_msg.on_bright_black());
+    presenter.println(output_msg);
+    FlowRecorder::global().record_reply(&app_address, &route, &reply);
+    emit_event(&scope.node_name, Event::reply_received(&route, &reply));
 
-    // Stop all workers, stop the node, cleanup and return.
-    node.stop().await
-}
+    // Export the diagrams when EXPORT_DIAGRAMS_DIR is set.
+    let snapshot = topology
+        .snapshot(node.context())
+        .await
+        .during(&scope, "take a snapshot of the topology")?;
+    match export_diagrams_from_env(
+        "04-routing-over-two-transport-hops",
+        &snapshot,
+        &FlowRecorder::global().flow_steps(),
+    ) {
+        Ok(Some(directory)) => presenter.println(format!("Exported diagrams to {:?}", directory)),
+        Ok(None) => {}
+        Err(error) => presenter.println(
+            format!("Can't export diagrams: {}", error)
+                .red()
+                .to_string(),
+        ),
+    }
 
-fn print_title(title: &str) {
-    let padding = "=".repeat(title.len());
-    println!("{}", padding.black().on_bright_white());
-    println!("{}", title.black().on_bright_white());
-    println!("{}", padding.black().on_bright_white());
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
 }
@@ -16,59 +16,120 @@
  */
 
 use colored::Colorize;
-use hello_ockam::{Echoer, Forwarder};
-use ockam::{
-    identity::{SecureChannelListenerOptions, SecureChannelOptions},
-    route, TcpConnectionOptions,
+use hello_ockam::{
+    emit_event, export_diagrams_from_env, print_title, print_topology, wait_for_signal, Dashboard,
+    Echoer, Event, FlowRecorder, Forwarder, NodeDataDir, NodeResult, NodeRole, NodeScope,
+    Presenter, ResultExt, RouteResolver, RouteSpec, ShutdownCoordinator, Topology,
 };
+use ockam::{identity::SecureChannelListenerOptions, TcpConnectionOptions};
 use ockam::{node, AsyncTryClone, Context, Result, TcpListenerOptions, TcpTransportExtension};
 
+/// The route that the initiator sends its message over, unless another one is passed as
+/// the first argument.
+const DEFAULT_ROUTE: &str = "secure(tcp:localhost:3000 / forward_to_bob / bob_listener) / echoer";
+
 /// From: <https://docs.ockam.io/reference/libraries/rust/secure-channels>
 #[ockam::node]
 async fn main(ctx: Context) -> Result<()> {
     let ctx_clone = ctx.async_try_clone().await?;
     let ctx_clone_2 = ctx.async_try_clone().await?;
-
-    let mut node_responder = create_responder_node(ctx).await.unwrap();
-
-    let mut node_middle = create_middle_node(ctx_clone).await.unwrap();
-
-    create_initiator_node(ctx_clone_2).await.unwrap();
-
-    node_responder.stop().await.ok();
-    node_middle.stop().await.ok();
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+    let mut topology = Topology::new();
+
+    // Record the messages that flow between the nodes, the coordinator prints them as a
+    // sequence diagram when it shuts down.
+    FlowRecorder::global().enable();
+
+    // Show the nodes in a terminal UI instead of on stdout when TUI is set.
+    let dashboard = Dashboard::from_env();
+
+    let _node_responder = create_responder_node(ctx, &mut topology, &dashboard)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+    dashboard.show_topology(&topology);
+
+    let _node_middle = create_middle_node(ctx_clone, &mut topology, &dashboard)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+    dashboard.show_topology(&topology);
+
+    // Ctrl-C while the initiator is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_initiator_node(ctx_clone_2, &mut topology, &dashboard) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+        _ = dashboard.quit_requested() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+    dashboard.show_topology(&topology);
+    dashboard.finish().await;
 
     println!(
         "{}",
-        "App finished, stopping responder & middle nodes".red()
+        "App finished, stopping initiator, middle & responder nodes".red()
     );
 
+    // Stop the initiator first, then the middle node, then the responder.
+    coordinator.register_topology(&topology);
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
     Ok(())
 }
 
 /// examples/05-secure-channel-over-two-transport-hops-responder.rs
 /// This node starts a tcp listener on 4000, a secure channel listener, and an echoer
 /// worker. It then runs forever waiting for messages.
-async fn create_responder_node(ctx: Context) -> Result<ockam::Node> {
+async fn create_responder_node(
+    ctx: Context,
+    topology: &mut Topology,
+    dashboard: &Dashboard,
+) -> NodeResult<ockam::Node> {
     print_title(
         "Create a node that runs tcp listener on 4000, a secure channel listener (for `bob`) to an echoer worker → wait for messages until stopped",
     );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
 
-    // Create a node with default implementations
-    let node = node(ctx);
+    // Create a node whose vault is stored in its data directory.
+    let data_dir = NodeDataDir::for_node("node_responder");
+    let node = data_dir.create_node(ctx).await.during(
+        &scope,
+        format!("open data directory {}", data_dir.path().display()),
+    )?;
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
 
     // Initialize the TCP Transport.
-    let tcp_transport = node.create_tcp_transport().await?;
-
-    node.start_worker("echoer", Echoer).await?;
-
-    // Create an identity `bob`.
-    let id_bob = node.create_identity().await?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
+
+    // Load the identity `bob`, or create it on the first run. Its identifier stays the same
+    // across runs, so that clients can allowlist it.
+    let (id_bob, origin) = data_dir
+        .identity(&node, "bob")
+        .await
+        .during(&scope, "load or create identity for 'bob'")?;
+    presenter.println(format!(
+        "🪪 {} identity 'bob': {}",
+        origin.as_str(),
+        id_bob.to_string().green()
+    ));
 
     // Create a TCP listener and wait for incoming connections.
     let listener = tcp_transport
         .listen("127.0.0.1:4000", TcpListenerOptions::new())
-        .await?;
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
 
     // Create a secure channel listener for `bob` that will wait for requests to
     // initiate an Authenticated Key Exchange.
@@ -78,11 +139,27 @@ async fn create_responder_node(ctx: Context) -> Result<ockam::Node> {
             "bob_listener",
             SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
         )
-        .await?;
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
 
     // Allow access to the Echoer via Secure Channels
     node.flow_controls()
         .add_consumer("echoer", secure_channel_listener.flow_control_id());
+    presenter.println(
+        "🎙️ echoer is reachable via secure channel listener 'bob_listener' on 127.0.0.1:4000",
+    );
+    dashboard.add_flow_control(
+        "node_responder",
+        "tcp listener 127.0.0.1:4000 → bob_listener",
+    );
+    dashboard.add_flow_control("node_responder", "bob_listener → echoer");
+    dashboard.add_secure_channel("node_responder", "listener 'bob_listener' for 'bob'");
+
+    topology
+        .node("node_responder", NodeRole::Responder)
+        .tcp_transport(&tcp_transport)
+        .worker("bob_listener")
+        .worker("echoer");
 
     Ok(node)
 }
@@ -92,19 +169,30 @@ async fn create_responder_node(ctx: Context) -> Result<ockam::Node> {
 /// Starts a forwarder worker to forward messages to 127.0.0.1:4000.
 /// Starts a tcp listener at 127.0.0.1:3000.
 /// It then runs forever waiting to route messages.
-async fn create_middle_node(ctx: Context) -> Result<ockam::Node> {
+async fn create_middle_node(
+    ctx: Context,
+    topology: &mut Topology,
+    dashboard: &Dashboard,
+) -> NodeResult<ockam::Node> {
     print_title("Create a middle (forwarder) node that listens for TCP on 3000 and forwards to 4000 (no secure channel) → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
 
     // Create a node with default implementations
     let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
 
     // Initialize the TCP Transport
-    let tcp_transport = node.create_tcp_transport().await?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
 
     // Create a TCP connection to `bob`.
     let tcp_connection_to_bob = tcp_transport
         .connect("127.0.0.1:4000", TcpConnectionOptions::new())
-        .await?;
+        .await
+        .during(&scope, "connect to 127.0.0.1:4000")?;
 
     // Start a Forwarder to forward messages to `bob` using the TCP connection.
     node.start_worker(
@@ -113,15 +201,31 @@ async fn create_middle_node(ctx: Context) -> Result<ockam::Node> {
             address: tcp_connection_to_bob.into(),
         },
     )
-    .await?;
+    .await
+    .during(&scope, "start worker 'forward_to_bob'")?;
 
     // Create a TCP listener and wait for incoming connections.
     let listener = tcp_transport
         .listen("127.0.0.1:3000", TcpListenerOptions::new())
-        .await?;
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
 
     node.flow_controls()
         .add_consumer("forward_to_bob", listener.flow_control_id());
+    presenter.println("👉 forward_to_bob forwards from 127.0.0.1:3000 to 127.0.0.1:4000");
+    dashboard.add_flow_control(
+        "node_middle",
+        "tcp listener 127.0.0.1:3000 → forward_to_bob",
+    );
+
+    topology
+        .node("node_middle", NodeRole::Forwarder)
+        .tcp_transport(&tcp_transport)
+        .worker("forward_to_bob");
 
     // Don't call node.stop() here so this node runs forever.
     Ok(node)
@@ -130,57 +234,140 @@ async fn create_middle_node(ctx: Context) -> Result<ockam::Node> {
 /// examples/05-secure-channel-over-two-transport-hops-initiator.rs
 /// This node creates an end-to-end encrypted secure channel over two tcp transport hops.
 /// It then routes a message, to a worker on a different node, through this encrypted channel.
-async fn create_initiator_node(ctx: Context) -> Result<()> {
+async fn create_initiator_node(
+    ctx: Context,
+    topology: &mut Topology,
+    dashboard: &Dashboard,
+) -> NodeResult<()> {
     print_title(
         "Create a node that creates an end-to-end encrypted secure channel (from `alice`), over two TCP transport hops, and routes a message (to `bob`), to a worker on a different node → stop",
     );
-
-    // Create a node with default implementations
-    let mut node = node(ctx);
-
-    // Create an Identity to represent `alice`.
-    let id_alice = node.create_identity().await?;
-
-    // Create a TCP connection to the middle node.
-    let tcp_transport = node.create_tcp_transport().await?;
-    let connection_to_middle_node = tcp_transport
-        .connect("localhost:3000", TcpConnectionOptions::new())
-        .await?;
-
-    // Connect to a secure channel listener and perform a handshake.
-    let channel_route = route![connection_to_middle_node, "forward_to_bob", "bob_listener"];
-    let channel_route_msg = format!("{:?}", channel_route);
-    let channel = node
-        .create_secure_channel(&id_alice, channel_route, SecureChannelOptions::new())
-        .await?;
-    println!(
-        "Connected to secure channel listener from 'alice' after performing handshake: {}",
-        channel_route_msg.green()
-    );
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
+
+    // Create a node whose vault is stored in its data directory.
+    let data_dir = NodeDataDir::for_node("node_initiator");
+    let node = data_dir.create_node(ctx).await.during(
+        &scope,
+        format!("open data directory {}", data_dir.path().display()),
+    )?;
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    // Load the Identity that represents `alice`, or create it on the first run.
+    let (id_alice, origin) = data_dir
+        .identity(&node, "alice")
+        .await
+        .during(&scope, "load or create identity for 'alice'")?;
+    presenter.println(format!(
+        "🪪 {} identity 'alice': {}",
+        origin.as_str(),
+        id_alice.to_string().green()
+    ));
+
+    // The route to the echoer, as text. Pass a different one as the first argument, eg:
+    // "secure(tcp:127.0.0.1:4000 / bob_listener) / echoer" to skip the middle node.
+    let route_spec: RouteSpec = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ROUTE.to_string())
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route")?;
+    presenter.println(format!("Route: {}", route_spec.to_string().green()));
+
+    // Connect to the middle node, then connect to a secure channel listener and perform a
+    // handshake, as the `tcp:` and `secure(...)` hops of the route say.
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let resolved = RouteResolver::new(&node, &tcp_transport)
+        .with_identity(&id_alice)
+        .resolve(&route_spec)
+        .await
+        .during(&scope, format!("resolve the route {}", route_spec))?;
+
+    // The connections are listed in the transport's registry once they have started.
+    for (socket_address, sender_address) in &resolved.tcp_connections {
+        node.context()
+            .wait_for(sender_address.clone())
+            .await
+            .during(
+                &scope,
+                format!("wait for the connection to {}", socket_address),
+            )?;
+    }
+
+    // Draw the nodes that are now running.
+    topology
+        .node("node_initiator", NodeRole::Initiator)
+        .context(node.context())
+        .tcp_transport(&tcp_transport);
+    print_topology(node.context(), topology)
+        .await
+        .during(&scope, "draw the topology")?;
+    dashboard.show_topology(topology);
+
+    for (channel_route, encryptor_address) in &resolved.secure_channels {
+        emit_event(
+            &scope.node_name,
+            Event::SecureChannelEstablished {
+                route: channel_route.to_string(),
+            },
+        );
+        dashboard.add_secure_channel("node_initiator", format!("'alice' → {}", channel_route));
+        topology
+            .node("node_initiator", NodeRole::Initiator)
+            .worker(encryptor_address.clone());
+        presenter.println(format!(
+            "Connected to secure channel listener from 'alice' after performing handshake: {}",
+            channel_route.to_string().green()
+        ));
+    }
 
     // Send a message to the echoer worker via the channel.
     // Wait to receive a reply and print it.
-    let route = route![channel, "echoer"];
+    let route = resolved.route;
     let route_msg = format!("{:?}", route);
     let msg = "Hello Ockam!";
+    let app_address = node.context().address();
+    FlowRecorder::global().record_send(&app_address, &route, msg);
+    emit_event(&scope.node_name, Event::message_sent(&route, msg));
     let reply = node
-        .send_and_receive::<String>(route, msg.to_string())
-        .await?;
+        .send_and_receive::<String>(route.clone(), msg.to_string())
+        .await
+        .during(
+            &scope,
+            format!("send '{}' and receive a reply over {}", msg, route_msg),
+        )?;
     let output_msg = format!(
         "App Sending: '{0}', \nover route: '{1}', \nand received: '{2}'",
         msg.red(),
         route_msg.green(),
         reply.yellow() // Should print "👈 echo back:  Hello Ockam!");
     );
-    println!("{}", output_msg.on_bright_black());
-
-    // Stop all workers, stop the node, cleanup and return.
-    node.stop().await
-}
-
-fn print_title(title: &str) {
-    let padding = "=".repeat(title.len());
-    println!("{}", padding.black().on_bright_white());
-    println!("{}", title.black().on_bright_white());
-    println!("{}", padding.black().on_bright_white());
+    presenter.println(output_msg);
+    FlowRecorder::global().record_reply(&app_address, &route, &reply);
+    emit_event(&scope.node_name, Event::reply_received(&route, &reply));
+
+    // Export the diagrams when EXPORT_DIAGRAMS_DIR is set.
+    let snapshot = topology
+        .snapshot(node.context())
+        .await
+        .during(&scope, "take a snapshot of the topology")?;
+    match export_diagrams_from_env(
+        "05-secure-channel-over-two-transport-hops",
+        &snapshot,
+        &FlowRecorder::global().flow_steps(),
+    ) {
+        Ok(Some(directory)) => presenter.println(format!("Exported diagrams to {:?}", directory)),
+        Ok(None) => {}
+        Err(error) => presenter.println(
+            format!("Can't export diagrams: {}", error)
+                .red()
+                .to_string(),
+        ),
+    }
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
 }
@@ -0,0 +1,67 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::{Color, Colorize};
+use hello_ockam::{print_title, ChildRole, Launcher};
+use std::path::PathBuf;
+
+/// examples/06-credential-exchange-launcher.rs
+/// This program runs the issuer, server, and client roles of
+/// examples/06-credential-exchange.rs, each in its own child process. Build the examples
+/// first so that the `06-credential-exchange` binary exists next to this one.
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    print_title("Launch the credential exchange issuer, server & client as child processes");
+
+    let program = sibling_example("06-credential-exchange")?;
+
+    let exit_code = Launcher::new()
+        .role(
+            ChildRole::new("issuer", Color::BrightMagenta, &program)
+                .arg("issuer")
+                .ready_when_stdout_contains("issuer started"),
+        )
+        .role(
+            ChildRole::new("server", Color::BrightBlue, &program)
+                .arg("server")
+                .ready_when_stdout_contains("server started on 4000"),
+        )
+        .role(ChildRole::new("client", Color::BrightGreen, &program).arg("client"))
+        .run()
+        .await?;
+
+    let output_msg = format!("Launcher finished, client exit code: {}", exit_code);
+    println!("{}", output_msg.on_bright_black());
+
+    std::process::exit(exit_code);
+}
+
+/// Examples are built into the same directory, eg: `target/debug/examples/`.
+fn sibling_example(name: &str) -> std::io::Result<PathBuf> {
+    let current_exe = std::env::current_exe()?;
+    let program = current_exe.with_file_name(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    if !program.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "{:?} not found, run `cargo build --example {}` first",
+                program, name
+            ),
+        ));
+    }
+    Ok(program)
+}
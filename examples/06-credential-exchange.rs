@@ -16,10 +16,14 @@
  */
 
 use colored::Colorize;
-use hello_ockam::Echoer;
+use hello_ockam::{
+    emit_event, print_title, wait_for_signal, DeadLetterQueue, Echoer, Event, NodeResult,
+    NodeRole, NodeScope, Presenter, ReportAccessDenied, ResultExt, ShutdownCoordinator,
+    DEAD_LETTER_ADDRESS,
+};
 use ockam::access_control::IdentityIdAccessControl;
-use ockam::identity::CredentialsIssuer;
 use ockam::identity::SecureChannelListenerOptions;
+use ockam::identity::{CredentialsIssuer, IdentityIdentifier};
 use ockam::TcpTransportExtension;
 use ockam::{
     abac::AbacAccessControl,
@@ -30,35 +34,89 @@ use ockam::{
 use ockam::{node, Context, Result, TcpListenerOptions};
 
 /// From: <https://docs.ockam.io/reference/libraries/rust/credentials>
-/// examples/06-credential-exchange-issuer.rs
+/// examples/06-credential-exchange.rs
 /// Ockam enables you to define various pluggable Enrollment Protocols to decide who
 /// should be issued credentials. For this example we'll assume that this list is known in
 /// advance.
+///
+/// Pass `issuer`, `server`, or `client` as the first argument to run just that role (this
+/// is what examples/06-credential-exchange-launcher.rs does). With no argument all three
+/// roles run in this process.
 #[ockam::node]
 async fn main(ctx: Context) -> Result<()> {
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    // Don't keep `Args` (which isn't Send) alive across the awaits below.
+    let role = std::env::args().nth(1);
+    match role.as_deref() {
+        Some("issuer") => {
+            let _node_issuer = create_issuer_node(ctx, &mut coordinator)
+                .await
+                .unwrap_or_else(|error| error.report_and_exit());
+            let report = coordinator.run_until_signal().await?;
+            print!("{}", report);
+        }
+        Some("server") => {
+            let _node_server = create_server_node(ctx, &mut coordinator)
+                .await
+                .unwrap_or_else(|error| error.report_and_exit());
+            let report = coordinator.run_until_signal().await?;
+            print!("{}", report);
+        }
+        Some("client") => {
+            let result = create_client_node(ctx).await;
+            coordinator.shutdown().await?;
+            if let Err(error) = result {
+                error.report_and_exit();
+            }
+        }
+        _ => run_all_roles(ctx, coordinator).await?,
+    }
+
+    Ok(())
+}
+
+async fn run_all_roles(ctx: Context, mut coordinator: ShutdownCoordinator) -> Result<()> {
     let ctx_clone = ctx.async_try_clone().await?;
     let ctx_clone_2 = ctx.async_try_clone().await?;
 
-    let mut node_issuer = create_issuer_node(ctx).await.unwrap();
-    let mut node_server = create_server_node(ctx_clone).await.unwrap();
-
-    create_client_node(ctx_clone_2).await.unwrap();
+    let _node_issuer = create_issuer_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+    let _node_server = create_server_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the client is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_client_node(ctx_clone_2) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
 
-    node_server.stop().await.ok();
-    node_issuer.stop().await.ok();
+    // Stop the server & issuer (the client doesn't start any long running workers).
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
 
     Ok(())
 }
 
-async fn create_client_node(ctx: Context) -> Result<()> {
+async fn create_client_node(ctx: Context) -> NodeResult<()> {
     print_title(
         "Create a node that is the client w/ identity known by issuer, connect to 5000 → stop",
     );
+    let scope = NodeScope::new(NodeRole::Initiator, "client");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "client");
 
     // Create a node with default implementations
-    let mut node = node(ctx);
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
     // Initialize the TCP Transport
-    let tcp_transport = node.create_tcp_transport().await?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
 
     // Create an Identity representing the client
     // We preload the client vault with a change history and secret key corresponding to the identity identifier
@@ -69,7 +127,10 @@ async fn create_client_node(ctx: Context) -> Result<()> {
     // to the credential issuer as a member of the production cluster.
     let change_history = "01dcf392551f796ef1bcb368177e53f9a5875a962f67279259207d24a01e690721000547c93239ba3d818ec26c9cdadd2a35cbdf1fa3b6d1a731e06164b1079fb7b8084f434b414d5f524b03012000000020a0d205f09cab9a9467591fcee560429aab1215d8136e5c985a6b7dc729e6f08203010140b098463a727454c0e5292390d8f4cbd4dd0cae5db95606832f3d0a138936487e1da1489c40d8a0995fce71cc1948c6bcfd67186467cdd78eab7e95c080141505";
     let secret = "41b6873b20d95567bf958e6bab2808e9157720040882630b1bb37a72f4015cd2";
-    let client = node.import_private_identity(change_history, secret).await?;
+    let client = node
+        .import_private_identity(change_history, secret)
+        .await
+        .during(&scope, "import identity for 'client'")?;
 
     // Connect with the credential issuer and authenticate using the latest private
     // key of this program's hardcoded identity.
@@ -79,32 +140,61 @@ async fn create_client_node(ctx: Context) -> Result<()> {
     // attesting to that knowledge.
     let issuer_connection = tcp_transport
         .connect("127.0.0.1:5000", TcpConnectionOptions::new())
-        .await?;
+        .await
+        .during(&scope, "connect to 127.0.0.1:5000")?;
     let issuer_channel = node
         .create_secure_channel(
             &client.identifier(),
             route![issuer_connection, "secure-issuer"],
             SecureChannelOptions::new(),
         )
-        .await?;
+        .await
+        .during(&scope, "create secure channel to 'secure-issuer'")?;
+    emit_event(
+        &scope.node_name,
+        Event::SecureChannelEstablished {
+            route: "secure-issuer".to_string(),
+        },
+    );
 
     let issuer_client =
-        CredentialsIssuerClient::new(route![issuer_channel, "issuer"], node.context()).await?;
-
-    let credential = issuer_client.credential().await?;
+        CredentialsIssuerClient::new(route![issuer_channel, "issuer"], node.context())
+            .await
+            .during(&scope, "create credentials issuer client")?;
+
+    let credential = issuer_client
+        .credential()
+        .await
+        .during(&scope, "retrieve credential from the issuer")?;
     let output_msg = format!("❓ Retrieving credential from issuer:\n{credential}");
-    println!("{}", output_msg.on_bright_blue());
+    presenter.println(output_msg);
 
     // Verify that the received credential has indeed be signed by the issuer.
     // The issuer identity must be provided out-of-band from a trusted source
     // and match the identity used to start the issuer node
     let issuer_identity = "0180370b91c5d0aa4af34580a9ab4b8fb2a28351bed061525c96b4f07e75c0ee18000547c93239ba3d818ec26c9cdadd2a35cbdf1fa3b6d1a731e06164b1079fb7b8084f434b414d5f524b03012000000020236f79490d3f683e0c3bf458a7381c366c99a8f2b2ac406db1ef8c130111f12703010140b23fddceb11cea25602aa681b6ef6abda036722c27a6dee291f1d6b2234a127af21cc79de2252201f27e7e34e0bf5064adbf3d01eb355aff4bf5c90b8f1fd80a";
-    let issuer = node.import_identity_hex(issuer_identity).await?;
+    let issuer = node
+        .import_identity_hex(issuer_identity)
+        .await
+        .during(&scope, "import issuer identity")?;
     node.credentials()
         .verify_credential(&client.identifier(), &[issuer.clone()], credential.clone())
-        .await?;
+        .await
+        .during(&scope, "verify credential is signed by the issuer")?;
+    for event in [
+        Event::CredentialIssued {
+            subject: client.identifier().to_string(),
+            issuer: issuer.identifier().to_string(),
+        },
+        Event::CredentialVerified {
+            subject: client.identifier().to_string(),
+            issuer: issuer.identifier().to_string(),
+        },
+    ] {
+        emit_event(&scope.node_name, event);
+    }
     let output_msg = format!("Verify that the recieved credential is signed by the issuer");
-    println!("{}", output_msg.on_bright_black());
+    presenter.println(output_msg);
 
     // Create a trust context that will be used to authenticate credential exchanges.
     // The trust context is needed to verify the credential.
@@ -118,12 +208,13 @@ async fn create_client_node(ctx: Context) -> Result<()> {
         )),
     );
     let output_msg = format!("Create a trust context (needed to verify the credential)");
-    println!("{}", output_msg.on_bright_black());
+    presenter.println(output_msg);
 
     // Create a secure channel to the node that is running the Echoer service.
     let server_connection = tcp_transport
         .connect("127.0.0.1:4000", TcpConnectionOptions::new())
-        .await?;
+        .await
+        .during(&scope, "connect to 127.0.0.1:4000")?;
     let channel = node
         .create_secure_channel(
             &client.identifier(),
@@ -132,36 +223,59 @@ async fn create_client_node(ctx: Context) -> Result<()> {
                 .with_trust_context(trust_context)
                 .with_credential(credential),
         )
-        .await?;
+        .await
+        .during(&scope, "create secure channel to 'secure-server'")?;
+    emit_event(
+        &scope.node_name,
+        Event::SecureChannelEstablished {
+            route: "secure-server".to_string(),
+        },
+    );
     let output_msg = format!("Create a secure channel to echoers");
-    println!("{}", output_msg.on_bright_black());
+    presenter.println(output_msg);
 
     // Send a message to the worker at address "echoer".
     // Wait to receive a reply and print it.
     let msg = "Hello Ockam!";
     let route = route![channel, "echoer"];
     let route_msg = format!("{:?}", route);
+    emit_event(&scope.node_name, Event::message_sent(&route, msg));
     let reply = node
-        .send_and_receive::<String>(route, msg.to_string())
-        .await?;
+        .send_and_receive::<String>(route.clone(), msg.to_string())
+        .await
+        .during(
+            &scope,
+            format!("send '{}' and receive a reply over {}", msg, route_msg),
+        )?;
+    emit_event(&scope.node_name, Event::reply_received(&route, &reply));
     let output_msg: String = format!(
         "App Sent: '{0}', via route: '{1}', Received: '{2}'",
         msg, route_msg, reply
     );
-    println!("{}", output_msg.on_bright_black()); // Should print "👈 echo back:  Hello Ockam!");
+    presenter.println(output_msg); // Should print "👈 echo back:  Hello Ockam!");
 
-    node.stop().await
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
 }
 
-async fn create_server_node(ctx: Context) -> Result<ockam::Node> {
+async fn create_server_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
     print_title(
         "Create a node that verifies credentials against the issuer, runs a tcp listener on 4000, secure channel listener, and echoer worker → wait for messages until stopped",
     );
+    let scope = NodeScope::new(NodeRole::Responder, "server");
+    let presenter = Presenter::for_role(NodeRole::Responder, "server");
 
     // Create a node with default implementations
     let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
     // Initialize the TCP Transport
-    let tcp = node.create_tcp_transport().await?;
+    let tcp = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
 
     // Create an Identity representing the server
     // Load an identity corresponding to the following public identifier
@@ -172,7 +286,10 @@ async fn create_server_node(ctx: Context) -> Result<ockam::Node> {
     // to the credential issuer as a member of the production cluster.
     let change_history = "01ed8a5b1303f975c1296c990d1bd3c1946cfef328de20531e3511ec5604ce0dd9000547c93239ba3d818ec26c9cdadd2a35cbdf1fa3b6d1a731e06164b1079fb7b8084f434b414d5f524b03012000000020e8c328bc0cc07a374762091d037e69c36fdd4d2e1a651abd4d43a1362d3f800503010140a349968063d7337d0c965969fa9c640824c01a6d37fe130d4ab963b0271b9d5bbf0923faa5e27f15359554f94f08676df01b99d997944e4feaf0caaa1189480e";
     let secret = "5b2b3f2abbd1787704d8f8b363529f8e2d8f423b6dd4b96a2c462e4f0e04ee18";
-    let server = node.import_private_identity(change_history, secret).await?;
+    let server = node
+        .import_private_identity(change_history, secret)
+        .await
+        .during(&scope, "import identity for 'server'")?;
 
     // Connect with the credential issuer and authenticate using the latest private
     // key of this program's hardcoded identity.
@@ -182,31 +299,60 @@ async fn create_server_node(ctx: Context) -> Result<ockam::Node> {
     // attesting to that knowledge.
     let issuer_connection = tcp
         .connect("127.0.0.1:5000", TcpConnectionOptions::new())
-        .await?;
+        .await
+        .during(&scope, "connect to 127.0.0.1:5000")?;
     let issuer_channel = node
         .create_secure_channel(
             &server.identifier(),
             route![issuer_connection, "secure-issuer"],
             SecureChannelOptions::new(),
         )
-        .await?;
+        .await
+        .during(&scope, "create secure channel to 'secure-issuer'")?;
+    emit_event(
+        &scope.node_name,
+        Event::SecureChannelEstablished {
+            route: "secure-issuer".to_string(),
+        },
+    );
 
     let issuer_client =
-        CredentialsIssuerClient::new(route![issuer_channel, "issuer"], node.context()).await?;
-    let credential = issuer_client.credential().await?;
+        CredentialsIssuerClient::new(route![issuer_channel, "issuer"], node.context())
+            .await
+            .during(&scope, "create credentials issuer client")?;
+    let credential = issuer_client
+        .credential()
+        .await
+        .during(&scope, "retrieve credential from the issuer")?;
     let output_msg = format!("❓ Retrieving credential from issuer:\n{credential}");
-    println!("{}", output_msg.on_bright_blue());
+    presenter.println(output_msg);
 
     // Verify that the received credential has indeed be signed by the issuer.
     // The issuer identity must be provided out-of-band from a trusted source
     // and match the identity used to start the issuer node
     let issuer_identity = "0180370b91c5d0aa4af34580a9ab4b8fb2a28351bed061525c96b4f07e75c0ee18000547c93239ba3d818ec26c9cdadd2a35cbdf1fa3b6d1a731e06164b1079fb7b8084f434b414d5f524b03012000000020236f79490d3f683e0c3bf458a7381c366c99a8f2b2ac406db1ef8c130111f12703010140b23fddceb11cea25602aa681b6ef6abda036722c27a6dee291f1d6b2234a127af21cc79de2252201f27e7e34e0bf5064adbf3d01eb355aff4bf5c90b8f1fd80a";
-    let issuer = node.import_identity_hex(issuer_identity).await?;
+    let issuer = node
+        .import_identity_hex(issuer_identity)
+        .await
+        .during(&scope, "import issuer identity")?;
     node.credentials()
         .verify_credential(&server.identifier(), &[issuer.clone()], credential.clone())
-        .await?;
+        .await
+        .during(&scope, "verify credential is signed by the issuer")?;
+    for event in [
+        Event::CredentialIssued {
+            subject: server.identifier().to_string(),
+            issuer: issuer.identifier().to_string(),
+        },
+        Event::CredentialVerified {
+            subject: server.identifier().to_string(),
+            issuer: issuer.identifier().to_string(),
+        },
+    ] {
+        emit_event(&scope.node_name, event);
+    }
     let output_msg = format!("🔒✅ Credential verified as signed by the issuer:\n{credential}");
-    println!("{}", output_msg.on_bright_blue());
+    presenter.println(output_msg);
 
     // Create a trust context that will be used to authenticate credential exchanges
     let trust_context = TrustContext::new(
@@ -219,7 +365,7 @@ async fn create_server_node(ctx: Context) -> Result<ockam::Node> {
         )),
     );
     let output_msg = format!("🔒✅ Starting a trust context: \n{}", trust_context.id());
-    println!("{}", output_msg.on_bright_blue());
+    presenter.println(output_msg);
 
     // Start an echoer worker that will only accept incoming requests from
     // identities that have authenticated credentials issued by the above credential
@@ -233,48 +379,87 @@ async fn create_server_node(ctx: Context) -> Result<ockam::Node> {
 
     node.flow_controls()
         .add_consumer("echoer", &sc_listener_options.spawner_flow_control_id());
-    let allow_production = AbacAccessControl::create(node.repository(), "cluster", "production");
+    // The messages that the access control rejects are kept here.
+    node.start_worker(DEAD_LETTER_ADDRESS, DeadLetterQueue::new())
+        .await
+        .during(&scope, format!("start worker '{}'", DEAD_LETTER_ADDRESS))?;
+    let allow_production = ReportAccessDenied::create(
+        node.context(),
+        &scope.node_name,
+        AbacAccessControl::create(node.repository(), "cluster", "production"),
+    )
+    .await
+    .during(&scope, "create the access control of worker 'echoer'")?;
     node.start_worker_with_access_control("echoer", Echoer, allow_production, AllowAll)
-        .await?;
+        .await
+        .during(&scope, "start worker 'echoer'")?;
     let output_msg = "🔒🪞 start echoer worker that only accepts requests from identities that have authenticated credentials issued by the above credential issuer, and have the right attributes";
-    println!("{}", output_msg.on_bright_blue());
+    presenter.println(output_msg);
 
     // Start a secure channel listener that only allows channels with
     // authenticated identities.
     node.create_secure_channel_listener(&server.identifier(), "secure-server", sc_listener_options)
-        .await?;
+        .await
+        .during(&scope, "create secure channel listener 'secure-server'")?;
     let output_msg = "🔒🎙️ create secure channel listener that only allows channels w/ auth ids";
-    println!("{}", output_msg.on_bright_blue());
+    presenter.println(output_msg);
 
     // Create a TCP listener and wait for incoming connections
-    tcp.listen("127.0.0.1:4000", tcp_listener_options).await?;
-
-    println!("{}", "🔒🖥️ server started on 4000".on_bright_blue());
+    let listener = tcp
+        .listen("127.0.0.1:4000", tcp_listener_options)
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(&scope.node_name, Event::tcp_listener_bound(listener.socket_address()));
+
+    presenter.println("🔒🖥️ server started on 4000");
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "server",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "server", "secure-server")
+        .register_worker(NodeRole::Responder, "server", "echoer")
+        .register_worker(NodeRole::Responder, "server", DEAD_LETTER_ADDRESS);
 
     Ok(node)
 }
 
-async fn create_issuer_node(ctx: Context) -> Result<ockam::Node> {
+async fn create_issuer_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
     print_title(
         "Create a node that runs a credential exchange issuer (creds are known in advance) on 5000 → wait for messages until stopped",
     );
+    let scope = NodeScope::new(NodeRole::Responder, "issuer");
+    let presenter = Presenter::for_role(NodeRole::Responder, "issuer");
 
     // Create a node with default implementations
     let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
 
     let issuer_identity = "0180370b91c5d0aa4af34580a9ab4b8fb2a28351bed061525c96b4f07e75c0ee18000547c93239ba3d818ec26c9cdadd2a35cbdf1fa3b6d1a731e06164b1079fb7b8084f434b414d5f524b03012000000020236f79490d3f683e0c3bf458a7381c366c99a8f2b2ac406db1ef8c130111f12703010140b23fddceb11cea25602aa681b6ef6abda036722c27a6dee291f1d6b2234a127af21cc79de2252201f27e7e34e0bf5064adbf3d01eb355aff4bf5c90b8f1fd80a";
     let secret = "9278735d525efceef16bfd9143d3534759f3d388e460e6002134b9541e06489f";
     let issuer = node
         .import_private_identity(issuer_identity, secret)
-        .await?;
+        .await
+        .during(&scope, "import identity for 'issuer'")?;
     let output_msg = format!("🔒 issuer identifier {}", issuer.identifier());
-    println!("{}", output_msg.on_bright_purple());
+    presenter.println(output_msg);
 
     // Tell the credential issuer about a set of public identifiers that are
     // known, in advance, to be members of the production cluster.
     let known_identifiers = vec![
-        "Pe92f183eb4c324804ef4d62962dea94cf095a265d4d28500c34e1a4e0d5ef638".try_into()?,
-        "Pada09e0f96e56580f6a0cb54f55ecbde6c973db6732e30dfb39b178760aed041".try_into()?,
+        IdentityIdentifier::try_from(
+            "Pe92f183eb4c324804ef4d62962dea94cf095a265d4d28500c34e1a4e0d5ef638",
+        )
+        .during(&scope, "parse known identifiers")?,
+        IdentityIdentifier::try_from(
+            "Pada09e0f96e56580f6a0cb54f55ecbde6c973db6732e30dfb39b178760aed041",
+        )
+        .during(&scope, "parse known identifiers")?,
     ];
 
     // Tell this credential issuer about the attributes to include in credentials
@@ -292,13 +477,15 @@ async fn create_issuer_node(ctx: Context) -> Result<ockam::Node> {
         issuer.identifier(),
         "trust_context".into(),
     )
-    .await?;
+    .await
+    .during(&scope, "create credentials issuer")?;
 
     for identifier in known_identifiers.iter() {
         node.identities()
             .repository()
             .put_attribute_value(identifier, "cluster", "production")
-            .await?;
+            .await
+            .during(&scope, "set attributes for known identifiers")?;
     }
 
     let tcp_listener_options = TcpListenerOptions::new();
@@ -310,7 +497,8 @@ async fn create_issuer_node(ctx: Context) -> Result<ockam::Node> {
     // at the other end of the channel can authenticate with the latest private key
     // corresponding to one of the above known public identifiers.
     node.create_secure_channel_listener(&issuer.identifier(), "secure-issuer", sc_listener_options)
-        .await?;
+        .await
+        .during(&scope, "create secure channel listener 'secure-issuer'")?;
 
     // Start a credential issuer worker that will only accept incoming requests from
     // authenticated secure channels with our known public identifiers.
@@ -318,22 +506,30 @@ async fn create_issuer_node(ctx: Context) -> Result<ockam::Node> {
     node.flow_controls()
         .add_consumer("issuer", &sc_listener_flow_control_id);
     node.start_worker_with_access_control("issuer", credential_issuer, allow_known, AllowAll)
-        .await?;
+        .await
+        .during(&scope, "start worker 'issuer'")?;
 
     // Initialize TCP Transport, create a TCP listener, and wait for connections.
-    let tcp_transport = node.create_tcp_transport().await?;
-    tcp_transport
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let listener = tcp_transport
         .listen("127.0.0.1:5000", tcp_listener_options)
-        .await?;
+        .await
+        .during(&scope, "listen on 127.0.0.1:5000")?;
+    emit_event(&scope.node_name, Event::tcp_listener_bound(listener.socket_address()));
 
-    println!("{}", "🔒 issuer started".on_bright_purple());
+    presenter.println("🔒 issuer started");
 
-    Ok(node)
-}
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "issuer",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "issuer", "secure-issuer")
+        .register_worker(NodeRole::Responder, "issuer", "issuer");
 
-fn print_title(title: &str) {
-    let padding = "=".repeat(title.len());
-    println!("{}", padding.black().on_bright_white());
-    println!("{}", title.black().on_bright_white());
-    println!("{}", padding.black().on_bright_white());
+    Ok(node)
 }
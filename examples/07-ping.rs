@@ -0,0 +1,251 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, wait_for_signal, Event, Forwarder, NodeResult, NodeRole, NodeScope,
+    PingClient, Pinger, Presenter, ResultExt, RouteResolver, RouteSpec, ShutdownCoordinator,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    node, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+use std::time::Duration;
+
+/// The route that the client pings, unless another one is passed as the first argument.
+const DEFAULT_ROUTE: &str = "secure(tcp:localhost:3000 / forward_to_bob / bob_listener) / pinger";
+
+/// examples/07-ping.rs
+/// Like examples/05-secure-channel-over-two-transport-hops-responder.rs, but w/ a pinger
+/// instead of an echoer. The client sends timestamped probes over a route (eg: TCP hops, a
+/// forwarder, and a secure channel) and reports the round trip times & loss, like `ping`.
+///
+/// Pass the route as the first argument (eg: "tcp:localhost:4000 / pinger"), and the
+/// number of probes as the second one.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the client is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_client_node(ctx_clone_2, &mut coordinator) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts a tcp listener on 4000, a secure channel listener, and a pinger
+/// worker that is reachable over both. It then runs forever waiting for probes.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that runs tcp listener on 4000, a secure channel listener (for `bob`) and a pinger worker → wait for probes until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    node.start_worker("pinger", Pinger)
+        .await
+        .during(&scope, "start worker 'pinger'")?;
+
+    let id_bob = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:4000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
+
+    // Allow access to the Pinger via TCP connections, and via secure channels.
+    node.flow_controls()
+        .add_consumer("pinger", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("pinger", secure_channel_listener.flow_control_id());
+    presenter.println(
+        "🏓 pinger is reachable via tcp listener on 127.0.0.1:4000, and via secure channel listener 'bob_listener'",
+    );
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "bob_listener")
+        .register_worker(NodeRole::Responder, "node_responder", "pinger");
+
+    Ok(node)
+}
+
+/// This node listens on 3000 and forwards everything to 127.0.0.1:4000.
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle (forwarder) node that listens for TCP on 3000 and forwards to 4000 → wait for probes until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    let tcp_connection_to_bob = tcp_transport
+        .connect("127.0.0.1:4000", TcpConnectionOptions::new())
+        .await
+        .during(&scope, "connect to 127.0.0.1:4000")?;
+    let tcp_connection_to_bob_address = tcp_connection_to_bob.sender_address().clone();
+    node.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: tcp_connection_to_bob.into(),
+        },
+    )
+    .await
+    .during(&scope, "start worker 'forward_to_bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:3000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+    presenter.println("👉 forward_to_bob forwards from 127.0.0.1:3000 to 127.0.0.1:4000");
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_bob")
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            tcp_connection_to_bob_address,
+        );
+
+    Ok(node)
+}
+
+/// This node resolves the route to the pinger, sends the probes, and prints the statistics.
+async fn create_client_node(ctx: Context, coordinator: &mut ShutdownCoordinator) -> NodeResult<()> {
+    print_title("Create a node that pings the pinger over a route → print the statistics → stop");
+    let scope = NodeScope::new(NodeRole::Initiator, "node_client");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_client");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    let route_spec: RouteSpec = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ROUTE.to_string())
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route")?;
+    let count = std::env::args()
+        .nth(2)
+        .and_then(|it| it.parse::<u64>().ok())
+        .unwrap_or(10);
+
+    let id_alice = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'alice'")?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let resolved = RouteResolver::new(&node, &tcp_transport)
+        .with_identity(&id_alice)
+        .resolve(&route_spec)
+        .await
+        .during(&scope, format!("resolve the route {}", route_spec))?;
+    let addresses = resolved
+        .tcp_connections
+        .iter()
+        .map(|it| &it.1)
+        .chain(resolved.secure_channels.iter().map(|it| &it.1));
+    for address in addresses {
+        coordinator.register_worker(NodeRole::Initiator, "node_client", address.clone());
+    }
+
+    presenter.println(format!(
+        "PING {} ({} probes)",
+        route_spec.to_string().green(),
+        count
+    ));
+    let report = PingClient::new(resolved.route)
+        .with_count(count)
+        .with_interval(Duration::from_millis(200))
+        .with_presenter(presenter.clone())
+        .run(node.context())
+        .await
+        .during(&scope, format!("ping over {}", route_spec))?;
+    presenter.println(report.to_string().yellow().to_string());
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
@@ -0,0 +1,296 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, wait_for_signal, DirectoryClient, Echoer, Event, Forwarder,
+    NodeResult, NodeRole, NodeScope, Presenter, ResultExt, RouteResolver, RouteSpec,
+    ServiceDirectory, ServiceHealth, ShutdownCoordinator,
+};
+use ockam::{
+    node, route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+use std::time::Duration;
+
+/// The only route that the initiator knows: where to find the directory.
+const DIRECTORY_ROUTE: &str = "tcp:localhost:3000 / directory";
+
+/// The name that the initiator asks the directory for.
+const ECHO_SERVICE: &str = "echo-service";
+
+/// examples/08-service-directory.rs
+/// Like examples/04-routing-over-two-transport-hops.rs, but the initiator doesn't hard code
+/// the route to the echoer. The middle node runs a directory, and registers the echoer as
+/// "echo-service" (w/ a TTL & health status). The initiator resolves that name to a route
+/// before sending its message.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the initiator is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_initiator_node(ctx_clone_2, &mut coordinator) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts a tcp listener on 4000 and an echoer worker.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that runs tcp listener on 4000 and echoer worker → wait for messages until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:4000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    presenter.println("🎙️ echoer is reachable via tcp listener on 127.0.0.1:4000");
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "echoer");
+
+    Ok(node)
+}
+
+/// This node listens on 3000, forwards to 127.0.0.1:4000, and runs the directory where it
+/// registers the echoer as "echo-service".
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle node that runs a directory, listens on 3000 and forwards to 4000 → register 'echo-service' → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    let connection_to_responder = tcp_transport
+        .connect("127.0.0.1:4000", TcpConnectionOptions::new())
+        .await
+        .during(&scope, "connect to 127.0.0.1:4000")?;
+    let connection_to_responder_address = connection_to_responder.sender_address().clone();
+    node.start_worker(
+        "forward_to_responder",
+        Forwarder {
+            address: connection_to_responder.into(),
+        },
+    )
+    .await
+    .during(&scope, "start worker 'forward_to_responder'")?;
+
+    node.start_worker("directory", ServiceDirectory::new())
+        .await
+        .during(&scope, "start worker 'directory'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:3000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("forward_to_responder", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("directory", listener.flow_control_id());
+
+    // Register the echoer w/ the route that the directory's clients use to reach it.
+    let echo_service_route: RouteSpec = "tcp:localhost:3000 / forward_to_responder / echoer"
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route to 'echo-service'")?;
+    let record = DirectoryClient::new(route!["directory"])
+        .register(
+            node.context(),
+            ECHO_SERVICE,
+            &echo_service_route,
+            Duration::from_secs(60),
+            ServiceHealth::Healthy,
+        )
+        .await
+        .during(&scope, format!("register '{}'", ECHO_SERVICE))?;
+    presenter.println(format!(
+        "📒 registered '{}' at '{}' for {}s",
+        record.name,
+        record.route.green(),
+        record.expires_in_ms / 1000
+    ));
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "directory")
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_responder")
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            connection_to_responder_address,
+        );
+
+    Ok(node)
+}
+
+/// This node asks the directory for "echo-service", and sends a message to it.
+async fn create_initiator_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<()> {
+    print_title(
+        "Create a node that resolves 'echo-service' w/ the directory, and routes a message to it → stop",
+    );
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let resolver = RouteResolver::new(&node, &tcp_transport);
+
+    // Connect to the directory.
+    let directory_route: RouteSpec = DIRECTORY_ROUTE
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route to the directory")?;
+    let directory = resolver
+        .resolve(&directory_route)
+        .await
+        .during(&scope, format!("resolve the route {}", directory_route))?;
+
+    // Ask it for the echo service.
+    let record = DirectoryClient::new(directory.route.clone())
+        .resolve(node.context(), ECHO_SERVICE)
+        .await
+        .during(
+            &scope,
+            format!("resolve '{}' w/ the directory", ECHO_SERVICE),
+        )?;
+    presenter.println(format!(
+        "📒 '{}' is at '{}' ({:?}, expires in {}s)",
+        record.name,
+        record.route.green(),
+        record.health,
+        record.expires_in_ms / 1000
+    ));
+    if record.health != ServiceHealth::Healthy {
+        presenter.println(
+            format!("'{}' is unhealthy, not sending", record.name)
+                .red()
+                .to_string(),
+        );
+        return Ok(());
+    }
+
+    let service_route = record
+        .route_spec()
+        .map_err(ockam::Error::from)
+        .during(&scope, format!("parse the route to '{}'", ECHO_SERVICE))?;
+    let service = resolver
+        .resolve(&service_route)
+        .await
+        .during(&scope, format!("resolve the route {}", service_route))?;
+    for (_, sender_address) in directory
+        .tcp_connections
+        .iter()
+        .chain(service.tcp_connections.iter())
+    {
+        coordinator.register_worker(
+            NodeRole::Initiator,
+            "node_initiator",
+            sender_address.clone(),
+        );
+    }
+
+    let route = service.route;
+    let route_msg = format!("{:?}", route);
+    let msg = "Hello Ockam!";
+    emit_event(&scope.node_name, Event::message_sent(&route, msg));
+    let reply = node
+        .send_and_receive::<String>(route.clone(), msg.to_string())
+        .await
+        .during(
+            &scope,
+            format!("send '{}' and receive a reply over {}", msg, route_msg),
+        )?;
+    emit_event(&scope.node_name, Event::reply_received(&route, &reply));
+    presenter.println(format!(
+        "App Sending: '{0}', to '{1}' over route: '{2}', and received: '{3}'",
+        msg.red(),
+        ECHO_SERVICE,
+        route_msg.green(),
+        reply.yellow()
+    ));
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
@@ -0,0 +1,155 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, wait_for_signal, EchoService, Event, NodeResult, NodeRole, NodeScope,
+    Presenter, ResultExt, RetryPolicy, RpcCallError, RpcClient, RpcServer, ShutdownCoordinator,
+};
+use ockam::{
+    node, route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+use std::time::Duration;
+
+/// examples/09-rpc.rs
+/// The responder runs the [EchoService] behind an [RpcServer] at "echo_rpc". The initiator
+/// calls it w/ an [RpcClient]: once w/ a valid request, once w/ an empty one (which gets an
+/// error reply), and once w/ the wrong address (which times out after 3 attempts).
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the initiator is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_initiator_node(ctx_clone, &mut coordinator) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts a tcp listener on 4000 and the echo rpc server.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that runs tcp listener on 4000 and 'echo_rpc' server → wait for calls until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    node.start_worker("echo_rpc", RpcServer::new(EchoService))
+        .await
+        .during(&scope, "start worker 'echo_rpc'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:4000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(&scope.node_name, Event::tcp_listener_bound(listener.socket_address()));
+    node.flow_controls()
+        .add_consumer("echo_rpc", listener.flow_control_id());
+    presenter.println("🎙️ echo_rpc is reachable via tcp listener on 127.0.0.1:4000");
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "echo_rpc");
+
+    Ok(node)
+}
+
+/// This node calls 'echo_rpc' 3 times and prints how each call turned out.
+async fn create_initiator_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<()> {
+    print_title("Create a node that calls 'echo_rpc' w/ a timeout & retries → stop");
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let connection_to_responder = tcp_transport
+        .connect("localhost:4000", TcpConnectionOptions::new())
+        .await
+        .during(&scope, "connect to localhost:4000")?;
+    let connection_address = connection_to_responder.sender_address().clone();
+    coordinator.register_worker(
+        NodeRole::Initiator,
+        "node_initiator",
+        connection_address.clone(),
+    );
+
+    let retry_policy = RetryPolicy::exponential(3, Duration::from_millis(100));
+    let echo = RpcClient::<String, String>::new(route![connection_address.clone(), "echo_rpc"])
+        .with_timeout(Duration::from_millis(500))
+        .with_retry_policy(retry_policy);
+    let wrong_address =
+        RpcClient::<String, String>::new(route![connection_address, "no_such_service"])
+            .with_timeout(Duration::from_millis(500))
+            .with_retry_policy(retry_policy);
+
+    let calls = [
+        (&echo, "Hello Ockam!"),
+        (&echo, ""),
+        (&wrong_address, "Hello Ockam!"),
+    ];
+    for (client, request) in calls {
+        let outcome = match client.call(node.context(), &request.to_string()).await {
+            Ok(response) => format!("✅ '{}'", response).green(),
+            Err(RpcCallError::Transport(error)) => {
+                return Err(error).during(&scope, format!("call w/ '{}'", request));
+            }
+            Err(error) => format!("❌ {}", error).red(),
+        };
+        presenter.println(format!(
+            "📞 Calling w/ '{}' → {}",
+            request.yellow(),
+            outcome
+        ));
+    }
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
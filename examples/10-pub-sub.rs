@@ -0,0 +1,374 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, wait_for_signal, Broker, BrokerClient, Event, Forwarder, NodeResult,
+    NodeRole, NodeScope, Presenter, ResultExt, RouteResolver, RouteSpec, ShutdownCoordinator,
+    Subscriber, SubscriberEvent,
+};
+use ockam::access_control::AllowAll;
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    node, Address, AsyncTryClone, Context, Result, Routed, TcpConnectionOptions,
+    TcpListenerOptions, TcpTransportExtension,
+};
+
+/// The topic that both subscribers subscribe to, and that the publisher publishes to.
+const TOPIC: &str = "news";
+
+/// What the publisher publishes.
+const MESSAGES: [&str; 3] = ["Hello Ockam!", "Hello again!", "Goodbye!"];
+
+/// The route to the broker over two TCP hops, like examples/04-routing-over-two-transport-hops.rs.
+const TCP_BROKER_ROUTE: &str = "tcp:localhost:3000 / forward_to_bob / broker";
+
+/// The route to the broker over a secure channel, like
+/// examples/05-secure-channel-over-two-transport-hops-responder.rs.
+const SECURE_BROKER_ROUTE: &str =
+    "secure(tcp:localhost:3000 / forward_to_bob / bob_listener) / broker";
+
+/// examples/10-pub-sub.rs
+/// The responder runs a broker, behind a middle (forwarder) node. One subscriber reaches it
+/// over two TCP hops, the other one over a secure channel. The publisher publishes a few
+/// messages to the topic, and the broker fans each one out to both subscribers.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    // The subscriber nodes receive the events that their subscriber forwards to them, which
+    // a context from `async_try_clone` can't (it denies all messages).
+    let ctx_clone_2 = ctx
+        .new_detached(
+            Address::random_tagged("node_subscriber_tcp"),
+            AllowAll,
+            AllowAll,
+        )
+        .await?;
+    let ctx_clone_3 = ctx
+        .new_detached(
+            Address::random_tagged("node_subscriber_sc"),
+            AllowAll,
+            AllowAll,
+        )
+        .await?;
+    let ctx_clone_4 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    let mut subscriber_nodes = vec![];
+    for (ctx, node_name, route) in [
+        (ctx_clone_2, "node_subscriber_tcp", TCP_BROKER_ROUTE),
+        (ctx_clone_3, "node_subscriber_sc", SECURE_BROKER_ROUTE),
+    ] {
+        let node = create_subscriber_node(ctx, &mut coordinator, node_name, route)
+            .await
+            .unwrap_or_else(|error| error.report_and_exit());
+        subscriber_nodes.push((node_name, node));
+    }
+
+    // Ctrl-C while the publisher is running stops all the nodes gracefully.
+    tokio::select! {
+        result = async {
+            create_publisher_node(ctx_clone_4, &mut coordinator).await?;
+            for (node_name, node) in subscriber_nodes.iter_mut() {
+                wait_for_deliveries(node_name, node).await?;
+            }
+            NodeResult::Ok(())
+        } => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts a tcp listener on 4000, a secure channel listener, and a broker
+/// that is reachable over both. It then runs forever fanning out messages.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that runs tcp listener on 4000, a secure channel listener (for `bob`) and a broker worker → wait for messages until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    node.start_worker("broker", Broker::new())
+        .await
+        .during(&scope, "start worker 'broker'")?;
+
+    let id_bob = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:4000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
+
+    // Allow access to the Broker via TCP connections, and via secure channels.
+    node.flow_controls()
+        .add_consumer("broker", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("broker", secure_channel_listener.flow_control_id());
+    presenter.println(
+        "📮 broker is reachable via tcp listener on 127.0.0.1:4000, and via secure channel listener 'bob_listener'",
+    );
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "bob_listener")
+        .register_worker(NodeRole::Responder, "node_responder", "broker");
+
+    Ok(node)
+}
+
+/// This node listens on 3000 and forwards everything to 127.0.0.1:4000.
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle (forwarder) node that listens for TCP on 3000 and forwards to 4000 → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    let tcp_connection_to_bob = tcp_transport
+        .connect("127.0.0.1:4000", TcpConnectionOptions::new())
+        .await
+        .during(&scope, "connect to 127.0.0.1:4000")?;
+    let tcp_connection_to_bob_address = tcp_connection_to_bob.sender_address().clone();
+    node.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: tcp_connection_to_bob.into(),
+        },
+    )
+    .await
+    .during(&scope, "start worker 'forward_to_bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:3000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+    presenter.println("👉 forward_to_bob forwards from 127.0.0.1:3000 to 127.0.0.1:4000");
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_bob")
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            tcp_connection_to_bob_address,
+        );
+
+    Ok(node)
+}
+
+/// This node resolves `broker_route`, and starts a subscriber to [TOPIC] over it. It
+/// returns once the broker has confirmed the subscription.
+async fn create_subscriber_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+    node_name: &str,
+    broker_route: &str,
+) -> NodeResult<ockam::Node> {
+    print_title(&format!(
+        "Create a node that subscribes to '{}' over '{}' → wait for messages until stopped",
+        TOPIC, broker_route
+    ));
+    let scope = NodeScope::new(NodeRole::Initiator, node_name);
+    let presenter = Presenter::for_role(NodeRole::Initiator, node_name);
+
+    let mut node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    let route_spec: RouteSpec = broker_route
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route to the broker")?;
+    let id_alice = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'alice'")?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let resolved = RouteResolver::new(&node, &tcp_transport)
+        .with_identity(&id_alice)
+        .resolve(&route_spec)
+        .await
+        .during(&scope, format!("resolve the route {}", route_spec))?;
+
+    let subscriber_address = format!("subscriber_{}", node_name);
+    // The broker's deliveries arrive over the route's connections & secure channels.
+    resolved.add_consumer(&node, subscriber_address.as_str());
+    let subscriber =
+        Subscriber::new(resolved.route, TOPIC).with_forward_to(node.context().address());
+    node.start_worker(subscriber_address.as_str(), subscriber)
+        .await
+        .during(&scope, format!("start worker '{}'", subscriber_address))?;
+
+    match node.receive::<SubscriberEvent>().await.map(Routed::body) {
+        Ok(SubscriberEvent::Subscribed {
+            subscription_id, ..
+        }) => presenter.println(format!(
+            "📬 '{}' is subscription #{} to '{}'",
+            subscriber_address,
+            subscription_id,
+            TOPIC.green()
+        )),
+        Ok(event) => presenter.println(format!("unexpected event: {:?}", event).red().to_string()),
+        Err(error) => return Err(error).during(&scope, format!("subscribe to '{}'", TOPIC)),
+    }
+
+    coordinator.register_worker(NodeRole::Initiator, node_name, subscriber_address);
+    let addresses = resolved
+        .tcp_connections
+        .iter()
+        .map(|it| &it.1)
+        .chain(resolved.secure_channels.iter().map(|it| &it.1));
+    for address in addresses {
+        coordinator.register_worker(NodeRole::Initiator, node_name, address.clone());
+    }
+
+    Ok(node)
+}
+
+/// This node publishes [MESSAGES] to [TOPIC] over two TCP hops.
+async fn create_publisher_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<()> {
+    print_title(&format!(
+        "Create a node that publishes {} messages to '{}' → stop",
+        MESSAGES.len(),
+        TOPIC
+    ));
+    let scope = NodeScope::new(NodeRole::Initiator, "node_publisher");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_publisher");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let route_spec: RouteSpec = TCP_BROKER_ROUTE
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route to the broker")?;
+    let resolved = RouteResolver::new(&node, &tcp_transport)
+        .resolve(&route_spec)
+        .await
+        .during(&scope, format!("resolve the route {}", route_spec))?;
+    for (_, sender_address) in &resolved.tcp_connections {
+        coordinator.register_worker(
+            NodeRole::Initiator,
+            "node_publisher",
+            sender_address.clone(),
+        );
+    }
+
+    let broker = BrokerClient::new(resolved.route);
+    for msg in MESSAGES {
+        let receipt = broker
+            .publish(node.context(), TOPIC, msg)
+            .await
+            .during(&scope, format!("publish '{}' to '{}'", msg, TOPIC))?;
+        presenter.println(format!(
+            "App Publishing: '{}' to '{}' → #{} sent to {} subscriber(s)",
+            msg.red(),
+            TOPIC.green(),
+            receipt.sequence,
+            receipt.delivered.to_string().yellow()
+        ));
+    }
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
+
+/// Wait until the subscriber on `node` has received every one of [MESSAGES].
+async fn wait_for_deliveries(node_name: &str, node: &mut ockam::Node) -> NodeResult<()> {
+    let scope = NodeScope::new(NodeRole::Initiator, node_name);
+    let mut received = 0;
+    while received < MESSAGES.len() {
+        let event = node
+            .receive::<SubscriberEvent>()
+            .await
+            .during(&scope, "wait for the published messages")?;
+        if let SubscriberEvent::Delivery(_) = event.body() {
+            received += 1;
+        }
+    }
+    Ok(())
+}
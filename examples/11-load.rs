@@ -0,0 +1,295 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, redirect_output, restore_output, wait_for_signal, Echoer, Event,
+    Forwarder, Hopper, LoadGenerator, NodeResult, NodeRole, NodeScope, Presenter, ResultExt,
+    RouteResolver, RouteSpec, ShutdownCoordinator,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    node, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+
+/// The routes that are measured, unless one is passed as the first argument. Each one adds
+/// a kind of hop to the one before it.
+const SCENARIOS: [&str; 6] = [
+    "echoer",
+    "h1 / h2 / h3 / echoer",
+    "tcp:localhost:4000 / echoer",
+    "tcp:localhost:3000 / forward_to_bob / echoer",
+    "secure(tcp:localhost:4000 / bob_listener) / echoer",
+    "secure(tcp:localhost:3000 / forward_to_bob / bob_listener) / echoer",
+];
+
+/// examples/11-load.rs
+/// The responder & middle nodes of
+/// examples/05-secure-channel-over-two-transport-hops-responder.rs, and a load node w/ a
+/// local hop chain. The load node sends messages to the echoer over each of the
+/// [SCENARIOS], and reports the throughput & latencies, to show what each hop costs.
+///
+/// Pass a route as the first argument to only measure that one (eg: "h1 / echoer"), and
+/// the concurrency, message size (in bytes), and number of messages as the next ones.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the load node is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_load_node(ctx_clone_2, &mut coordinator) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+    restore_output();
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts a tcp listener on 4000, a secure channel listener, and an echoer
+/// worker that is reachable over both.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that runs tcp listener on 4000, a secure channel listener (for `bob`) and an echoer worker → wait for messages until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
+
+    let id_bob = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:4000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
+
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("echoer", secure_channel_listener.flow_control_id());
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "bob_listener")
+        .register_worker(NodeRole::Responder, "node_responder", "echoer");
+
+    Ok(node)
+}
+
+/// This node listens on 3000 and forwards everything to 127.0.0.1:4000.
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle (forwarder) node that listens for TCP on 3000 and forwards to 4000 → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    let tcp_connection_to_bob = tcp_transport
+        .connect("127.0.0.1:4000", TcpConnectionOptions::new())
+        .await
+        .during(&scope, "connect to 127.0.0.1:4000")?;
+    let tcp_connection_to_bob_address = tcp_connection_to_bob.sender_address().clone();
+    node.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: tcp_connection_to_bob.into(),
+        },
+    )
+    .await
+    .during(&scope, "start worker 'forward_to_bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:3000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_bob")
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            tcp_connection_to_bob_address,
+        );
+
+    Ok(node)
+}
+
+/// This node starts the local hop chain, and then measures each route in turn.
+async fn create_load_node(ctx: Context, coordinator: &mut ShutdownCoordinator) -> NodeResult<()> {
+    print_title("Create a node that sends load over each route → print the results → stop");
+    let scope = NodeScope::new(NodeRole::Initiator, "node_load");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_load");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    let args: Vec<String> = std::env::args().collect();
+    let routes: Vec<String> = match args.get(1) {
+        Some(route) => vec![route.clone()],
+        None => SCENARIOS.iter().map(|it| it.to_string()).collect(),
+    };
+    let arg = |index: usize, default: u64| {
+        args.get(index)
+            .and_then(|it| it.parse::<u64>().ok())
+            .unwrap_or(default)
+    };
+    let (concurrency, message_size, messages) = (arg(2, 8), arg(3, 256), arg(4, 2000));
+
+    for hopper in ["h1", "h2", "h3"] {
+        node.start_worker(hopper, Hopper)
+            .await
+            .during(&scope, format!("start worker '{}'", hopper))?;
+        coordinator.register_worker(NodeRole::Initiator, "node_load", hopper);
+    }
+    let id_alice = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'alice'")?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let resolver = RouteResolver::new(&node, &tcp_transport).with_identity(&id_alice);
+
+    let mut results = vec![];
+    for route in routes {
+        let route_spec: RouteSpec = route
+            .parse()
+            .map_err(ockam::Error::from)
+            .during(&scope, "parse the route")?;
+        let resolved = resolver
+            .resolve(&route_spec)
+            .await
+            .during(&scope, format!("resolve the route {}", route_spec))?;
+        let addresses = resolved
+            .tcp_connections
+            .iter()
+            .map(|it| &it.1)
+            .chain(resolved.secure_channels.iter().map(|it| &it.1));
+        for address in addresses {
+            coordinator.register_worker(NodeRole::Initiator, "node_load", address.clone());
+        }
+
+        presenter.println(format!(
+            "🚚 {} messages of {} bytes, {} at once, over {}",
+            messages,
+            message_size,
+            concurrency,
+            route_spec.to_string().green()
+        ));
+        // Don't let the workers print every message while measuring.
+        redirect_output(|_, _| {});
+        let report = LoadGenerator::new(resolved.route)
+            .with_concurrency(concurrency as usize)
+            .with_message_size(message_size as usize)
+            .with_messages(messages)
+            .run(node.context())
+            .await;
+        restore_output();
+        let report = report.during(&scope, format!("send load over {}", route_spec))?;
+        presenter.println(report.to_string().yellow().to_string());
+        results.push((route_spec, report));
+    }
+
+    presenter.println(format!(
+        "{:>10} {:>10} {:>10}  route",
+        "msg/s", "p50 ms", "p99 ms"
+    ));
+    for (route_spec, report) in results {
+        let millis = |percentile: f64| {
+            report
+                .percentile(percentile)
+                .map(|it| format!("{:.3}", it.as_secs_f64() * 1000.0))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        presenter.println(format!(
+            "{:>10.0} {:>10} {:>10}  {}",
+            report.throughput(),
+            millis(50.0),
+            millis(99.0),
+            route_spec
+        ));
+    }
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
@@ -0,0 +1,238 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, start_forwarder, wait_for_signal, Echoer, Event, NodeResult, NodeRole,
+    NodeScope, NodeTransports, Presenter, ResultExt, RouteHop, RouteResolver, RouteSpec,
+    ShutdownCoordinator,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{node, AsyncTryClone, Context, MessageSendReceiveOptions, Result};
+use std::time::Duration;
+
+/// The routes that the initiator sends "Hello Ockam!" over: the two hop echo, and the
+/// secure channel across the forwarder.
+const ROUTES: [&str; 2] = [
+    "udp:localhost:3000 / forward_to_bob / echoer",
+    "secure(udp:localhost:3000 / forward_to_bob / bob_listener) / echoer",
+];
+
+/// examples/12-udp.rs
+/// Like examples/04-routing-over-two-transport-hops.rs and
+/// examples/05-secure-channel-over-two-transport-hops-responder.rs, but all the hops
+/// between the nodes are UDP datagrams on loopback instead of TCP connections.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the initiator is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_initiator_node(ctx_clone_2, &mut coordinator) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts a udp listener on 4000, a secure channel listener, and an echoer
+/// worker that is reachable over both.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that runs udp listener on 4000, a secure channel listener (for `bob`) and an echoer worker → wait for messages until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let transports = NodeTransports::create(&node)
+        .await
+        .during(&scope, "create transports")?;
+
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
+
+    let id_bob = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'bob'")?;
+
+    let listener = transports
+        .listen(&RouteHop::Udp("127.0.0.1:4000".into()))
+        .await
+        .during(&scope, "listen on udp:127.0.0.1:4000")?;
+
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
+
+    // Allow access to the Echoer via UDP, and via secure channels.
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("echoer", secure_channel_listener.flow_control_id());
+    presenter.println(
+        "📣 echoer is reachable via udp listener on 127.0.0.1:4000, and via secure channel listener 'bob_listener'",
+    );
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "bob_listener")
+        .register_worker(NodeRole::Responder, "node_responder", "echoer");
+
+    Ok(node)
+}
+
+/// This node listens for UDP on 3000 and forwards everything to 127.0.0.1:4000.
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle (forwarder) node that listens for UDP on 3000 and forwards to 4000 → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let transports = NodeTransports::create(&node)
+        .await
+        .during(&scope, "create transports")?;
+
+    let connection_to_bob = transports
+        .connect(&RouteHop::Udp("127.0.0.1:4000".into()))
+        .await
+        .during(&scope, "connect to udp:127.0.0.1:4000")?;
+    let listener = transports
+        .listen(&RouteHop::Udp("127.0.0.1:3000".into()))
+        .await
+        .during(&scope, "listen on udp:127.0.0.1:3000")?;
+    start_forwarder(&node, "forward_to_bob", &listener, &connection_to_bob)
+        .await
+        .during(&scope, "start worker 'forward_to_bob'")?;
+    presenter.println("👉 forward_to_bob forwards from udp 127.0.0.1:3000 to udp 127.0.0.1:4000");
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            connection_to_bob.receiver_address().clone(),
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_bob")
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            connection_to_bob.sender_address().clone(),
+        );
+
+    Ok(node)
+}
+
+/// This node resolves each of the [ROUTES], sends "Hello Ockam!" over it, and prints the
+/// reply.
+async fn create_initiator_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<()> {
+    print_title("Create a node that sends a message over UDP, w/ & w/out a secure channel → print the replies → stop");
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    let id_alice = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'alice'")?;
+    let transports = NodeTransports::create(&node)
+        .await
+        .during(&scope, "create transports")?;
+    let resolver = RouteResolver::new(&node, &transports.tcp)
+        .with_udp_transport(&transports.udp)
+        .with_identity(&id_alice);
+
+    for text in ROUTES {
+        let route_spec: RouteSpec = text
+            .parse()
+            .map_err(ockam::Error::from)
+            .during(&scope, "parse the route")?;
+        let resolved = resolver
+            .resolve(&route_spec)
+            .await
+            .during(&scope, format!("resolve the route {}", route_spec))?;
+        let addresses = resolved
+            .udp_connections
+            .iter()
+            .map(|it| &it.1)
+            .chain(resolved.secure_channels.iter().map(|it| &it.1));
+        for address in addresses {
+            coordinator.register_worker(NodeRole::Initiator, "node_initiator", address.clone());
+        }
+
+        let reply = node
+            .context()
+            .send_and_receive_extended::<String>(
+                resolved.route,
+                "Hello Ockam!".to_string(),
+                MessageSendReceiveOptions::new().with_timeout(Duration::from_secs(5)),
+            )
+            .await
+            .during(&scope, format!("send a message over {}", route_spec))?;
+        presenter.println(format!(
+            "{} → {}",
+            route_spec.to_string().green(),
+            reply.body().yellow()
+        ));
+    }
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
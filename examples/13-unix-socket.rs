@@ -0,0 +1,279 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, wait_for_signal, Echoer, Event, Forwarder, NodeResult, NodeRole,
+    NodeScope, Presenter, ResultExt, RouteResolver, RouteSpec, ShutdownCoordinator,
+    UnixConnectionOptions, UnixListenerOptions, UnixTransportExtension,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    node, AsyncTryClone, Context, MessageSendReceiveOptions, Result, TcpListenerOptions,
+    TcpTransportExtension,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The routes that the initiator sends "Hello Ockam!" over: the two hop echo, and the
+/// secure channel across the forwarder.
+const ROUTES: [&str; 2] = [
+    "tcp:localhost:3000 / forward_to_bob / echoer",
+    "secure(tcp:localhost:3000 / forward_to_bob / bob_listener) / echoer",
+];
+
+/// examples/13-unix-socket.rs
+/// Like examples/05-secure-channel-over-two-transport-hops-responder.rs, but the responder
+/// doesn't open a TCP port: it listens on a Unix socket file that only its user may connect
+/// to, and the middle node (on the same host) forwards to it over that socket.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the initiator is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_initiator_node(ctx_clone_2, &mut coordinator) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// The socket file that the responder listens on.
+fn bob_socket_path() -> PathBuf {
+    std::env::temp_dir().join("hello_ockam_bob.sock")
+}
+
+/// This node listens on a Unix socket file (w/ 0o600 permissions), and starts a secure
+/// channel listener, and an echoer worker that is reachable over both.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that listens on a unix socket, a secure channel listener (for `bob`) and an echoer worker → wait for messages until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let unix_transport = node
+        .create_unix_transport()
+        .await
+        .during(&scope, "create unix transport")?;
+
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
+
+    let id_bob = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'bob'")?;
+
+    let path = bob_socket_path();
+    let listener = unix_transport
+        .listen(&path, UnixListenerOptions::new().with_mode(0o600))
+        .await
+        .during(&scope, format!("listen on {}", path.display()))?;
+    emit_event(
+        &scope.node_name,
+        Event::ListenerBound {
+            transport: "unix".to_string(),
+            socket_address: path.display().to_string(),
+        },
+    );
+
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
+
+    // Allow access to the Echoer via the Unix socket, and via secure channels.
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("echoer", secure_channel_listener.flow_control_id());
+    presenter.println(format!(
+        "📣 echoer is reachable via unix socket {}, and via secure channel listener 'bob_listener'",
+        path.display()
+    ));
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "bob_listener")
+        .register_worker(NodeRole::Responder, "node_responder", "echoer");
+
+    Ok(node)
+}
+
+/// This node listens for TCP on 3000 and forwards everything to the responder's socket.
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle (forwarder) node that listens for TCP on 3000 and forwards to the unix socket → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let unix_transport = node
+        .create_unix_transport()
+        .await
+        .during(&scope, "create unix transport")?;
+
+    let path = bob_socket_path();
+    let unix_connection_to_bob = unix_transport
+        .connect(&path, UnixConnectionOptions::new())
+        .await
+        .during(&scope, format!("connect to {}", path.display()))?;
+    let unix_connection_to_bob_sender = unix_connection_to_bob.sender_address().clone();
+    let unix_connection_to_bob_receiver = unix_connection_to_bob.receiver_address().clone();
+    node.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: unix_connection_to_bob.into(),
+        },
+    )
+    .await
+    .during(&scope, "start worker 'forward_to_bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:3000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+    presenter.println(format!(
+        "👉 forward_to_bob forwards from 127.0.0.1:3000 to {}",
+        path.display()
+    ));
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            unix_connection_to_bob_receiver,
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_bob")
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            unix_connection_to_bob_sender,
+        );
+
+    Ok(node)
+}
+
+/// This node resolves each of the [ROUTES], sends "Hello Ockam!" over it, and prints the
+/// reply.
+async fn create_initiator_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<()> {
+    print_title("Create a node that sends a message over TCP & the forwarder, w/ & w/out a secure channel → print the replies → stop");
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    let id_alice = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'alice'")?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let resolver = RouteResolver::new(&node, &tcp_transport).with_identity(&id_alice);
+
+    for text in ROUTES {
+        let route_spec: RouteSpec = text
+            .parse()
+            .map_err(ockam::Error::from)
+            .during(&scope, "parse the route")?;
+        let resolved = resolver
+            .resolve(&route_spec)
+            .await
+            .during(&scope, format!("resolve the route {}", route_spec))?;
+        let addresses = resolved
+            .tcp_connections
+            .iter()
+            .map(|it| &it.1)
+            .chain(resolved.secure_channels.iter().map(|it| &it.1));
+        for address in addresses {
+            coordinator.register_worker(NodeRole::Initiator, "node_initiator", address.clone());
+        }
+
+        let reply = node
+            .context()
+            .send_and_receive_extended::<String>(
+                resolved.route,
+                "Hello Ockam!".to_string(),
+                MessageSendReceiveOptions::new().with_timeout(Duration::from_secs(5)),
+            )
+            .await
+            .during(&scope, format!("send a message over {}", route_spec))?;
+        presenter.println(format!(
+            "{} → {}",
+            route_spec.to_string().green(),
+            reply.body().yellow()
+        ));
+    }
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
@@ -0,0 +1,281 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, http_get, print_title, wait_for_signal, Event, Forwarder, HttpTarget, NodeResult,
+    NodeRole, NodeScope, Presenter, ResultExt, RouteResolver, RouteSpec, ShutdownCoordinator,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    node, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpInletOptions,
+    TcpListenerOptions, TcpOutletOptions, TcpTransportExtension,
+};
+
+/// The route from the inlet to the outlet, unless another one is passed as the first
+/// argument.
+const DEFAULT_ROUTE: &str = "secure(tcp:localhost:3000 / forward_to_bob / bob_listener) / outlet";
+
+/// Where the inlet listens for TCP connections, on the initiator's side.
+const INLET_ADDRESS: &str = "127.0.0.1:4001";
+
+/// Where the HTTP server that the outlet connects to listens, on the responder's side.
+const TARGET_ADDRESS: &str = "127.0.0.1:5000";
+
+/// examples/14-portal.rs
+/// Like examples/05-secure-channel-over-two-transport-hops-responder.rs, but instead of
+/// sending String messages to an echoer, it tunnels a real TCP service: the raw TCP streams
+/// that connect to the inlet (on the initiator's side) go through the secure channel and
+/// the forwarder to the outlet, which connects to an HTTP server next to the responder.
+///
+/// Pass the route to the outlet as the first argument, and the number of HTTP requests as
+/// the second one.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let (_node_responder, _http_target) = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the client is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_client_node(ctx_clone_2, &mut coordinator) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts an HTTP server on 5000, a tcp listener on 4000, a secure channel
+/// listener, and an outlet that is only reachable over secure channels. It then runs
+/// forever waiting for connections.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<(ockam::Node, HttpTarget)> {
+    print_title(
+        "Create a node that runs an HTTP server on 5000, tcp listener on 4000, a secure channel listener (for `bob`) and an outlet to the HTTP server → wait for connections until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let http_target = HttpTarget::start(
+        TARGET_ADDRESS,
+        "👋 Hello from the HTTP server behind the outlet!",
+    )
+    .await
+    .during(
+        &scope,
+        format!("start the HTTP server on {}", TARGET_ADDRESS),
+    )?;
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    let id_bob = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:4000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
+
+    // Only allow access to the outlet via secure channels, not via plain TCP.
+    tcp_transport
+        .create_outlet(
+            "outlet",
+            TARGET_ADDRESS,
+            TcpOutletOptions::new().as_consumer(secure_channel_listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, format!("create outlet to {}", TARGET_ADDRESS))?;
+    presenter.println(format!(
+        "🚪 outlet connects to {}, and is reachable via secure channel listener 'bob_listener'",
+        TARGET_ADDRESS
+    ));
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "bob_listener")
+        .register_worker(NodeRole::Responder, "node_responder", "outlet");
+
+    Ok((node, http_target))
+}
+
+/// This node listens on 3000 and forwards everything to 127.0.0.1:4000.
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle (forwarder) node that listens for TCP on 3000 and forwards to 4000 → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    let tcp_connection_to_bob = tcp_transport
+        .connect("127.0.0.1:4000", TcpConnectionOptions::new())
+        .await
+        .during(&scope, "connect to 127.0.0.1:4000")?;
+    let tcp_connection_to_bob_address = tcp_connection_to_bob.sender_address().clone();
+    node.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: tcp_connection_to_bob.into(),
+        },
+    )
+    .await
+    .during(&scope, "start worker 'forward_to_bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:3000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+    presenter.println("👉 forward_to_bob forwards from 127.0.0.1:3000 to 127.0.0.1:4000");
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_bob")
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            tcp_connection_to_bob_address,
+        );
+
+    Ok(node)
+}
+
+/// This node resolves the route to the outlet, starts an inlet on 4001 that tunnels to it,
+/// and sends HTTP requests to the inlet.
+async fn create_client_node(ctx: Context, coordinator: &mut ShutdownCoordinator) -> NodeResult<()> {
+    print_title("Create a node that runs an inlet on 4001 → send HTTP requests to it, which go through the outlet → print the responses → stop");
+    let scope = NodeScope::new(NodeRole::Initiator, "node_client");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_client");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    let route_spec: RouteSpec = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ROUTE.to_string())
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route")?;
+    let count = std::env::args()
+        .nth(2)
+        .and_then(|it| it.parse::<u64>().ok())
+        .unwrap_or(3);
+
+    let id_alice = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'alice'")?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let resolved = RouteResolver::new(&node, &tcp_transport)
+        .with_identity(&id_alice)
+        .resolve(&route_spec)
+        .await
+        .during(&scope, format!("resolve the route {}", route_spec))?;
+    let addresses = resolved
+        .tcp_connections
+        .iter()
+        .map(|it| &it.1)
+        .chain(resolved.secure_channels.iter().map(|it| &it.1));
+    for address in addresses {
+        coordinator.register_worker(NodeRole::Initiator, "node_client", address.clone());
+    }
+
+    let (inlet_socket_address, inlet_address) = tcp_transport
+        .create_inlet(INLET_ADDRESS, resolved.route, TcpInletOptions::new())
+        .await
+        .during(&scope, format!("create inlet on {}", INLET_ADDRESS))?;
+    coordinator.register_processor(NodeRole::Initiator, "node_client", inlet_address);
+    presenter.println(format!(
+        "🚪 inlet on {} tunnels to {}",
+        inlet_socket_address,
+        route_spec.to_string().green()
+    ));
+
+    for index in 1..=count {
+        let response = http_get(&inlet_socket_address.to_string(), "/")
+            .await
+            .during(&scope, format!("GET http://{}/", inlet_socket_address))?;
+        presenter.println(format!(
+            "#{} GET http://{}/ → {} {}",
+            index,
+            inlet_socket_address,
+            response.status,
+            response.body.yellow()
+        ));
+    }
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
@@ -0,0 +1,292 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, wait_for_signal, ChaosProxy, ChaosSettings, Echoer, Event, Forwarder,
+    NodeResult, NodeRole, NodeScope, Presenter, ResultExt, RouteResolver, RouteSpec,
+    ShutdownCoordinator,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    node, AsyncTryClone, Context, MessageSendReceiveOptions, Result, TcpConnectionOptions,
+    TcpListenerOptions, TcpTransportExtension,
+};
+use std::time::{Duration, Instant};
+
+/// The route that the initiator sends "Hello Ockam!" over.
+const ROUTE: &str = "secure(tcp:localhost:3000 / forward_to_bob / bob_listener) / echoer";
+
+/// The chaos proxy listens here, and connects to the responder's listener on 4000.
+const PROXY_ADDRESS: &str = "127.0.0.1:4100";
+
+/// How long the initiator waits for each reply.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A change to the link, eg: `|proxy| proxy.blackhole()`.
+type LinkChange = fn(&ChaosProxy);
+
+/// examples/15-chaos.rs
+/// Like examples/05-secure-channel-over-two-transport-hops-responder.rs, but the middle
+/// node connects to the responder via a [ChaosProxy] on 4100. The initiator sends a message
+/// over the secure channel after each change to the link (a delay, a slow link, a
+/// blackhole, healing it, and resetting the connections), to show how the forwarder & the
+/// secure channel behave.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    print_title("Start a chaos proxy on 4100 that connects to 4000");
+    let proxy = ChaosProxy::start(PROXY_ADDRESS, "127.0.0.1:4000").await?;
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the initiator is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_initiator_node(ctx_clone_2, &mut coordinator, &proxy) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts a tcp listener on 4000, a secure channel listener, and an echoer
+/// worker that is reachable over both.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that runs tcp listener on 4000, a secure channel listener (for `bob`) and an echoer worker → wait for messages until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
+
+    let id_bob = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:4000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
+
+    // Allow access to the Echoer via TCP connections, and via secure channels.
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("echoer", secure_channel_listener.flow_control_id());
+    presenter.println(
+        "📣 echoer is reachable via tcp listener on 127.0.0.1:4000, and via secure channel listener 'bob_listener'",
+    );
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "bob_listener")
+        .register_worker(NodeRole::Responder, "node_responder", "echoer");
+
+    Ok(node)
+}
+
+/// This node listens on 3000 and forwards everything to the chaos proxy on 4100.
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle (forwarder) node that listens for TCP on 3000 and forwards to the chaos proxy on 4100 → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    let tcp_connection_to_bob = tcp_transport
+        .connect(PROXY_ADDRESS, TcpConnectionOptions::new())
+        .await
+        .during(&scope, format!("connect to {}", PROXY_ADDRESS))?;
+    let tcp_connection_to_bob_address = tcp_connection_to_bob.sender_address().clone();
+    node.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: tcp_connection_to_bob.into(),
+        },
+    )
+    .await
+    .during(&scope, "start worker 'forward_to_bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:3000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+    presenter.println(format!(
+        "👉 forward_to_bob forwards from 127.0.0.1:3000 to {}",
+        PROXY_ADDRESS
+    ));
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_bob")
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            tcp_connection_to_bob_address,
+        );
+
+    Ok(node)
+}
+
+/// This node creates a secure channel over the forwarder & the chaos proxy, and sends a
+/// message over it after each change to the link.
+async fn create_initiator_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+    proxy: &ChaosProxy,
+) -> NodeResult<()> {
+    print_title("Create a node that sends a message over a secure channel after each change to the link → print what happened → stop");
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    let id_alice = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'alice'")?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let route_spec: RouteSpec = ROUTE
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route")?;
+    let resolved = RouteResolver::new(&node, &tcp_transport)
+        .with_identity(&id_alice)
+        .resolve(&route_spec)
+        .await
+        .during(&scope, format!("resolve the route {}", route_spec))?;
+    let addresses = resolved
+        .tcp_connections
+        .iter()
+        .map(|it| &it.1)
+        .chain(resolved.secure_channels.iter().map(|it| &it.1));
+    for address in addresses {
+        coordinator.register_worker(NodeRole::Initiator, "node_initiator", address.clone());
+    }
+
+    // Each change to the link, and how to make it.
+    let changes: [(&str, LinkChange); 5] = [
+        ("a 300ms delay each way", |proxy| {
+            proxy.set_settings(ChaosSettings::normal().with_delay(Duration::from_millis(300)))
+        }),
+        ("a 1 KB/s link", |proxy| {
+            proxy.set_settings(ChaosSettings::normal().with_bandwidth(1024))
+        }),
+        ("a blackhole", |proxy| proxy.blackhole()),
+        ("healed", |proxy| proxy.heal()),
+        ("the connections reset", |proxy| proxy.reset_connections()),
+    ];
+    send_and_report(&node, &presenter, &resolved.route, "a normal link").await;
+    for (name, change) in changes {
+        change(proxy);
+        send_and_report(&node, &presenter, &resolved.route, name).await;
+    }
+    presenter.println(format!("{:?}", proxy.stats()).yellow().to_string());
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
+
+/// Send "Hello Ockam!" over `route`, and print the reply & how long it took, or the error.
+async fn send_and_report(
+    node: &ockam::Node,
+    presenter: &Presenter,
+    route: &ockam::Route,
+    link: &str,
+) {
+    let started = Instant::now();
+    let reply = node
+        .context()
+        .send_and_receive_extended::<String>(
+            route.clone(),
+            "Hello Ockam!".to_string(),
+            MessageSendReceiveOptions::new().with_timeout(REPLY_TIMEOUT),
+        )
+        .await;
+    let outcome = match reply {
+        Ok(reply) => format!("{} in {:?}", reply.body(), started.elapsed()).green(),
+        Err(error) => format!("no reply after {:?}: {}", started.elapsed(), error).red(),
+    };
+    presenter.println(format!("w/ {} → {}", link, outcome));
+}
@@ -0,0 +1,282 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, wait_for_signal, ConnectionPool, Echoer, Event, Forwarder, NodeResult,
+    NodeRole, NodeScope, Presenter, ResultExt, RouteResolver, RouteSpec, ShutdownCoordinator,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    node, AsyncTryClone, Context, MessageSendReceiveOptions, Result, TcpConnectionOptions,
+    TcpListenerOptions, TcpTransportExtension,
+};
+use std::time::{Duration, Instant};
+
+/// The route that the initiator sends "Hello Ockam!" over.
+const ROUTE: &str = "secure(tcp:localhost:3000 / forward_to_bob / bob_listener) / echoer";
+
+/// How long the initiator waits for each reply.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// examples/16-connection-pool.rs
+/// Like examples/05-secure-channel-over-two-transport-hops-responder.rs, but the initiator
+/// sends "Hello Ockam!" several times: first w/ a new TCP connection & secure channel for
+/// each message, and then w/ the ones that a [ConnectionPool] keeps. It prints how long the
+/// messages took each way.
+///
+/// Pass the number of messages as the first argument.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the initiator is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_initiator_node(ctx_clone_2, &mut coordinator) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts a tcp listener on 4000, a secure channel listener, and an echoer
+/// worker that is reachable over both.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that runs tcp listener on 4000, a secure channel listener (for `bob`) and an echoer worker → wait for messages until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
+
+    let id_bob = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:4000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
+
+    // Allow access to the Echoer via TCP connections, and via secure channels.
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("echoer", secure_channel_listener.flow_control_id());
+    presenter.println(
+        "📣 echoer is reachable via tcp listener on 127.0.0.1:4000, and via secure channel listener 'bob_listener'",
+    );
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "bob_listener")
+        .register_worker(NodeRole::Responder, "node_responder", "echoer");
+
+    Ok(node)
+}
+
+/// This node listens on 3000 and forwards everything to 127.0.0.1:4000.
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle (forwarder) node that listens for TCP on 3000 and forwards to 4000 → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    let tcp_connection_to_bob = tcp_transport
+        .connect("127.0.0.1:4000", TcpConnectionOptions::new())
+        .await
+        .during(&scope, "connect to 127.0.0.1:4000")?;
+    let tcp_connection_to_bob_address = tcp_connection_to_bob.sender_address().clone();
+    node.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: tcp_connection_to_bob.into(),
+        },
+    )
+    .await
+    .during(&scope, "start worker 'forward_to_bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:3000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+    presenter.println("👉 forward_to_bob forwards from 127.0.0.1:3000 to 127.0.0.1:4000");
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_bob")
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            tcp_connection_to_bob_address,
+        );
+
+    Ok(node)
+}
+
+/// This node sends the messages w/out & w/ a [ConnectionPool], and prints how long they
+/// took.
+async fn create_initiator_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<()> {
+    print_title("Create a node that sends messages w/ a new secure channel each time, and then w/ a pooled one → print how long they took → stop");
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    let count = std::env::args()
+        .nth(1)
+        .and_then(|it| it.parse::<u32>().ok())
+        .unwrap_or(10)
+        .max(1);
+    let id_alice = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'alice'")?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let route_spec: RouteSpec = ROUTE
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route")?;
+
+    // A new connection & channel for each message, like the other examples.
+    let started = Instant::now();
+    for _ in 0..count {
+        let resolved = RouteResolver::new(&node, &tcp_transport)
+            .with_identity(&id_alice)
+            .resolve(&route_spec)
+            .await
+            .during(&scope, format!("resolve the route {}", route_spec))?;
+        let addresses = resolved
+            .tcp_connections
+            .iter()
+            .map(|it| &it.1)
+            .chain(resolved.secure_channels.iter().map(|it| &it.1));
+        for address in addresses {
+            coordinator.register_worker(NodeRole::Initiator, "node_initiator", address.clone());
+        }
+        node.context()
+            .send_and_receive_extended::<String>(
+                resolved.route,
+                "Hello Ockam!".to_string(),
+                MessageSendReceiveOptions::new().with_timeout(REPLY_TIMEOUT),
+            )
+            .await
+            .during(&scope, format!("send a message over {}", route_spec))?;
+    }
+    let unpooled = started.elapsed() / count;
+
+    let pool = ConnectionPool::new(&node, &tcp_transport);
+    let started = Instant::now();
+    for _ in 0..count {
+        let _reply: String = pool
+            .send_and_receive(
+                node.context(),
+                &route_spec,
+                Some(&id_alice),
+                "Hello Ockam!".to_string(),
+                REPLY_TIMEOUT,
+            )
+            .await
+            .during(&scope, format!("send a pooled message over {}", route_spec))?;
+    }
+    let pooled = started.elapsed() / count;
+
+    presenter.println(format!(
+        "{} messages over {}: {} each w/ a new secure channel, {} each w/ a pooled one",
+        count,
+        route_spec.to_string().green(),
+        format!("{:?}", unpooled).red(),
+        format!("{:?}", pooled).green()
+    ));
+    presenter.println(format!("{:?}", pool.stats().await).yellow().to_string());
+    pool.close_all().await;
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
@@ -0,0 +1,396 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::Colorize;
+use hello_ockam::{
+    emit_event, print_title, wait_for_signal, ChaosProxy, DeadLetterClient, DeadLetterQueue,
+    Echoer, Event, Forwarder, HeartbeatMonitor, HeartbeatResponder, LinkEvent, LinkHealth,
+    LinkStatus, NodeResult, NodeRole, NodeScope, Presenter, ResultExt, RouteResolver, RouteSpec,
+    ShutdownCoordinator, DEAD_LETTER_ADDRESS, HEARTBEAT_ADDRESS,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    node, route, AsyncTryClone, Context, MessageSendReceiveOptions, Result, TcpConnectionOptions,
+    TcpListenerOptions, TcpTransportExtension,
+};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// The route that the initiator sends "Hello Ockam!" over.
+const ROUTE: &str = "secure(tcp:localhost:3000 / forward_to_bob / bob_listener) / echoer";
+
+/// The chaos proxy listens here, and connects to the responder's listener on 4000.
+const PROXY_ADDRESS: &str = "127.0.0.1:4100";
+
+/// The links that the middle node & the initiator watch.
+const MIDDLE_LINK: &str = "node_middle→node_responder";
+const SECURE_CHANNEL_LINK: &str = "alice→bob";
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the initiator waits for each reply, and for each link event.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+const LINK_EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// examples/17-heartbeat.rs
+/// Like examples/15-chaos.rs, but the responder runs a [HeartbeatResponder], the middle
+/// node watches its TCP link to the responder (via a [ChaosProxy] on 4100), and the
+/// initiator watches its secure channel to `bob`. The initiator subscribes to the link
+/// events, blackholes the proxy until both links are down, and heals it until they are up
+/// again. While the middle node's link is down, its forwarder keeps the messages as dead
+/// letters (which the initiator lists over TCP) instead of sending them into the void.
+#[ockam::node]
+async fn main(ctx: Context) -> Result<()> {
+    let ctx_clone = ctx.async_try_clone().await?;
+    let ctx_clone_2 = ctx.async_try_clone().await?;
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+
+    let _node_responder = create_responder_node(ctx, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    print_title("Start a chaos proxy on 4100 that connects to 4000");
+    let proxy = ChaosProxy::start(PROXY_ADDRESS, "127.0.0.1:4000").await?;
+
+    // Subscribe before the monitors start, so that no link event is missed.
+    let events = LinkHealth::global().subscribe();
+
+    let _node_middle = create_middle_node(ctx_clone, &mut coordinator)
+        .await
+        .unwrap_or_else(|error| error.report_and_exit());
+
+    // Ctrl-C while the initiator is running stops all the nodes gracefully.
+    tokio::select! {
+        result = create_initiator_node(ctx_clone_2, &mut coordinator, &proxy, events) => {
+            result.unwrap_or_else(|error| error.report_and_exit())
+        }
+        _ = wait_for_signal() => println!("{}", "Interrupted, stopping all nodes".red()),
+    }
+
+    let report = coordinator.shutdown().await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+/// This node starts a tcp listener on 4000, a secure channel listener, and an echoer &
+/// a heartbeat responder that are reachable over both.
+async fn create_responder_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title(
+        "Create a node that runs tcp listener on 4000, a secure channel listener (for `bob`), an echoer and a heartbeat responder → wait for messages until stopped",
+    );
+    let scope = NodeScope::new(NodeRole::Responder, "node_responder");
+    let presenter = Presenter::for_role(NodeRole::Responder, "node_responder");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    node.start_worker("echoer", Echoer)
+        .await
+        .during(&scope, "start worker 'echoer'")?;
+    node.start_worker(HEARTBEAT_ADDRESS, HeartbeatResponder)
+        .await
+        .during(&scope, "start worker 'heartbeat'")?;
+
+    let id_bob = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'bob'")?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:4000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:4000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await
+        .during(&scope, "create secure channel listener 'bob_listener'")?;
+
+    // Allow access to the Echoer & the heartbeat responder via TCP connections, and via
+    // secure channels.
+    for worker in ["echoer", HEARTBEAT_ADDRESS] {
+        node.flow_controls()
+            .add_consumer(worker, listener.flow_control_id());
+        node.flow_controls()
+            .add_consumer(worker, secure_channel_listener.flow_control_id());
+    }
+    presenter.println(
+        "📣 echoer & heartbeat are reachable via tcp listener on 127.0.0.1:4000, and via secure channel listener 'bob_listener'",
+    );
+
+    coordinator
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "bob_listener")
+        .register_worker(NodeRole::Responder, "node_responder", "echoer")
+        .register_worker(NodeRole::Responder, "node_responder", HEARTBEAT_ADDRESS);
+
+    Ok(node)
+}
+
+/// This node listens on 3000, forwards everything to the chaos proxy on 4100, and watches
+/// its link to the responder.
+async fn create_middle_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+) -> NodeResult<ockam::Node> {
+    print_title("Create a middle (forwarder) node that listens for TCP on 3000, forwards to the chaos proxy on 4100, and sends heartbeats to the responder → wait for messages until stopped");
+    let scope = NodeScope::new(NodeRole::Forwarder, "node_middle");
+    let presenter = Presenter::for_role(NodeRole::Forwarder, "node_middle");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+
+    let tcp_connection_to_bob = tcp_transport
+        .connect(PROXY_ADDRESS, TcpConnectionOptions::new())
+        .await
+        .during(&scope, format!("connect to {}", PROXY_ADDRESS))?;
+    let tcp_connection_to_bob_address = tcp_connection_to_bob.sender_address().clone();
+    node.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: tcp_connection_to_bob.into(),
+        },
+    )
+    .await
+    .during(&scope, "start worker 'forward_to_bob'")?;
+    node.start_worker(DEAD_LETTER_ADDRESS, DeadLetterQueue::new())
+        .await
+        .during(&scope, format!("start worker '{}'", DEAD_LETTER_ADDRESS))?;
+
+    let monitor_address = HeartbeatMonitor::new(
+        MIDDLE_LINK,
+        route![tcp_connection_to_bob_address.clone(), HEARTBEAT_ADDRESS],
+    )
+    .with_interval(HEARTBEAT_INTERVAL)
+    .with_timeout(HEARTBEAT_TIMEOUT)
+    .start(node.context())
+    .await
+    .during(
+        &scope,
+        format!("start heartbeat monitor for '{}'", MIDDLE_LINK),
+    )?;
+
+    let listener = tcp_transport
+        .listen("127.0.0.1:3000", TcpListenerOptions::new())
+        .await
+        .during(&scope, "listen on 127.0.0.1:3000")?;
+    emit_event(
+        &scope.node_name,
+        Event::tcp_listener_bound(listener.socket_address()),
+    );
+    node.flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer(DEAD_LETTER_ADDRESS, listener.flow_control_id());
+    presenter.println(format!(
+        "👉 forward_to_bob forwards from 127.0.0.1:3000 to {}, while the link is up",
+        PROXY_ADDRESS
+    ));
+
+    coordinator
+        .register_processor(
+            NodeRole::Forwarder,
+            "node_middle",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Forwarder, "node_middle", "forward_to_bob")
+        .register_worker(NodeRole::Forwarder, "node_middle", DEAD_LETTER_ADDRESS)
+        .register_worker(NodeRole::Forwarder, "node_middle", monitor_address)
+        .register_worker(
+            NodeRole::Forwarder,
+            "node_middle",
+            tcp_connection_to_bob_address,
+        );
+
+    Ok(node)
+}
+
+/// This node creates a secure channel over the forwarder & the chaos proxy, watches it,
+/// and sends a message over it while the links are up, and while they are down.
+async fn create_initiator_node(
+    ctx: Context,
+    coordinator: &mut ShutdownCoordinator,
+    proxy: &ChaosProxy,
+    mut events: broadcast::Receiver<LinkEvent>,
+) -> NodeResult<()> {
+    print_title("Create a node that watches its secure channel, breaks & heals the link to the responder → print the link events → stop");
+    let scope = NodeScope::new(NodeRole::Initiator, "node_initiator");
+    let presenter = Presenter::for_role(NodeRole::Initiator, "node_initiator");
+
+    let node = node(ctx);
+    emit_event(&scope.node_name, Event::NodeStarted { role: scope.role });
+
+    let id_alice = node
+        .create_identity()
+        .await
+        .during(&scope, "create identity for 'alice'")?;
+    let tcp_transport = node
+        .create_tcp_transport()
+        .await
+        .during(&scope, "create tcp transport")?;
+    let route_spec: RouteSpec = ROUTE
+        .parse()
+        .map_err(ockam::Error::from)
+        .during(&scope, "parse the route")?;
+    let resolved = RouteResolver::new(&node, &tcp_transport)
+        .with_identity(&id_alice)
+        .resolve(&route_spec)
+        .await
+        .during(&scope, format!("resolve the route {}", route_spec))?;
+    let addresses = resolved
+        .tcp_connections
+        .iter()
+        .map(|it| &it.1)
+        .chain(resolved.secure_channels.iter().map(|it| &it.1));
+    for address in addresses {
+        coordinator.register_worker(NodeRole::Initiator, "node_initiator", address.clone());
+    }
+
+    // The heartbeats to `bob` go through the same secure channel as the messages.
+    if let Some((_, encryptor_address)) = resolved.secure_channels.first() {
+        let monitor_address = HeartbeatMonitor::new(
+            SECURE_CHANNEL_LINK,
+            route![encryptor_address.clone(), HEARTBEAT_ADDRESS],
+        )
+        .with_interval(HEARTBEAT_INTERVAL)
+        .with_timeout(HEARTBEAT_TIMEOUT)
+        .start(node.context())
+        .await
+        .during(
+            &scope,
+            format!("start heartbeat monitor for '{}'", SECURE_CHANNEL_LINK),
+        )?;
+        coordinator.register_worker(NodeRole::Initiator, "node_initiator", monitor_address);
+    }
+
+    wait_for_links(&presenter, &mut events, LinkStatus::Up).await;
+    send_and_report(&node, &presenter, &resolved.route).await;
+
+    print_title("Blackhole the chaos proxy → wait until both links are down");
+    proxy.blackhole();
+    wait_for_links(&presenter, &mut events, LinkStatus::Down).await;
+    send_and_report(&node, &presenter, &resolved.route).await;
+    // Ask the middle node for the dead letters that its forwarder kept, over the same TCP
+    // connection.
+    let letters = match resolved.tcp_connections.first() {
+        Some((_, connection_to_middle)) => {
+            DeadLetterClient::new(route![connection_to_middle.clone(), DEAD_LETTER_ADDRESS])
+                .list(node.context())
+                .await
+                .during(&scope, "list the dead letters of 'node_middle'")?
+        }
+        None => vec![],
+    };
+    for letter in letters {
+        presenter.println(
+            format!(
+                "🪦 dead letter #{} at '{}': {}",
+                letter.id,
+                letter.failed_at,
+                letter.reason.as_str()
+            )
+            .yellow()
+            .to_string(),
+        );
+    }
+
+    print_title("Heal the chaos proxy → wait until both links are up again");
+    proxy.heal();
+    wait_for_links(&presenter, &mut events, LinkStatus::Up).await;
+    send_and_report(&node, &presenter, &resolved.route).await;
+
+    // Don't call node.stop() here, the coordinator stops all the nodes in order.
+    Ok(())
+}
+
+/// Print the link events until both links have `status`, or [LINK_EVENT_TIMEOUT] passes.
+async fn wait_for_links(
+    presenter: &Presenter,
+    events: &mut broadcast::Receiver<LinkEvent>,
+    status: LinkStatus,
+) {
+    let links = [MIDDLE_LINK, SECURE_CHANNEL_LINK];
+    let all_have_status = || {
+        links
+            .iter()
+            .all(|link| LinkHealth::global().status(link) == Some(status))
+    };
+    let wait = async {
+        while !all_have_status() {
+            if let Ok(event) = events.recv().await {
+                presenter.println(format!(
+                    "🔔 link '{}' is {} ({:?} since the last heartbeat)",
+                    event.link,
+                    event.status.as_str(),
+                    event.silent_for
+                ));
+            }
+        }
+    };
+    if tokio::time::timeout(LINK_EVENT_TIMEOUT, wait)
+        .await
+        .is_err()
+    {
+        presenter.println(
+            format!("the links aren't all {} yet", status.as_str())
+                .red()
+                .to_string(),
+        );
+    }
+}
+
+/// Send "Hello Ockam!" over `route`, and print the reply, or the error.
+async fn send_and_report(node: &ockam::Node, presenter: &Presenter, route: &ockam::Route) {
+    let reply = node
+        .context()
+        .send_and_receive_extended::<String>(
+            route.clone(),
+            "Hello Ockam!".to_string(),
+            MessageSendReceiveOptions::new().with_timeout(REPLY_TIMEOUT),
+        )
+        .await;
+    let outcome = match reply {
+        Ok(reply) => reply.body().as_str().green(),
+        Err(error) => format!("no reply: {}", error).red(),
+    };
+    presenter.println(format!("Hello Ockam! → {}", outcome));
+}
@@ -0,0 +1,170 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{is_output_redirected, Topology, TopologyElement, TopologyNode};
+use colored::Colorize;
+use ockam::{Context, Result};
+
+/// The narrowest that a box around an element can be.
+const MIN_ELEMENT_WIDTH: usize = 16;
+
+/// Space between two nodes that are drawn side by side.
+const GAP: &str = "    ";
+const REQUEST_ARROW: &str = "───►";
+const REPLY_ARROW: &str = "◄───";
+
+/// Render `topology` as a box-and-arrow diagram, in the same style as the `HELP_TEXT`
+/// diagrams that used to be drawn by hand (<http://asciiflow.com>). Nodes are drawn left to
+/// right (initiators, forwarders, then responders), w/ their elements stacked top to
+/// bottom. TCP links between adjacent nodes are drawn as arrows, and all the links are
+/// listed below the diagram.
+///
+/// Use a [Topology::snapshot] so that only what is actually running is drawn.
+pub fn render_ascii_diagram(topology: &Topology) -> String {
+    let nodes = topology.nodes();
+    let links = topology.links();
+    let rendered: Vec<RenderedNode> = nodes.iter().map(|it| RenderedNode::new(it)).collect();
+    let height = rendered.iter().map(|it| it.lines.len()).max().unwrap_or(0);
+
+    let mut output = String::new();
+    for row in 0..height {
+        let mut line = String::new();
+        for (index, node) in rendered.iter().enumerate() {
+            line.push_str(&node.line(row));
+            let Some(next) = rendered.get(index + 1) else {
+                continue;
+            };
+            let is_linked = links.iter().any(|it| {
+                (it.from_node == node.name && it.to_node == next.name)
+                    || (it.from_node == next.name && it.to_node == node.name)
+            });
+            let connection_row = node.connection_row.or(next.connection_row);
+            line.push_str(match connection_row {
+                Some(connection_row) if is_linked && row == connection_row => REQUEST_ARROW,
+                Some(connection_row) if is_linked && row == connection_row + 1 => REPLY_ARROW,
+                _ => GAP,
+            });
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+
+    if !links.is_empty() {
+        output.push_str("\nLinks:\n");
+        for link in links {
+            output.push_str(&format!(
+                "  {} ──tcp {}──► {}\n",
+                link.from_node, link.socket_address, link.to_node
+            ));
+        }
+    }
+
+    output
+}
+
+struct RenderedNode {
+    name: String,
+    width: usize,
+    lines: Vec<String>,
+    /// The row of the first TCP connection in this node, if it has one.
+    connection_row: Option<usize>,
+}
+
+impl RenderedNode {
+    fn new(node: &TopologyNode) -> Self {
+        let labels: Vec<[String; 2]> = node.elements.iter().map(|it| it.label()).collect();
+        let element_width = labels
+            .iter()
+            .flatten()
+            .map(|it| it.chars().count())
+            .chain([
+                node.name.chars().count().saturating_sub(4),
+                MIN_ELEMENT_WIDTH,
+            ])
+            .max()
+            .unwrap_or(MIN_ELEMENT_WIDTH);
+        let node_width = element_width + 4;
+
+        let mut lines = vec![
+            format!("┌{}┐", "─".repeat(node_width)),
+            format!("│{}│", pad(&node.name, node_width)),
+            format!("├{}┤", "─".repeat(node_width)),
+        ];
+        let mut connection_row = None;
+
+        let count = labels.len();
+        for (index, label) in labels.iter().enumerate() {
+            let flows_in = index > 0;
+            let flows_out = index + 1 < count;
+            if connection_row.is_none()
+                && matches!(node.elements[index], TopologyElement::TcpConnection { .. })
+            {
+                connection_row = Some(lines.len() + 1);
+            }
+            let top = if flows_in {
+                format!("┌─▼{}┴─┐", "─".repeat(element_width - 4))
+            } else {
+                format!("┌{}┐", "─".repeat(element_width))
+            };
+            let bottom = if flows_out {
+                format!("└─┬{}▲─┘", "─".repeat(element_width - 4))
+            } else {
+                format!("└{}┘", "─".repeat(element_width))
+            };
+            for element_line in [
+                top,
+                format!("│{}│", pad(&label[0], element_width)),
+                format!("│{}│", pad(&label[1], element_width)),
+                bottom,
+            ] {
+                lines.push(format!("│ {} │", element_line));
+            }
+        }
+        lines.push(format!("└{}┘", "─".repeat(node_width)));
+
+        Self {
+            name: node.name.clone(),
+            width: node_width + 2,
+            lines,
+            connection_row,
+        }
+    }
+
+    /// Row `row` of this node, or blank space below the bottom of the node.
+    fn line(&self, row: usize) -> String {
+        match self.lines.get(row) {
+            Some(line) => line.clone(),
+            None => " ".repeat(self.width),
+        }
+    }
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!("{:<width$}", text, width = width)
+}
+
+/// Print the diagram for what is currently running from `topology`. Nothing is printed
+/// while the output is redirected (eg: to the [crate::Dashboard], which shows the nodes
+/// itself).
+pub async fn print_topology(ctx: &Context, topology: &Topology) -> Result<()> {
+    if is_output_redirected() {
+        return Ok(());
+    }
+    let snapshot = topology.snapshot(ctx).await?;
+    println!("{}", render_ascii_diagram(&snapshot).green());
+    Ok(())
+}
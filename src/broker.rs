@@ -0,0 +1,399 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{emit_event, format_route, Event, FlowRecorder, Presenter};
+use colored::Colorize;
+use ockam::errcode::{Kind, Origin};
+use ockam::{
+    route, Address, Context, Message, MessageSendReceiveOptions, Result, Route, Routed, Worker,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+/// How many deliveries a subscriber can leave unacknowledged before the [Broker] treats it
+/// as dead and prunes it.
+const DEFAULT_MAX_UNACKED: usize = 3;
+
+/// How long the [BrokerClient] waits for the broker to reply.
+const DEFAULT_BROKER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub enum BrokerRequest {
+    /// Sent by the subscriber itself, since the broker delivers to the return route of this
+    /// request (eg: back over the TCP connection or secure channel that it arrived on).
+    Subscribe {
+        topic: String,
+    },
+    Unsubscribe {
+        subscription_id: u64,
+    },
+    Publish {
+        topic: String,
+        payload: String,
+    },
+    /// Sent by the subscriber for each [TopicMessage] that it receives.
+    Ack {
+        subscription_id: u64,
+        sequence: u64,
+    },
+}
+
+/// What the [Broker] sends to a subscriber.
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub enum SubscriberEvent {
+    Subscribed { topic: String, subscription_id: u64 },
+    Delivery(TopicMessage),
+    Unsubscribed { subscription_id: u64, existed: bool },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TopicMessage {
+    pub topic: String,
+    pub subscription_id: u64,
+    /// Starts at 1 and increases w/ each message published to the topic.
+    pub sequence: u64,
+    pub payload: String,
+}
+
+/// The [Broker]'s reply to a [BrokerRequest::Publish].
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub struct PublishReceipt {
+    pub topic: String,
+    pub sequence: u64,
+    /// How many subscribers the message was sent to.
+    pub delivered: usize,
+    /// How many dead subscribers were removed instead.
+    pub pruned: usize,
+}
+
+struct Subscription {
+    topic: String,
+    route: Route,
+    unacked: BTreeSet<u64>,
+}
+
+/// A worker that fans out the messages published to a topic to every live subscriber of
+/// that topic. A subscriber is pruned when a delivery to it can't be sent (eg: its worker
+/// is gone), or when it leaves too many deliveries unacknowledged (eg: its node is gone).
+pub struct Broker {
+    subscriptions: BTreeMap<u64, Subscription>,
+    next_subscription_id: u64,
+    sequences: BTreeMap<String, u64>,
+    max_unacked: usize,
+}
+
+impl Default for Broker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Broker {
+    pub fn new() -> Self {
+        Self {
+            subscriptions: BTreeMap::new(),
+            next_subscription_id: 1,
+            sequences: BTreeMap::new(),
+            max_unacked: DEFAULT_MAX_UNACKED,
+        }
+    }
+
+    pub fn with_max_unacked(mut self, max_unacked: usize) -> Self {
+        self.max_unacked = max_unacked.max(1);
+        self
+    }
+
+    async fn publish(&mut self, ctx: &Context, topic: String, payload: String) -> PublishReceipt {
+        let sequence = self.sequences.entry(topic.clone()).or_default();
+        *sequence += 1;
+        let sequence = *sequence;
+
+        let (mut delivered, mut dead) = (0, vec![]);
+        for (subscription_id, subscription) in self.subscriptions.iter_mut() {
+            if subscription.topic != topic {
+                continue;
+            }
+            if subscription.unacked.len() >= self.max_unacked {
+                dead.push(*subscription_id);
+                continue;
+            }
+            let delivery = SubscriberEvent::Delivery(TopicMessage {
+                topic: topic.clone(),
+                subscription_id: *subscription_id,
+                sequence,
+                payload: payload.clone(),
+            });
+            match ctx.send(subscription.route.clone(), delivery).await {
+                Ok(()) => {
+                    subscription.unacked.insert(sequence);
+                    delivered += 1;
+                }
+                Err(_) => dead.push(*subscription_id),
+            }
+        }
+
+        let presenter = Presenter::for_name(&ctx.address().to_string());
+        for subscription_id in &dead {
+            if let Some(subscription) = self.subscriptions.remove(subscription_id) {
+                presenter.println(
+                    format!(
+                        "🪦 pruned subscription #{} to '{}' at {}",
+                        subscription_id,
+                        subscription.topic,
+                        format_route(&subscription.route)
+                    )
+                    .red()
+                    .to_string(),
+                );
+            }
+        }
+
+        PublishReceipt {
+            topic,
+            sequence,
+            delivered,
+            pruned: dead.len(),
+        }
+    }
+}
+
+#[ockam::worker]
+impl Worker for Broker {
+    type Context = Context;
+    type Message = BrokerRequest;
+
+    async fn handle_message(
+        &mut self,
+        ctx: &mut Context,
+        msg: Routed<BrokerRequest>,
+    ) -> Result<()> {
+        let address_string = ctx.address().to_string();
+        let return_route = msg.return_route();
+        let request = msg.as_body().clone();
+
+        // Acks aren't shown or recorded, they'd drown out the messages.
+        if let BrokerRequest::Ack {
+            subscription_id,
+            sequence,
+        } = request
+        {
+            if let Some(subscription) = self.subscriptions.get_mut(&subscription_id) {
+                subscription.unacked.remove(&sequence);
+            }
+            return Ok(());
+        }
+
+        let output_msg = format!("📮 Address: {}, Received: {:?}", address_string, request);
+        Presenter::for_name(&address_string).println(output_msg.white().to_string());
+        let payload = format!("{:?}", request);
+        FlowRecorder::global().record_routed(&ctx.address(), &msg, &payload);
+        emit_event(
+            ctx.address().address(),
+            Event::message_received(&msg, &payload),
+        );
+
+        match request {
+            BrokerRequest::Subscribe { topic } => {
+                let subscription_id = self.next_subscription_id;
+                self.next_subscription_id += 1;
+                self.subscriptions.insert(
+                    subscription_id,
+                    Subscription {
+                        topic: topic.clone(),
+                        route: return_route.clone(),
+                        unacked: BTreeSet::new(),
+                    },
+                );
+                let event = SubscriberEvent::Subscribed {
+                    topic,
+                    subscription_id,
+                };
+                ctx.send(return_route, event).await
+            }
+            BrokerRequest::Unsubscribe { subscription_id } => {
+                let event = SubscriberEvent::Unsubscribed {
+                    subscription_id,
+                    existed: self.subscriptions.remove(&subscription_id).is_some(),
+                };
+                ctx.send(return_route, event).await
+            }
+            BrokerRequest::Publish { topic, payload } => {
+                let receipt = self.publish(ctx, topic, payload).await;
+                ctx.send(return_route, receipt).await
+            }
+            BrokerRequest::Ack { .. } => Ok(()),
+        }
+    }
+}
+
+/// A worker that subscribes to `topic` when it starts, prints & acknowledges each message
+/// that the [Broker] delivers, and unsubscribes when it stops.
+pub struct Subscriber {
+    broker_route: Route,
+    topic: String,
+    subscription_id: Option<u64>,
+    forward_to: Option<Address>,
+}
+
+impl Subscriber {
+    /// Eg: `Subscriber::new(route![connection_to_middle_node, "forward_to_bob", "broker"], "news")`.
+    pub fn new(broker_route: impl Into<Route>, topic: &str) -> Self {
+        Self {
+            broker_route: broker_route.into(),
+            topic: topic.to_string(),
+            subscription_id: None,
+            forward_to: None,
+        }
+    }
+
+    /// Also send every [SubscriberEvent] to the local `address`, eg: to wait until the
+    /// subscription is in place.
+    pub fn with_forward_to(mut self, address: impl Into<Address>) -> Self {
+        self.forward_to = Some(address.into());
+        self
+    }
+}
+
+#[ockam::worker]
+impl Worker for Subscriber {
+    type Context = Context;
+    type Message = SubscriberEvent;
+
+    async fn initialize(&mut self, ctx: &mut Context) -> Result<()> {
+        let request = BrokerRequest::Subscribe {
+            topic: self.topic.clone(),
+        };
+        ctx.send(self.broker_route.clone(), request).await
+    }
+
+    async fn shutdown(&mut self, ctx: &mut Context) -> Result<()> {
+        if let Some(subscription_id) = self.subscription_id.take() {
+            // The broker (or the route to it) may already be gone.
+            let request = BrokerRequest::Unsubscribe { subscription_id };
+            ctx.send(self.broker_route.clone(), request).await.ok();
+        }
+        Ok(())
+    }
+
+    async fn handle_message(
+        &mut self,
+        ctx: &mut Context,
+        msg: Routed<SubscriberEvent>,
+    ) -> Result<()> {
+        let address_string = ctx.address().to_string();
+        let presenter = Presenter::for_name(&address_string);
+        let event = msg.as_body().clone();
+
+        match &event {
+            SubscriberEvent::Subscribed {
+                topic,
+                subscription_id,
+            } => {
+                self.subscription_id = Some(*subscription_id);
+                presenter.println(format!(
+                    "📬 subscribed to '{}' as #{}",
+                    topic.green(),
+                    subscription_id
+                ));
+            }
+            SubscriberEvent::Delivery(message) => {
+                presenter.println(format!(
+                    "📬 '{}' #{}: '{}'",
+                    message.topic.green(),
+                    message.sequence,
+                    message.payload.white()
+                ));
+                FlowRecorder::global().record_routed(&ctx.address(), &msg, &message.payload);
+                emit_event(
+                    ctx.address().address(),
+                    Event::message_received(&msg, &message.payload),
+                );
+                let ack = BrokerRequest::Ack {
+                    subscription_id: message.subscription_id,
+                    sequence: message.sequence,
+                };
+                ctx.send(self.broker_route.clone(), ack).await?;
+            }
+            SubscriberEvent::Unsubscribed { .. } => {
+                self.subscription_id = None;
+                presenter.println(format!("📭 unsubscribed from '{}'", self.topic.green()));
+            }
+        }
+
+        if let Some(address) = &self.forward_to {
+            ctx.send(route![address.clone()], event).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Publishes to a [Broker] over `broker_route`.
+#[derive(Debug, Clone)]
+pub struct BrokerClient {
+    broker_route: Route,
+    timeout: Duration,
+}
+
+impl BrokerClient {
+    /// Eg: `BrokerClient::new(route![connection_to_middle_node, "forward_to_bob", "broker"])`.
+    pub fn new(broker_route: impl Into<Route>) -> Self {
+        Self {
+            broker_route: broker_route.into(),
+            timeout: DEFAULT_BROKER_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns once the broker has sent `payload` to the subscribers of `topic`, which
+    /// doesn't mean that they have received it.
+    pub async fn publish(
+        &self,
+        ctx: &Context,
+        topic: &str,
+        payload: &str,
+    ) -> Result<PublishReceipt> {
+        let request = BrokerRequest::Publish {
+            topic: topic.to_string(),
+            payload: payload.to_string(),
+        };
+        emit_event(
+            ctx.address().address(),
+            Event::message_sent(&self.broker_route, payload),
+        );
+        let receipt = ctx
+            .send_and_receive_extended::<PublishReceipt>(
+                self.broker_route.clone(),
+                request,
+                MessageSendReceiveOptions::new().with_timeout(self.timeout),
+            )
+            .await?
+            .body();
+        if receipt.topic != topic {
+            return Err(ockam::Error::new(
+                Origin::Application,
+                Kind::Protocol,
+                format!("expected a receipt for '{}', got {:?}", topic, receipt),
+            ));
+        }
+        Ok(receipt)
+    }
+}
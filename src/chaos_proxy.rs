@@ -0,0 +1,299 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::transport_io_error;
+use ockam::Result;
+use socket2::SockRef;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{ReadHalf, WriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// How much is read from a socket at a time.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// How the [ChaosProxy] treats the bytes that go through it, in both directions. The
+/// default passes them through untouched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChaosSettings {
+    /// Added to every chunk of bytes, w/out slowing down the ones behind it.
+    pub delay: Duration,
+    /// Bytes per second. Chunks queue up behind each other when the link is busy.
+    pub bandwidth: Option<u64>,
+    /// Stop reading & writing, but keep the connections open (unlike a reset). The bytes
+    /// wait in the socket buffers, so the peers only notice because nothing arrives until
+    /// it's healed.
+    pub blackhole: bool,
+}
+
+impl ChaosSettings {
+    pub fn normal() -> Self {
+        Self::default()
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_bandwidth(mut self, bytes_per_second: u64) -> Self {
+        self.bandwidth = Some(bytes_per_second.max(1));
+        self
+    }
+
+    pub fn with_blackhole(mut self, blackhole: bool) -> Self {
+        self.blackhole = blackhole;
+        self
+    }
+}
+
+/// What went through a [ChaosProxy] since it started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChaosStats {
+    pub accepted: u64,
+    /// Connections that are still open.
+    pub active: u64,
+    /// Connections that were closed by [ChaosProxy::reset_connections].
+    pub reset: u64,
+    /// Connections that were closed because the upstream address couldn't be reached.
+    pub refused: u64,
+    pub forwarded_bytes: u64,
+}
+
+type SharedStats = Arc<Mutex<ChaosStats>>;
+
+/// A TCP proxy on loopback that can be put between any two nodes (eg: between the middle
+/// node and the responder's listener on 4000), to see how connections, forwarders &
+/// secure channels behave when the link misbehaves. Change how it behaves at any time w/
+/// [ChaosProxy::set_settings], [ChaosProxy::blackhole] & [ChaosProxy::reset_connections];
+/// the open connections are affected right away. It stops (& resets its connections) when
+/// it is dropped.
+pub struct ChaosProxy {
+    listen_address: SocketAddr,
+    upstream_address: String,
+    settings: watch::Sender<ChaosSettings>,
+    stats: SharedStats,
+    resets: watch::Sender<u64>,
+    task: JoinHandle<()>,
+}
+
+impl ChaosProxy {
+    /// Eg: `ChaosProxy::start("127.0.0.1:4100", "127.0.0.1:4000")`, and connect the middle
+    /// node to 4100 instead of 4000. Pass port 0 to let the OS pick one.
+    pub async fn start(listen_address: &str, upstream_address: &str) -> Result<Self> {
+        let listener = TcpListener::bind(listen_address)
+            .await
+            .map_err(transport_io_error)?;
+        let listen_address = listener.local_addr().map_err(transport_io_error)?;
+        let (settings, settings_rx) = watch::channel(ChaosSettings::normal());
+        let stats = SharedStats::default();
+        let (resets, resets_rx) = watch::channel(0);
+        let task = tokio::spawn(accept_connections(
+            listener,
+            upstream_address.to_string(),
+            settings_rx,
+            stats.clone(),
+            resets_rx,
+        ));
+
+        Ok(Self {
+            listen_address,
+            upstream_address: upstream_address.to_string(),
+            settings,
+            stats,
+            resets,
+            task,
+        })
+    }
+
+    pub fn listen_address(&self) -> SocketAddr {
+        self.listen_address
+    }
+
+    pub fn upstream_address(&self) -> &str {
+        &self.upstream_address
+    }
+
+    pub fn settings(&self) -> ChaosSettings {
+        self.settings.borrow().clone()
+    }
+
+    pub fn set_settings(&self, settings: ChaosSettings) {
+        self.settings.send_replace(settings);
+    }
+
+    /// Let nothing through from now on, until [ChaosProxy::heal] is called.
+    pub fn blackhole(&self) {
+        self.settings.send_modify(|it| it.blackhole = true);
+    }
+
+    /// Go back to [ChaosSettings::normal]. The connections that were reset stay closed,
+    /// the bytes that were held back by a blackhole go through.
+    pub fn heal(&self) {
+        self.settings.send_replace(ChaosSettings::normal());
+    }
+
+    /// Close all the open connections w/ a TCP reset, on both sides. New connections are
+    /// still accepted.
+    pub fn reset_connections(&self) {
+        self.resets.send_modify(|generation| *generation += 1);
+    }
+
+    pub fn stats(&self) -> ChaosStats {
+        *lock(&self.stats)
+    }
+}
+
+impl Drop for ChaosProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.reset_connections();
+    }
+}
+
+async fn accept_connections(
+    listener: TcpListener,
+    upstream_address: String,
+    settings: watch::Receiver<ChaosSettings>,
+    stats: SharedStats,
+    resets: watch::Receiver<u64>,
+) {
+    while let Ok((client, _)) = listener.accept().await {
+        lock(&stats).accepted += 1;
+        let upstream = match TcpStream::connect(&upstream_address).await {
+            Ok(upstream) => upstream,
+            Err(_) => {
+                lock(&stats).refused += 1;
+                continue;
+            }
+        };
+        tokio::spawn(run_connection(
+            client,
+            upstream,
+            settings.clone(),
+            stats.clone(),
+            resets.clone(),
+        ));
+    }
+}
+
+/// Pump the bytes both ways until both sides are done, or the connection is reset.
+async fn run_connection(
+    mut client: TcpStream,
+    mut upstream: TcpStream,
+    settings: watch::Receiver<ChaosSettings>,
+    stats: SharedStats,
+    mut resets: watch::Receiver<u64>,
+) {
+    lock(&stats).active += 1;
+    // Only the resets from now on are for this connection.
+    resets.borrow_and_update();
+
+    let reset = {
+        let (client_read, client_write) = client.split();
+        let (upstream_read, upstream_write) = upstream.split();
+        let pumps = async {
+            tokio::join!(
+                pump(client_read, upstream_write, settings.clone(), &stats),
+                pump(upstream_read, client_write, settings.clone(), &stats)
+            )
+        };
+        tokio::select! {
+            _ = pumps => false,
+            _ = resets.changed() => true,
+        }
+    };
+
+    let mut locked = lock(&stats);
+    locked.active -= 1;
+    if reset {
+        locked.reset += 1;
+        // Closing w/ a zero linger time sends a reset instead of a FIN. This doesn't block
+        // on drop, since there's nothing left to send.
+        let _ = SockRef::from(&client).set_linger(Some(Duration::ZERO));
+        let _ = SockRef::from(&upstream).set_linger(Some(Duration::ZERO));
+    }
+}
+
+/// Copy the bytes from `read` to `write`, w/ the delay, bandwidth & blackhole settings
+/// at the time. Reading & writing run concurrently, so that a delay doesn't limit the
+/// throughput. A blackhole stops both, until it's healed.
+async fn pump(
+    mut read: ReadHalf<'_>,
+    mut write: WriteHalf<'_>,
+    settings: watch::Receiver<ChaosSettings>,
+    stats: &SharedStats,
+) {
+    let (chunks_tx, mut chunks_rx) = mpsc::unbounded_channel::<(Instant, Vec<u8>)>();
+
+    let mut reader_settings = settings.clone();
+    let reader = async move {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            if !wait_until_healed(&mut reader_settings).await {
+                return;
+            }
+            let length = match read.read(&mut buffer).await {
+                Ok(0) | Err(_) => return,
+                Ok(length) => length,
+            };
+            let due = Instant::now() + reader_settings.borrow().delay;
+            if chunks_tx.send((due, buffer[..length].to_vec())).is_err() {
+                return;
+            }
+        }
+    };
+
+    let mut writer_settings = settings;
+    let writer = async move {
+        let mut busy_until = Instant::now();
+        while let Some((due, chunk)) = chunks_rx.recv().await {
+            tokio::time::sleep_until(due).await;
+            let bandwidth = writer_settings.borrow().bandwidth;
+            if let Some(bandwidth) = bandwidth {
+                let transmit = Duration::from_secs_f64(chunk.len() as f64 / bandwidth as f64);
+                busy_until = busy_until.max(Instant::now()) + transmit;
+                tokio::time::sleep_until(busy_until).await;
+            }
+            // Blackholed while it was on its way.
+            if !wait_until_healed(&mut writer_settings).await {
+                return;
+            }
+            if write.write_all(&chunk).await.is_err() {
+                return;
+            }
+            lock(stats).forwarded_bytes += chunk.len() as u64;
+        }
+        let _ = write.shutdown().await;
+    };
+
+    tokio::join!(reader, writer);
+}
+
+/// Returns false if the proxy was dropped in the meantime.
+async fn wait_until_healed(settings: &mut watch::Receiver<ChaosSettings>) -> bool {
+    settings.wait_for(|it| !it.blackhole).await.is_ok()
+}
+
+fn lock(stats: &SharedStats) -> std::sync::MutexGuard<'_, ChaosStats> {
+    stats.lock().unwrap_or_else(|it| it.into_inner())
+}
@@ -0,0 +1,588 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{RouteHop, RouteSpec};
+use ockam::access_control::{AllowAll, AllowOnwardAddress};
+use ockam::errcode::{Kind, Origin};
+use ockam::identity::{IdentityIdentifier, SecureChannelOptions};
+use ockam::{
+    route, Address, Context, Message, MessageReceiveOptions, Node, Result, Route, Routed,
+    TcpConnectionOptions, TcpTransport,
+};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// What a [ConnectionPool] did since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub tcp_connections_created: u64,
+    pub tcp_connections_reused: u64,
+    pub secure_channels_created: u64,
+    pub secure_channels_reused: u64,
+    /// Connections & channels that were closed because they weren't used for the idle
+    /// timeout.
+    pub closed_idle: u64,
+    /// Connections & channels that were dropped because they failed a health check, or a
+    /// request over them failed.
+    pub evicted_unhealthy: u64,
+    /// Requests that couldn't be sent (eg: their connection was already closed), and were
+    /// sent again after the connections & channels that they use were re-established.
+    pub retried: u64,
+}
+
+#[derive(Debug)]
+struct PooledConnection {
+    sender_address: Address,
+    last_used: Instant,
+    last_checked: Instant,
+}
+
+#[derive(Debug)]
+struct PooledChannel {
+    encryptor_address: Address,
+    /// The socket & sender addresses of the TCP connections that the channel goes over.
+    tcp_connections: Vec<(String, Address)>,
+    last_used: Instant,
+    last_checked: Instant,
+}
+
+/// Secure channels are keyed by the identity that created them, and the route to their
+/// listener (in the [RouteSpec] syntax).
+type ChannelKey = (String, String);
+
+#[derive(Debug, Default)]
+struct PoolState {
+    tcp_connections: BTreeMap<String, PooledConnection>,
+    secure_channels: BTreeMap<ChannelKey, PooledChannel>,
+    stats: PoolStats,
+}
+
+/// Like [crate::RouteResolver], but it keeps the TCP connections (by socket address) and
+/// secure channels (by identity & route) that it creates, so that a client that talks to
+/// the same server again & again doesn't pay for a handshake each time. Connections &
+/// channels that aren't used for the idle timeout are closed, and the ones that are
+/// reused are checked every `health_check_interval` (eg: a TCP connection that was closed
+/// by its peer is created again).
+///
+/// Only `tcp:`, `secure(...)` & worker hops are pooled.
+pub struct ConnectionPool<'a> {
+    node: &'a Node,
+    tcp_transport: &'a TcpTransport,
+    idle_timeout: Duration,
+    health_check_interval: Duration,
+    /// Only locked briefly, never while connecting or running a handshake.
+    state: Mutex<PoolState>,
+    /// See [ConnectionPool::lock_key].
+    key_locks: Mutex<BTreeMap<String, Arc<Mutex<()>>>>,
+}
+
+impl<'a> ConnectionPool<'a> {
+    pub fn new(node: &'a Node, tcp_transport: &'a TcpTransport) -> Self {
+        Self {
+            node,
+            tcp_transport,
+            idle_timeout: Duration::from_secs(60),
+            health_check_interval: Duration::from_secs(5),
+            state: Mutex::new(PoolState::default()),
+            key_locks: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// How long a connection or channel is trusted after it was last checked. Pass
+    /// `Duration::ZERO` to check it each time it is reused.
+    pub fn with_health_check_interval(mut self, health_check_interval: Duration) -> Self {
+        self.health_check_interval = health_check_interval;
+        self
+    }
+
+    pub async fn stats(&self) -> PoolStats {
+        self.state.lock().await.stats
+    }
+
+    /// Turn `spec` into a [Route], reusing the pooled connections & channels for its hops,
+    /// and creating the missing ones. `identity` is required when the route has a
+    /// `secure(...)` hop.
+    pub async fn resolve(
+        &self,
+        spec: &RouteSpec,
+        identity: Option<&IdentityIdentifier>,
+    ) -> Result<Route> {
+        self.close_idle().await;
+        let mut route = route![];
+        self.resolve_into(spec, &[], identity, &mut route).await?;
+        Ok(route)
+    }
+
+    /// Send `message` over `spec` (see [ConnectionPool::resolve]), and wait for the reply.
+    /// If that fails, the pooled connections & channels are dropped. The message is only
+    /// sent once more (over new ones) if it couldn't be sent at all, since once it went out
+    /// it may have been handled, eg: when only the reply was lost.
+    pub async fn send_and_receive<M, T>(
+        &self,
+        ctx: &Context,
+        spec: &RouteSpec,
+        identity: Option<&IdentityIdentifier>,
+        message: T,
+        timeout: Duration,
+    ) -> Result<M>
+    where
+        M: Message,
+        T: Message + Clone,
+    {
+        let route = self.resolve(spec, identity).await?;
+        match send_then_receive(ctx, route, message.clone(), timeout).await {
+            Attempt::Sent(Ok(reply)) => return Ok(reply),
+            Attempt::Sent(Err(error)) => {
+                self.evict(spec, identity).await;
+                return Err(error);
+            }
+            Attempt::NotSent(_) => self.evict(spec, identity).await,
+        }
+
+        let route = self.resolve(spec, identity).await?;
+        self.state.lock().await.stats.retried += 1;
+        match send_then_receive(ctx, route, message, timeout).await {
+            Attempt::Sent(result) => result,
+            Attempt::NotSent(error) => Err(error),
+        }
+    }
+
+    /// Close the connections & channels that weren't used for the idle timeout. This also
+    /// happens each time a route is resolved.
+    pub async fn close_idle(&self) {
+        let idle_timeout = self.idle_timeout;
+        let is_idle = |last_used: Instant| last_used.elapsed() >= idle_timeout;
+        let (channels, connections) = {
+            let mut state = self.state.lock().await;
+            let channels = take_where(&mut state.secure_channels, |it| is_idle(it.last_used));
+            let connections = take_where(&mut state.tcp_connections, |it| is_idle(it.last_used));
+            state.stats.closed_idle += (channels.len() + connections.len()) as u64;
+            (channels, connections)
+        };
+        // The locks that no request holds or waits for.
+        self.key_locks
+            .lock()
+            .await
+            .retain(|_, it| Arc::strong_count(it) > 1);
+        self.close(channels, connections).await;
+    }
+
+    /// Close all the pooled connections & channels.
+    pub async fn close_all(&self) {
+        let (channels, connections) = {
+            let mut state = self.state.lock().await;
+            (
+                std::mem::take(&mut state.secure_channels)
+                    .into_values()
+                    .collect(),
+                std::mem::take(&mut state.tcp_connections)
+                    .into_values()
+                    .collect(),
+            )
+        };
+        self.close(channels, connections).await;
+    }
+
+    /// Drop (& close) the pooled connections & channels that `spec` goes over.
+    async fn evict(&self, spec: &RouteSpec, identity: Option<&IdentityIdentifier>) {
+        let mut channel_keys = vec![];
+        let mut socket_addresses = vec![];
+        collect_keys(
+            spec,
+            &[],
+            identity,
+            &mut channel_keys,
+            &mut socket_addresses,
+        );
+        let (channels, connections) = {
+            let mut state = self.state.lock().await;
+            let channels: Vec<PooledChannel> = channel_keys
+                .iter()
+                .filter_map(|key| state.secure_channels.remove(key))
+                .collect();
+            let connections: Vec<PooledConnection> = socket_addresses
+                .iter()
+                .filter_map(|socket_address| state.tcp_connections.remove(socket_address))
+                .collect();
+            state.stats.evicted_unhealthy += (channels.len() + connections.len()) as u64;
+            (channels, connections)
+        };
+        self.close(channels, connections).await;
+    }
+
+    /// Stop the encryptors of `channels`, and disconnect `connections`. They must have
+    /// been taken out of the pool already.
+    async fn close(&self, channels: Vec<PooledChannel>, connections: Vec<PooledConnection>) {
+        for channel in channels {
+            let _ = self
+                .node
+                .context()
+                .stop_worker(channel.encryptor_address)
+                .await;
+        }
+        for connection in connections {
+            let _ = self
+                .tcp_transport
+                .disconnect(&connection.sender_address)
+                .await;
+        }
+    }
+
+    /// Held while the connection or channel for `key` is checked or created, so that the
+    /// requests that need it at the same time create it once, w/out waiting for the
+    /// handshakes of the other keys.
+    async fn lock_key(&self, key: String) -> OwnedMutexGuard<()> {
+        let lock = self.key_locks.lock().await.entry(key).or_default().clone();
+        lock.lock_owned().await
+    }
+
+    /// Whether `address` still runs, if it wasn't checked within the health check
+    /// interval.
+    async fn is_healthy(&self, address: &Address, last_checked: Instant) -> bool {
+        if last_checked.elapsed() < self.health_check_interval {
+            return true;
+        }
+        self.node
+            .context()
+            .list_workers()
+            .await
+            .map(|running| running.contains(address))
+            .unwrap_or(false)
+    }
+
+    async fn tcp_connection(&self, socket_address: &str) -> Result<Address> {
+        let _key_guard = self.lock_key(format!("tcp:{}", socket_address)).await;
+        let pooled = self
+            .state
+            .lock()
+            .await
+            .tcp_connections
+            .get(socket_address)
+            .map(|it| (it.sender_address.clone(), it.last_checked));
+        if let Some((sender_address, last_checked)) = pooled {
+            let healthy = self.is_healthy(&sender_address, last_checked).await;
+            let mut state = self.state.lock().await;
+            // It may have been closed as idle in the meantime.
+            if let Some(connection) = state
+                .tcp_connections
+                .get_mut(socket_address)
+                .filter(|it| it.sender_address == sender_address)
+            {
+                if healthy {
+                    connection.last_used = Instant::now();
+                    if connection.last_checked.elapsed() >= self.health_check_interval {
+                        connection.last_checked = Instant::now();
+                    }
+                    state.stats.tcp_connections_reused += 1;
+                    return Ok(sender_address);
+                }
+                state.tcp_connections.remove(socket_address);
+                state.stats.evicted_unhealthy += 1;
+            }
+        }
+
+        let connection = self
+            .tcp_transport
+            .connect(socket_address, TcpConnectionOptions::new())
+            .await?;
+        let sender_address = connection.sender_address().clone();
+        let now = Instant::now();
+        let mut state = self.state.lock().await;
+        state.tcp_connections.insert(
+            socket_address.to_string(),
+            PooledConnection {
+                sender_address: sender_address.clone(),
+                last_used: now,
+                last_checked: now,
+            },
+        );
+        state.stats.tcp_connections_created += 1;
+        Ok(sender_address)
+    }
+
+    /// Append the hops in `spec` to `route`. `prefix` is the hops before `spec`, which are
+    /// part of the key of the secure channels in it. This is boxed since `secure(...)` hops
+    /// resolve their inner route recursively.
+    fn resolve_into<'b>(
+        &'b self,
+        spec: &'b RouteSpec,
+        prefix: &'b [RouteHop],
+        identity: Option<&'b IdentityIdentifier>,
+        route: &'b mut Route,
+    ) -> BoxFuture<'b, Result<()>> {
+        Box::pin(async move {
+            let mut hops_so_far = prefix.to_vec();
+            for hop in &spec.hops {
+                match hop {
+                    RouteHop::Worker(address) => {
+                        route.modify().append(address.as_str());
+                    }
+                    RouteHop::Tcp(socket_address) => {
+                        let sender_address = self.tcp_connection(socket_address).await?;
+                        route.modify().append(sender_address);
+                    }
+                    RouteHop::Secure(inner) => {
+                        let Some(identity) = identity else {
+                            return Err(ockam::Error::new(
+                                Origin::Application,
+                                Kind::Misuse,
+                                format!("'secure({})' needs an identity", inner),
+                            ));
+                        };
+                        let encryptor_address = self
+                            .secure_channel(inner, &hops_so_far, identity, route)
+                            .await?;
+                        *route = route![encryptor_address];
+                    }
+                    RouteHop::Udp(_) | RouteHop::Unix(_) => {
+                        return Err(ockam::Error::new(
+                            Origin::Application,
+                            Kind::Unsupported,
+                            format!(
+                                "'{}' isn't pooled, use a `RouteResolver`",
+                                RouteSpec::new(vec![hop.clone()])
+                            ),
+                        ));
+                    }
+                }
+                hops_so_far.push(hop.clone());
+            }
+            Ok(())
+        })
+    }
+
+    /// The encryptor address of a secure channel for `identity` to the listener at the end
+    /// of `inner`, which continues from `route`.
+    async fn secure_channel(
+        &self,
+        inner: &RouteSpec,
+        prefix: &[RouteHop],
+        identity: &IdentityIdentifier,
+        route: &Route,
+    ) -> Result<Address> {
+        let mut listener_hops = prefix.to_vec();
+        listener_hops.extend(inner.hops.iter().cloned());
+        let key = (
+            identity.to_string(),
+            RouteSpec::new(listener_hops).to_string(),
+        );
+        let _key_guard = self.lock_key(format!("secure:{}:{}", key.0, key.1)).await;
+
+        // The channel's encryptor, & its connections (w/ when they were last checked).
+        let pooled = {
+            let state = self.state.lock().await;
+            state.secure_channels.get(&key).map(|channel| {
+                let connections: Vec<(Address, Option<Instant>)> = channel
+                    .tcp_connections
+                    .iter()
+                    .map(|(socket_address, sender_address)| {
+                        let last_checked = state
+                            .tcp_connections
+                            .get(socket_address)
+                            .filter(|it| it.sender_address == *sender_address)
+                            .map(|it| it.last_checked);
+                        (sender_address.clone(), last_checked)
+                    })
+                    .collect();
+                (
+                    channel.encryptor_address.clone(),
+                    channel.last_checked,
+                    connections,
+                )
+            })
+        };
+        if let Some((encryptor_address, last_checked, connections)) = pooled {
+            let mut healthy = self.is_healthy(&encryptor_address, last_checked).await;
+            // The channel is broken if one of its connections was closed, even if it has
+            // been created again since.
+            for (sender_address, last_checked) in &connections {
+                healthy &= match last_checked {
+                    Some(last_checked) => self.is_healthy(sender_address, *last_checked).await,
+                    None => false,
+                };
+            }
+
+            let mut guard = self.state.lock().await;
+            let state = &mut *guard;
+            // It may have been closed as idle in the meantime.
+            if let Some(channel) = state
+                .secure_channels
+                .get_mut(&key)
+                .filter(|it| it.encryptor_address == encryptor_address)
+            {
+                if healthy {
+                    channel.last_used = Instant::now();
+                    if channel.last_checked.elapsed() >= self.health_check_interval {
+                        channel.last_checked = Instant::now();
+                    }
+                    // Using the channel also uses its connections.
+                    for (socket_address, _) in &channel.tcp_connections {
+                        if let Some(connection) = state.tcp_connections.get_mut(socket_address) {
+                            connection.last_used = Instant::now();
+                        }
+                    }
+                    state.stats.secure_channels_reused += 1;
+                    return Ok(encryptor_address);
+                }
+                let stale: Vec<PooledChannel> =
+                    state.secure_channels.remove(&key).into_iter().collect();
+                state.stats.evicted_unhealthy += 1;
+                drop(guard);
+                self.close(stale, vec![]).await;
+            }
+        }
+
+        let mut channel_route = route.clone();
+        self.resolve_into(inner, prefix, Some(identity), &mut channel_route)
+            .await?;
+        let channel = self
+            .node
+            .create_secure_channel(identity, channel_route, SecureChannelOptions::new())
+            .await?;
+        let encryptor_address = channel.encryptor_address().clone();
+        let mut socket_addresses = vec![];
+        collect_keys(inner, prefix, None, &mut vec![], &mut socket_addresses);
+        let mut state = self.state.lock().await;
+        let tcp_connections = socket_addresses
+            .into_iter()
+            .filter_map(|socket_address| {
+                let sender_address = state
+                    .tcp_connections
+                    .get(&socket_address)?
+                    .sender_address
+                    .clone();
+                Some((socket_address, sender_address))
+            })
+            .collect();
+        let now = Instant::now();
+        state.secure_channels.insert(
+            key,
+            PooledChannel {
+                encryptor_address: encryptor_address.clone(),
+                tcp_connections,
+                last_used: now,
+                last_checked: now,
+            },
+        );
+        state.stats.secure_channels_created += 1;
+        Ok(encryptor_address)
+    }
+}
+
+/// What happened to a request, see [send_then_receive].
+enum Attempt<M> {
+    /// Eg: the first hop of the route is a connection that was closed.
+    NotSent(ockam::Error),
+    Sent(Result<M>),
+}
+
+/// Like `Context::send_and_receive_extended`, but it tells a message that couldn't be sent
+/// apart from one that was sent & got no (or a bad) reply.
+async fn send_then_receive<M: Message>(
+    ctx: &Context,
+    route: Route,
+    message: impl Message,
+    timeout: Duration,
+) -> Attempt<M> {
+    let next = match route.next() {
+        Ok(next) => next.clone(),
+        Err(error) => return Attempt::NotSent(error),
+    };
+    let address = Address::random_tagged("ConnectionPool.send_then_receive");
+    // To receive the reply over the connection that the route starts w/, if any.
+    if let Some(producer) = ctx
+        .flow_controls()
+        .find_flow_control_with_producer_address(&next)
+    {
+        ctx.flow_controls()
+            .add_consumer(address.clone(), producer.flow_control_id());
+    }
+    let mut child_ctx = match ctx
+        .new_detached(address, AllowAll, AllowOnwardAddress(next))
+        .await
+    {
+        Ok(child_ctx) => child_ctx,
+        Err(error) => return Attempt::NotSent(error),
+    };
+    if let Err(error) = child_ctx.send(route, message).await {
+        return Attempt::NotSent(error);
+    }
+    let reply = child_ctx
+        .receive_extended::<M>(MessageReceiveOptions::new().with_timeout(timeout))
+        .await;
+    Attempt::Sent(reply.map(Routed::body))
+}
+
+/// Remove the values that `predicate` holds for from `map`.
+fn take_where<K: Ord + Clone, V>(
+    map: &mut BTreeMap<K, V>,
+    predicate: impl Fn(&V) -> bool,
+) -> Vec<V> {
+    let keys: Vec<K> = map
+        .iter()
+        .filter(|(_, it)| predicate(it))
+        .map(|(key, _)| key.clone())
+        .collect();
+    keys.iter().filter_map(|key| map.remove(key)).collect()
+}
+
+/// The keys of the channels (only if `identity` is set) & connections that `spec` goes
+/// over.
+fn collect_keys(
+    spec: &RouteSpec,
+    prefix: &[RouteHop],
+    identity: Option<&IdentityIdentifier>,
+    channel_keys: &mut Vec<ChannelKey>,
+    socket_addresses: &mut Vec<String>,
+) {
+    let mut hops_so_far = prefix.to_vec();
+    for hop in &spec.hops {
+        match hop {
+            RouteHop::Tcp(socket_address) => socket_addresses.push(socket_address.clone()),
+            RouteHop::Secure(inner) => {
+                if let Some(identity) = identity {
+                    let mut listener_hops = hops_so_far.clone();
+                    listener_hops.extend(inner.hops.iter().cloned());
+                    channel_keys.push((
+                        identity.to_string(),
+                        RouteSpec::new(listener_hops).to_string(),
+                    ));
+                }
+                collect_keys(
+                    inner,
+                    &hops_so_far,
+                    identity,
+                    channel_keys,
+                    socket_addresses,
+                );
+            }
+            RouteHop::Worker(_) | RouteHop::Udp(_) | RouteHop::Unix(_) => {}
+        }
+        hops_so_far.push(hop.clone());
+    }
+}
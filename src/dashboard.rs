@@ -0,0 +1,455 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{
+    is_output_redirected, redirect_output, restore_output, FlowRecorder, NodeRole, Topology,
+    TopologyElement, TopologyNode, TITLE_NAME,
+};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Set this environment variable (to anything but an empty string) to run the multi node
+/// examples w/ the [Dashboard], eg: `TUI=1 cargo run --example 05-...`.
+pub const TUI_ENV: &str = "TUI";
+
+/// The oldest lines are dropped once the log has this many lines.
+const MAX_LOG_LINES: usize = 2000;
+
+/// How often the dashboard is redrawn (and checks for key presses).
+const TICK: Duration = Duration::from_millis(100);
+
+/// Height of the log for output that doesn't belong to any node.
+const OTHER_LOG_HEIGHT: u16 = 6;
+
+#[derive(Debug, Default)]
+struct DashboardState {
+    title: String,
+    topology: Topology,
+    secure_channels: Vec<(String, String)>,
+    flow_controls: Vec<(String, String)>,
+    log: VecDeque<(String, String)>,
+    scroll_back: usize,
+    finished: bool,
+    stopping: bool,
+}
+
+/// A full screen terminal UI for the multi node examples. It shows a panel per node w/ the
+/// workers, listeners, connections, secure channels & flow controls that it runs, counters
+/// for the messages that its workers received & sent (from [FlowRecorder::global]), and a
+/// scrolling log of its output. The output of each [crate::Presenter] is sent to the log
+/// of the node that it belongs to, instead of interleaving on stdout.
+///
+/// When it isn't enabled (see [Dashboard::from_env]) all the methods do nothing, so the
+/// examples can call them unconditionally.
+pub struct Dashboard {
+    state: Option<Arc<Mutex<DashboardState>>>,
+    quit: watch::Receiver<bool>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Dashboard {
+    /// A running dashboard when [TUI_ENV] is set and stdout is a terminal, otherwise a
+    /// disabled one. If the terminal can't be set up, the error is printed and the
+    /// example carries on w/out the dashboard.
+    pub fn from_env() -> Self {
+        let enabled = std::env::var_os(TUI_ENV).is_some_and(|it| !it.is_empty());
+        if !enabled || !io::stdout().is_terminal() {
+            return Self::disabled();
+        }
+        Self::start().unwrap_or_else(|error| {
+            eprintln!("Can't start the dashboard: {}", error);
+            Self::disabled()
+        })
+    }
+
+    pub fn disabled() -> Self {
+        let (_, quit) = watch::channel(false);
+        Self {
+            state: None,
+            quit,
+            thread: None,
+        }
+    }
+
+    /// An enabled dashboard that keeps track of what the methods are given, but doesn't
+    /// take over the terminal or the output. Draw it w/ [Dashboard::draw_to], eg: on a
+    /// `ratatui::backend::TestBackend`.
+    pub fn in_memory() -> Self {
+        let (_, quit) = watch::channel(false);
+        Self {
+            state: Some(Arc::new(Mutex::new(DashboardState::default()))),
+            quit,
+            thread: None,
+        }
+    }
+
+    /// Take over the terminal, and start redrawing it on a separate thread.
+    pub fn start() -> io::Result<Self> {
+        enable_raw_mode()?;
+        if let Err(error) = execute!(io::stdout(), EnterAlternateScreen) {
+            disable_raw_mode().ok();
+            return Err(error);
+        }
+
+        FlowRecorder::global().enable();
+        let state = Arc::new(Mutex::new(DashboardState::default()));
+        let sink_state = state.clone();
+        redirect_output(move |name, line| {
+            let mut state = lock(&sink_state);
+            if name == TITLE_NAME {
+                state.title = line.to_string();
+                return;
+            }
+            if state.log.len() == MAX_LOG_LINES {
+                state.log.pop_front();
+            }
+            state.log.push_back((name.to_string(), line.to_string()));
+        });
+
+        let (quit_sender, quit) = watch::channel(false);
+        let thread_state = state.clone();
+        let thread = std::thread::spawn(move || run(thread_state, quit_sender));
+
+        Ok(Self {
+            state: Some(state),
+            quit,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// Show the nodes in `topology`. Call this again whenever a node registers more
+    /// elements.
+    pub fn show_topology(&self, topology: &Topology) {
+        if let Some(state) = &self.state {
+            lock(state).topology = topology.clone();
+        }
+    }
+
+    /// Eg: `add_secure_channel("node_initiator", "to 'bob' via 127.0.0.1:3000")`.
+    pub fn add_secure_channel(&self, node_name: &str, description: impl Into<String>) {
+        if let Some(state) = &self.state {
+            lock(state)
+                .secure_channels
+                .push((node_name.to_string(), description.into()));
+        }
+    }
+
+    /// Eg: `add_flow_control("node_responder", "listener 127.0.0.1:4000 → echoer")`.
+    pub fn add_flow_control(&self, node_name: &str, description: impl Into<String>) {
+        if let Some(state) = &self.state {
+            lock(state)
+                .flow_controls
+                .push((node_name.to_string(), description.into()));
+        }
+    }
+
+    /// Draw the current state to `terminal`. Does nothing when the dashboard is disabled.
+    pub fn draw_to<B: Backend>(&self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        if let Some(state) = &self.state {
+            terminal.draw(|frame| draw(frame, &lock(state)))?;
+        }
+        Ok(())
+    }
+
+    /// Completes when the user presses `q`, `Esc` or `Ctrl-C` (while the terminal is in raw
+    /// mode, `Ctrl-C` doesn't send SIGINT). Never completes when the dashboard is disabled.
+    pub async fn quit_requested(&self) {
+        let mut quit = self.quit.clone();
+        if !self.is_enabled() || quit.wait_for(|it| *it).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Keep showing the dashboard until the user quits, so that the final state can be
+    /// inspected, then give the terminal back. Returns right away when it is disabled.
+    pub async fn finish(mut self) {
+        if let Some(state) = &self.state {
+            lock(state).finished = true;
+            self.quit_requested().await;
+        }
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        let Some(state) = self.state.take() else {
+            return;
+        };
+        lock(&state).stopping = true;
+        if let Some(thread) = self.thread.take() {
+            if let Ok(Err(error)) = thread.join() {
+                eprintln!("The dashboard failed: {}", error);
+            }
+        }
+        restore_terminal();
+    }
+}
+
+/// Leave the alternate screen & raw mode, and print to stdout again. Does nothing unless a
+/// [Dashboard] is running, so it is safe to call before exiting on an error.
+pub fn restore_terminal() {
+    if !is_output_redirected() {
+        return;
+    }
+    restore_output();
+    execute!(io::stdout(), LeaveAlternateScreen).ok();
+    disable_raw_mode().ok();
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn lock(state: &Mutex<DashboardState>) -> MutexGuard<'_, DashboardState> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Redraw every [TICK] and handle key presses, until [Dashboard::stop] is called.
+fn run(state: Arc<Mutex<DashboardState>>, quit: watch::Sender<bool>) -> io::Result<()> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    terminal.hide_cursor()?;
+
+    loop {
+        {
+            let state = lock(&state);
+            if state.stopping {
+                break;
+            }
+            terminal.draw(|frame| draw(frame, &state))?;
+        }
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                quit.send_replace(true);
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                quit.send_replace(true);
+            }
+            KeyCode::Up => lock(&state).scroll_back += 1,
+            KeyCode::Down => {
+                let mut state = lock(&state);
+                state.scroll_back = state.scroll_back.saturating_sub(1);
+            }
+            KeyCode::End => lock(&state).scroll_back = 0,
+            _ => {}
+        }
+    }
+
+    terminal.show_cursor()
+}
+
+fn draw<B: Backend>(frame: &mut Frame<'_, B>, state: &DashboardState) {
+    let nodes = state.topology.nodes();
+    let other_log: Vec<&str> = state
+        .log
+        .iter()
+        .filter(|(name, _)| !nodes.iter().any(|node| belongs_to(node, name)))
+        .map(|(_, line)| line.as_str())
+        .collect();
+
+    let mut rows = vec![Constraint::Length(1), Constraint::Min(0)];
+    if !other_log.is_empty() {
+        rows.push(Constraint::Length(OTHER_LOG_HEIGHT));
+    }
+    rows.push(Constraint::Length(1));
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(rows)
+        .split(frame.size());
+
+    let title = Paragraph::new(state.title.clone())
+        .style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+    frame.render_widget(title, rows[0]);
+
+    if !nodes.is_empty() {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Ratio(1, nodes.len() as u32); nodes.len()])
+            .split(rows[1]);
+        for (node, area) in nodes.iter().zip(columns.iter()) {
+            draw_node(frame, *area, node, state);
+        }
+    }
+
+    if !other_log.is_empty() {
+        draw_log(
+            frame,
+            rows[2],
+            "Other output",
+            &other_log,
+            state.scroll_back,
+        );
+    }
+
+    let help = if state.finished {
+        "Finished. q: quit, ↑/↓: scroll the logs, End: follow the logs"
+    } else {
+        "q: quit, ↑/↓: scroll the logs, End: follow the logs"
+    };
+    let footer = Paragraph::new(help).style(Style::default().fg(Color::DarkGray));
+    frame.render_widget(footer, rows[rows.len() - 1]);
+}
+
+fn draw_node<B: Backend>(
+    frame: &mut Frame<'_, B>,
+    area: Rect,
+    node: &TopologyNode,
+    state: &DashboardState,
+) {
+    let color = role_color(node.role);
+    let mut details: Vec<Line<'_>> = vec![];
+
+    for element in node.live_elements() {
+        let text = match element {
+            TopologyElement::Worker { address } => format!("worker      {}", address.address()),
+            TopologyElement::TcpListener { socket_address, .. } => {
+                format!("listener    {}", socket_address)
+            }
+            TopologyElement::TcpConnection { socket_address, .. } => {
+                format!("connection  → {}", socket_address)
+            }
+        };
+        details.push(Line::from(text));
+    }
+    for (_, description) in state.secure_channels.iter().filter(|it| it.0 == node.name) {
+        details.push(Line::from(format!("secure      {}", description)));
+    }
+    for (_, description) in state.flow_controls.iter().filter(|it| it.0 == node.name) {
+        details.push(Line::from(format!("flow ctrl   {}", description)));
+    }
+
+    let workers: Vec<String> = node
+        .elements
+        .iter()
+        .filter_map(|it| match it {
+            TopologyElement::Worker { address } => Some(address.address().to_string()),
+            _ => None,
+        })
+        .collect();
+    let events = FlowRecorder::global().events();
+    let received = events
+        .iter()
+        .filter(|it| workers.contains(&it.receiver))
+        .count();
+    let sent = events
+        .iter()
+        .filter(|it| workers.contains(&it.sender))
+        .count();
+    let log: Vec<&str> = state
+        .log
+        .iter()
+        .filter(|(name, _)| belongs_to(node, name))
+        .map(|(_, line)| line.as_str())
+        .collect();
+    details.push(Line::from(vec![
+        Span::styled("received ", Style::default().fg(Color::DarkGray)),
+        Span::styled(received.to_string(), Style::default().fg(color)),
+        Span::styled("  sent ", Style::default().fg(Color::DarkGray)),
+        Span::styled(sent.to_string(), Style::default().fg(color)),
+        Span::styled("  log lines ", Style::default().fg(Color::DarkGray)),
+        Span::styled(log.len().to_string(), Style::default().fg(color)),
+    ]));
+
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(details.len() as u16 + 2),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(color))
+        .title(Span::styled(
+            format!(" {} ({}) ", node.name, node.role),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        ));
+    frame.render_widget(Paragraph::new(details).block(block), parts[0]);
+
+    draw_log(frame, parts[1], "Log", &log, state.scroll_back);
+}
+
+/// Show the end of `lines` that fits in `area`, `scroll_back` lines up from the bottom.
+fn draw_log<B: Backend>(
+    frame: &mut Frame<'_, B>,
+    area: Rect,
+    title: &str,
+    lines: &[&str],
+    scroll_back: usize,
+) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let end = lines.len().saturating_sub(scroll_back.min(lines.len()));
+    let start = end.saturating_sub(visible);
+    let text: Vec<Line<'_>> = lines[start..end]
+        .iter()
+        .map(|it| Line::from(it.to_string()))
+        .collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray))
+        .title(format!(" {} ", title));
+    frame.render_widget(Paragraph::new(text).block(block), area);
+}
+
+/// Whether the output of the presenter w/ `name` belongs to `node`: either the node itself
+/// (eg: `Presenter::for_role`) or one of its workers (eg: `Presenter::for_name`).
+fn belongs_to(node: &TopologyNode, name: &str) -> bool {
+    node.name == name
+        || node.elements.iter().any(|it| {
+            matches!(it, TopologyElement::Worker { address } if address.address() == name || address.to_string() == name)
+        })
+}
+
+fn role_color(role: NodeRole) -> Color {
+    match role {
+        NodeRole::Initiator => Color::Gray,
+        NodeRole::Forwarder => Color::LightBlue,
+        NodeRole::Responder => Color::LightMagenta,
+    }
+}
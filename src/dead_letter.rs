@@ -0,0 +1,359 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{emit_event, format_route, Event, Presenter};
+use colored::Colorize;
+use ockam::errcode::{Kind, Origin};
+use ockam::{
+    route, Context, LocalMessage, Message, MessageSendReceiveOptions, Result, Route, Routed,
+    TransportMessage, Worker,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The address that the workers in this crate send their dead letters to, on their own
+/// node. Start a [DeadLetterQueue] there.
+pub const DEAD_LETTER_ADDRESS: &str = "dead_letters";
+
+/// The oldest dead letters are dropped beyond this many.
+const MAX_DEAD_LETTERS: usize = 1000;
+
+/// How long the [DeadLetterClient] waits for the queue to reply.
+const DEFAULT_DEAD_LETTER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Why a message couldn't be delivered.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    /// There is no worker at the next address on the onward route.
+    UnknownAddress,
+    /// The TCP connection (or other worker) that a [crate::Forwarder] forwards to is gone.
+    ConnectionClosed,
+    /// The destination's incoming access control rejected the message, see
+    /// [crate::ReportAccessDenied].
+    AccessDenied,
+    /// A [crate::HeartbeatMonitor] says that the link to the next hop is down.
+    LinkDown,
+}
+
+impl DeadLetterReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeadLetterReason::UnknownAddress => "unknown_address",
+            DeadLetterReason::ConnectionClosed => "connection_closed",
+            DeadLetterReason::AccessDenied => "access_denied",
+            DeadLetterReason::LinkDown => "link_down",
+        }
+    }
+}
+
+/// A message that couldn't be delivered, w/ its routes as they were when it failed, so that
+/// it can be replayed as is.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub id: u64,
+    /// The address of the worker (or access control) that couldn't deliver the message.
+    pub failed_at: String,
+    pub reason: DeadLetterReason,
+    pub error: String,
+    /// Milliseconds since the Unix epoch.
+    pub failed_at_unix_ms: u64,
+    pub onward_route: Route,
+    pub return_route: Route,
+    pub payload: Vec<u8>,
+}
+
+impl DeadLetter {
+    /// `message`, which the worker (or access control) at `failed_at` couldn't deliver. Its
+    /// id is set by the [DeadLetterQueue] that keeps it.
+    pub fn new(
+        failed_at: &str,
+        reason: DeadLetterReason,
+        error: impl ToString,
+        message: &LocalMessage,
+    ) -> Self {
+        let transport = message.transport();
+        Self {
+            id: 0,
+            failed_at: failed_at.to_string(),
+            reason,
+            error: error.to_string(),
+            failed_at_unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|it| it.as_millis() as u64)
+                .unwrap_or_default(),
+            onward_route: transport.onward_route.clone(),
+            return_route: transport.return_route.clone(),
+            payload: transport.payload.clone(),
+        }
+    }
+}
+
+/// Send `letter` to the [DeadLetterQueue] at [DEAD_LETTER_ADDRESS] on the node of `ctx`. The
+/// [crate::Hopper] & [crate::Forwarder] workers, and [crate::ReportAccessDenied], do this
+/// w/ the messages that they can't deliver, instead of leaving them to the ockam logs
+/// (which the examples turn off). W/out a queue the dead letter is only printed.
+pub async fn send_dead_letter(ctx: &Context, letter: DeadLetter) {
+    let failed_at = letter.failed_at.clone();
+    let summary = format!(
+        "{} over {} ({})",
+        letter.reason.as_str(),
+        format_route(&letter.onward_route),
+        letter.error
+    );
+    let request = DeadLetterRequest::Deposit(letter);
+    if let Err(error) = ctx.send(route![DEAD_LETTER_ADDRESS], request).await {
+        let output_msg = format!("🪦 dead letter dropped: {}, {}", summary, error);
+        Presenter::for_name(&failed_at).println(output_msg.red().to_string());
+    }
+}
+
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub enum DeadLetterRequest {
+    /// Keep the dead letter (see [send_dead_letter]), under a new id. There is no reply.
+    Deposit(DeadLetter),
+    List,
+    /// Send the message again w/ its original return route, and its original onward route
+    /// unless another one is given (eg: when the next hop has moved).
+    Replay {
+        id: u64,
+        onward_route: Option<Route>,
+    },
+    Purge,
+}
+
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub enum DeadLetterResponse {
+    Letters(Vec<DeadLetter>),
+    /// The dead letter was sent again, and removed.
+    Replayed {
+        id: u64,
+    },
+    /// The dead letter couldn't be sent again either, so it is kept.
+    ReplayFailed {
+        id: u64,
+        error: String,
+    },
+    NotFound {
+        id: u64,
+    },
+    Purged {
+        count: usize,
+    },
+}
+
+/// A worker that keeps the dead letters sent to it, lists them (eg: to another node) and
+/// replays them on request. Start it at [DEAD_LETTER_ADDRESS].
+#[derive(Debug)]
+pub struct DeadLetterQueue {
+    letters: VecDeque<DeadLetter>,
+    next_id: u64,
+}
+
+impl Default for DeadLetterQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeadLetterQueue {
+    pub fn new() -> Self {
+        Self {
+            letters: VecDeque::new(),
+            next_id: 1,
+        }
+    }
+
+    fn deposit(&mut self, mut letter: DeadLetter) {
+        letter.id = self.next_id;
+        self.next_id += 1;
+
+        let output_msg = format!(
+            "🪦 dead letter #{}: {} over {} ({})",
+            letter.id,
+            letter.reason.as_str(),
+            format_route(&letter.onward_route),
+            letter.error
+        );
+        Presenter::for_name(&letter.failed_at).println(output_msg.red().to_string());
+        emit_event(
+            &letter.failed_at,
+            Event::MessageDeadLettered {
+                id: letter.id,
+                reason: letter.reason.as_str().to_string(),
+                onward_route: format_route(&letter.onward_route),
+                error: letter.error.clone(),
+            },
+        );
+
+        if self.letters.len() == MAX_DEAD_LETTERS {
+            self.letters.pop_front();
+        }
+        self.letters.push_back(letter);
+    }
+
+    async fn replay(
+        &mut self,
+        ctx: &Context,
+        id: u64,
+        onward_route: Option<Route>,
+    ) -> DeadLetterResponse {
+        let Some(index) = self.letters.iter().position(|it| it.id == id) else {
+            return DeadLetterResponse::NotFound { id };
+        };
+        let letter = self.letters[index].clone();
+        let transport_message = TransportMessage::v1(
+            onward_route.unwrap_or(letter.onward_route),
+            letter.return_route,
+            letter.payload,
+        );
+        match ctx
+            .forward(LocalMessage::new(transport_message, vec![]))
+            .await
+        {
+            Ok(()) => {
+                self.letters.remove(index);
+                DeadLetterResponse::Replayed { id }
+            }
+            Err(error) => DeadLetterResponse::ReplayFailed {
+                id,
+                error: error.to_string(),
+            },
+        }
+    }
+}
+
+#[ockam::worker]
+impl Worker for DeadLetterQueue {
+    type Context = Context;
+    type Message = DeadLetterRequest;
+
+    async fn handle_message(
+        &mut self,
+        ctx: &mut Context,
+        msg: Routed<DeadLetterRequest>,
+    ) -> Result<()> {
+        let address_string = ctx.address().to_string();
+        let return_route = msg.return_route();
+        let request = msg.body();
+        // A deposit prints the dead letter instead.
+        if !matches!(request, DeadLetterRequest::Deposit(_)) {
+            let output_msg = format!("🪦 Address: {}, Received: {:?}", address_string, request);
+            Presenter::for_name(&address_string).println(output_msg.white().to_string());
+        }
+
+        let response = match request {
+            DeadLetterRequest::Deposit(letter) => {
+                self.deposit(letter);
+                return Ok(());
+            }
+            DeadLetterRequest::List => {
+                DeadLetterResponse::Letters(self.letters.iter().cloned().collect())
+            }
+            DeadLetterRequest::Replay { id, onward_route } => {
+                self.replay(ctx, id, onward_route).await
+            }
+            DeadLetterRequest::Purge => DeadLetterResponse::Purged {
+                count: self.letters.drain(..).count(),
+            },
+        };
+        ctx.send(return_route, response).await
+    }
+}
+
+/// Talks to a [DeadLetterQueue] over `queue_route`.
+#[derive(Debug, Clone)]
+pub struct DeadLetterClient {
+    queue_route: Route,
+    timeout: Duration,
+}
+
+impl DeadLetterClient {
+    /// Eg: `DeadLetterClient::new(route![DEAD_LETTER_ADDRESS])`.
+    pub fn new(queue_route: impl Into<Route>) -> Self {
+        Self {
+            queue_route: queue_route.into(),
+            timeout: DEFAULT_DEAD_LETTER_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn list(&self, ctx: &Context) -> Result<Vec<DeadLetter>> {
+        match self.request(ctx, DeadLetterRequest::List).await? {
+            DeadLetterResponse::Letters(letters) => Ok(letters),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns an error w/ [Kind::NotFound] if there is no dead letter w/ `id` (eg: it was
+    /// already replayed), or one w/ [Kind::Io] if it couldn't be sent again.
+    pub async fn replay(&self, ctx: &Context, id: u64, onward_route: Option<Route>) -> Result<()> {
+        let request = DeadLetterRequest::Replay { id, onward_route };
+        match self.request(ctx, request).await? {
+            DeadLetterResponse::Replayed { .. } => Ok(()),
+            DeadLetterResponse::ReplayFailed { id, error } => Err(ockam::Error::new(
+                Origin::Application,
+                Kind::Io,
+                format!("can't replay dead letter #{}: {}", id, error),
+            )),
+            DeadLetterResponse::NotFound { id } => Err(ockam::Error::new(
+                Origin::Application,
+                Kind::NotFound,
+                format!("there is no dead letter #{}", id),
+            )),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns how many dead letters were removed.
+    pub async fn purge(&self, ctx: &Context) -> Result<usize> {
+        match self.request(ctx, DeadLetterRequest::Purge).await? {
+            DeadLetterResponse::Purged { count } => Ok(count),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    async fn request(
+        &self,
+        ctx: &Context,
+        request: DeadLetterRequest,
+    ) -> Result<DeadLetterResponse> {
+        let response = ctx
+            .send_and_receive_extended::<DeadLetterResponse>(
+                self.queue_route.clone(),
+                request,
+                MessageSendReceiveOptions::new().with_timeout(self.timeout),
+            )
+            .await?;
+        Ok(response.body())
+    }
+}
+
+fn unexpected(response: DeadLetterResponse) -> ockam::Error {
+    ockam::Error::new(
+        Origin::Application,
+        Kind::Protocol,
+        format!(
+            "unexpected reply from the dead letter queue: {:?}",
+            response
+        ),
+    )
+}
@@ -0,0 +1,297 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{Presenter, RouteSpec, RouteSyntaxError};
+use colored::Colorize;
+use ockam::errcode::{Kind, Origin};
+use ockam::{Context, Message, MessageSendReceiveOptions, Result, Route, Routed, Worker};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// How long the [DirectoryClient] waits for the directory to reply.
+const DEFAULT_DIRECTORY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The longest TTL that a registration gets, longer ones (eg: `u64::MAX` ms, from a peer)
+/// are cut down to it.
+pub const MAX_SERVICE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Whether a service is able to handle requests, as reported by whoever registered it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceHealth {
+    Healthy,
+    Unhealthy,
+}
+
+/// A service that is registered w/ the [ServiceDirectory].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ServiceRecord {
+    pub name: String,
+    /// The route to the service (from the directory's clients), in the [RouteSpec] syntax,
+    /// eg: `tcp:localhost:3000 / forward_to_responder / echoer`.
+    pub route: String,
+    pub health: ServiceHealth,
+    /// Time left before the registration expires, unless it is renewed.
+    pub expires_in_ms: u64,
+}
+
+impl ServiceRecord {
+    pub fn route_spec(&self) -> std::result::Result<RouteSpec, RouteSyntaxError> {
+        self.route.parse()
+    }
+}
+
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub enum DirectoryRequest {
+    /// Add a service, or renew it (eg: before its TTL runs out, or to change its health).
+    /// The TTL is at most [MAX_SERVICE_TTL].
+    Register {
+        name: String,
+        route: String,
+        ttl_ms: u64,
+        health: ServiceHealth,
+    },
+    Deregister {
+        name: String,
+    },
+    Resolve {
+        name: String,
+    },
+    List,
+}
+
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub enum DirectoryResponse {
+    Registered(ServiceRecord),
+    Deregistered {
+        name: String,
+        existed: bool,
+    },
+    Resolved(ServiceRecord),
+    NotFound {
+        name: String,
+    },
+    Services(Vec<ServiceRecord>),
+    /// The request was rejected, eg: because its route can't be parsed.
+    Invalid {
+        reason: String,
+    },
+}
+
+struct ServiceEntry {
+    route: String,
+    health: ServiceHealth,
+    expires_at: Instant,
+}
+
+/// A worker that maps service names (eg: "echo-service") to routes, so that initiators can
+/// ask for a service by name instead of hard coding the addresses of every hop. Each
+/// registration has a TTL, and is dropped if it isn't renewed in time.
+#[derive(Default)]
+pub struct ServiceDirectory {
+    services: BTreeMap<String, ServiceEntry>,
+}
+
+impl ServiceDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn remove_expired(&mut self) {
+        let now = Instant::now();
+        self.services.retain(|_, entry| entry.expires_at > now);
+    }
+
+    fn record(name: &str, entry: &ServiceEntry) -> ServiceRecord {
+        ServiceRecord {
+            name: name.to_string(),
+            route: entry.route.clone(),
+            health: entry.health,
+            expires_in_ms: entry
+                .expires_at
+                .saturating_duration_since(Instant::now())
+                .as_millis() as u64,
+        }
+    }
+
+    fn handle(&mut self, request: DirectoryRequest) -> DirectoryResponse {
+        self.remove_expired();
+        match request {
+            DirectoryRequest::Register {
+                name,
+                route,
+                ttl_ms,
+                health,
+            } => {
+                // Normalize the route, so that clients always get the same syntax back.
+                let route = match route.parse::<RouteSpec>() {
+                    Ok(spec) => spec.to_string(),
+                    Err(error) => {
+                        return DirectoryResponse::Invalid {
+                            reason: error.to_string(),
+                        }
+                    }
+                };
+                let entry = ServiceEntry {
+                    route,
+                    health,
+                    expires_at: Instant::now() + Duration::from_millis(ttl_ms).min(MAX_SERVICE_TTL),
+                };
+                let record = Self::record(&name, &entry);
+                self.services.insert(name, entry);
+                DirectoryResponse::Registered(record)
+            }
+            DirectoryRequest::Deregister { name } => DirectoryResponse::Deregistered {
+                existed: self.services.remove(&name).is_some(),
+                name,
+            },
+            DirectoryRequest::Resolve { name } => match self.services.get(&name) {
+                Some(entry) => DirectoryResponse::Resolved(Self::record(&name, entry)),
+                None => DirectoryResponse::NotFound { name },
+            },
+            DirectoryRequest::List => DirectoryResponse::Services(
+                self.services
+                    .iter()
+                    .map(|(name, entry)| Self::record(name, entry))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[ockam::worker]
+impl Worker for ServiceDirectory {
+    type Context = Context;
+    type Message = DirectoryRequest;
+
+    async fn handle_message(
+        &mut self,
+        ctx: &mut Context,
+        msg: Routed<DirectoryRequest>,
+    ) -> Result<()> {
+        let address_string = ctx.address().to_string();
+        let return_route = msg.return_route();
+        let request = msg.body();
+        let output_msg = format!("📒 Address: {}, Received: {:?}", address_string, request);
+        Presenter::for_name(&address_string).println(output_msg.white().to_string());
+
+        let response = self.handle(request);
+        ctx.send(return_route, response).await
+    }
+}
+
+/// Talks to a [ServiceDirectory] over `directory_route`.
+#[derive(Debug, Clone)]
+pub struct DirectoryClient {
+    directory_route: Route,
+    timeout: Duration,
+}
+
+impl DirectoryClient {
+    /// Eg: `DirectoryClient::new(route![connection_to_middle_node, "directory"])`.
+    pub fn new(directory_route: impl Into<Route>) -> Self {
+        Self {
+            directory_route: directory_route.into(),
+            timeout: DEFAULT_DIRECTORY_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Register (or renew) `name` at `route` for `ttl`.
+    pub async fn register(
+        &self,
+        ctx: &Context,
+        name: &str,
+        route: &RouteSpec,
+        ttl: Duration,
+        health: ServiceHealth,
+    ) -> Result<ServiceRecord> {
+        let request = DirectoryRequest::Register {
+            name: name.to_string(),
+            route: route.to_string(),
+            ttl_ms: u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX),
+            health,
+        };
+        match self.request(ctx, request).await? {
+            DirectoryResponse::Registered(record) => Ok(record),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns false if `name` wasn't registered.
+    pub async fn deregister(&self, ctx: &Context, name: &str) -> Result<bool> {
+        let request = DirectoryRequest::Deregister {
+            name: name.to_string(),
+        };
+        match self.request(ctx, request).await? {
+            DirectoryResponse::Deregistered { existed, .. } => Ok(existed),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// The registration of `name`, or an error w/ [Kind::NotFound] if it isn't registered
+    /// (or its TTL ran out). The record is returned even if the service is unhealthy, so
+    /// check [ServiceRecord::health] before sending to it.
+    pub async fn resolve(&self, ctx: &Context, name: &str) -> Result<ServiceRecord> {
+        let request = DirectoryRequest::Resolve {
+            name: name.to_string(),
+        };
+        match self.request(ctx, request).await? {
+            DirectoryResponse::Resolved(record) => Ok(record),
+            DirectoryResponse::NotFound { name } => Err(ockam::Error::new(
+                Origin::Application,
+                Kind::NotFound,
+                format!("no service named '{}' is registered", name),
+            )),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn list(&self, ctx: &Context) -> Result<Vec<ServiceRecord>> {
+        match self.request(ctx, DirectoryRequest::List).await? {
+            DirectoryResponse::Services(records) => Ok(records),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    async fn request(&self, ctx: &Context, request: DirectoryRequest) -> Result<DirectoryResponse> {
+        let response = ctx
+            .send_and_receive_extended::<DirectoryResponse>(
+                self.directory_route.clone(),
+                request,
+                MessageSendReceiveOptions::new().with_timeout(self.timeout),
+            )
+            .await?;
+        Ok(response.body())
+    }
+}
+
+fn unexpected(response: DirectoryResponse) -> ockam::Error {
+    let kind = match response {
+        DirectoryResponse::Invalid { .. } => Kind::Invalid,
+        _ => Kind::Protocol,
+    };
+    ockam::Error::new(
+        Origin::Application,
+        kind,
+        format!("unexpected reply from the directory: {:?}", response),
+    )
+}
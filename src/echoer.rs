@@ -17,6 +17,7 @@
 
 // src/echoer.rs
 
+use crate::{emit_event, Event, FlowRecorder, Presenter};
 use colored::Colorize;
 use ockam::{Context, Result, Routed, Worker};
 
@@ -49,9 +50,13 @@ impl Worker for Echoer {
             format!("    Received: '{}'", msg_string.white()),
             format!("    Sent: '{}'", new_msg_string.white()),
         ];
-        lines
-            .iter()
-            .for_each(|line| println!("{}", line.black().on_bright_magenta()));
+        Presenter::for_name(&address_string).println(lines.join("\n"));
+        FlowRecorder::global().record_routed(&ctx.address(), &msg, &msg_string);
+        emit_event(ctx.address().address(), Event::message_received(&msg, &msg_string));
+        emit_event(
+            ctx.address().address(),
+            Event::message_sent(&msg.return_route(), &new_msg_string),
+        );
 
         ctx.send(msg.return_route(), new_msg_string).await
     }
@@ -0,0 +1,163 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{restore_terminal, NodeRole};
+use colored::Colorize;
+use ockam::errcode::{Kind, Origin};
+use std::fmt::{Display, Formatter};
+
+pub type NodeResult<T> = std::result::Result<T, NodeError>;
+
+/// Identifies the node that a step runs on, eg: `NodeScope::new(NodeRole::Forwarder,
+/// "node_middle")`. Use it with [ResultExt::during] to turn an [ockam::Error] into a
+/// [NodeError].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeScope {
+    pub role: NodeRole,
+    pub node_name: String,
+}
+
+impl NodeScope {
+    pub fn new(role: NodeRole, node_name: &str) -> Self {
+        Self {
+            role,
+            node_name: node_name.to_string(),
+        }
+    }
+}
+
+/// An [ockam::Error] along with the node that it happened on, the step that was being
+/// performed (eg: "connect to 127.0.0.1:5000"), and suggested fixes for common causes.
+#[derive(Debug)]
+pub struct NodeError {
+    pub scope: NodeScope,
+    pub step: String,
+    pub source: ockam::Error,
+}
+
+impl NodeError {
+    /// Suggested fixes based on the kind of error and the step that failed.
+    pub fn suggestions(&self) -> Vec<&'static str> {
+        let message = self.source.to_string().to_lowercase();
+        let step = self.step.to_lowercase();
+        let code = self.source.code();
+        let mut suggestions = vec![];
+
+        // The TCP transport turns a failed bind into a generic I/O error, & a refused
+        // connection into "peer not found" (Kind::Misuse), w/o the OS error text.
+        if code.origin == Origin::Transport {
+            if code.kind == Kind::Io && step.starts_with("listen") {
+                suggestions.push(
+                    "The port is already in use. Stop the other process (or a previous run of \
+                     this example) that is listening on it, or pick a different port.",
+                );
+            }
+            if code.kind == Kind::Misuse && step.starts_with("connect") {
+                suggestions.push(
+                    "Nothing is listening on that port. Start the node that should be \
+                     listening there first.",
+                );
+            }
+        }
+        if code.kind == Kind::Timeout || message.contains("timeout") {
+            suggestions.push(
+                "No reply arrived. If the destination worker is reached via a TCP or secure \
+                 channel listener, make sure it was added as a flow control consumer, eg: \
+                 `flow_controls().add_consumer(\"echoer\", listener.flow_control_id())`.",
+            );
+            suggestions.push(
+                "Check that every address in the route (eg: the forwarder or secure channel \
+                 listener name) matches a worker that is running on the next node.",
+            );
+        }
+        if step.contains("credential") {
+            suggestions.push(
+                "Check that the issuer identity is the one that signed the credential, and that \
+                 this identity's identifier is known to the issuer.",
+            );
+        }
+        if step.contains("secure channel") && code.kind != Kind::Timeout {
+            suggestions.push(
+                "Check that the secure channel listener is a consumer of the TCP listener's \
+                 flow control, eg: `SecureChannelListenerOptions::new().as_consumer(...)`.",
+            );
+        }
+
+        suggestions
+    }
+
+    /// Print this error (with its suggestions) to stderr and exit the process. If the
+    /// [crate::Dashboard] is running, the terminal is given back first.
+    pub fn report_and_exit(self) -> ! {
+        restore_terminal();
+        eprintln!("{}", self);
+        std::process::exit(1)
+    }
+}
+
+impl Display for NodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{}",
+            format!(
+                "❌ {} ({}) failed to {}",
+                self.scope.node_name, self.scope.role, self.step
+            )
+            .red()
+        )?;
+        writeln!(f, "    cause: {}", self.source)?;
+        for suggestion in self.suggestions() {
+            writeln!(f, "    {} {}", "hint:".yellow(), suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Allows a [NodeError] to be returned from `#[ockam::node]` functions.
+impl From<NodeError> for ockam::Error {
+    fn from(error: NodeError) -> Self {
+        let kind = error.source.code().kind;
+        ockam::Error::new(Origin::Application, kind, error)
+    }
+}
+
+/// An I/O error of a socket or a stream, like the ones that the TCP transport returns.
+pub(crate) fn transport_io_error(error: std::io::Error) -> ockam::Error {
+    ockam::Error::new(Origin::Transport, Kind::Io, error)
+}
+
+pub trait ResultExt<T> {
+    /// Attach the node and the step that was being performed to an error.
+    fn during(self, scope: &NodeScope, step: impl Into<String>) -> NodeResult<T>;
+}
+
+impl<T> ResultExt<T> for ockam::Result<T> {
+    fn during(self, scope: &NodeScope, step: impl Into<String>) -> NodeResult<T> {
+        self.map_err(|source| NodeError {
+            scope: scope.clone(),
+            step: step.into(),
+            source,
+        })
+    }
+}
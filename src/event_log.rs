@@ -0,0 +1,404 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{
+    format_route, send_dead_letter, summarize_payload, DeadLetter, DeadLetterReason, NodeRole,
+};
+use async_trait::async_trait;
+use ockam::access_control::{AllowAll, DenyAll, IncomingAccessControl};
+use ockam::identity::IdentitySecureChannelLocalInfo;
+use ockam::{Address, Context, Message, Result, Route, Routed};
+use ockam_core::RelayMessage;
+use serde_json::{json, Value};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Set this environment variable to a file path to write the events to that file (it is
+/// appended to, so the processes started by [crate::Launcher] can share it).
+pub const EVENT_LOG_ENV: &str = "EVENT_LOG";
+
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+static GLOBAL: OnceLock<EventLog> = OnceLock::new();
+
+/// Something meaningful that happened on a node or in a worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    NodeStarted {
+        role: NodeRole,
+    },
+    /// `transport` is eg: `"tcp"`.
+    ListenerBound {
+        transport: String,
+        socket_address: String,
+    },
+    /// `route` is the route to the secure channel listener.
+    SecureChannelEstablished {
+        route: String,
+    },
+    CredentialIssued {
+        subject: String,
+        issuer: String,
+    },
+    CredentialVerified {
+        subject: String,
+        issuer: String,
+    },
+    MessageSent {
+        route: String,
+        payload: String,
+    },
+    MessageReceived {
+        from: String,
+        onward_route: String,
+        return_route: String,
+        payload: String,
+    },
+    /// `to` is the next hop that the message was forwarded to.
+    MessageForwarded {
+        from: String,
+        to: String,
+        payload: String,
+    },
+    /// A message for `destination` was rejected by its incoming access control.
+    AccessDenied {
+        destination: String,
+        from: String,
+        their_identity: Option<String>,
+    },
+    /// A message couldn't be delivered, and was kept as dead letter `id` (see
+    /// [crate::DeadLetterQueue]). `reason` is one of the [crate::DeadLetterReason::as_str] values.
+    MessageDeadLettered {
+        id: u64,
+        reason: String,
+        onward_route: String,
+        error: String,
+    },
+    /// A [crate::HeartbeatMonitor] saw `link` go up or down. `status` is one of the
+    /// [crate::LinkStatus::as_str] values.
+    LinkStatusChanged {
+        link: String,
+        status: String,
+        route: String,
+        silent_for_ms: u64,
+    },
+}
+
+impl Event {
+    /// Eg: `Event::tcp_listener_bound(listener.socket_address())`.
+    pub fn tcp_listener_bound(socket_address: &SocketAddr) -> Self {
+        Event::ListenerBound {
+            transport: "tcp".to_string(),
+            socket_address: socket_address.to_string(),
+        }
+    }
+
+    pub fn message_sent(route: &Route, payload: &str) -> Self {
+        Event::MessageSent {
+            route: format_route(route),
+            payload: summarize_payload(payload),
+        }
+    }
+
+    /// The `reply` to a message that was sent over `route` (eg: w/ `send_and_receive`), as
+    /// received by the initiator. The reply is shown as coming from the last hop on that
+    /// route, like in [crate::FlowRecorder::record_reply].
+    pub fn reply_received(route: &Route, reply: &str) -> Self {
+        Event::MessageReceived {
+            from: route
+                .iter()
+                .last()
+                .map(|it| it.address().to_string())
+                .unwrap_or_else(|| "?".to_string()),
+            onward_route: "[]".to_string(),
+            return_route: "[]".to_string(),
+            payload: summarize_payload(reply),
+        }
+    }
+
+    /// `msg` as received by a worker. The sender is the next hop on its return route.
+    pub fn message_received<M: Message>(msg: &Routed<M>, payload: &str) -> Self {
+        Event::MessageReceived {
+            from: previous_hop(msg),
+            onward_route: format_route(&msg.onward_route()),
+            return_route: format_route(&msg.return_route()),
+            payload: summarize_payload(payload),
+        }
+    }
+
+    /// `msg` as forwarded by a worker to `to`.
+    pub fn message_forwarded<M: Message>(msg: &Routed<M>, to: &str, payload: &str) -> Self {
+        Event::MessageForwarded {
+            from: previous_hop(msg),
+            to: to.to_string(),
+            payload: summarize_payload(payload),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::NodeStarted { .. } => "node_started",
+            Event::ListenerBound { .. } => "listener_bound",
+            Event::SecureChannelEstablished { .. } => "secure_channel_established",
+            Event::CredentialIssued { .. } => "credential_issued",
+            Event::CredentialVerified { .. } => "credential_verified",
+            Event::MessageSent { .. } => "message_sent",
+            Event::MessageReceived { .. } => "message_received",
+            Event::MessageForwarded { .. } => "message_forwarded",
+            Event::AccessDenied { .. } => "access_denied",
+            Event::MessageDeadLettered { .. } => "message_dead_lettered",
+            Event::LinkStatusChanged { .. } => "link_status_changed",
+        }
+    }
+
+    fn data(&self) -> Value {
+        match self {
+            Event::NodeStarted { role } => json!({ "role": role.as_str() }),
+            Event::ListenerBound {
+                transport,
+                socket_address,
+            } => json!({ "transport": transport, "socket_address": socket_address }),
+            Event::SecureChannelEstablished { route } => json!({ "route": route }),
+            Event::CredentialIssued { subject, issuer }
+            | Event::CredentialVerified { subject, issuer } => {
+                json!({ "subject": subject, "issuer": issuer })
+            }
+            Event::MessageSent { route, payload } => {
+                json!({ "route": route, "payload": payload })
+            }
+            Event::MessageReceived {
+                from,
+                onward_route,
+                return_route,
+                payload,
+            } => json!({
+                "from": from,
+                "onward_route": onward_route,
+                "return_route": return_route,
+                "payload": payload,
+            }),
+            Event::MessageForwarded { from, to, payload } => {
+                json!({ "from": from, "to": to, "payload": payload })
+            }
+            Event::AccessDenied {
+                destination,
+                from,
+                their_identity,
+            } => json!({
+                "destination": destination,
+                "from": from,
+                "their_identity": their_identity,
+            }),
+            Event::MessageDeadLettered {
+                id,
+                reason,
+                onward_route,
+                error,
+            } => json!({
+                "id": id,
+                "reason": reason,
+                "onward_route": onward_route,
+                "error": error,
+            }),
+            Event::LinkStatusChanged {
+                link,
+                status,
+                route,
+                silent_for_ms,
+            } => json!({
+                "link": link,
+                "status": status,
+                "route": route,
+                "silent_for_ms": silent_for_ms,
+            }),
+        }
+    }
+
+    /// The JSON line for this event (w/out the trailing newline).
+    pub fn to_json_line(&self, source: &str, timestamp_ms: u128) -> String {
+        json!({
+            "schema": EVENT_SCHEMA_VERSION,
+            "timestamp_ms": timestamp_ms as u64,
+            "source": source,
+            "kind": self.kind(),
+            "data": self.data(),
+        })
+        .to_string()
+    }
+}
+
+fn previous_hop<M: Message>(msg: &Routed<M>) -> String {
+    msg.return_route()
+        .next()
+        .map(|it| it.address().to_string())
+        .unwrap_or_else(|_| "?".to_string())
+}
+
+/// Writes [Event]s to a JSONL file, for tools (eg: CI) that need to assert on what happened
+/// in a run. The colored output is for people & can change at any time, but this schema is
+/// stable: fields may be added, but never renamed or removed w/out bumping
+/// [EVENT_SCHEMA_VERSION].
+///
+/// Each line has exactly these keys:
+///
+/// | key            | value                                                          |
+/// |----------------|----------------------------------------------------------------|
+/// | `schema`       | [EVENT_SCHEMA_VERSION]                                         |
+/// | `timestamp_ms` | milliseconds since the Unix epoch                              |
+/// | `source`       | the node name (eg: `"server"`), or worker address (eg: `"echoer"`) |
+/// | `kind`         | one of the [Event::kind] values, eg: `"message_received"`      |
+/// | `data`         | an object w/ the fields of that kind, see [Event]              |
+///
+/// Eg:
+/// `{"data":{"from":"h2","onward_route":"[echoer]","payload":"Hello Ockam!","return_route":"[h2 → h1 → app]"},"kind":"message_received","schema":1,"source":"echoer","timestamp_ms":1690000000000}`
+///
+/// The keys are always written in alphabetical order.
+///
+/// It does nothing unless it was opened, so the workers & examples can emit events
+/// unconditionally.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    file: Option<Mutex<BufWriter<File>>>,
+    path: Option<PathBuf>,
+}
+
+impl EventLog {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Append to the file at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Some(Mutex::new(BufWriter::new(file))),
+            path: Some(path.to_path_buf()),
+        })
+    }
+
+    /// Make `log` the one that [emit_event] writes to, eg: `EventLog::open(path)` in a test,
+    /// instead of the one at [EVENT_LOG_ENV]. Gives `log` back if [EventLog::global] was
+    /// already set up.
+    pub fn init_global(log: EventLog) -> std::result::Result<(), EventLog> {
+        GLOBAL.set(log)
+    }
+
+    /// The log that [emit_event] writes to: the one passed to [EventLog::init_global], or
+    /// else the file at [EVENT_LOG_ENV] if it is set (and can be opened), otherwise a
+    /// disabled one.
+    pub fn global() -> &'static EventLog {
+        GLOBAL.get_or_init(|| match std::env::var_os(EVENT_LOG_ENV) {
+            Some(path) if !path.is_empty() => EventLog::open(&path).unwrap_or_else(|error| {
+                eprintln!("Can't open the event log {:?}: {}", path, error);
+                EventLog::disabled()
+            }),
+            _ => EventLog::disabled(),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Write `event` as one line, and flush it right away so that the file is complete
+    /// even if the process is killed.
+    pub fn emit(&self, source: &str, event: Event) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|it| it.as_millis())
+            .unwrap_or_default();
+        let line = event.to_json_line(source, timestamp_ms);
+        if let Ok(mut file) = file.lock() {
+            writeln!(file, "{}", line).and_then(|_| file.flush()).ok();
+        }
+    }
+}
+
+/// Eg: `emit_event("server", Event::NodeStarted { role: NodeRole::Responder })`.
+pub fn emit_event(source: &str, event: Event) {
+    EventLog::global().emit(source, event);
+}
+
+/// Wraps an incoming access control (eg: `AbacAccessControl`), and emits an
+/// [Event::AccessDenied] for each message that it rejects. The rejected messages are
+/// dropped before they reach the worker, so the worker can't report them itself. They are
+/// also sent to the node's [crate::DeadLetterQueue] (see [send_dead_letter]).
+#[derive(Debug)]
+pub struct ReportAccessDenied<A> {
+    source: String,
+    inner: A,
+    /// Sends the dead letters, since access controls don't get a context.
+    ctx: Context,
+}
+
+impl<A: IncomingAccessControl> ReportAccessDenied<A> {
+    pub async fn create(ctx: &Context, source: &str, inner: A) -> Result<Self> {
+        let ctx = ctx
+            .new_detached(
+                Address::random_tagged("ReportAccessDenied"),
+                DenyAll,
+                AllowAll,
+            )
+            .await?;
+        Ok(Self {
+            source: source.to_string(),
+            inner,
+            ctx,
+        })
+    }
+}
+
+#[async_trait]
+impl<A: IncomingAccessControl> IncomingAccessControl for ReportAccessDenied<A> {
+    async fn is_authorized(&self, relay_msg: &RelayMessage) -> Result<bool> {
+        let authorized = self.inner.is_authorized(relay_msg).await?;
+        if !authorized {
+            let their_identity =
+                IdentitySecureChannelLocalInfo::find_info(relay_msg.local_message())
+                    .ok()
+                    .map(|it| it.their_identity_id().to_string());
+            emit_event(
+                &self.source,
+                Event::AccessDenied {
+                    destination: relay_msg.destination().address().to_string(),
+                    from: relay_msg.source().address().to_string(),
+                    their_identity,
+                },
+            );
+            let letter = DeadLetter::new(
+                &self.source,
+                DeadLetterReason::AccessDenied,
+                "rejected by the incoming access control",
+                relay_msg.local_message(),
+            );
+            send_dead_letter(&self.ctx, letter).await;
+        }
+        Ok(authorized)
+    }
+}
@@ -0,0 +1,293 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::FlowStep;
+use ockam::{route, Address, Message, Route, Routed};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Payload summaries longer than this are truncated.
+const MAX_PAYLOAD_SUMMARY: usize = 40;
+
+/// The narrowest that a participant's column can be in the sequence diagram.
+const MIN_COLUMN_WIDTH: usize = 14;
+
+/// One message as seen by the worker (or initiator) that received it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowEvent {
+    /// Time since the recorder was created.
+    pub at: Duration,
+    pub sender: String,
+    pub receiver: String,
+    pub onward_route: String,
+    pub return_route: String,
+    pub payload: String,
+}
+
+/// Records the messages that flow between workers in this process, so that a run can be
+/// shown as a sequence diagram (see [FlowRecorder::render_sequence_diagram]) instead of
+/// as interleaved colored output. The [crate::Echoer], [crate::Hopper] and
+/// [crate::Forwarder] workers report every message they receive to
+/// [FlowRecorder::global], which only keeps them once it has been enabled.
+#[derive(Debug)]
+pub struct FlowRecorder {
+    enabled: AtomicBool,
+    started: Instant,
+    events: Mutex<Vec<FlowEvent>>,
+}
+
+impl Default for FlowRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlowRecorder {
+    pub fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            started: Instant::now(),
+            events: Mutex::new(vec![]),
+        }
+    }
+
+    /// The recorder that the workers in this crate report to.
+    pub fn global() -> &'static FlowRecorder {
+        static GLOBAL: OnceLock<FlowRecorder> = OnceLock::new();
+        GLOBAL.get_or_init(FlowRecorder::new)
+    }
+
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn record(
+        &self,
+        sender: impl Into<String>,
+        receiver: impl Into<String>,
+        onward_route: impl Into<String>,
+        return_route: impl Into<String>,
+        payload: &str,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        let event = FlowEvent {
+            at: self.started.elapsed(),
+            sender: sender.into(),
+            receiver: receiver.into(),
+            onward_route: onward_route.into(),
+            return_route: return_route.into(),
+            payload: summarize_payload(payload),
+        };
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+
+    /// Record `msg` arriving at the worker w/ `receiver` address. The sender is the next
+    /// hop on the message's return route.
+    pub fn record_routed<M: Message>(&self, receiver: &Address, msg: &Routed<M>, payload: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        let sender = msg
+            .return_route()
+            .next()
+            .map(|it| it.address().to_string())
+            .unwrap_or_else(|_| "?".to_string());
+        self.record(
+            sender,
+            receiver.address(),
+            format_route(&msg.onward_route()),
+            format_route(&msg.return_route()),
+            payload,
+        );
+    }
+
+    /// Record the initiator w/ `sender` address sending `payload` over `route`. Call this
+    /// before sending, so that the event is ordered before the ones that it causes.
+    pub fn record_send(&self, sender: &Address, route: &Route, payload: &str) {
+        let receiver = route
+            .iter()
+            .next()
+            .map(|it| it.address().to_string())
+            .unwrap_or_else(|| "?".to_string());
+        self.record(
+            sender.address(),
+            receiver,
+            format_route(route),
+            format_route(&route![sender.clone()]),
+            payload,
+        );
+    }
+
+    /// Record the initiator w/ `receiver` address receiving the `reply` to a message that
+    /// it sent over `route`. The reply is shown as coming from the last hop on that route.
+    pub fn record_reply(&self, receiver: &Address, route: &Route, reply: &str) {
+        let sender = route
+            .iter()
+            .last()
+            .map(|it| it.address().to_string())
+            .unwrap_or_else(|| "?".to_string());
+        self.record(
+            sender,
+            receiver.address(),
+            format_route(&route![receiver.clone()]),
+            "[]",
+            reply,
+        );
+    }
+
+    pub fn events(&self) -> Vec<FlowEvent> {
+        self.events.lock().map(|it| it.clone()).unwrap_or_default()
+    }
+
+    /// The recorded events as steps for [crate::export_diagrams].
+    pub fn flow_steps(&self) -> Vec<FlowStep> {
+        self.events()
+            .into_iter()
+            .map(|it| FlowStep::new(it.sender, it.receiver, it.payload))
+            .collect()
+    }
+
+    /// An ASCII sequence diagram w/ a column per participant (in order of appearance) and an
+    /// arrow per event, followed by the timestamp and routes of each event.
+    pub fn render_sequence_diagram(&self) -> String {
+        let events = self.events();
+        let mut participants: Vec<String> = vec![];
+        for event in &events {
+            for participant in [&event.sender, &event.receiver] {
+                if !participants.contains(participant) {
+                    participants.push(participant.clone());
+                }
+            }
+        }
+        if participants.is_empty() {
+            return "No messages were recorded\n".to_string();
+        }
+
+        let column_width = participants
+            .iter()
+            .map(|it| it.chars().count() + 2)
+            .max()
+            .unwrap_or(0)
+            .max(MIN_COLUMN_WIDTH);
+        let width = column_width * participants.len();
+        let center = |index: usize| index * column_width + column_width / 2;
+        let index_of = |name: &str| participants.iter().position(|it| it == name).unwrap_or(0);
+        let lifelines = || {
+            let mut row = vec![' '; width];
+            (0..participants.len()).for_each(|index| row[center(index)] = '│');
+            row
+        };
+
+        let mut output = String::new();
+
+        // Header w/ the participant names centered over their lifelines.
+        let mut header = vec![' '; width];
+        for (index, participant) in participants.iter().enumerate() {
+            let start = center(index).saturating_sub(participant.chars().count() / 2);
+            for (offset, char) in participant.chars().enumerate() {
+                header[start + offset] = char;
+            }
+        }
+        push_row(&mut output, &header);
+        push_row(&mut output, &lifelines());
+
+        for (number, event) in events.iter().enumerate() {
+            let from = center(index_of(&event.sender));
+            let to = center(index_of(&event.receiver));
+            let (left, right) = (from.min(to), from.max(to));
+
+            // Label row, eg: "1. Hello Ockam!", between the two lifelines.
+            let mut label_row = lifelines();
+            let label = format!("{}. {}", number + 1, event.payload);
+            let label_width = if left == right {
+                column_width - 2
+            } else {
+                right - left - 2
+            };
+            for (offset, char) in label.chars().take(label_width).enumerate() {
+                label_row[left + 2 + offset] = char;
+            }
+            push_row(&mut output, &label_row);
+
+            // Arrow row.
+            let mut arrow_row = lifelines();
+            if from < to {
+                arrow_row[from] = '├';
+                (from + 1..to - 1).for_each(|it| arrow_row[it] = '─');
+                arrow_row[to - 1] = '►';
+            } else if from > to {
+                arrow_row[to + 1] = '◄';
+                (to + 2..from).for_each(|it| arrow_row[it] = '─');
+                arrow_row[from] = '┤';
+            } else {
+                arrow_row[from] = '↺';
+            }
+            push_row(&mut output, &arrow_row);
+        }
+        push_row(&mut output, &lifelines());
+
+        output.push('\n');
+        for (number, event) in events.iter().enumerate() {
+            writeln!(
+                output,
+                "{}. +{}ms {} → {}: '{}'\n   onward_route: {}\n   return_route: {}",
+                number + 1,
+                event.at.as_millis(),
+                event.sender,
+                event.receiver,
+                event.payload,
+                event.onward_route,
+                event.return_route
+            )
+            .ok();
+        }
+
+        output
+    }
+}
+
+/// Eg: `[hopper2 → echoer]`.
+pub fn format_route(route: &Route) -> String {
+    let addresses: Vec<String> = route.iter().map(|it| it.address().to_string()).collect();
+    format!("[{}]", addresses.join(" → "))
+}
+
+pub(crate) fn summarize_payload(payload: &str) -> String {
+    let payload = payload.replace('\n', " ");
+    if payload.chars().count() <= MAX_PAYLOAD_SUMMARY {
+        payload
+    } else {
+        let truncated: String = payload.chars().take(MAX_PAYLOAD_SUMMARY - 1).collect();
+        format!("{}…", truncated)
+    }
+}
+
+fn push_row(output: &mut String, row: &[char]) {
+    let row: String = row.iter().collect();
+    output.push_str(row.trim_end());
+    output.push('\n');
+}
@@ -15,6 +15,10 @@
  *   limitations under the License.
  */
 
+use crate::{
+    emit_event, send_dead_letter, DeadLetter, DeadLetterReason, Event, FlowRecorder, LinkHealth,
+    LinkStatus, Presenter,
+};
 use colored::Colorize;
 use ockam::{Address, Any, Context, LocalMessage, Result, Routed, Worker};
 
@@ -37,7 +41,13 @@ impl Worker for Forwarder {
             format!("{}", ctx.address()).white(),
             format!("{}", msg).white()
         );
-        println!("{}", output_msg.on_bright_blue());
+        Presenter::for_name(&ctx.address().to_string()).println(output_msg);
+        let payload = format!("{} bytes", msg.payload().len());
+        FlowRecorder::global().record_routed(&ctx.address(), &msg, &payload);
+        emit_event(
+            ctx.address().address(),
+            Event::message_forwarded(&msg, self.address.address(), &payload),
+        );
 
         // Some type conversion
         let mut transport_message = msg.into_local_message().into_transport_message();
@@ -69,7 +79,37 @@ impl Worker for Forwarder {
                 .add_consumer(self.address.clone(), info.flow_control_id());
         }
 
-        // Send the message on its onward_route
-        ctx.forward(message).await
+        // Don't send into a link that a heartbeat monitor says is down, keep the message
+        // as a dead letter so that it can be replayed once the link is up again.
+        if LinkHealth::global().status_of_next_hop(&self.address) == Some(LinkStatus::Down) {
+            send_dead_letter(
+                ctx,
+                DeadLetter::new(
+                    ctx.address().address(),
+                    DeadLetterReason::LinkDown,
+                    format!("the link to {} is down", self.address),
+                    &message,
+                ),
+            )
+            .await;
+            return Ok(());
+        }
+
+        // Send the message on its onward_route, or keep it as a dead letter if the
+        // connection (or worker) at my predefined address is gone.
+        let dead_letter = message.clone();
+        if let Err(error) = ctx.forward(message).await {
+            send_dead_letter(
+                ctx,
+                DeadLetter::new(
+                    ctx.address().address(),
+                    DeadLetterReason::ConnectionClosed,
+                    error,
+                    &dead_letter,
+                ),
+            )
+            .await;
+        }
+        Ok(())
     }
 }
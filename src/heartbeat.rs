@@ -0,0 +1,330 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{emit_event, format_route, Event, Presenter};
+use colored::Colorize;
+use ockam::{Address, Context, DelayedEvent, Message, Result, Route, Routed, Worker};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Where nodes usually start their [HeartbeatResponder].
+pub const HEARTBEAT_ADDRESS: &str = "heartbeat";
+
+/// How many [LinkEvent]s a slow [LinkHealth::subscribe]r can fall behind by.
+const LINK_EVENTS_CAPACITY: usize = 64;
+
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub enum Heartbeat {
+    /// Sent by a [HeartbeatMonitor] to a [HeartbeatResponder].
+    Beat { sequence: u64 },
+    /// The [HeartbeatResponder]'s reply to a [Heartbeat::Beat].
+    Ack { sequence: u64 },
+    /// Sent to a [HeartbeatMonitor] by its own timer, to send the next beat.
+    Tick,
+}
+
+/// Replies to each [Heartbeat::Beat] w/ a [Heartbeat::Ack]. Make it a consumer of the
+/// listeners (eg: TCP & secure channel) that the monitors reach it through, like the
+/// [crate::Echoer]. It is quiet, so that the heartbeats don't drown out the other
+/// messages.
+pub struct HeartbeatResponder;
+
+#[ockam::worker]
+impl Worker for HeartbeatResponder {
+    type Context = Context;
+    type Message = Heartbeat;
+
+    async fn handle_message(&mut self, ctx: &mut Context, msg: Routed<Heartbeat>) -> Result<()> {
+        let return_route = msg.return_route();
+        match msg.body() {
+            Heartbeat::Beat { sequence } => {
+                ctx.send(return_route, Heartbeat::Ack { sequence }).await
+            }
+            Heartbeat::Ack { .. } | Heartbeat::Tick => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStatus {
+    Up,
+    Down,
+}
+
+impl LinkStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkStatus::Up => "up",
+            LinkStatus::Down => "down",
+        }
+    }
+}
+
+/// A link went up or down, as seen by its [HeartbeatMonitor].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LinkEvent {
+    pub link: String,
+    pub status: LinkStatus,
+    /// The route to the [HeartbeatResponder], eg: `[0#tcp_sender_1, 0#heartbeat]`.
+    pub route: String,
+    /// How long it has been since the last ack (or since the monitor started).
+    pub silent_for: Duration,
+}
+
+#[derive(Debug)]
+struct LinkRecord {
+    status: LinkStatus,
+    next_hop: Option<Address>,
+}
+
+/// The status of every link that a [HeartbeatMonitor] watches in this process, for the
+/// code that needs to know whether a neighbor is still there (eg: a [crate::Forwarder]
+/// keeps the messages for a link that is down as dead letters). Use [LinkHealth::global].
+pub struct LinkHealth {
+    links: Mutex<BTreeMap<String, LinkRecord>>,
+    events: broadcast::Sender<LinkEvent>,
+}
+
+impl LinkHealth {
+    pub fn global() -> &'static LinkHealth {
+        static LINK_HEALTH: OnceLock<LinkHealth> = OnceLock::new();
+        LINK_HEALTH.get_or_init(|| LinkHealth {
+            links: Mutex::new(BTreeMap::new()),
+            events: broadcast::channel(LINK_EVENTS_CAPACITY).0,
+        })
+    }
+
+    /// None until the link's monitor got its first ack, or timed out.
+    pub fn status(&self, link: &str) -> Option<LinkStatus> {
+        self.lock().get(link).map(|it| it.status)
+    }
+
+    /// The status of the links whose route starts at `address` (eg: the sender address of
+    /// a TCP connection), or None if no monitor watches it. Down if any of them is down.
+    pub fn status_of_next_hop(&self, address: &Address) -> Option<LinkStatus> {
+        let links = self.lock();
+        let mut statuses = links
+            .values()
+            .filter(|it| it.next_hop.as_ref() == Some(address))
+            .map(|it| it.status)
+            .peekable();
+        statuses.peek()?;
+        Some(if statuses.any(|it| it == LinkStatus::Down) {
+            LinkStatus::Down
+        } else {
+            LinkStatus::Up
+        })
+    }
+
+    /// Get every [LinkEvent] from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<LinkEvent> {
+        self.events.subscribe()
+    }
+
+    fn update(&self, next_hop: Option<Address>, event: LinkEvent) {
+        self.lock().insert(
+            event.link.clone(),
+            LinkRecord {
+                status: event.status,
+                next_hop,
+            },
+        );
+        // There may be no subscribers.
+        let _ = self.events.send(event);
+    }
+
+    fn remove(&self, link: &str) {
+        self.lock().remove(link);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, LinkRecord>> {
+        self.links.lock().unwrap_or_else(|it| it.into_inner())
+    }
+}
+
+/// Sends a [Heartbeat::Beat] over `route` (eg: over a TCP connection, or a secure channel)
+/// to a [HeartbeatResponder] every interval. The link is down when no ack has arrived for
+/// the timeout, and up again when one does. Only the acks of the beats that were sent
+/// within the timeout count. The changes go to [LinkHealth::global], and to the event log.
+pub struct HeartbeatMonitor {
+    link: String,
+    route: Route,
+    interval: Duration,
+    timeout: Duration,
+    sequence: u64,
+    /// The beats that haven't been acked, w/ when they were sent.
+    outstanding: BTreeMap<u64, Instant>,
+    started: Instant,
+    last_ack: Option<Instant>,
+    status: Option<LinkStatus>,
+    /// Sends the next [Heartbeat::Tick] to this monitor, see [Worker::initialize].
+    timer: Option<DelayedEvent<Heartbeat>>,
+}
+
+impl HeartbeatMonitor {
+    /// Eg: `HeartbeatMonitor::new("middle→responder", route![connection, HEARTBEAT_ADDRESS])`.
+    pub fn new(link: impl Into<String>, route: impl Into<Route>) -> Self {
+        Self {
+            link: link.into(),
+            route: route.into(),
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(3),
+            sequence: 0,
+            outstanding: BTreeMap::new(),
+            started: Instant::now(),
+            last_ack: None,
+            status: None,
+            timer: None,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// How long the link may be silent before it is down. Make it a few intervals, so that
+    /// one lost beat doesn't take the link down.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Start the monitor at a random address, which is returned so that it can be
+    /// stopped.
+    pub async fn start(self, ctx: &Context) -> Result<Address> {
+        let address = Address::random_tagged("HeartbeatMonitor");
+        ctx.start_worker(address.clone(), self).await?;
+        Ok(address)
+    }
+
+    /// Send the next beat, and forget the ones that are too old to be acked.
+    async fn beat(&mut self, ctx: &Context) {
+        let timeout = self.timeout;
+        self.outstanding
+            .retain(|_, sent_at| sent_at.elapsed() < timeout);
+        self.sequence += 1;
+        self.outstanding.insert(self.sequence, Instant::now());
+        // A beat that can't be sent (eg: the connection is closed) is like a lost one.
+        let _ = ctx
+            .send(
+                self.route.clone(),
+                Heartbeat::Beat {
+                    sequence: self.sequence,
+                },
+            )
+            .await;
+    }
+
+    fn change_status(&mut self, ctx: &Context, status: LinkStatus) {
+        if self.status == Some(status) {
+            return;
+        }
+        self.status = Some(status);
+        let silent_for = self.last_ack.unwrap_or(self.started).elapsed();
+        let route = format_route(&self.route);
+
+        let output_msg = match status {
+            LinkStatus::Up => format!("💓 link '{}' is up", self.link).green(),
+            LinkStatus::Down => format!(
+                "💔 link '{}' is down, no heartbeat for {:?}",
+                self.link, silent_for
+            )
+            .red(),
+        };
+        Presenter::for_name(&ctx.address().to_string()).println(output_msg.to_string());
+        emit_event(
+            ctx.address().address(),
+            Event::LinkStatusChanged {
+                link: self.link.clone(),
+                status: status.as_str().to_string(),
+                route: route.clone(),
+                silent_for_ms: silent_for.as_millis() as u64,
+            },
+        );
+        LinkHealth::global().update(
+            self.route.iter().next().cloned(),
+            LinkEvent {
+                link: self.link.clone(),
+                status,
+                route,
+                silent_for,
+            },
+        );
+    }
+}
+
+#[ockam::worker]
+impl Worker for HeartbeatMonitor {
+    type Context = Context;
+    type Message = Heartbeat;
+
+    async fn initialize(&mut self, ctx: &mut Context) -> Result<()> {
+        // Allow the acks to come back over the first hop, like the [crate::Forwarder] does.
+        if let Some(next_hop) = self.route.iter().next() {
+            if let Some(info) = ctx
+                .flow_controls()
+                .find_flow_control_with_producer_address(next_hop)
+            {
+                ctx.flow_controls()
+                    .add_consumer(ctx.address(), info.flow_control_id());
+            }
+        }
+        // The timer sends from a context of its own, which may only send to this monitor.
+        let mut timer = DelayedEvent::create(ctx, ctx.address(), Heartbeat::Tick).await?;
+        timer.schedule(Duration::ZERO).await?;
+        self.timer = Some(timer);
+        Ok(())
+    }
+
+    async fn shutdown(&mut self, _ctx: &mut Context) -> Result<()> {
+        // Dropping the timer cancels the next tick.
+        self.timer = None;
+        LinkHealth::global().remove(&self.link);
+        Ok(())
+    }
+
+    async fn handle_message(&mut self, ctx: &mut Context, msg: Routed<Heartbeat>) -> Result<()> {
+        match msg.body() {
+            Heartbeat::Tick => {
+                let silent_for = self.last_ack.unwrap_or(self.started).elapsed();
+                if silent_for >= self.timeout {
+                    self.change_status(ctx, LinkStatus::Down);
+                }
+                self.beat(ctx).await;
+                if let Some(timer) = &mut self.timer {
+                    timer.schedule(self.interval).await?;
+                }
+            }
+            Heartbeat::Ack { sequence } => {
+                // Eg: a duplicate, or the ack of a beat that was sent before the timeout.
+                if self.outstanding.remove(&sequence).is_none() {
+                    return Ok(());
+                }
+                // The acks of the beats before it may still come, but they're not needed.
+                self.outstanding.retain(|it, _| *it > sequence);
+                self.last_ack = Some(Instant::now());
+                self.change_status(ctx, LinkStatus::Up);
+            }
+            Heartbeat::Beat { .. } => {}
+        }
+        Ok(())
+    }
+}
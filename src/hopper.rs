@@ -15,7 +15,9 @@
  *   limitations under the License.
  */
 
-use colored::Colorize;
+use crate::{
+    emit_event, send_dead_letter, DeadLetter, DeadLetterReason, Event, FlowRecorder, Presenter,
+};
 use ockam::{Any, Context, Result, Routed, Worker};
 
 pub struct Hopper;
@@ -32,8 +34,20 @@ impl Worker for Hopper {
     async fn handle_message(&mut self, ctx: &mut Context, msg: Routed<Any>) -> Result<()> {
         // Cast the msg to a Routed<String>
         let msg: Routed<String> = msg.cast()?;
+        let presenter = Presenter::for_name(&ctx.address().to_string());
         let output_msg = format!("🐇 Address: {}, Received: {}", ctx.address(), msg);
-        println!("{}", output_msg.on_bright_blue());
+        presenter.println(output_msg);
+        FlowRecorder::global().record_routed(&ctx.address(), &msg, msg.as_body());
+        let next_hop = msg
+            .onward_route()
+            .iter()
+            .nth(1)
+            .map(|it| it.address().to_string())
+            .unwrap_or_default();
+        emit_event(
+            ctx.address().address(),
+            Event::message_forwarded(&msg, &next_hop, msg.as_body()),
+        );
 
         // Some type conversion
         let mut message = msg.into_local_message();
@@ -47,7 +61,7 @@ impl Worker for Hopper {
             removed_address,
             ctx.address()
         );
-        println!("{}", output_msg.on_bright_blue());
+        presenter.println(output_msg);
 
         // Insert my address at the beginning return_route
         transport_message
@@ -55,7 +69,21 @@ impl Worker for Hopper {
             .modify()
             .prepend(ctx.address());
 
-        // Send the message on its onward_route
-        ctx.forward(message).await
+        // Send the message on its onward_route, or keep it as a dead letter if the next hop
+        // doesn't exist.
+        let dead_letter = message.clone();
+        if let Err(error) = ctx.forward(message).await {
+            send_dead_letter(
+                ctx,
+                DeadLetter::new(
+                    ctx.address().address(),
+                    DeadLetterReason::UnknownAddress,
+                    error,
+                    &dead_letter,
+                ),
+            )
+            .await;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,363 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use colored::{Color, Colorize};
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Instant};
+
+/// How long to wait for a long running role to print its readiness line.
+pub const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for a child to exit after asking it to stop (SIGTERM), before killing
+/// it (SIGKILL).
+pub const DEFAULT_STOP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// How often to check whether the long running roles are still alive.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// One node role (eg: the credential issuer) that is run as a child process.
+pub struct ChildRole {
+    name: String,
+    color: Color,
+    program: PathBuf,
+    args: Vec<String>,
+    ready_line: Option<String>,
+}
+
+impl ChildRole {
+    pub fn new(name: &str, color: Color, program: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.to_string(),
+            color,
+            program: program.into(),
+            args: vec![],
+            ready_line: None,
+        }
+    }
+
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Mark this role as long running (eg: a responder or issuer). The launcher waits until
+    /// the child prints a line containing `ready_line` before it starts the next role, and
+    /// it stops the child once the last role exits.
+    pub fn ready_when_stdout_contains(mut self, ready_line: &str) -> Self {
+        self.ready_line = Some(ready_line.to_string());
+        self
+    }
+
+    fn prefix(&self) -> String {
+        format!("[{}]", self.name)
+            .color(self.color)
+            .bold()
+            .to_string()
+    }
+}
+
+struct RunningChild {
+    name: String,
+    prefix: String,
+    child: Child,
+    /// The tasks that print the child's stdout & stderr.
+    output: Vec<JoinHandle<()>>,
+}
+
+impl RunningChild {
+    /// Wait until everything that the child wrote has been printed.
+    async fn join_output(&mut self) {
+        for task in self.output.drain(..) {
+            task.await.ok();
+        }
+    }
+}
+
+/// Runs each node role in its own child process, in the order they are added. Each line
+/// that a child writes to stdout or stderr is prefixed with its colored role name.
+///
+/// - Long running roles (see [ChildRole::ready_when_stdout_contains]) must become ready
+///   before the next role is started.
+/// - The exit code of the last role is the exit code of the launcher.
+/// - If any role fails to start, doesn't become ready, or a long running role exits
+///   before the last role does, then all the children are stopped and that role's exit
+///   code is returned.
+/// - Each child runs in its own process group, which is stopped as a whole, so that the
+///   processes that a role starts don't outlive it.
+pub struct Launcher {
+    roles: Vec<ChildRole>,
+    ready_timeout: Duration,
+    stop_grace_period: Duration,
+}
+
+impl Default for Launcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Launcher {
+    pub fn new() -> Self {
+        Self {
+            roles: vec![],
+            ready_timeout: DEFAULT_READY_TIMEOUT,
+            stop_grace_period: DEFAULT_STOP_GRACE_PERIOD,
+        }
+    }
+
+    pub fn role(mut self, role: ChildRole) -> Self {
+        self.roles.push(role);
+        self
+    }
+
+    pub fn with_ready_timeout(mut self, ready_timeout: Duration) -> Self {
+        self.ready_timeout = ready_timeout;
+        self
+    }
+
+    pub fn with_stop_grace_period(mut self, stop_grace_period: Duration) -> Self {
+        self.stop_grace_period = stop_grace_period;
+        self
+    }
+
+    /// Run all the roles and return the exit code that the launcher should exit with.
+    pub async fn run(self) -> std::io::Result<i32> {
+        let mut running: Vec<RunningChild> = vec![];
+        let role_count = self.roles.len();
+
+        for (index, role) in self.roles.into_iter().enumerate() {
+            let is_last = index + 1 == role_count;
+            let (mut current, ready_rx) = match spawn_role(&role) {
+                Ok(spawned) => spawned,
+                Err(error) => {
+                    eprintln!(
+                        "{} {}",
+                        role.prefix(),
+                        format!("failed to start {:?}: {}", role.program, error).red()
+                    );
+                    stop_all(running, self.stop_grace_period).await;
+                    return Ok(1);
+                }
+            };
+
+            if is_last {
+                // Wait for the last role, unless a long running one exits first.
+                let status = loop {
+                    tokio::select! {
+                        status = current.child.wait() => break status?,
+                        _ = sleep(EXIT_POLL_INTERVAL) => {
+                            if let Some(code) = exited_early(&mut running).await {
+                                running.push(current);
+                                stop_all(running, self.stop_grace_period).await;
+                                return Ok(code);
+                            }
+                        }
+                    }
+                };
+                current.join_output().await;
+                stop_all(running, self.stop_grace_period).await;
+                return Ok(exit_code(&current.name, status));
+            }
+
+            if role.ready_line.is_some() {
+                // Wait for the readiness line, unless the child (or a long running role
+                // that was started before it) exits first.
+                let deadline = Instant::now() + self.ready_timeout;
+                let mut ready_rx = ready_rx;
+                let became_ready = loop {
+                    tokio::select! {
+                        ready = &mut ready_rx => break ready.is_ok(),
+                        status = current.child.wait() => {
+                            let code = exit_code(&current.name, status?);
+                            eprintln!("{} {}", current.prefix, "exited before it became ready".red());
+                            current.join_output().await;
+                            stop_all(running, self.stop_grace_period).await;
+                            return Ok(if code == 0 { 1 } else { code });
+                        }
+                        _ = sleep(EXIT_POLL_INTERVAL) => {
+                            if let Some(code) = exited_early(&mut running).await {
+                                running.push(current);
+                                stop_all(running, self.stop_grace_period).await;
+                                return Ok(code);
+                            }
+                            if Instant::now() >= deadline {
+                                break false;
+                            }
+                        }
+                    }
+                };
+                if !became_ready {
+                    eprintln!("{} {}", current.prefix, "didn't become ready in time".red());
+                    running.push(current);
+                    stop_all(running, self.stop_grace_period).await;
+                    return Ok(1);
+                }
+            }
+
+            running.push(current);
+        }
+
+        stop_all(running, self.stop_grace_period).await;
+        Ok(0)
+    }
+}
+
+/// Start `role` in its own process group, and the tasks that print its output. The
+/// receiver is signalled when the role prints its readiness line.
+fn spawn_role(role: &ChildRole) -> std::io::Result<(RunningChild, oneshot::Receiver<()>)> {
+    let mut command = std::process::Command::new(&role.program);
+    command
+        .args(&role.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = Command::from(command).kill_on_drop(true).spawn()?;
+
+    let prefix = role.prefix();
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let mut output = vec![];
+    if let Some(stdout) = child.stdout.take() {
+        output.push(tokio::spawn(print_lines(
+            stdout,
+            prefix.clone(),
+            role.ready_line.clone(),
+            Some(ready_tx),
+        )));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        output.push(tokio::spawn(print_lines(
+            stderr,
+            prefix.clone(),
+            None,
+            None,
+        )));
+    }
+    let running_child = RunningChild {
+        name: role.name.clone(),
+        prefix,
+        child,
+        output,
+    };
+    Ok((running_child, ready_rx))
+}
+
+/// The exit code to return if one of the long running roles has exited (they are supposed
+/// to run until they are stopped).
+async fn exited_early(running: &mut [RunningChild]) -> Option<i32> {
+    for running_child in running.iter_mut() {
+        if let Ok(Some(status)) = running_child.child.try_wait() {
+            let code = exit_code(&running_child.name, status);
+            eprintln!("{} {}", running_child.prefix, "exited early".red());
+            running_child.join_output().await;
+            return Some(if code == 0 { 1 } else { code });
+        }
+    }
+    None
+}
+
+/// Print each line from `reader` with `prefix`. When a line contains `ready_line`, signal
+/// `ready_tx`.
+async fn print_lines(
+    reader: impl AsyncRead + Unpin,
+    prefix: String,
+    ready_line: Option<String>,
+    mut ready_tx: Option<oneshot::Sender<()>>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        println!("{} {}", prefix, line);
+        if let Some(ready_line) = &ready_line {
+            if line.contains(ready_line.as_str()) {
+                if let Some(ready_tx) = ready_tx.take() {
+                    ready_tx.send(()).ok();
+                }
+            }
+        }
+    }
+}
+
+/// Stop the children in the reverse order that they were started. Each child's process
+/// group gets a SIGTERM so that it can shut down gracefully, and is killed if the child
+/// doesn't exit within `grace_period`.
+async fn stop_all(running: Vec<RunningChild>, grace_period: Duration) {
+    for mut running_child in running.into_iter().rev() {
+        if let Ok(None) = running_child.child.try_wait() {
+            terminate(&running_child.child);
+            if timeout(grace_period, running_child.child.wait())
+                .await
+                .is_err()
+            {
+                println!(
+                    "{}",
+                    format!("Killing {} (didn't stop after SIGTERM)", running_child.name).red()
+                );
+                kill(&mut running_child.child);
+                running_child.child.wait().await.ok();
+            }
+        }
+        running_child.join_output().await;
+    }
+}
+
+#[cfg(unix)]
+fn terminate(child: &Child) {
+    signal_process_group(child, libc::SIGTERM);
+}
+
+#[cfg(not(unix))]
+fn terminate(_child: &Child) {}
+
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    signal_process_group(child, libc::SIGKILL);
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    child.start_kill().ok();
+}
+
+/// Send `signal` to every process in the child's process group (which has the child's pid
+/// as its id, see [spawn_role]).
+#[cfg(unix)]
+fn signal_process_group(child: &Child, signal: libc::c_int) {
+    if let Some(pid) = child.id() {
+        // SAFETY: `pid` belongs to a child process that hasn't been reaped yet, so its
+        // process group still exists.
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), signal);
+        }
+    }
+}
+
+fn exit_code(name: &str, status: ExitStatus) -> i32 {
+    match status.code() {
+        Some(code) => code,
+        None => {
+            println!("{}", format!("{} was killed by a signal", name).red());
+            1
+        }
+    }
+}
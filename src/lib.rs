@@ -22,11 +22,69 @@
 #![warn(rust_2018_idioms)]
 
 // Import files.
+mod ascii_diagram;
+mod broker;
+mod chaos_proxy;
+mod connection_pool;
+mod dashboard;
+mod dead_letter;
+mod directory;
 mod echoer;
+mod error;
+mod event_log;
+mod flow_recorder;
 mod forwarder;
+mod heartbeat;
 mod hopper;
+mod launcher;
+mod load_generator;
+mod node_data_dir;
+mod node_role;
+mod node_transports;
+mod pinger;
+mod portal;
+mod presentation;
+mod route_syntax;
+mod rpc;
+mod shutdown;
+mod sim_transport;
+mod stats;
+mod topology;
+mod topology_export;
+mod udp_transport;
+#[cfg(unix)]
+mod unix_transport;
 
 // Re-export symbols.
+pub use ascii_diagram::*;
+pub use broker::*;
+pub use chaos_proxy::*;
+pub use connection_pool::*;
+pub use dashboard::*;
+pub use dead_letter::*;
+pub use directory::*;
 pub use echoer::*;
+pub use error::*;
+pub use event_log::*;
+pub use flow_recorder::*;
 pub use forwarder::*;
+pub use heartbeat::*;
 pub use hopper::*;
+pub use launcher::*;
+pub use load_generator::*;
+pub use node_data_dir::*;
+pub use node_role::*;
+pub use node_transports::*;
+pub use pinger::*;
+pub use portal::*;
+pub use presentation::*;
+pub use route_syntax::*;
+pub use rpc::*;
+pub use shutdown::*;
+pub use sim_transport::*;
+pub use stats::*;
+pub use topology::*;
+pub use topology_export::*;
+pub use udp_transport::*;
+#[cfg(unix)]
+pub use unix_transport::*;
@@ -0,0 +1,241 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{millis, nearest_rank_percentile};
+use ockam::errcode::{Kind, Origin};
+use ockam::{AsyncTryClone, Context, MessageSendReceiveOptions, Result, Route};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The widest bar in the [LoadReport] histogram.
+const HISTOGRAM_BAR_WIDTH: usize = 40;
+
+/// Sends `String` messages to an [crate::Echoer] over any route (eg: a local hop chain, TCP
+/// hops, or a secure channel across a [crate::Forwarder]) from `concurrency` senders at
+/// once, each one waiting for the reply before sending its next message. Use it to compare
+/// what each hop costs.
+///
+/// The workers print every message that they handle, which would dominate the numbers, so
+/// send their output elsewhere w/ [crate::redirect_output] during a run.
+#[derive(Debug, Clone)]
+pub struct LoadGenerator {
+    route: Route,
+    concurrency: usize,
+    message_size: usize,
+    messages: u64,
+    timeout: Duration,
+}
+
+impl LoadGenerator {
+    /// `route` ends at an echoer, eg: `route![connection, "forward_to_bob", "echoer"]`.
+    pub fn new(route: impl Into<Route>) -> Self {
+        Self {
+            route: route.into(),
+            concurrency: 4,
+            message_size: 64,
+            messages: 1000,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// How many messages are in flight at once.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// The size of each message in bytes.
+    pub fn with_message_size(mut self, message_size: usize) -> Self {
+        self.message_size = message_size;
+        self
+    }
+
+    /// How many messages to send in total, across all the senders.
+    pub fn with_messages(mut self, messages: u64) -> Self {
+        self.messages = messages;
+        self
+    }
+
+    /// How long to wait for each reply. Messages w/out a reply count as errors.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn run(&self, ctx: &Context) -> Result<LoadReport> {
+        let payload = "x".repeat(self.message_size);
+        let remaining = Arc::new(AtomicU64::new(self.messages));
+
+        let started = Instant::now();
+        let mut senders = vec![];
+        for _ in 0..self.concurrency {
+            let ctx = ctx.async_try_clone().await?;
+            let (route, payload, remaining) =
+                (self.route.clone(), payload.clone(), remaining.clone());
+            let timeout = self.timeout;
+            senders.push(tokio::spawn(async move {
+                let (mut latencies, mut errors) = (vec![], 0);
+                while take_one(&remaining) {
+                    let sent_at = Instant::now();
+                    let reply = ctx
+                        .send_and_receive_extended::<String>(
+                            route.clone(),
+                            payload.clone(),
+                            MessageSendReceiveOptions::new().with_timeout(timeout),
+                        )
+                        .await;
+                    match reply {
+                        Ok(_) => latencies.push(sent_at.elapsed()),
+                        Err(_) => errors += 1,
+                    }
+                }
+                (latencies, errors)
+            }));
+        }
+
+        let mut report = LoadReport {
+            concurrency: self.concurrency,
+            message_size: self.message_size,
+            sent: self.messages,
+            errors: 0,
+            elapsed: Duration::ZERO,
+            latencies: vec![],
+        };
+        for sender in senders {
+            let (latencies, errors) = sender
+                .await
+                .map_err(|error| ockam::Error::new(Origin::Application, Kind::Internal, error))?;
+            report.latencies.extend(latencies);
+            report.errors += errors;
+        }
+        report.elapsed = started.elapsed();
+        Ok(report)
+    }
+}
+
+/// Returns false once there are no messages left to send.
+fn take_one(remaining: &AtomicU64) -> bool {
+    remaining
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |it| it.checked_sub(1))
+        .is_ok()
+}
+
+/// The throughput and latencies of a [LoadGenerator] run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadReport {
+    pub concurrency: usize,
+    pub message_size: usize,
+    pub sent: u64,
+    /// Messages w/out a reply (eg: timed out, or the route can't be reached).
+    pub errors: u64,
+    pub elapsed: Duration,
+    /// The round trip time of each message that got a reply.
+    pub latencies: Vec<Duration>,
+}
+
+impl LoadReport {
+    pub fn received(&self) -> u64 {
+        self.latencies.len() as u64
+    }
+
+    /// Replies per second.
+    pub fn throughput(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.received() as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// The nearest rank percentile, eg: `percentile(99.0)` for the p99 latency.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        nearest_rank_percentile(&self.latencies, percentile)
+    }
+
+    /// The latencies counted in buckets that double in width, as `(upper bound, count)`
+    /// pairs from the first to the last non empty bucket. Eg: a latency of 300µs is counted
+    /// in the `512µs` bucket, w/ the ones from 257µs to 512µs.
+    pub fn histogram(&self) -> Vec<(Duration, u64)> {
+        let bucket_of = |latency: &Duration| {
+            let micros = latency.as_micros().max(1) as u64;
+            (u64::BITS - (micros - 1).leading_zeros()) as usize
+        };
+        let mut counts = vec![0; u64::BITS as usize + 1];
+        for latency in &self.latencies {
+            counts[bucket_of(latency)] += 1;
+        }
+        let Some(first) = counts.iter().position(|it| *it > 0) else {
+            return vec![];
+        };
+        let last = counts.iter().rposition(|it| *it > 0).unwrap_or(first);
+        (first..=last)
+            .map(|bucket| (Duration::from_micros(1 << bucket), counts[bucket]))
+            .collect()
+    }
+}
+
+impl Display for LoadReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} messages of {} bytes, {} at once: {} replies, {} errors in {:.2}s",
+            self.sent,
+            self.message_size,
+            self.concurrency,
+            self.received(),
+            self.errors,
+            self.elapsed.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "throughput = {:.0} msg/s, {:.2} MiB/s",
+            self.throughput(),
+            self.throughput() * self.message_size as f64 / (1024.0 * 1024.0)
+        )?;
+        if let (Some(p50), Some(p90), Some(p99), Some(max)) = (
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.percentile(100.0),
+        ) {
+            writeln!(
+                f,
+                "latency p50/p90/p99/max = {:.3}/{:.3}/{:.3}/{:.3} ms",
+                millis(p50),
+                millis(p90),
+                millis(p99),
+                millis(max)
+            )?;
+        }
+
+        let histogram = self.histogram();
+        let most = histogram.iter().map(|it| it.1).max().unwrap_or(0);
+        for (upper_bound, count) in histogram {
+            let width = (count as f64 / most as f64 * HISTOGRAM_BAR_WIDTH as f64).ceil() as usize;
+            writeln!(
+                f,
+                "  ≤ {:>9.3} ms │{:<bar$}│ {}",
+                millis(upper_bound),
+                "█".repeat(width),
+                count,
+                bar = HISTOGRAM_BAR_WIDTH
+            )?;
+        }
+        Ok(())
+    }
+}
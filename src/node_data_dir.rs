@@ -0,0 +1,369 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use ockam::errcode::{Kind, Origin};
+use ockam::identity::IdentityIdentifier;
+use ockam::vault::{KeyId, StoredSecret};
+use ockam::{Context, Node, Result};
+use ockam_core::async_trait;
+use ockam_node::KeyValueStorage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The directory that [NodeDataDir::for_node] keeps each node's data under. The default is
+/// `hello_ockam` in the user's data directory, eg: `~/.local/share/hello_ockam`. (Not the
+/// temp directory, which is wiped on reboot & shared w/ the other users.)
+pub const NODE_DATA_DIR_ENV: &str = "NODE_DATA_DIR";
+
+/// Only the owner may enter the data directory, & read or write the files in it.
+const DIRECTORY_MODE: u32 = 0o700;
+const FILE_MODE: u32 = 0o600;
+
+/// Whether [NodeDataDir::identity] created a new identity, or loaded the one from disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityOrigin {
+    Created,
+    Loaded,
+}
+
+impl IdentityOrigin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IdentityOrigin::Created => "created",
+            IdentityOrigin::Loaded => "loaded",
+        }
+    }
+}
+
+/// What is stored for each identity. The secret keys are in the vault, not in here.
+#[derive(Serialize, Deserialize, Debug)]
+struct IdentityFile {
+    identifier: String,
+    /// The hex encoded change history, as exported by ockam.
+    change_history: String,
+}
+
+/// A node's data directory, eg: `~/.local/share/hello_ockam/node_responder`, w/:
+/// - `vault.json`, the secrets of the node's vault.
+/// - `identities/<name>.json`, the change history of each identity.
+///
+/// A node that is created w/ [NodeDataDir::create_node] gets the same identities (& so the
+/// same identifiers) on each run, so that its peers & credential issuers can allowlist them.
+/// Everything in the directory is only accessible by its owner, and files that are
+/// accessible by anyone else are refused, like ssh does w/ private keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeDataDir {
+    path: PathBuf,
+}
+
+impl NodeDataDir {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The data directory of `node_name` under [NODE_DATA_DIR_ENV].
+    pub fn for_node(node_name: &str) -> Self {
+        let base = match std::env::var_os(NODE_DATA_DIR_ENV) {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => user_data_dir().join("hello_ockam"),
+        };
+        Self::new(base.join(node_name))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn vault_path(&self) -> PathBuf {
+        self.path.join("vault.json")
+    }
+
+    pub fn identity_path(&self, name: &str) -> PathBuf {
+        self.path.join("identities").join(format!("{}.json", name))
+    }
+
+    /// Create a node whose vault is stored in this directory (which is created if needed).
+    pub async fn create_node(&self, ctx: Context) -> Result<Node> {
+        create_private_dir(&self.path)?;
+        create_private_dir(&self.path.join("identities"))?;
+        let vault_storage = PrivateVaultStorage::load(self.vault_path())?;
+        Node::builder()
+            .with_vault_storage(Arc::new(vault_storage))
+            .build(ctx)
+            .await
+    }
+
+    /// Load the identity `name` into `node`, or create it (& store it) if this directory
+    /// doesn't have it yet. `node` must have been created w/ [NodeDataDir::create_node], so
+    /// that the identity's secret key is in (or goes into) this directory's vault.
+    pub async fn identity(
+        &self,
+        node: &Node,
+        name: &str,
+    ) -> Result<(IdentityIdentifier, IdentityOrigin)> {
+        let path = self.identity_path(name);
+        check_private_file(&path)?;
+        let repository = node.identities().repository();
+
+        if path.exists() {
+            let file: IdentityFile =
+                serde_json::from_slice(&std::fs::read(&path).map_err(io_error)?)
+                    .map_err(|error| invalid_identity_file(&path, error))?;
+            let change_history = hex::decode(&file.change_history)
+                .map_err(|error| invalid_identity_file(&path, error))?;
+            let identity = node
+                .identities()
+                .identities_creation()
+                .decode_identity(&change_history)
+                .await?;
+            if identity.identifier().to_string() != file.identifier {
+                return Err(invalid_identity_file(
+                    &path,
+                    format!("its change history is for {}", identity.identifier()),
+                ));
+            }
+            // Eg: the vault was deleted, or belongs to another data directory.
+            let key_id = node
+                .identities()
+                .identities_keys()
+                .get_secret_key(&identity, None)
+                .await?;
+            if node
+                .identities_vault()
+                .get_public_key(&key_id)
+                .await
+                .is_err()
+            {
+                return Err(ockam::Error::new(
+                    Origin::Vault,
+                    Kind::NotFound,
+                    format!(
+                        "the vault '{}' doesn't have the secret key of the identity '{}', \
+                         remove '{}' to create a new one",
+                        self.vault_path().display(),
+                        name,
+                        path.display()
+                    ),
+                ));
+            }
+            repository.update_identity(&identity).await?;
+            return Ok((identity.identifier(), IdentityOrigin::Loaded));
+        }
+
+        let identifier = node.create_identity().await?;
+        let identity = repository.get_identity(&identifier).await?;
+        let file = IdentityFile {
+            identifier: identifier.to_string(),
+            change_history: hex::encode(identity.export()?),
+        };
+        write_private_file(
+            &path,
+            &serde_json::to_vec_pretty(&file)
+                .map_err(|error| invalid_identity_file(&path, error))?,
+        )?;
+        Ok((identifier, IdentityOrigin::Created))
+    }
+
+    /// Delete this directory & everything in it, so that the next run starts w/ new
+    /// identities.
+    pub fn remove(&self) -> Result<()> {
+        match std::fs::remove_dir_all(&self.path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(io_error(error)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The secrets of a node's vault, in `vault.json`. Each write goes to a new file that only
+/// its owner can read, which then replaces `vault.json`. (Ockam's own file storage creates
+/// that file w/ the umask's permissions, so the secrets may be readable by anyone until it
+/// is locked down.)
+struct PrivateVaultStorage {
+    path: PathBuf,
+    secrets: Mutex<BTreeMap<KeyId, StoredSecret>>,
+}
+
+impl PrivateVaultStorage {
+    fn load(path: PathBuf) -> Result<Self> {
+        check_private_file(&path)?;
+        let secrets = match std::fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|error| invalid_vault_file(&path, error))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => return Err(io_error(error)),
+        };
+        Ok(Self {
+            path,
+            secrets: Mutex::new(secrets),
+        })
+    }
+
+    fn save(&self, secrets: &BTreeMap<KeyId, StoredSecret>) -> Result<()> {
+        let contents = serde_json::to_vec_pretty(secrets)
+            .map_err(|error| invalid_vault_file(&self.path, error))?;
+        let temp_path = self.path.with_extension("json.new");
+        // A leftover from a write that failed, which may have other permissions.
+        match std::fs::remove_file(&temp_path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                return Err(io_error(error))
+            }
+            _ => {}
+        }
+        write_private_file(&temp_path, &contents)?;
+        std::fs::rename(&temp_path, &self.path).map_err(io_error)
+    }
+}
+
+#[async_trait]
+impl KeyValueStorage<KeyId, StoredSecret> for PrivateVaultStorage {
+    async fn put(&self, key: KeyId, value: StoredSecret) -> Result<()> {
+        let mut secrets = self.secrets.lock().unwrap();
+        secrets.insert(key, value);
+        self.save(&secrets)
+    }
+
+    async fn get(&self, key: &KeyId) -> Result<Option<StoredSecret>> {
+        Ok(self.secrets.lock().unwrap().get(key).cloned())
+    }
+
+    async fn delete(&self, key: &KeyId) -> Result<Option<StoredSecret>> {
+        let mut secrets = self.secrets.lock().unwrap();
+        let removed = secrets.remove(key);
+        if removed.is_some() {
+            self.save(&secrets)?;
+        }
+        Ok(removed)
+    }
+
+    async fn keys(&self) -> Result<Vec<KeyId>> {
+        Ok(self.secrets.lock().unwrap().keys().cloned().collect())
+    }
+}
+
+/// `$XDG_DATA_HOME`, or else `~/.local/share`, or else the current directory.
+fn user_data_dir() -> PathBuf {
+    let absolute = |name: &str| {
+        std::env::var_os(name)
+            .map(PathBuf::from)
+            .filter(|it| it.is_absolute())
+    };
+    absolute("XDG_DATA_HOME")
+        .or_else(|| absolute("HOME").map(|it| it.join(".local").join("share")))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+fn create_private_dir(path: &Path) -> Result<()> {
+    if !path.exists() {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, DIRECTORY_MODE);
+        builder.create(path).map_err(io_error)?;
+        lock_down(path, DIRECTORY_MODE)?;
+    }
+    check_private_file(path)
+}
+
+/// Write `contents` to a new file at `path` that only its owner can read.
+fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, FILE_MODE);
+    let mut file = options.open(path).map_err(io_error)?;
+    std::io::Write::write_all(&mut file, contents).map_err(io_error)
+}
+
+#[cfg(unix)]
+fn lock_down(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    if !path.exists() {
+        return Ok(());
+    }
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).map_err(io_error)
+}
+
+#[cfg(not(unix))]
+fn lock_down(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Refuse `path` (if it exists) when it isn't owned by this user, or when anyone else may
+/// access it.
+#[cfg(unix)]
+fn check_private_file(path: &Path) -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    // SAFETY: getuid can't fail.
+    let uid = unsafe { libc::getuid() };
+    if metadata.uid() != uid {
+        return Err(ockam::Error::new(
+            Origin::Node,
+            Kind::Misuse,
+            format!("'{}' is owned by another user", path.display()),
+        ));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        return Err(ockam::Error::new(
+            Origin::Node,
+            Kind::Misuse,
+            format!(
+                "'{}' is accessible by other users (mode {:o}), run `chmod go-rwx` on it",
+                path.display(),
+                metadata.mode() & 0o777
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_private_file(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+fn invalid_identity_file(path: &Path, error: impl ToString) -> ockam::Error {
+    ockam::Error::new(
+        Origin::Identity,
+        Kind::Invalid,
+        format!(
+            "the identity file '{}' is invalid: {}",
+            path.display(),
+            error.to_string()
+        ),
+    )
+}
+
+fn invalid_vault_file(path: &Path, error: impl ToString) -> ockam::Error {
+    ockam::Error::new(
+        Origin::Vault,
+        Kind::Invalid,
+        format!(
+            "the vault file '{}' is invalid: {}",
+            path.display(),
+            error.to_string()
+        ),
+    )
+}
+
+fn io_error(error: std::io::Error) -> ockam::Error {
+    ockam::Error::new(Origin::Node, Kind::Io, error)
+}
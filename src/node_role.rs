@@ -0,0 +1,54 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use std::fmt::{Display, Formatter};
+
+/// The role that a node plays in the multi node examples. Roles are used to decide the
+/// order in which nodes are stopped, and to label the output of each node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum NodeRole {
+    /// Sends messages and waits for replies (eg: `node_initiator`, the credential client).
+    Initiator,
+    /// Routes messages between other nodes (eg: the middle node running a `Forwarder`).
+    Forwarder,
+    /// Waits for messages until stopped (eg: `node_responder`, the server, the issuer).
+    Responder,
+}
+
+impl NodeRole {
+    /// The order in which roles are stopped during a graceful shutdown. Initiators stop
+    /// producing new messages first, then forwarders stop routing, then responders stop.
+    pub const SHUTDOWN_ORDER: [NodeRole; 3] = [
+        NodeRole::Initiator,
+        NodeRole::Forwarder,
+        NodeRole::Responder,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeRole::Initiator => "initiator",
+            NodeRole::Forwarder => "forwarder",
+            NodeRole::Responder => "responder",
+        }
+    }
+}
+
+impl Display for NodeRole {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -0,0 +1,186 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{
+    Forwarder, RouteHop, RouteSpec, UdpConnectionOptions, UdpListenerOptions, UdpTransport,
+    UdpTransportExtension,
+};
+use ockam::errcode::{Kind, Origin};
+use ockam::flow_control::FlowControlId;
+use ockam::{
+    Address, Node, Result, TcpConnectionOptions, TcpListenerOptions, TcpTransport,
+    TcpTransportExtension,
+};
+use std::net::SocketAddr;
+
+/// The TCP & UDP transports of a node, so that it can listen & connect w/ either one, as
+/// picked by the `tcp:` or `udp:` [RouteHop] that it's given. Eg:
+/// `transports.listen(&RouteHop::Udp("127.0.0.1:4000".into())).await?`.
+pub struct NodeTransports {
+    pub tcp: TcpTransport,
+    pub udp: UdpTransport,
+}
+
+impl NodeTransports {
+    pub async fn create(node: &Node) -> Result<Self> {
+        Ok(Self {
+            tcp: node.create_tcp_transport().await?,
+            udp: node.create_udp_transport().await?,
+        })
+    }
+
+    pub async fn listen(&self, hop: &RouteHop) -> Result<HopListener> {
+        match hop {
+            RouteHop::Tcp(socket_address) => {
+                let listener = self
+                    .tcp
+                    .listen(socket_address, TcpListenerOptions::new())
+                    .await?;
+                Ok(HopListener {
+                    socket_address: *listener.socket_address(),
+                    processor_address: listener.processor_address().clone(),
+                    flow_control_id: listener.flow_control_id().clone(),
+                })
+            }
+            RouteHop::Udp(socket_address) => {
+                let listener = self
+                    .udp
+                    .listen(socket_address, UdpListenerOptions::new())
+                    .await?;
+                Ok(HopListener {
+                    socket_address: *listener.socket_address(),
+                    processor_address: listener.processor_address().clone(),
+                    flow_control_id: listener.flow_control_id().clone(),
+                })
+            }
+            _ => Err(not_a_transport_hop(hop)),
+        }
+    }
+
+    pub async fn connect(&self, hop: &RouteHop) -> Result<HopConnection> {
+        match hop {
+            RouteHop::Tcp(socket_address) => {
+                let connection = self
+                    .tcp
+                    .connect(socket_address, TcpConnectionOptions::new())
+                    .await?;
+                Ok(HopConnection {
+                    sender_address: connection.sender_address().clone(),
+                    receiver_address: connection.receiver_address().clone(),
+                    flow_control_id: connection.flow_control_id().clone(),
+                })
+            }
+            RouteHop::Udp(socket_address) => {
+                let connection = self
+                    .udp
+                    .connect(socket_address, UdpConnectionOptions::new())
+                    .await?;
+                Ok(HopConnection {
+                    sender_address: connection.sender_address().clone(),
+                    receiver_address: connection.receiver_address().clone(),
+                    flow_control_id: connection.flow_control_id().clone(),
+                })
+            }
+            _ => Err(not_a_transport_hop(hop)),
+        }
+    }
+}
+
+/// A TCP or UDP listener, see [NodeTransports::listen].
+#[derive(Debug, Clone)]
+pub struct HopListener {
+    socket_address: SocketAddr,
+    processor_address: Address,
+    flow_control_id: FlowControlId,
+}
+
+impl HopListener {
+    pub fn socket_address(&self) -> &SocketAddr {
+        &self.socket_address
+    }
+
+    /// The address of the processor that accepts the connections or receives the
+    /// datagrams, eg: to stop it.
+    pub fn processor_address(&self) -> &Address {
+        &self.processor_address
+    }
+
+    pub fn flow_control_id(&self) -> &FlowControlId {
+        &self.flow_control_id
+    }
+}
+
+/// A TCP or UDP connection, see [NodeTransports::connect].
+#[derive(Debug, Clone)]
+pub struct HopConnection {
+    sender_address: Address,
+    receiver_address: Address,
+    flow_control_id: FlowControlId,
+}
+
+impl HopConnection {
+    pub fn sender_address(&self) -> &Address {
+        &self.sender_address
+    }
+
+    /// The address of the processor that receives the replies, eg: to stop it.
+    pub fn receiver_address(&self) -> &Address {
+        &self.receiver_address
+    }
+
+    pub fn flow_control_id(&self) -> &FlowControlId {
+        &self.flow_control_id
+    }
+}
+
+impl From<&HopConnection> for Address {
+    fn from(connection: &HopConnection) -> Self {
+        connection.sender_address.clone()
+    }
+}
+
+/// Start a [Forwarder] at `address` that forwards the messages from `listener` over
+/// `connection`, like `forward_to_responder` in
+/// examples/04-routing-over-two-transport-hops.rs. The transports of the two can differ.
+pub async fn start_forwarder(
+    node: &Node,
+    address: &str,
+    listener: &HopListener,
+    connection: &HopConnection,
+) -> Result<()> {
+    node.start_worker(
+        address,
+        Forwarder {
+            address: connection.into(),
+        },
+    )
+    .await?;
+    node.flow_controls()
+        .add_consumer(address, listener.flow_control_id());
+    Ok(())
+}
+
+fn not_a_transport_hop(hop: &RouteHop) -> ockam::Error {
+    ockam::Error::new(
+        Origin::Application,
+        Kind::Unsupported,
+        format!(
+            "'{}' isn't a `tcp:` or `udp:` hop",
+            RouteSpec::new(vec![hop.clone()])
+        ),
+    )
+}
@@ -0,0 +1,266 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{emit_event, millis, nearest_rank_percentile, Event, FlowRecorder, Presenter};
+use colored::Colorize;
+use ockam::{Context, Message, MessageSendReceiveOptions, Result, Route, Routed, Worker};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A probe sent by the [PingClient]. `padding` makes it `payload_size` bytes bigger.
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub struct Ping {
+    pub sequence: u64,
+    /// Microseconds since the Unix epoch, on the client.
+    pub sent_at_micros: u64,
+    pub padding: Vec<u8>,
+}
+
+/// The [Pinger]'s reply to a [Ping].
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub struct Pong {
+    pub sequence: u64,
+    /// Copied from the [Ping].
+    pub sent_at_micros: u64,
+    /// Microseconds since the Unix epoch, on the pinger.
+    pub received_at_micros: u64,
+    pub padding: Vec<u8>,
+}
+
+/// Replies to each [Ping] w/ a [Pong] on its return route. Unlike the [crate::Echoer], the
+/// reply carries the timestamps & sequence number that [PingClient] needs to measure the
+/// round trip time and to detect lost or late replies.
+pub struct Pinger;
+
+#[ockam::worker]
+impl Worker for Pinger {
+    type Context = Context;
+    type Message = Ping;
+
+    async fn handle_message(&mut self, ctx: &mut Context, msg: Routed<Ping>) -> Result<()> {
+        let address_string = ctx.address().to_string();
+        let summary = format!("ping #{}", msg.as_body().sequence);
+        Presenter::for_name(&address_string).println(format!(
+            "🏓 'pinger' worker → Address: {}, Received: {}",
+            address_string.white(),
+            summary.white()
+        ));
+        FlowRecorder::global().record_routed(&ctx.address(), &msg, &summary);
+        emit_event(
+            ctx.address().address(),
+            Event::message_received(&msg, &summary),
+        );
+
+        let return_route = msg.return_route();
+        let ping = msg.body();
+        let pong = Pong {
+            sequence: ping.sequence,
+            sent_at_micros: ping.sent_at_micros,
+            received_at_micros: now_micros(),
+            padding: ping.padding,
+        };
+        emit_event(
+            ctx.address().address(),
+            Event::message_sent(&return_route, &format!("pong #{}", pong.sequence)),
+        );
+        ctx.send(return_route, pong).await
+    }
+}
+
+/// Sends [Ping]s to a [Pinger] over any route (eg: over TCP hops, [crate::Forwarder]s and
+/// secure channels), one at a time, like the `ping` tool. Each probe that isn't answered
+/// within the timeout counts as lost.
+#[derive(Debug, Clone)]
+pub struct PingClient {
+    route: Route,
+    count: u64,
+    interval: Duration,
+    timeout: Duration,
+    payload_size: usize,
+    presenter: Option<Presenter>,
+}
+
+impl PingClient {
+    /// `route` ends at a [Pinger], eg: `route![connection, "forward_to_bob", "pinger"]`.
+    pub fn new(route: impl Into<Route>) -> Self {
+        Self {
+            route: route.into(),
+            count: 5,
+            interval: Duration::from_millis(200),
+            timeout: Duration::from_secs(2),
+            payload_size: 0,
+            presenter: None,
+        }
+    }
+
+    pub fn with_count(mut self, count: u64) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// The time between the start of one probe and the next.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_payload_size(mut self, payload_size: usize) -> Self {
+        self.payload_size = payload_size;
+        self
+    }
+
+    /// Print a line for each probe w/ `presenter`.
+    pub fn with_presenter(mut self, presenter: Presenter) -> Self {
+        self.presenter = Some(presenter);
+        self
+    }
+
+    /// Send all the probes, and return their statistics. Only errors that aren't timeouts
+    /// (eg: the route can't be reached at all) are returned as errors.
+    pub async fn run(&self, ctx: &Context) -> Result<PingReport> {
+        let mut report = PingReport::default();
+
+        for sequence in 1..=self.count {
+            let started = Instant::now();
+            let ping = Ping {
+                sequence,
+                sent_at_micros: now_micros(),
+                padding: vec![0; self.payload_size],
+            };
+            report.transmitted += 1;
+
+            let reply = ctx
+                .send_and_receive_extended::<Pong>(
+                    self.route.clone(),
+                    ping,
+                    MessageSendReceiveOptions::new().with_timeout(self.timeout),
+                )
+                .await;
+            let rtt = started.elapsed();
+
+            match reply {
+                Ok(pong) if pong.as_body().sequence == sequence => {
+                    report.rtts.push(rtt);
+                    self.print(format!("pong: seq={} time={:.3} ms", sequence, millis(rtt)));
+                }
+                Ok(pong) => {
+                    self.print(format!(
+                        "seq={} got the reply for seq={} (counted as lost)",
+                        sequence,
+                        pong.as_body().sequence
+                    ));
+                }
+                Err(error) if error.code().kind == ockam::errcode::Kind::Timeout => {
+                    self.print(format!("seq={} timed out", sequence));
+                }
+                Err(error) => return Err(error),
+            }
+
+            if sequence < self.count {
+                tokio::time::sleep(self.interval.saturating_sub(rtt)).await;
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn print(&self, line: String) {
+        if let Some(presenter) = &self.presenter {
+            presenter.println(line);
+        }
+    }
+}
+
+/// The statistics of a [PingClient::run], printed like the summary of the `ping` tool.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PingReport {
+    pub transmitted: u64,
+    /// The round trip time of each probe that was answered, in the order they were sent.
+    pub rtts: Vec<Duration>,
+}
+
+impl PingReport {
+    pub fn received(&self) -> u64 {
+        self.rtts.len() as u64
+    }
+
+    pub fn loss_percent(&self) -> f64 {
+        if self.transmitted == 0 {
+            return 0.0;
+        }
+        100.0 * (self.transmitted - self.received()) as f64 / self.transmitted as f64
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.rtts.iter().min().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.rtts.iter().max().copied()
+    }
+
+    pub fn avg(&self) -> Option<Duration> {
+        if self.rtts.is_empty() {
+            return None;
+        }
+        Some(self.rtts.iter().sum::<Duration>() / self.rtts.len() as u32)
+    }
+
+    /// The nearest rank percentile, eg: `percentile(99.0)` for the p99 RTT.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        nearest_rank_percentile(&self.rtts, percentile)
+    }
+}
+
+impl Display for PingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "--- ping statistics ---")?;
+        writeln!(
+            f,
+            "{} probes transmitted, {} received, {:.1}% loss",
+            self.transmitted,
+            self.received(),
+            self.loss_percent()
+        )?;
+        if let (Some(min), Some(avg), Some(max), Some(p99)) =
+            (self.min(), self.avg(), self.max(), self.percentile(99.0))
+        {
+            writeln!(
+                f,
+                "rtt min/avg/max/p99 = {:.3}/{:.3}/{:.3}/{:.3} ms",
+                millis(min),
+                millis(avg),
+                millis(max),
+                millis(p99)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_micros() as u64)
+        .unwrap_or_default()
+}
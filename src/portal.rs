@@ -0,0 +1,133 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::transport_io_error;
+use ockam::errcode::{Kind, Origin};
+use ockam::Result;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// The most that [HttpTarget] reads of a request before it replies anyway.
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+/// A tiny HTTP server to put behind a TCP outlet, eg: w/
+/// `tcp_transport.create_outlet("outlet", target.socket_address().to_string(), ..)`. It
+/// answers every request w/ the same body, and closes the connection. It is stopped when
+/// it is dropped.
+pub struct HttpTarget {
+    socket_address: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl HttpTarget {
+    /// Eg: `HttpTarget::start("127.0.0.1:5000", "Hello from the outlet!")`. Pass port 0 to
+    /// let the OS pick one, and get it from [HttpTarget::socket_address].
+    pub async fn start(bind_address: &str, body: impl Into<String>) -> Result<Self> {
+        let listener = TcpListener::bind(bind_address)
+            .await
+            .map_err(transport_io_error)?;
+        let socket_address = listener.local_addr().map_err(transport_io_error)?;
+        let body = body.into();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(respond(stream, body.clone()));
+            }
+        });
+        Ok(Self {
+            socket_address,
+            task,
+        })
+    }
+
+    pub fn socket_address(&self) -> SocketAddr {
+        self.socket_address
+    }
+}
+
+impl Drop for HttpTarget {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn respond(mut stream: TcpStream, body: String) {
+    let mut request = vec![];
+    let mut buffer = [0; 4096];
+    while !request.windows(4).any(|it| it == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(length) => request.extend_from_slice(&buffer[..length]),
+        }
+    }
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// What [http_get] got back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// Send a `GET` for `path` to `socket_address` (eg: a TCP inlet), and read the response
+/// until the server closes the connection.
+pub async fn http_get(socket_address: &str, path: &str) -> Result<HttpResponse> {
+    let mut stream = TcpStream::connect(socket_address)
+        .await
+        .map_err(transport_io_error)?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, socket_address
+    );
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(transport_io_error)?;
+    let mut response = vec![];
+    stream
+        .read_to_end(&mut response)
+        .await
+        .map_err(transport_io_error)?;
+
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| invalid_response("the response has no headers"))?;
+    // Eg: `HTTP/1.1 200 OK`.
+    let status = head
+        .lines()
+        .next()
+        .and_then(|it| it.split_whitespace().nth(1))
+        .and_then(|it| it.parse().ok())
+        .ok_or_else(|| invalid_response("the response has no status code"))?;
+    Ok(HttpResponse {
+        status,
+        body: body.to_string(),
+    })
+}
+
+fn invalid_response(message: &str) -> ockam::Error {
+    ockam::Error::new(Origin::Application, Kind::Protocol, message.to_string())
+}
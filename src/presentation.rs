@@ -0,0 +1,275 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::NodeRole;
+use colored::{Color, Colorize};
+use std::io::{IsTerminal, Write};
+use std::sync::{Arc, Mutex, Once};
+
+/// Width of the `[name]` prefix column, so that the output from concurrent nodes lines up.
+const PREFIX_WIDTH: usize = 18;
+
+/// Used when the terminal width can't be determined (eg: stdout isn't a TTY).
+const DEFAULT_WIDTH: usize = 120;
+
+/// Narrower terminals get lines that overflow, rather than a few characters per line.
+const MIN_WIDTH: usize = 40;
+
+/// The backgrounds that [Theme::for_name] picks from. Each one is readable w/ black text.
+const PALETTE: [Color; 8] = [
+    Color::BrightMagenta,
+    Color::BrightBlue,
+    Color::BrightCyan,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightRed,
+    Color::Magenta,
+    Color::Cyan,
+];
+
+static INIT: Once = Once::new();
+
+/// Receives the `(name, line)` pairs that would otherwise be printed to stdout, see
+/// [redirect_output].
+type OutputSink = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+static SINK: Mutex<Option<OutputSink>> = Mutex::new(None);
+
+/// Send everything that is printed via this module to `sink` instead of stdout, as
+/// `(name, line)` pairs w/out colors, until [restore_output] is called. Titles use the
+/// name [TITLE_NAME]. This is how [crate::Dashboard] collects the output of each node.
+pub fn redirect_output(sink: impl Fn(&str, &str) + Send + Sync + 'static) {
+    if let Ok(mut it) = SINK.lock() {
+        *it = Some(Arc::new(sink));
+    }
+}
+
+pub fn restore_output() {
+    if let Ok(mut it) = SINK.lock() {
+        *it = None;
+    }
+}
+
+/// Whether the output is currently sent to a [redirect_output] sink.
+pub fn is_output_redirected() -> bool {
+    SINK.lock().map(|it| it.is_some()).unwrap_or(false)
+}
+
+/// The name that [print_title] passes to the [redirect_output] sink.
+pub const TITLE_NAME: &str = "title";
+
+/// Returns false when `text` was sent to the [redirect_output] sink. The lock is released
+/// before calling the sink, so that the sink can print (or redirect) too.
+fn print_to_stdout(name: &str, text: &str) -> bool {
+    let Some(sink) = SINK.lock().ok().and_then(|it| it.clone()) else {
+        return true;
+    };
+    for line in text.lines() {
+        sink(name, &strip_ansi(line));
+    }
+    false
+}
+
+/// Turn colors off when `NO_COLOR` is set (<https://no-color.org>) or when stdout isn't a
+/// terminal (eg: it is piped to a file or to [crate::Launcher]). This runs once, the first
+/// time anything is printed via this module.
+pub fn init_presentation() {
+    INIT.call_once(|| {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|it| !it.is_empty());
+        if no_color || !std::io::stdout().is_terminal() {
+            colored::control::set_override(false);
+        }
+    });
+}
+
+/// The width available for output, from the `COLUMNS` environment variable, or else the
+/// size of the terminal. It is at least [MIN_WIDTH].
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|it| it.parse::<usize>().ok())
+        .or_else(|| {
+            crossterm::terminal::size()
+                .ok()
+                .map(|(columns, _)| columns as usize)
+        })
+        .filter(|it| *it > 0)
+        .map(|it| it.max(MIN_WIDTH))
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Print a title w/ a line of padding above and below it.
+pub fn print_title(title: &str) {
+    init_presentation();
+    if !print_to_stdout(TITLE_NAME, title) {
+        return;
+    }
+    let padding = "=".repeat(title.chars().count());
+    println!("{}", padding.black().on_bright_white());
+    println!("{}", title.black().on_bright_white());
+    println!("{}", padding.black().on_bright_white());
+}
+
+/// The colors used to print the output of a node or worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl Theme {
+    pub fn for_role(role: NodeRole) -> Self {
+        let background = match role {
+            NodeRole::Initiator => Color::BrightBlack,
+            NodeRole::Forwarder => Color::BrightBlue,
+            NodeRole::Responder => Color::BrightMagenta,
+        };
+        Self {
+            foreground: Color::Black,
+            background,
+        }
+    }
+
+    /// A theme that is stable for a given name (the same name always gets the same colors,
+    /// across runs and processes).
+    pub fn for_name(name: &str) -> Self {
+        Self {
+            foreground: Color::Black,
+            background: PALETTE[(fnv1a(name) % PALETTE.len() as u64) as usize],
+        }
+    }
+}
+
+/// Prints the output of a single node or worker. Each line is prefixed w/ the name in a
+/// fixed width column, wrapped to the [terminal_width], and written while holding the
+/// stdout lock so that lines from concurrent nodes don't interleave mid message.
+#[derive(Debug, Clone)]
+pub struct Presenter {
+    name: String,
+    theme: Theme,
+}
+
+impl Presenter {
+    pub fn new(name: &str, theme: Theme) -> Self {
+        Self {
+            name: name.to_string(),
+            theme,
+        }
+    }
+
+    pub fn for_role(role: NodeRole, node_name: &str) -> Self {
+        Self::new(node_name, Theme::for_role(role))
+    }
+
+    /// Eg: `Presenter::for_name(&ctx.address().to_string())` in a worker.
+    pub fn for_name(name: &str) -> Self {
+        Self::new(name, Theme::for_name(name))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Print `text`, which may contain several lines and colored spans.
+    pub fn println(&self, text: impl AsRef<str>) {
+        init_presentation();
+        if !print_to_stdout(&self.name, text.as_ref()) {
+            return;
+        }
+        let prefix = self.prefix();
+        let available = terminal_width() - PREFIX_WIDTH - 1;
+
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        for line in text.as_ref().lines() {
+            for chunk in wrap(line, available) {
+                let chunk = chunk
+                    .color(self.theme.foreground)
+                    .on_color(self.theme.background);
+                writeln!(stdout, "{} {}", prefix, chunk).ok();
+            }
+        }
+    }
+
+    fn prefix(&self) -> String {
+        let mut name: String = self.name.chars().take(PREFIX_WIDTH - 2).collect();
+        if name.chars().count() < self.name.chars().count() {
+            name.pop();
+            name.push('…');
+        }
+        let prefix = format!("{:<width$}", format!("[{}]", name), width = PREFIX_WIDTH);
+        prefix.color(self.theme.background).bold().to_string()
+    }
+}
+
+/// Split `line` into chunks of at most `width` visible characters. ANSI escape sequences
+/// (eg: from `"text".white()`) don't count towards the width and are never split.
+pub fn wrap(line: &str, width: usize) -> Vec<String> {
+    let mut chunks = vec![];
+    let mut chunk = String::new();
+    let mut visible = 0;
+    let mut in_escape = false;
+
+    for char in line.chars() {
+        if in_escape {
+            chunk.push(char);
+            in_escape = char != 'm';
+            continue;
+        }
+        if char == '\x1b' {
+            chunk.push(char);
+            in_escape = true;
+            continue;
+        }
+        if visible == width {
+            chunks.push(std::mem::take(&mut chunk));
+            visible = 0;
+        }
+        chunk.push(char);
+        visible += 1;
+    }
+
+    chunks.push(chunk);
+    chunks
+}
+
+/// `line` w/out its ANSI escape sequences.
+pub fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::new();
+    let mut in_escape = false;
+    for char in line.chars() {
+        if in_escape {
+            in_escape = char != 'm';
+        } else if char == '\x1b' {
+            in_escape = true;
+        } else {
+            stripped.push(char);
+        }
+    }
+    stripped
+}
+
+/// <https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function>
+pub(crate) fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,476 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{UdpConnectionOptions, UdpTransport};
+#[cfg(unix)]
+use crate::{UnixConnectionOptions, UnixTransport};
+use ockam::errcode::{Kind, Origin};
+use ockam::flow_control::FlowControlId;
+use ockam::identity::{IdentityIdentifier, SecureChannelOptions};
+use ockam::{route, Address, Node, Result, Route, TcpConnectionOptions, TcpTransport};
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+
+/// A boxed future that can be moved between threads, eg: when a resolver runs on a task.
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// One hop in a [RouteSpec].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteHop {
+    /// Eg: `echoer`.
+    Worker(String),
+    /// Eg: `tcp:127.0.0.1:3000`, resolved by connecting to that socket address.
+    Tcp(String),
+    /// Eg: `udp:127.0.0.1:3000`, like [RouteHop::Tcp] but over [crate::UdpTransport].
+    Udp(String),
+    /// Eg: `unix(/tmp/bob.sock)`, resolved by connecting to that socket file w/
+    /// [crate::UnixTransport]. The path is in parentheses since it has `/`s in it.
+    Unix(String),
+    /// Eg: `secure(tcp:localhost:4000 / secure-server)`, resolved by creating a secure
+    /// channel to the listener at the end of the inner route. The inner route continues
+    /// from the hops before it, and the hops after it go through the channel.
+    Secure(RouteSpec),
+}
+
+/// A route written as text, so that it can be passed on the command line or read from a
+/// config file instead of being built w/ the `route![...]` macro. Hops are separated by
+/// `/`, and whitespace around them is ignored, eg:
+///
+/// - `tcp:127.0.0.1:3000 / forward_to_responder / echoer`
+/// - `secure(tcp:localhost:4000 / secure-server) / echoer`
+///
+/// It is parsed w/ [str::parse], printed in the same (normalized) syntax w/ [Display], and
+/// turned into a [Route] w/ [RouteResolver].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteSpec {
+    pub hops: Vec<RouteHop>,
+}
+
+impl RouteSpec {
+    pub fn new(hops: Vec<RouteHop>) -> Self {
+        Self { hops }
+    }
+}
+
+impl Display for RouteSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, hop) in self.hops.iter().enumerate() {
+            if index > 0 {
+                write!(f, " / ")?;
+            }
+            match hop {
+                RouteHop::Worker(address) => write!(f, "{}", address)?,
+                RouteHop::Tcp(socket_address) => write!(f, "tcp:{}", socket_address)?,
+                RouteHop::Udp(socket_address) => write!(f, "udp:{}", socket_address)?,
+                RouteHop::Unix(path) => write!(f, "unix({})", path)?,
+                RouteHop::Secure(inner) => write!(f, "secure({})", inner)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RouteSpec {
+    type Err = RouteSyntaxError;
+
+    fn from_str(input: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parser = Parser {
+            input,
+            chars: input.char_indices().collect(),
+            position: 0,
+        };
+        let spec = parser.route()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(spec),
+            Some(')') => Err(parser.error("unexpected ')'")),
+            Some(char) => Err(parser.error(format!("expected '/' but found '{}'", char))),
+        }
+    }
+}
+
+/// Why a [RouteSpec] couldn't be parsed, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteSyntaxError {
+    pub input: String,
+    /// The byte offset in `input` where the error was found.
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for RouteSyntaxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let column = self.input[..self.position].chars().count();
+        writeln!(f, "Invalid route: {}", self.message)?;
+        writeln!(f, "  {}", self.input)?;
+        write!(f, "  {}^", " ".repeat(column))
+    }
+}
+
+impl std::error::Error for RouteSyntaxError {}
+
+impl From<RouteSyntaxError> for ockam::Error {
+    fn from(error: RouteSyntaxError) -> Self {
+        ockam::Error::new(Origin::Application, Kind::Invalid, error)
+    }
+}
+
+/// route := hop ('/' hop)*
+/// hop   := 'secure(' route ')' | 'tcp:' socket_address | 'udp:' socket_address
+///        | 'unix(' path ')' | worker_address
+struct Parser<'a> {
+    input: &'a str,
+    chars: Vec<(usize, char)>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn route(&mut self) -> std::result::Result<RouteSpec, RouteSyntaxError> {
+        let mut hops = vec![self.hop()?];
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('/') {
+                return Ok(RouteSpec::new(hops));
+            }
+            self.position += 1;
+            hops.push(self.hop()?);
+        }
+    }
+
+    fn hop(&mut self) -> std::result::Result<RouteHop, RouteSyntaxError> {
+        self.skip_whitespace();
+        if self.eat("secure(") {
+            let inner = self.route()?;
+            self.skip_whitespace();
+            if !self.eat(")") {
+                return Err(self.error("expected ')' to close 'secure('"));
+            }
+            return Ok(RouteHop::Secure(inner));
+        }
+        if self.eat("tcp:") {
+            let socket_address = self.word();
+            if socket_address.is_empty() {
+                return Err(self.error("expected a socket address after 'tcp:'"));
+            }
+            return Ok(RouteHop::Tcp(socket_address));
+        }
+        if self.eat("udp:") {
+            let socket_address = self.word();
+            if socket_address.is_empty() {
+                return Err(self.error("expected a socket address after 'udp:'"));
+            }
+            return Ok(RouteHop::Udp(socket_address));
+        }
+        if self.eat("unix(") {
+            let mut path = String::new();
+            while let Some(char) = self.peek().filter(|it| *it != ')') {
+                path.push(char);
+                self.position += 1;
+            }
+            let path = path.trim().to_string();
+            if path.is_empty() {
+                return Err(self.error("expected a socket file path after 'unix('"));
+            }
+            if !self.eat(")") {
+                return Err(self.error("expected ')' to close 'unix('"));
+            }
+            return Ok(RouteHop::Unix(path));
+        }
+
+        let start = self.position;
+        let address = self.word();
+        if address.is_empty() {
+            return Err(
+                self.error("expected a worker address, 'tcp:', 'udp:', 'unix(' or 'secure('")
+            );
+        }
+        if address.contains(':') || address.contains('(') {
+            self.position = start;
+            return Err(self.error(format!("unknown hop type in '{}'", address)));
+        }
+        Ok(RouteHop::Worker(address))
+    }
+
+    /// Everything up to the next separator, whitespace, or end of the input.
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(char) = self.peek() {
+            if char == '/' || char == ')' || char.is_whitespace() {
+                break;
+            }
+            word.push(char);
+            self.position += 1;
+        }
+        word
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        let matches = expected.chars().enumerate().all(|(offset, char)| {
+            self.chars.get(self.position + offset).map(|it| it.1) == Some(char)
+        });
+        if matches {
+            self.position += expected.chars().count();
+        }
+        matches
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).map(|it| it.1)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> RouteSyntaxError {
+        RouteSyntaxError {
+            input: self.input.to_string(),
+            position: self
+                .chars
+                .get(self.position)
+                .map(|it| it.0)
+                .unwrap_or(self.input.len()),
+            message: message.into(),
+        }
+    }
+}
+
+/// A [RouteSpec] that has been turned into a [Route], w/ the TCP, UDP & Unix connections and
+/// secure channels that were created for it (eg: to register them w/ a
+/// [crate::ShutdownCoordinator] or [crate::Topology]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedRoute {
+    pub route: Route,
+    /// The socket address of each connection, and its sender address.
+    pub tcp_connections: Vec<(String, Address)>,
+    /// The socket address of each UDP connection, and its sender address.
+    pub udp_connections: Vec<(String, Address)>,
+    /// The path of each Unix socket connection, and its sender address.
+    pub unix_connections: Vec<(String, Address)>,
+    /// The route to each secure channel listener, and the channel's encryptor address.
+    pub secure_channels: Vec<(RouteSpec, Address)>,
+    /// The flow control id of each connection & secure channel. Replies that arrive over
+    /// them are only delivered to workers that are consumers of these.
+    pub flow_control_ids: Vec<FlowControlId>,
+}
+
+impl ResolvedRoute {
+    /// Let the worker at `address` receive the messages (eg: replies) that arrive over the
+    /// connections & secure channels of this route.
+    pub fn add_consumer(&self, node: &Node, address: impl Into<Address>) {
+        let address = address.into();
+        for flow_control_id in &self.flow_control_ids {
+            node.flow_controls()
+                .add_consumer(address.clone(), flow_control_id);
+        }
+    }
+}
+
+/// Turns a [RouteSpec] into a [Route] on `node`: `tcp:` hops are resolved by connecting w/
+/// `tcp_transport`, `udp:` hops w/ the transport passed to
+/// [RouteResolver::with_udp_transport], `unix(...)` hops w/ the one passed to
+/// `RouteResolver::with_unix_transport`, and `secure(...)` hops by creating a secure
+/// channel for the identity passed to [RouteResolver::with_identity].
+pub struct RouteResolver<'a> {
+    node: &'a Node,
+    tcp_transport: &'a TcpTransport,
+    udp_transport: Option<&'a UdpTransport>,
+    #[cfg(unix)]
+    unix_transport: Option<&'a UnixTransport>,
+    identity: Option<IdentityIdentifier>,
+}
+
+impl<'a> RouteResolver<'a> {
+    pub fn new(node: &'a Node, tcp_transport: &'a TcpTransport) -> Self {
+        Self {
+            node,
+            tcp_transport,
+            udp_transport: None,
+            #[cfg(unix)]
+            unix_transport: None,
+            identity: None,
+        }
+    }
+
+    /// The transport to connect w/. This is required when the route has a `udp:` hop.
+    pub fn with_udp_transport(mut self, udp_transport: &'a UdpTransport) -> Self {
+        self.udp_transport = Some(udp_transport);
+        self
+    }
+
+    /// The transport to connect w/. This is required when the route has a `unix(...)` hop.
+    #[cfg(unix)]
+    pub fn with_unix_transport(mut self, unix_transport: &'a UnixTransport) -> Self {
+        self.unix_transport = Some(unix_transport);
+        self
+    }
+
+    /// The identity to create secure channels w/. This is required when the route has a
+    /// `secure(...)` hop.
+    pub fn with_identity(mut self, identity: &IdentityIdentifier) -> Self {
+        self.identity = Some(identity.clone());
+        self
+    }
+
+    pub async fn resolve(&self, spec: &RouteSpec) -> Result<ResolvedRoute> {
+        let mut resolved = ResolvedRoute {
+            route: route![],
+            tcp_connections: vec![],
+            udp_connections: vec![],
+            unix_connections: vec![],
+            secure_channels: vec![],
+            flow_control_ids: vec![],
+        };
+        self.resolve_into(spec, &mut resolved).await?;
+        Ok(resolved)
+    }
+
+    /// Append the hops in `spec` to `resolved`. This is boxed since `secure(...)` hops
+    /// resolve their inner route recursively.
+    fn resolve_into<'b>(
+        &'b self,
+        spec: &'b RouteSpec,
+        resolved: &'b mut ResolvedRoute,
+    ) -> BoxFuture<'b, Result<()>> {
+        Box::pin(async move {
+            for hop in &spec.hops {
+                match hop {
+                    RouteHop::Worker(address) => {
+                        resolved.route.modify().append(address.as_str());
+                    }
+                    RouteHop::Tcp(socket_address) => {
+                        let connection = self
+                            .tcp_transport
+                            .connect(socket_address, TcpConnectionOptions::new())
+                            .await?;
+                        let sender_address = connection.sender_address().clone();
+                        resolved
+                            .flow_control_ids
+                            .push(connection.flow_control_id().clone());
+                        resolved.route.modify().append(sender_address.clone());
+                        resolved
+                            .tcp_connections
+                            .push((socket_address.clone(), sender_address));
+                    }
+                    RouteHop::Udp(socket_address) => {
+                        let Some(udp_transport) = self.udp_transport else {
+                            return Err(ockam::Error::new(
+                                Origin::Application,
+                                Kind::Misuse,
+                                format!("'udp:{}' needs a UDP transport, see `RouteResolver::with_udp_transport`", socket_address),
+                            ));
+                        };
+                        let connection = udp_transport
+                            .connect(socket_address, UdpConnectionOptions::new())
+                            .await?;
+                        let sender_address = connection.sender_address().clone();
+                        resolved
+                            .flow_control_ids
+                            .push(connection.flow_control_id().clone());
+                        resolved.route.modify().append(sender_address.clone());
+                        resolved
+                            .udp_connections
+                            .push((socket_address.clone(), sender_address));
+                    }
+                    #[cfg(unix)]
+                    RouteHop::Unix(path) => {
+                        let Some(unix_transport) = self.unix_transport else {
+                            return Err(ockam::Error::new(
+                                Origin::Application,
+                                Kind::Misuse,
+                                format!("'unix({})' needs a Unix socket transport, see `RouteResolver::with_unix_transport`", path),
+                            ));
+                        };
+                        let connection = unix_transport
+                            .connect(path, UnixConnectionOptions::new())
+                            .await?;
+                        let sender_address = connection.sender_address().clone();
+                        resolved
+                            .flow_control_ids
+                            .push(connection.flow_control_id().clone());
+                        resolved.route.modify().append(sender_address.clone());
+                        resolved
+                            .unix_connections
+                            .push((path.clone(), sender_address));
+                    }
+                    #[cfg(not(unix))]
+                    RouteHop::Unix(path) => {
+                        return Err(ockam::Error::new(
+                            Origin::Application,
+                            Kind::Unsupported,
+                            format!("'unix({})' needs Unix domain sockets", path),
+                        ));
+                    }
+                    RouteHop::Secure(inner) => {
+                        let Some(identity) = &self.identity else {
+                            return Err(ockam::Error::new(
+                                Origin::Application,
+                                Kind::Misuse,
+                                format!("'secure({})' needs an identity, see `RouteResolver::with_identity`", inner),
+                            ));
+                        };
+                        let mut channel_route = ResolvedRoute {
+                            route: resolved.route.clone(),
+                            tcp_connections: vec![],
+                            udp_connections: vec![],
+                            unix_connections: vec![],
+                            secure_channels: vec![],
+                            flow_control_ids: vec![],
+                        };
+                        self.resolve_into(inner, &mut channel_route).await?;
+                        let channel = self
+                            .node
+                            .create_secure_channel(
+                                identity,
+                                channel_route.route,
+                                SecureChannelOptions::new(),
+                            )
+                            .await?;
+                        let encryptor_address = channel.encryptor_address().clone();
+                        resolved
+                            .flow_control_ids
+                            .extend(channel_route.flow_control_ids);
+                        resolved
+                            .flow_control_ids
+                            .push(channel.flow_control_id().clone());
+                        resolved.route = route![encryptor_address.clone()];
+                        resolved
+                            .tcp_connections
+                            .extend(channel_route.tcp_connections);
+                        resolved
+                            .udp_connections
+                            .extend(channel_route.udp_connections);
+                        resolved
+                            .unix_connections
+                            .extend(channel_route.unix_connections);
+                        resolved
+                            .secure_channels
+                            .extend(channel_route.secure_channels);
+                        resolved
+                            .secure_channels
+                            .push((inner.clone(), encryptor_address));
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
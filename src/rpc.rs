@@ -0,0 +1,377 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{emit_event, Event, FlowRecorder, Presenter};
+use async_trait::async_trait;
+use colored::Colorize;
+use ockam::errcode::Kind;
+use ockam::{Context, Message, MessageSendReceiveOptions, Result, Route, Routed, Worker};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The envelope that an [RpcClient] sends. `body` is the JSON encoded request.
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub struct RpcRequest {
+    /// Copied to the [RpcResponse], and kept the same when a call is retried.
+    pub correlation_id: u64,
+    /// Milliseconds since the Unix epoch after which the client won't wait for the reply.
+    pub deadline_unix_ms: u64,
+    pub body: Vec<u8>,
+}
+
+/// The envelope that an [RpcServer] replies w/.
+#[derive(Serialize, Deserialize, Message, Debug, Clone, PartialEq, Eq)]
+pub struct RpcResponse {
+    pub correlation_id: u64,
+    pub result: RpcResult,
+}
+
+/// Error replies are a separate variant, so they can't be mistaken for a success payload.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum RpcResult {
+    /// The JSON encoded response.
+    Ok(Vec<u8>),
+    Err(RpcError),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcErrorCode {
+    /// The request body couldn't be decoded, or the service rejected it.
+    InvalidRequest,
+    /// The request arrived after its deadline, so it wasn't handled.
+    DeadlineExceeded,
+    /// The service failed while handling the request.
+    Internal,
+}
+
+/// An error reply from an [RpcService].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: RpcErrorCode,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: RpcErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(RpcErrorCode::InvalidRequest, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(RpcErrorCode::Internal, message)
+    }
+}
+
+impl Display for RpcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// The typed request handler behind an [RpcServer], eg: [EchoService].
+#[async_trait]
+pub trait RpcService: Send + 'static {
+    type Request: DeserializeOwned + Send;
+    type Response: Serialize + Send;
+
+    async fn handle(
+        &mut self,
+        ctx: &mut Context,
+        request: Self::Request,
+    ) -> std::result::Result<Self::Response, RpcError>;
+}
+
+/// A worker that decodes each [RpcRequest], passes it to its [RpcService], and replies w/
+/// an [RpcResponse] on the request's return route.
+pub struct RpcServer<S: RpcService> {
+    service: S,
+}
+
+impl<S: RpcService> RpcServer<S> {
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+
+    async fn result(&mut self, ctx: &mut Context, request: &RpcRequest) -> RpcResult {
+        if now_unix_ms() > request.deadline_unix_ms {
+            return RpcResult::Err(RpcError::new(
+                RpcErrorCode::DeadlineExceeded,
+                "the deadline passed before the request was handled",
+            ));
+        }
+        let decoded = match serde_json::from_slice::<S::Request>(&request.body) {
+            Ok(decoded) => decoded,
+            Err(error) => return RpcResult::Err(RpcError::invalid_request(error.to_string())),
+        };
+        match self.service.handle(ctx, decoded).await {
+            Ok(response) => match serde_json::to_vec(&response) {
+                Ok(body) => RpcResult::Ok(body),
+                Err(error) => RpcResult::Err(RpcError::internal(error.to_string())),
+            },
+            Err(error) => RpcResult::Err(error),
+        }
+    }
+}
+
+#[ockam::worker]
+impl<S: RpcService> Worker for RpcServer<S> {
+    type Context = Context;
+    type Message = RpcRequest;
+
+    async fn handle_message(&mut self, ctx: &mut Context, msg: Routed<RpcRequest>) -> Result<()> {
+        let address_string = ctx.address().to_string();
+        let return_route = msg.return_route();
+        let payload = format!("rpc #{}", msg.as_body().correlation_id);
+        FlowRecorder::global().record_routed(&ctx.address(), &msg, &payload);
+        emit_event(
+            ctx.address().address(),
+            Event::message_received(&msg, &payload),
+        );
+        let request = msg.body();
+
+        let result = self.result(ctx, &request).await;
+        let output_msg = match &result {
+            RpcResult::Ok(_) => format!("📞 rpc #{} → ok", request.correlation_id),
+            RpcResult::Err(error) => format!("📞 rpc #{} → {}", request.correlation_id, error),
+        };
+        Presenter::for_name(&address_string).println(output_msg.white().to_string());
+        emit_event(
+            ctx.address().address(),
+            Event::message_sent(&return_route, &payload),
+        );
+
+        let response = RpcResponse {
+            correlation_id: request.correlation_id,
+            result,
+        };
+        ctx.send(return_route, response).await
+    }
+}
+
+/// Which failed calls are sent again, and how long to wait before each retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Including the first attempt.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    /// Each backoff is this many times longer than the one before it.
+    pub multiplier: u32,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::ZERO,
+            multiplier: 1,
+        }
+    }
+
+    pub fn fixed(max_attempts: u32, backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff: backoff,
+            multiplier: 1,
+        }
+    }
+
+    pub fn exponential(max_attempts: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff,
+            multiplier: 2,
+        }
+    }
+
+    /// The time to wait after the failed `attempt` (starting from 1).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff * self.multiplier.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Why an [RpcClient::call] failed.
+#[derive(Debug)]
+pub enum RpcCallError {
+    /// The service replied w/ an error. These are never retried.
+    Remote(RpcError),
+    /// No reply arrived within the timeout, for any of the attempts.
+    Timeout { attempts: u32 },
+    /// The request couldn't be sent (eg: the route can't be reached).
+    Transport(ockam::Error),
+    /// The request couldn't be encoded, or the reply couldn't be decoded.
+    Codec(String),
+}
+
+impl Display for RpcCallError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcCallError::Remote(error) => write!(f, "the service replied w/ an error: {}", error),
+            RpcCallError::Timeout { attempts } => {
+                write!(f, "no reply after {} attempt(s)", attempts)
+            }
+            RpcCallError::Transport(error) => write!(f, "can't send the request: {}", error),
+            RpcCallError::Codec(message) => write!(f, "can't encode or decode: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RpcCallError {}
+
+impl From<RpcCallError> for ockam::Error {
+    fn from(error: RpcCallError) -> Self {
+        let kind = match &error {
+            RpcCallError::Remote(_) => Kind::Invalid,
+            RpcCallError::Timeout { .. } => Kind::Timeout,
+            RpcCallError::Transport(error) => error.code().kind,
+            RpcCallError::Codec(_) => Kind::Serialization,
+        };
+        ockam::Error::new(ockam::errcode::Origin::Application, kind, error)
+    }
+}
+
+/// Calls an [RpcServer] over `route`, w/ a timeout per attempt and a [RetryPolicy] for the
+/// attempts that time out.
+#[derive(Debug)]
+pub struct RpcClient<Request, Response> {
+    route: Route,
+    timeout: Duration,
+    retry_policy: RetryPolicy,
+    next_correlation_id: AtomicU64,
+    _types: PhantomData<fn(Request) -> Response>,
+}
+
+impl<Request: Serialize, Response: DeserializeOwned> RpcClient<Request, Response> {
+    /// Eg: `RpcClient::<String, String>::new(route![connection, "echo_rpc"])`.
+    pub fn new(route: impl Into<Route>) -> Self {
+        Self {
+            route: route.into(),
+            timeout: Duration::from_secs(5),
+            retry_policy: RetryPolicy::none(),
+            next_correlation_id: AtomicU64::new(1),
+            _types: PhantomData,
+        }
+    }
+
+    /// How long each attempt waits for the reply.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub async fn call(
+        &self,
+        ctx: &Context,
+        request: &Request,
+    ) -> std::result::Result<Response, RpcCallError> {
+        let correlation_id = self.next_correlation_id.fetch_add(1, Ordering::Relaxed);
+        let body =
+            serde_json::to_vec(request).map_err(|error| RpcCallError::Codec(error.to_string()))?;
+        let max_attempts = self.retry_policy.max_attempts.max(1);
+
+        for attempt in 1..=max_attempts {
+            let envelope = RpcRequest {
+                correlation_id,
+                deadline_unix_ms: now_unix_ms() + self.timeout.as_millis() as u64,
+                body: body.clone(),
+            };
+            let reply = ctx
+                .send_and_receive_extended::<RpcResponse>(
+                    self.route.clone(),
+                    envelope,
+                    MessageSendReceiveOptions::new().with_timeout(self.timeout),
+                )
+                .await;
+
+            match reply {
+                Ok(reply) => {
+                    let response = reply.body();
+                    if response.correlation_id != correlation_id {
+                        return Err(RpcCallError::Codec(format!(
+                            "expected the reply to #{} but got #{}",
+                            correlation_id, response.correlation_id
+                        )));
+                    }
+                    return match response.result {
+                        RpcResult::Ok(body) => serde_json::from_slice(&body)
+                            .map_err(|error| RpcCallError::Codec(error.to_string())),
+                        RpcResult::Err(error) => Err(RpcCallError::Remote(error)),
+                    };
+                }
+                Err(error) if error.code().kind == Kind::Timeout => {
+                    if attempt < max_attempts {
+                        tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+                    }
+                }
+                Err(error) => return Err(RpcCallError::Transport(error)),
+            }
+        }
+
+        Err(RpcCallError::Timeout {
+            attempts: max_attempts,
+        })
+    }
+}
+
+/// The [crate::Echoer] as an [RpcService]: replies w/ the request, and rejects empty ones.
+pub struct EchoService;
+
+#[async_trait]
+impl RpcService for EchoService {
+    type Request = String;
+    type Response = String;
+
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context,
+        request: String,
+    ) -> std::result::Result<String, RpcError> {
+        if request.is_empty() {
+            return Err(RpcError::invalid_request("there is nothing to echo"));
+        }
+        Ok(format!("👈 echo back: {}", request))
+    }
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_millis() as u64)
+        .unwrap_or_default()
+}
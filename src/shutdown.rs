@@ -0,0 +1,330 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{FlowRecorder, NodeRole, Topology, TopologyElement};
+use colored::Colorize;
+use ockam::{Address, Context, Result, Worker};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use tokio::time::{sleep, timeout, Instant};
+
+/// How long to wait for the workers of a single role to stop (and drain the messages in
+/// their mailboxes) before giving up on them and moving on to the next role.
+pub const DEFAULT_DRAIN_DEADLINE: Duration = Duration::from_secs(3);
+
+/// How often to check whether a worker that was asked to stop is gone.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The signal that caused the shutdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownSignal {
+    /// SIGINT, eg: Ctrl-C in the terminal.
+    Interrupt,
+    /// SIGTERM, eg: `kill <pid>`.
+    Terminate,
+}
+
+/// Wait for SIGINT (Ctrl-C) or SIGTERM.
+pub async fn wait_for_signal() -> std::io::Result<ShutdownSignal> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.map(|_| ShutdownSignal::Interrupt),
+            _ = terminate.recv() => Ok(ShutdownSignal::Terminate),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await?;
+        Ok(ShutdownSignal::Interrupt)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AddressKind {
+    Worker,
+    Processor,
+}
+
+#[derive(Debug, Clone)]
+struct Registration {
+    role: NodeRole,
+    node_name: String,
+    address: Address,
+    kind: AddressKind,
+}
+
+/// All the "nodes" in the examples share a single ockam router (they are created from
+/// clones of the same [Context]), so calling `node.stop()` on any one of them stops all
+/// of them, in no particular order. Instead, each node registers the addresses of the
+/// workers (and processors, eg: TCP listeners) that it starts with this coordinator,
+/// which then stops them role by role in [NodeRole::SHUTDOWN_ORDER] before finally
+/// stopping the router.
+///
+/// Use [ShutdownCoordinator::run_until_signal] for long running nodes, and
+/// [ShutdownCoordinator::shutdown] once an initiator is done.
+pub struct ShutdownCoordinator {
+    ctx: Context,
+    registrations: Vec<Registration>,
+    drain_deadline: Duration,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(ctx: Context) -> Self {
+        Self {
+            ctx,
+            registrations: vec![],
+            drain_deadline: DEFAULT_DRAIN_DEADLINE,
+        }
+    }
+
+    /// Override the [DEFAULT_DRAIN_DEADLINE] that is applied to each role.
+    pub fn with_drain_deadline(mut self, drain_deadline: Duration) -> Self {
+        self.drain_deadline = drain_deadline;
+        self
+    }
+
+    pub fn register_worker(
+        &mut self,
+        role: NodeRole,
+        node_name: &str,
+        address: impl Into<Address>,
+    ) -> &mut Self {
+        self.register(role, node_name, address.into(), AddressKind::Worker)
+    }
+
+    /// Register a processor, eg: the address returned by
+    /// `TcpListener::processor_address()`.
+    pub fn register_processor(
+        &mut self,
+        role: NodeRole,
+        node_name: &str,
+        address: impl Into<Address>,
+    ) -> &mut Self {
+        self.register(role, node_name, address.into(), AddressKind::Processor)
+    }
+
+    /// Register everything in `topology` (except the nodes' own contexts): the workers,
+    /// and the TCP listeners & connections that are open right now.
+    pub fn register_topology(&mut self, topology: &Topology) -> &mut Self {
+        for node in topology.nodes() {
+            for element in node.live_elements() {
+                match element {
+                    TopologyElement::Worker { address } if node.is_context(&address) => {}
+                    TopologyElement::Worker { address } => {
+                        self.register_worker(node.role, &node.name, address);
+                    }
+                    TopologyElement::TcpListener {
+                        processor_address, ..
+                    } => {
+                        self.register_processor(node.role, &node.name, processor_address);
+                    }
+                    TopologyElement::TcpConnection { sender_address, .. } => {
+                        self.register_worker(node.role, &node.name, sender_address);
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    fn register(
+        &mut self,
+        role: NodeRole,
+        node_name: &str,
+        address: Address,
+        kind: AddressKind,
+    ) -> &mut Self {
+        self.registrations.push(Registration {
+            role,
+            node_name: node_name.to_string(),
+            address,
+            kind,
+        });
+        self
+    }
+
+    /// Wait for SIGINT or SIGTERM and then run [ShutdownCoordinator::shutdown].
+    pub async fn run_until_signal(self) -> Result<ShutdownReport> {
+        match wait_for_signal().await {
+            Ok(signal) => println!("{}", format!("Received {:?}, shutting down", signal).red()),
+            Err(error) => println!(
+                "{}",
+                format!("Can't listen for signals ({}), shutting down", error).red()
+            ),
+        }
+        self.shutdown().await
+    }
+
+    /// Stop all the registered workers & processors, role by role, then stop the router.
+    /// Each role gets [ShutdownCoordinator::with_drain_deadline] to process the messages
+    /// that are already in flight. Anything that doesn't stop within that time is
+    /// reported in the returned [ShutdownReport]. When the [FlowRecorder::global] is
+    /// enabled, the messages that flowed during the run are printed as a sequence diagram.
+    pub async fn shutdown(mut self) -> Result<ShutdownReport> {
+        let mut report = ShutdownReport::default();
+
+        for role in NodeRole::SHUTDOWN_ORDER {
+            let registrations: Vec<Registration> = self
+                .registrations
+                .iter()
+                .filter(|it| it.role == role)
+                .cloned()
+                .collect();
+            if registrations.is_empty() {
+                continue;
+            }
+            println!("{}", format!("Stopping {} nodes", role).red());
+            self.stop_role(registrations, &mut report).await;
+        }
+
+        let recorder = FlowRecorder::global();
+        if recorder.is_enabled() {
+            println!("{}", "Message flows".green());
+            print!("{}", recorder.render_sequence_diagram().green());
+        }
+
+        // The router stops whatever is left, then each cluster (eg: the TCP transport's
+        // workers) in turn, but it never hears back from a cluster whose workers were all
+        // stopped above. It only gives up waiting (after 1s) if it had a worker left to
+        // stop, which is what the sentinel is for.
+        self.ctx
+            .start_worker(Address::random_local(), ShutdownSentinel)
+            .await?;
+        self.ctx.stop().await?;
+        Ok(report)
+    }
+
+    async fn stop_role(&self, registrations: Vec<Registration>, report: &mut ShutdownReport) {
+        let deadline = Instant::now() + self.drain_deadline;
+
+        // Ask everything in this role to stop.
+        let mut stopping = vec![];
+        for registration in registrations {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let result = match registration.kind {
+                AddressKind::Worker => {
+                    timeout(
+                        remaining,
+                        self.ctx.stop_worker(registration.address.clone()),
+                    )
+                    .await
+                }
+                AddressKind::Processor => {
+                    timeout(
+                        remaining,
+                        self.ctx.stop_processor(registration.address.clone()),
+                    )
+                    .await
+                }
+            };
+            match result {
+                Ok(Ok(())) => stopping.push(registration),
+                Ok(Err(error)) => report.fail(registration, error.to_string()),
+                Err(_) => report.fail(registration, "timed out asking it to stop".to_string()),
+            }
+        }
+
+        // Wait for the workers to drain their mailboxes and go away. The router lists the
+        // processors that are still running too.
+        loop {
+            let running = self.ctx.list_workers().await.unwrap_or_default();
+            let (still_running, stopped): (Vec<_>, Vec<_>) = stopping
+                .into_iter()
+                .partition(|it| running.contains(&it.address));
+            stopped.into_iter().for_each(|it| report.stop(it));
+            stopping = still_running;
+
+            if stopping.is_empty() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                stopping.into_iter().for_each(|it| {
+                    report.fail(it, "didn't stop before the drain deadline".to_string())
+                });
+                break;
+            }
+            sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Started right before the router is stopped, see [ShutdownCoordinator::shutdown].
+struct ShutdownSentinel;
+
+#[ockam::worker]
+impl Worker for ShutdownSentinel {
+    type Context = Context;
+    type Message = ();
+}
+
+/// A worker or processor that didn't stop cleanly.
+#[derive(Debug, Clone)]
+pub struct ShutdownFailure {
+    pub role: NodeRole,
+    pub node_name: String,
+    pub address: Address,
+    pub reason: String,
+}
+
+/// What happened to each registered address during [ShutdownCoordinator::shutdown].
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownReport {
+    pub stopped: Vec<(NodeRole, String, Address)>,
+    pub failed: Vec<ShutdownFailure>,
+}
+
+impl ShutdownReport {
+    pub fn is_clean(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    fn stop(&mut self, registration: Registration) {
+        self.stopped.push((
+            registration.role,
+            registration.node_name,
+            registration.address,
+        ));
+    }
+
+    fn fail(&mut self, registration: Registration, reason: String) {
+        self.failed.push(ShutdownFailure {
+            role: registration.role,
+            node_name: registration.node_name,
+            address: registration.address,
+            reason,
+        });
+    }
+}
+
+impl Display for ShutdownReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (role, node_name, address) in &self.stopped {
+            writeln!(f, "✅ {} ({}) → stopped: {}", node_name, role, address)?;
+        }
+        for failure in &self.failed {
+            writeln!(
+                f,
+                "❌ {} ({}) → {} didn't stop cleanly: {}",
+                failure.node_name, failure.role, failure.address, failure.reason
+            )?;
+        }
+        Ok(())
+    }
+}
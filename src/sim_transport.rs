@@ -0,0 +1,480 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::presentation::fnv1a;
+use crate::{send_dead_letter, DeadLetter, DeadLetterReason};
+use ockam::access_control::AllowAll;
+use ockam::{Address, Any, Context, LocalMessage, Result, Routed, Worker};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
+
+/// How a simulated link between two nodes behaves. The default is a perfect link.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkSettings {
+    pub latency: Duration,
+    /// Up to this much is added to the latency of each message. Like on a TCP connection,
+    /// this doesn't reorder the messages, only `reorder` does.
+    pub jitter: Duration,
+    /// Bytes per second. Messages queue up behind each other when the link is busy.
+    pub bandwidth: Option<u64>,
+    /// The probability (from 0 to 1) that a message is lost.
+    pub loss: f64,
+    /// The probability (from 0 to 1) that a message is held back by `reorder_delay`, so
+    /// that the messages sent after it can overtake it.
+    pub reorder: f64,
+    pub reorder_delay: Duration,
+}
+
+impl LinkSettings {
+    pub fn perfect() -> Self {
+        Self::default()
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_bandwidth(mut self, bytes_per_second: u64) -> Self {
+        self.bandwidth = Some(bytes_per_second.max(1));
+        self
+    }
+
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        self.loss = loss.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_reordering(mut self, reorder: f64, reorder_delay: Duration) -> Self {
+        self.reorder = reorder.clamp(0.0, 1.0);
+        self.reorder_delay = reorder_delay;
+        self
+    }
+}
+
+/// What happened to the messages sent over one direction of a link.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub sent: u64,
+    pub delivered: u64,
+    pub lost: u64,
+    /// Dropped because the link was partitioned when they were sent, or when they were
+    /// due to arrive.
+    pub partitioned: u64,
+    /// Arrived, but couldn't be forwarded to the next hop (see [crate::send_dead_letter]).
+    pub undeliverable: u64,
+}
+
+impl LinkStats {
+    /// Messages that were sent but haven't arrived (or been dropped) yet.
+    pub fn in_flight(&self) -> u64 {
+        self.sent - self.delivered - self.lost - self.partitioned - self.undeliverable
+    }
+}
+
+/// <https://prng.di.unimi.it/splitmix64.c>, so that a run only depends on its seed (& not
+/// on the version of a random number crate).
+#[derive(Debug)]
+struct SimRng(u64);
+
+impl SimRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// From 0 (inclusive) to 1 (exclusive).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The state of one direction of a link.
+#[derive(Debug)]
+struct LinkState {
+    settings: LinkSettings,
+    partitioned: bool,
+    rng: SimRng,
+    busy_until: Option<Instant>,
+    /// When the last message that wasn't held back by `reorder` arrives.
+    arrives_in_order_at: Option<Instant>,
+    stats: LinkStats,
+}
+
+impl LinkState {
+    /// When a message of `size` bytes sent now should arrive, or None if it is dropped.
+    /// Only the random numbers come from the seed, `now` is the wall clock.
+    fn schedule(&mut self, size: usize) -> Option<Instant> {
+        self.stats.sent += 1;
+        // Always draw the same random numbers for each message, so that changing one
+        // setting doesn't change what happens to the messages because of the others.
+        let (loss, jitter, reorder) = (
+            self.rng.next_f64(),
+            self.rng.next_f64(),
+            self.rng.next_f64(),
+        );
+        if self.partitioned {
+            self.stats.partitioned += 1;
+            return None;
+        }
+        if loss < self.settings.loss {
+            self.stats.lost += 1;
+            return None;
+        }
+
+        let now = Instant::now();
+        let transmit = self
+            .settings
+            .bandwidth
+            .map(|it| Duration::from_secs_f64(size as f64 / it as f64))
+            .unwrap_or_default();
+        let sent_at = self.busy_until.map_or(now, |it| it.max(now)) + transmit;
+        self.busy_until = Some(sent_at);
+
+        let mut arrives_at = sent_at + self.settings.latency + self.settings.jitter.mul_f64(jitter);
+        arrives_at = self
+            .arrives_in_order_at
+            .map_or(arrives_at, |it| it.max(arrives_at));
+        self.arrives_in_order_at = Some(arrives_at);
+        if reorder < self.settings.reorder {
+            arrives_at += self.settings.reorder_delay;
+        }
+        Some(arrives_at)
+    }
+}
+
+type Link = Arc<Mutex<LinkState>>;
+
+/// The links from one node to another, by the names of the nodes.
+type Links = Arc<Mutex<BTreeMap<(String, String), Vec<Link>>>>;
+
+/// A set of simulated links between nodes in this process, w/ their [LinkSettings], all
+/// driven by one seed. For the n-th message on a link, the seed decides whether it is
+/// lost, and how much jitter & reorder delay it gets. Only the losses are fully repeatable
+/// though: the delays start when a message is sent on tokio's clock, which is the wall
+/// clock in an ockam node (its runtime can't be paused). So when a message arrives, and
+/// whether a later one overtakes it, also depends on how far apart the messages were sent.
+/// Tests of eg: a [crate::Forwarder] or a secure channel under a bad network should allow
+/// for that. A [SimConnection] is used in routes like a TCP connection:
+///
+/// ```ignore
+/// let network = SimNetwork::new(42);
+/// let connection = network
+///     .connect(&ctx, "alice", "middle", LinkSettings::perfect().with_loss(0.1))
+///     .await?;
+/// let route = route![connection, "forward_to_bob", "echoer"];
+/// ```
+#[derive(Debug, Clone)]
+pub struct SimNetwork {
+    /// Keeps the worker addresses of different networks in the same process apart.
+    id: u64,
+    seed: u64,
+    links: Links,
+}
+
+impl SimNetwork {
+    pub fn new(seed: u64) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self {
+            id: NEXT_ID.fetch_add(1, AtomicOrdering::Relaxed),
+            seed,
+            links: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Create a link from node `from` to node `to` (& the reverse direction for the
+    /// replies), both w/ `settings`. Messages sent to the returned connection arrive at
+    /// `to` after they have gone through the link.
+    pub async fn connect(
+        &self,
+        ctx: &Context,
+        from: &str,
+        to: &str,
+        settings: LinkSettings,
+    ) -> Result<SimConnection> {
+        let index = {
+            let links = self.links.lock().unwrap_or_else(|it| it.into_inner());
+            links
+                .get(&(from.to_string(), to.to_string()))
+                .map_or(0, |it| it.len())
+        };
+        let address_of = |from: &str, to: &str| {
+            Address::from_string(format!("sim{}_{}_{}_{}", self.id, from, to, index))
+        };
+        let (sender_address, reverse_address) = (address_of(from, to), address_of(to, from));
+
+        for (from, to, address, reverse) in [
+            (from, to, &sender_address, &reverse_address),
+            (to, from, &reverse_address, &sender_address),
+        ] {
+            let name = format!("{}_{}_{}", from, to, index);
+            let link = Arc::new(Mutex::new(LinkState {
+                settings: settings.clone(),
+                partitioned: false,
+                rng: SimRng(self.seed ^ fnv1a(&name)),
+                busy_until: None,
+                arrives_in_order_at: None,
+                stats: LinkStats::default(),
+            }));
+            self.links
+                .lock()
+                .unwrap_or_else(|it| it.into_inner())
+                .entry((from.to_string(), to.to_string()))
+                .or_default()
+                .push(link.clone());
+
+            let (deliveries, pending) = unbounded_channel();
+            // A context from `async_try_clone` denies all messages, so the ones forwarded
+            // from it would silently be dropped.
+            let deliver_ctx = ctx
+                .new_detached(
+                    Address::random_tagged("SimLink.deliver"),
+                    AllowAll,
+                    AllowAll,
+                )
+                .await?;
+            tokio::spawn(deliver(
+                deliver_ctx,
+                address.address().to_string(),
+                link.clone(),
+                pending,
+            ));
+            let worker = SimLinkWorker {
+                link,
+                reverse: reverse.clone(),
+                deliveries,
+                sequence: 0,
+            };
+            ctx.start_worker(address.clone(), worker).await?;
+        }
+
+        Ok(SimConnection {
+            sender_address,
+            reverse_address,
+        })
+    }
+
+    /// Replace the settings of the links from `from` to `to`.
+    pub fn set_link(&self, from: &str, to: &str, settings: LinkSettings) {
+        self.for_each_link(from, to, |it| it.settings = settings.clone());
+    }
+
+    /// Drop every message between `a` & `b` (in both directions) until [SimNetwork::heal]
+    /// is called, including the ones that are already in flight.
+    pub fn partition(&self, a: &str, b: &str) {
+        self.for_each_link(a, b, |it| it.partitioned = true);
+        self.for_each_link(b, a, |it| it.partitioned = true);
+    }
+
+    pub fn heal(&self, a: &str, b: &str) {
+        self.for_each_link(a, b, |it| it.partitioned = false);
+        self.for_each_link(b, a, |it| it.partitioned = false);
+    }
+
+    /// The stats of all the links from `from` to `to`, added up.
+    pub fn stats(&self, from: &str, to: &str) -> LinkStats {
+        let mut total = LinkStats::default();
+        self.for_each_link(from, to, |it| {
+            total.sent += it.stats.sent;
+            total.delivered += it.stats.delivered;
+            total.lost += it.stats.lost;
+            total.partitioned += it.stats.partitioned;
+            total.undeliverable += it.stats.undeliverable;
+        });
+        total
+    }
+
+    fn for_each_link(&self, from: &str, to: &str, mut f: impl FnMut(&mut LinkState)) {
+        let links = self.links.lock().unwrap_or_else(|it| it.into_inner());
+        for link in links
+            .get(&(from.to_string(), to.to_string()))
+            .into_iter()
+            .flatten()
+        {
+            f(&mut link.lock().unwrap_or_else(|it| it.into_inner()));
+        }
+    }
+}
+
+/// One end of a link created by [SimNetwork::connect]. Like a `TcpConnection`, it can be
+/// used as the first hop of a route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimConnection {
+    sender_address: Address,
+    reverse_address: Address,
+}
+
+impl SimConnection {
+    /// The address that messages are sent to, to go over the link.
+    pub fn sender_address(&self) -> &Address {
+        &self.sender_address
+    }
+
+    /// The address that the replies go through, on the other end of the link.
+    pub fn reverse_address(&self) -> &Address {
+        &self.reverse_address
+    }
+}
+
+impl From<SimConnection> for Address {
+    fn from(connection: SimConnection) -> Self {
+        connection.sender_address
+    }
+}
+
+impl From<&SimConnection> for Address {
+    fn from(connection: &SimConnection) -> Self {
+        connection.sender_address.clone()
+    }
+}
+
+/// A message that is on its way over a link.
+struct Pending {
+    arrives_at: Instant,
+    /// Keeps the order of messages that are due at the same time.
+    sequence: u64,
+    message: LocalMessage,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    /// Reversed, so that the [BinaryHeap] pops the message that is due first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.arrives_at, other.sequence).cmp(&(self.arrives_at, self.sequence))
+    }
+}
+
+/// One direction of a link. Like a TCP connection, it removes its own address from the
+/// onward route, and adds the address of the other direction to the return route.
+struct SimLinkWorker {
+    link: Link,
+    reverse: Address,
+    deliveries: UnboundedSender<Pending>,
+    sequence: u64,
+}
+
+#[ockam::worker]
+impl Worker for SimLinkWorker {
+    type Context = Context;
+    type Message = Any;
+
+    async fn handle_message(&mut self, _ctx: &mut Context, msg: Routed<Any>) -> Result<()> {
+        let mut transport_message = msg.into_local_message().into_transport_message();
+        transport_message.onward_route.step()?;
+        transport_message
+            .return_route
+            .modify()
+            .prepend(self.reverse.clone());
+
+        let size = transport_message.payload.len();
+        let arrives_at = self
+            .link
+            .lock()
+            .unwrap_or_else(|it| it.into_inner())
+            .schedule(size);
+        if let Some(arrives_at) = arrives_at {
+            self.sequence += 1;
+            // Wipe all local info, like a message that went over the network.
+            let pending = Pending {
+                arrives_at,
+                sequence: self.sequence,
+                message: LocalMessage::new(transport_message, vec![]),
+            };
+            self.deliveries.send(pending).ok();
+        }
+        Ok(())
+    }
+}
+
+/// Forward the messages on a link when they're due, until its worker is stopped.
+async fn deliver(
+    ctx: Context,
+    link_name: String,
+    link: Link,
+    mut pending: UnboundedReceiver<Pending>,
+) {
+    let mut in_flight = BinaryHeap::new();
+    loop {
+        let next = in_flight.peek().map(|it: &Pending| it.arrives_at);
+        tokio::select! {
+            received = pending.recv() => match received {
+                Some(it) => in_flight.push(it),
+                None => return,
+            },
+            _ = tokio::time::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                let Some(due) = in_flight.pop() else {
+                    continue;
+                };
+                {
+                    let mut link = link.lock().unwrap_or_else(|it| it.into_inner());
+                    if link.partitioned {
+                        link.stats.partitioned += 1;
+                        continue;
+                    }
+                }
+                let dead_letter = due.message.clone();
+                let forwarded = ctx.forward(due.message).await;
+                {
+                    let mut link = link.lock().unwrap_or_else(|it| it.into_inner());
+                    match forwarded {
+                        Ok(()) => link.stats.delivered += 1,
+                        Err(_) => link.stats.undeliverable += 1,
+                    }
+                }
+                if let Err(error) = forwarded {
+                    let letter = DeadLetter::new(
+                        &link_name,
+                        DeadLetterReason::UnknownAddress,
+                        error,
+                        &dead_letter,
+                    );
+                    send_dead_letter(&ctx, letter).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::NodeRole;
+use ockam::flow_control::FlowControlId;
+use ockam::{Address, Context, Result, TcpTransport};
+use ockam_transport_tcp::{TcpConnectionMode, TcpRegistry};
+use std::fmt::{Debug, Formatter};
+use std::net::SocketAddr;
+
+/// Something that runs on a node: a worker, or one end of a transport link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopologyElement {
+    Worker {
+        address: Address,
+    },
+    TcpListener {
+        socket_address: SocketAddr,
+        flow_control_id: FlowControlId,
+        processor_address: Address,
+    },
+    TcpConnection {
+        socket_address: SocketAddr,
+        flow_control_id: FlowControlId,
+        sender_address: Address,
+    },
+}
+
+impl TopologyElement {
+    /// The address that is used to check whether this element is still running.
+    fn live_address(&self) -> &Address {
+        match self {
+            TopologyElement::Worker { address } => address,
+            TopologyElement::TcpListener {
+                processor_address, ..
+            } => processor_address,
+            TopologyElement::TcpConnection { sender_address, .. } => sender_address,
+        }
+    }
+
+    /// The two lines of text that describe this element in a diagram.
+    pub fn label(&self) -> [String; 2] {
+        match self {
+            TopologyElement::Worker { address } => {
+                ["Address:".to_string(), format!("'{}'", address.address())]
+            }
+            TopologyElement::TcpListener { socket_address, .. } => [
+                "TCP transport".to_string(),
+                format!("listening on {}", socket_address.port()),
+            ],
+            TopologyElement::TcpConnection { socket_address, .. } => [
+                "TCP transport".to_string(),
+                format!("connect to {}", socket_address.port()),
+            ],
+        }
+    }
+}
+
+/// A node and the elements that run on it, in the order that messages flow through them:
+/// TCP listeners, then workers, then outgoing TCP connections.
+#[derive(Clone)]
+pub struct TopologyNode {
+    pub name: String,
+    pub role: NodeRole,
+    pub elements: Vec<TopologyElement>,
+    contexts: Vec<Address>,
+    tcp_registries: Vec<TcpRegistry>,
+}
+
+impl TopologyNode {
+    /// A worker that this node started, eg: `"echoer"`.
+    pub fn worker(&mut self, address: impl Into<Address>) -> &mut Self {
+        self.elements.push(TopologyElement::Worker {
+            address: address.into(),
+        });
+        self
+    }
+
+    /// The node's own context, eg: the address that an initiator sends from. It is drawn
+    /// like a worker, but [crate::ShutdownCoordinator::register_topology] doesn't stop it.
+    pub fn context(&mut self, ctx: &Context) -> &mut Self {
+        self.contexts.push(ctx.address());
+        self.worker(ctx.address())
+    }
+
+    /// The TCP listeners & outgoing connections of `tcp_transport` are read from its
+    /// registry each time they are needed, so ones that are created later show up too.
+    pub fn tcp_transport(&mut self, tcp_transport: &TcpTransport) -> &mut Self {
+        self.tcp_registries.push(tcp_transport.registry().clone());
+        self
+    }
+
+    pub(crate) fn is_context(&self, address: &Address) -> bool {
+        self.contexts.contains(address)
+    }
+
+    /// The workers, plus the listeners & outgoing connections that are currently in the
+    /// registries of this node's TCP transports.
+    pub(crate) fn live_elements(&self) -> Vec<TopologyElement> {
+        let listeners = self.tcp_registries.iter().flat_map(|registry| {
+            registry
+                .get_all_listeners()
+                .into_iter()
+                .map(|it| TopologyElement::TcpListener {
+                    socket_address: it.socket_address(),
+                    flow_control_id: it.flow_control_id().clone(),
+                    processor_address: it.address().clone(),
+                })
+        });
+        let connections = self.tcp_registries.iter().flat_map(|registry| {
+            registry
+                .get_all_sender_workers()
+                .into_iter()
+                .filter(|it| matches!(it.mode(), TcpConnectionMode::Outgoing))
+                .map(|it| TopologyElement::TcpConnection {
+                    socket_address: it.socket_address(),
+                    flow_control_id: it.flow_control_id().clone(),
+                    sender_address: it.address().clone(),
+                })
+        });
+        listeners
+            .chain(self.elements.iter().cloned())
+            .chain(connections)
+            .collect()
+    }
+}
+
+impl Debug for TopologyNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TopologyNode")
+            .field("name", &self.name)
+            .field("role", &self.role)
+            .field("elements", &self.elements)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A TCP connection from one node to the listener on another node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopologyLink {
+    pub from_node: String,
+    pub to_node: String,
+    pub socket_address: SocketAddr,
+}
+
+/// The nodes in an example, the workers running on each one, and the transport links
+/// between them. All the nodes share one router, so `ctx.list_workers()` can't tell which
+/// node a worker is on. Instead each node names its workers & TCP transports, and
+/// [Topology::snapshot] reads the rest from the router & the transports' registries, so
+/// that diagrams rendered from it (eg: w/ [crate::render_ascii_diagram]) can't drift from
+/// the code.
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    nodes: Vec<TopologyNode>,
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the node w/ `name`, adding it if it doesn't exist yet.
+    pub fn node(&mut self, name: &str, role: NodeRole) -> &mut TopologyNode {
+        let index = match self.nodes.iter().position(|it| it.name == name) {
+            Some(index) => index,
+            None => {
+                self.nodes.push(TopologyNode {
+                    name: name.to_string(),
+                    role,
+                    elements: vec![],
+                    contexts: vec![],
+                    tcp_registries: vec![],
+                });
+                self.nodes.len() - 1
+            }
+        };
+        &mut self.nodes[index]
+    }
+
+    /// The nodes, ordered by role (initiators, then forwarders, then responders) and then
+    /// in the order that they were added.
+    pub fn nodes(&self) -> Vec<&TopologyNode> {
+        let mut nodes: Vec<&TopologyNode> = self.nodes.iter().collect();
+        nodes.sort_by_key(|it| it.role);
+        nodes
+    }
+
+    /// Each TCP connection that ends at a TCP listener on another node in this topology.
+    pub fn links(&self) -> Vec<TopologyLink> {
+        let mut links = vec![];
+        for from_node in self.nodes() {
+            for element in &from_node.elements {
+                let TopologyElement::TcpConnection { socket_address, .. } = element else {
+                    continue;
+                };
+                let to_node = self.nodes().into_iter().find(|node| {
+                    node.elements.iter().any(|it| {
+                        matches!(it,
+                        TopologyElement::TcpListener { socket_address: listener_address, .. }
+                            if listener_address == socket_address)
+                    })
+                });
+                if let Some(to_node) = to_node {
+                    links.push(TopologyLink {
+                        from_node: from_node.name.clone(),
+                        to_node: to_node.name.clone(),
+                        socket_address: *socket_address,
+                    });
+                }
+            }
+        }
+        links
+    }
+
+    /// A copy of this topology w/ the TCP listeners & connections that are open right
+    /// now, w/out the workers that are no longer running on `ctx`'s router, and w/out
+    /// nodes that have nothing left running.
+    pub async fn snapshot(&self, ctx: &Context) -> Result<Topology> {
+        let running = ctx.list_workers().await?;
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| TopologyNode {
+                name: node.name.clone(),
+                role: node.role,
+                elements: node
+                    .live_elements()
+                    .into_iter()
+                    .filter(|it| running.contains(it.live_address()))
+                    .collect(),
+                contexts: node.contexts.clone(),
+                tcp_registries: vec![],
+            })
+            .filter(|node| !node.elements.is_empty())
+            .collect();
+        Ok(Topology { nodes })
+    }
+}
@@ -0,0 +1,521 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::presentation::fnv1a;
+use crate::{Topology, TopologyElement, TopologyNode};
+use ockam::{Address, Route};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// One message going from one participant (a worker address, eg: `echoer`) to another. A
+/// run of an example is a list of these, in the order that they happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowStep {
+    pub from: String,
+    pub to: String,
+    pub label: String,
+}
+
+impl FlowStep {
+    pub fn new(from: impl Into<String>, to: impl Into<String>, label: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            label: label.into(),
+        }
+    }
+
+    /// A `request` sent from `from` along each hop of `route`, then its `reply` coming back
+    /// from the last hop, eg: the flow of a `send_and_receive`.
+    pub fn request_and_reply(
+        from: &Address,
+        route: &Route,
+        request: &str,
+        reply: &str,
+    ) -> Vec<Self> {
+        let mut steps = vec![];
+        let mut previous = from.address().to_string();
+        for hop in route.iter() {
+            let hop = hop.address().to_string();
+            steps.push(Self::new(previous, hop.clone(), request));
+            previous = hop;
+        }
+        steps.push(Self::new(previous, from.address(), reply));
+        steps
+    }
+}
+
+/// When this environment variable is set, the examples export their diagrams to the
+/// directory that it names, eg: `EXPORT_DIAGRAMS_DIR=diagrams/generated`.
+pub const EXPORT_DIAGRAMS_DIR_ENV: &str = "EXPORT_DIAGRAMS_DIR";
+
+/// Run [export_diagrams] if [EXPORT_DIAGRAMS_DIR_ENV] is set, and return the directory.
+pub fn export_diagrams_from_env(
+    name: &str,
+    topology: &Topology,
+    flows: &[FlowStep],
+) -> std::io::Result<Option<PathBuf>> {
+    let Some(directory) = std::env::var_os(EXPORT_DIAGRAMS_DIR_ENV) else {
+        return Ok(None);
+    };
+    let directory = PathBuf::from(directory);
+    export_diagrams(&directory, name, topology, flows)?;
+    Ok(Some(directory))
+}
+
+/// Write `<name>.mmd`, `<name>.dot` and `<name>.excalidraw` to `directory`.
+pub fn export_diagrams(
+    directory: impl AsRef<Path>,
+    name: &str,
+    topology: &Topology,
+    flows: &[FlowStep],
+) -> std::io::Result<()> {
+    let directory = directory.as_ref();
+    std::fs::create_dir_all(directory)?;
+    std::fs::write(
+        directory.join(format!("{}.mmd", name)),
+        to_mermaid(topology, flows),
+    )?;
+    std::fs::write(
+        directory.join(format!("{}.dot", name)),
+        to_dot(topology, flows),
+    )?;
+    std::fs::write(
+        directory.join(format!("{}.excalidraw", name)),
+        to_excalidraw(topology, flows),
+    )?;
+    Ok(())
+}
+
+/// A Mermaid sequence diagram (<https://mermaid.js.org/syntax/sequenceDiagram.html>) w/ a
+/// `box` per node that contains its workers, and a message per [FlowStep].
+pub fn to_mermaid(topology: &Topology, flows: &[FlowStep]) -> String {
+    let mut output = String::from("sequenceDiagram\n");
+    let mut declared: Vec<String> = vec![];
+
+    for node in topology.nodes() {
+        let workers = worker_addresses(node);
+        if workers.is_empty() {
+            continue;
+        }
+        output.push_str(&format!("    box {}\n", node.name));
+        for worker in workers {
+            output.push_str(&format!(
+                "    participant {} as {}\n",
+                sanitize(&worker),
+                worker
+            ));
+            declared.push(worker);
+        }
+        output.push_str("    end\n");
+    }
+
+    // Participants that aren't workers in the topology (eg: a secure channel encryptor).
+    for participant in flows.iter().flat_map(|it| [&it.from, &it.to]) {
+        if !declared.contains(participant) {
+            output.push_str(&format!(
+                "    participant {} as {}\n",
+                sanitize(participant),
+                participant
+            ));
+            declared.push(participant.clone());
+        }
+    }
+
+    for flow in flows {
+        output.push_str(&format!(
+            "    {}->>{}: {}\n",
+            sanitize(&flow.from),
+            sanitize(&flow.to),
+            flow.label.replace(';', ",")
+        ));
+    }
+    output
+}
+
+/// A Graphviz DOT graph (<https://graphviz.org/doc/info/lang.html>) w/ a cluster per node.
+/// Solid edges are the flow between the elements of a node, bold edges are TCP links, and
+/// dashed (numbered) edges are the [FlowStep]s.
+pub fn to_dot(topology: &Topology, flows: &[FlowStep]) -> String {
+    let mut output = String::from(
+        "digraph topology {\n    rankdir=LR;\n    node [shape=box, fontname=\"monospace\"];\n",
+    );
+
+    for (node_index, node) in topology.nodes().into_iter().enumerate() {
+        output.push_str(&format!(
+            "    subgraph cluster_{} {{\n        label=\"{}\";\n",
+            node_index,
+            escape(&node.name)
+        ));
+        for (element_index, element) in node.elements.iter().enumerate() {
+            let [line_1, line_2] = element.label();
+            output.push_str(&format!(
+                "        {} [label=\"{}\\n{}\"];\n",
+                element_id(node, element_index),
+                escape(&line_1),
+                escape(&line_2)
+            ));
+            if element_index > 0 {
+                output.push_str(&format!(
+                    "        {} -> {};\n",
+                    element_id(node, element_index - 1),
+                    element_id(node, element_index)
+                ));
+            }
+        }
+        output.push_str("    }\n");
+    }
+
+    for (from, to, socket_address) in link_element_ids(topology) {
+        output.push_str(&format!(
+            "    {} -> {} [label=\"tcp {}\", style=bold];\n",
+            from,
+            to,
+            escape(&socket_address.to_string())
+        ));
+    }
+
+    for (index, flow) in flows.iter().enumerate() {
+        output.push_str(&format!(
+            "    {} -> {} [label=\"{}: {}\", style=dashed, constraint=false];\n",
+            participant_id(topology, &flow.from),
+            participant_id(topology, &flow.to),
+            index + 1,
+            escape(&flow.label)
+        ));
+    }
+
+    output.push_str("}\n");
+    output
+}
+
+const EXCALIDRAW_NODE_WIDTH: f64 = 260.0;
+const EXCALIDRAW_NODE_GAP: f64 = 120.0;
+const EXCALIDRAW_ELEMENT_HEIGHT: f64 = 60.0;
+const EXCALIDRAW_ELEMENT_GAP: f64 = 30.0;
+const EXCALIDRAW_PADDING: f64 = 20.0;
+const EXCALIDRAW_FONT_SIZE: f64 = 16.0;
+
+/// An Excalidraw scene (<https://excalidraw.com>) in the same style as the hand drawn
+/// diagrams in `diagrams/`: a rectangle per node, a rectangle per element, arrows for the
+/// flow inside each node and for the TCP links, and a numbered list of [FlowStep]s.
+pub fn to_excalidraw(topology: &Topology, flows: &[FlowStep]) -> String {
+    let mut elements: Vec<Value> = vec![];
+    let mut bounds: Vec<(String, f64, f64, f64, f64)> = vec![];
+    let mut bottom: f64 = 0.0;
+
+    for (node_index, node) in topology.nodes().into_iter().enumerate() {
+        let x = node_index as f64 * (EXCALIDRAW_NODE_WIDTH + EXCALIDRAW_NODE_GAP);
+        let height = EXCALIDRAW_PADDING * 2.0
+            + node.elements.len() as f64 * (EXCALIDRAW_ELEMENT_HEIGHT + EXCALIDRAW_ELEMENT_GAP)
+            - EXCALIDRAW_ELEMENT_GAP;
+        bottom = bottom.max(height);
+
+        elements.push(excalidraw_shape(
+            &format!("node_{}", node_index),
+            "rectangle",
+            x,
+            0.0,
+            EXCALIDRAW_NODE_WIDTH,
+            height,
+            "#2f9e44",
+        ));
+        elements.push(excalidraw_text(
+            &format!("node_{}_title", node_index),
+            &node.name,
+            x,
+            -EXCALIDRAW_FONT_SIZE * 1.25 - 8.0,
+            EXCALIDRAW_NODE_WIDTH,
+            "#2f9e44",
+        ));
+
+        for (element_index, element) in node.elements.iter().enumerate() {
+            let id = element_id(node, element_index);
+            let element_x = x + EXCALIDRAW_PADDING;
+            let element_y = EXCALIDRAW_PADDING
+                + element_index as f64 * (EXCALIDRAW_ELEMENT_HEIGHT + EXCALIDRAW_ELEMENT_GAP);
+            let element_width = EXCALIDRAW_NODE_WIDTH - EXCALIDRAW_PADDING * 2.0;
+            elements.push(excalidraw_shape(
+                &id,
+                "rectangle",
+                element_x,
+                element_y,
+                element_width,
+                EXCALIDRAW_ELEMENT_HEIGHT,
+                "#1e1e1e",
+            ));
+            elements.push(excalidraw_text(
+                &format!("{}_text", id),
+                &element.label().join("\n"),
+                element_x,
+                element_y + 10.0,
+                element_width,
+                "#1e1e1e",
+            ));
+            bounds.push((
+                id,
+                element_x,
+                element_y,
+                element_width,
+                EXCALIDRAW_ELEMENT_HEIGHT,
+            ));
+        }
+    }
+
+    let find = |id: &str| bounds.iter().find(|it| it.0 == id).cloned();
+    let mut arrows: Vec<(String, String)> = vec![];
+    for node in topology.nodes() {
+        for element_index in 1..node.elements.len() {
+            arrows.push((
+                element_id(node, element_index - 1),
+                element_id(node, element_index),
+            ));
+        }
+    }
+    for (from, to, _) in link_element_ids(topology) {
+        arrows.push((from, to));
+    }
+    for (index, (from, to)) in arrows.into_iter().enumerate() {
+        if let (Some(from), Some(to)) = (find(&from), find(&to)) {
+            elements.push(excalidraw_arrow(&format!("arrow_{}", index), &from, &to));
+        }
+    }
+
+    if !flows.is_empty() {
+        let text = flows
+            .iter()
+            .enumerate()
+            .map(|(index, it)| format!("{}. {} → {}: {}", index + 1, it.from, it.to, it.label))
+            .collect::<Vec<_>>()
+            .join("\n");
+        elements.push(excalidraw_text(
+            "flows",
+            &text,
+            0.0,
+            bottom + EXCALIDRAW_ELEMENT_GAP * 2.0,
+            EXCALIDRAW_NODE_WIDTH * 2.0,
+            "#1971c2",
+        ));
+    }
+
+    let scene = json!({
+        "type": "excalidraw",
+        "version": 2,
+        "source": "hello_ockam",
+        "elements": elements,
+        "appState": { "gridSize": null, "viewBackgroundColor": "#ffffff" },
+        "files": {},
+    });
+    serde_json::to_string_pretty(&scene).unwrap_or_default()
+}
+
+fn excalidraw_common(
+    id: &str,
+    kind: &str,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    color: &str,
+) -> Value {
+    json!({
+        "id": id,
+        "type": kind,
+        "x": x,
+        "y": y,
+        "width": width,
+        "height": height,
+        "angle": 0,
+        "strokeColor": color,
+        "backgroundColor": "transparent",
+        "fillStyle": "hachure",
+        "strokeWidth": 2,
+        "strokeStyle": "solid",
+        "roughness": 1,
+        "opacity": 100,
+        "groupIds": [],
+        "frameId": null,
+        "roundness": null,
+        // Derived from the id so that exporting the same topology is reproducible.
+        "seed": fnv1a(id) % 2_000_000_000,
+        "version": 1,
+        "versionNonce": fnv1a(id) % 1_000_000_000,
+        "isDeleted": false,
+        "boundElements": null,
+        "updated": 1,
+        "link": null,
+        "locked": false,
+    })
+}
+
+fn excalidraw_shape(
+    id: &str,
+    kind: &str,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    color: &str,
+) -> Value {
+    let mut shape = excalidraw_common(id, kind, x, y, width, height, color);
+    shape["roundness"] = json!({ "type": 3 });
+    shape
+}
+
+fn excalidraw_text(id: &str, text: &str, x: f64, y: f64, width: f64, color: &str) -> Value {
+    let line_count = text.lines().count().max(1) as f64;
+    let height = line_count * EXCALIDRAW_FONT_SIZE * 1.25;
+    let mut element = excalidraw_common(id, "text", x, y, width, height, color);
+    let extra = json!({
+        "text": text,
+        "fontSize": EXCALIDRAW_FONT_SIZE,
+        "fontFamily": 3,
+        "textAlign": "center",
+        "verticalAlign": "top",
+        "baseline": EXCALIDRAW_FONT_SIZE - 2.0,
+        "containerId": null,
+        "originalText": text,
+        "lineHeight": 1.25,
+    });
+    if let (Some(element), Some(extra)) = (element.as_object_mut(), extra.as_object()) {
+        element.extend(extra.clone());
+    }
+    element
+}
+
+/// An arrow from the right (or bottom) edge of `from` to the left (or top) edge of `to`.
+fn excalidraw_arrow(
+    id: &str,
+    from: &(String, f64, f64, f64, f64),
+    to: &(String, f64, f64, f64, f64),
+) -> Value {
+    let (_, from_x, from_y, from_width, from_height) = *from;
+    let (_, to_x, to_y, to_width, _) = *to;
+    let same_column = (from_x - to_x).abs() < f64::EPSILON;
+    let (start_x, start_y, end_x, end_y) = if same_column {
+        (
+            from_x + from_width / 2.0,
+            from_y + from_height,
+            to_x + to_width / 2.0,
+            to_y,
+        )
+    } else {
+        (
+            from_x + from_width,
+            from_y + from_height / 2.0,
+            to_x,
+            to_y + EXCALIDRAW_ELEMENT_HEIGHT / 2.0,
+        )
+    };
+    let width = end_x - start_x;
+    let height = end_y - start_y;
+    let mut arrow = excalidraw_common(
+        id,
+        "arrow",
+        start_x,
+        start_y,
+        width.abs(),
+        height.abs(),
+        "#2f9e44",
+    );
+    let extra = json!({
+        "roundness": { "type": 2 },
+        "points": [[0.0, 0.0], [width, height]],
+        "lastCommittedPoint": null,
+        "startBinding": { "elementId": from.0, "focus": 0, "gap": 1 },
+        "endBinding": { "elementId": to.0, "focus": 0, "gap": 1 },
+        "startArrowhead": null,
+        "endArrowhead": "arrow",
+    });
+    if let (Some(arrow), Some(extra)) = (arrow.as_object_mut(), extra.as_object()) {
+        arrow.extend(extra.clone());
+    }
+    arrow
+}
+
+fn worker_addresses(node: &TopologyNode) -> Vec<String> {
+    node.elements
+        .iter()
+        .filter_map(|it| match it {
+            TopologyElement::Worker { address } => Some(address.address().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The (connection, listener, socket address) element ids of each TCP link.
+fn link_element_ids(topology: &Topology) -> Vec<(String, String, SocketAddr)> {
+    let nodes = topology.nodes();
+    let find_element = |node_name: &str, predicate: &dyn Fn(&TopologyElement) -> bool| {
+        nodes
+            .iter()
+            .find(|it| it.name == node_name)
+            .and_then(|node| {
+                node.elements
+                    .iter()
+                    .position(predicate)
+                    .map(|index| element_id(node, index))
+            })
+    };
+
+    topology
+        .links()
+        .into_iter()
+        .filter_map(|link| {
+            let from = find_element(&link.from_node, &|it| {
+                matches!(it, TopologyElement::TcpConnection { socket_address, .. }
+                    if *socket_address == link.socket_address)
+            })?;
+            let to = find_element(&link.to_node, &|it| {
+                matches!(it, TopologyElement::TcpListener { socket_address, .. }
+                    if *socket_address == link.socket_address)
+            })?;
+            Some((from, to, link.socket_address))
+        })
+        .collect()
+}
+
+/// The DOT id of the worker w/ `address`, or a quoted id for participants that aren't
+/// workers in the topology.
+fn participant_id(topology: &Topology, address: &str) -> String {
+    for node in topology.nodes() {
+        for (index, element) in node.elements.iter().enumerate() {
+            if let TopologyElement::Worker { address: worker } = element {
+                if worker.address() == address {
+                    return element_id(node, index);
+                }
+            }
+        }
+    }
+    format!("\"{}\"", escape(address))
+}
+
+fn element_id(node: &TopologyNode, element_index: usize) -> String {
+    format!("{}_{}", sanitize(&node.name), element_index)
+}
+
+/// Mermaid participant & DOT ids can only contain letters, digits and `_`.
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|it| if it.is_ascii_alphanumeric() { it } else { '_' })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
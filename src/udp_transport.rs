@@ -0,0 +1,488 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::transport_io_error;
+use async_trait::async_trait;
+use ockam::access_control::DenyAll;
+use ockam::errcode::{Kind, Origin};
+use ockam::flow_control::{FlowControlId, FlowControlOutgoingAccessControl, FlowControls};
+use ockam::{
+    Address, Any, AsyncTryClone, Context, LocalMessage, Node, Processor, Result, Route, Routed,
+    TransportMessage, Worker,
+};
+use ockam_core::{Decodable, Encodable};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// The largest datagram that is sent or received. Bigger messages are dropped.
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// How often the receivers check whether they have been asked to stop.
+const RECEIVE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a listener keeps the sender for a peer that it hasn't heard from.
+const DEFAULT_PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How many peers a listener keeps a sender for.
+const DEFAULT_MAX_PEERS: usize = 1024;
+
+/// What goes in each datagram: a message w/ the routes that it had when it was sent. It is
+/// BARE encoded, like the messages that ockam sends over TCP.
+#[derive(Serialize, Deserialize)]
+struct Datagram {
+    onward_route: Route,
+    return_route: Route,
+    payload: Vec<u8>,
+}
+
+/// Like `TcpTransportExtension`, eg: `let udp_transport = node.create_udp_transport().await?`.
+#[async_trait]
+pub trait UdpTransportExtension {
+    async fn create_udp_transport(&self) -> Result<UdpTransport>;
+}
+
+#[async_trait]
+impl UdpTransportExtension for Node {
+    async fn create_udp_transport(&self) -> Result<UdpTransport> {
+        UdpTransport::create(self.context()).await
+    }
+}
+
+/// Each [UdpListenerOptions::new] gets its own flow control id, like `TcpListenerOptions`.
+/// Add the workers that may receive messages from the listener as its consumers.
+#[derive(Debug, Clone)]
+pub struct UdpListenerOptions {
+    flow_control_id: FlowControlId,
+    peer_idle_timeout: Duration,
+    max_peers: usize,
+}
+
+impl Default for UdpListenerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UdpListenerOptions {
+    pub fn new() -> Self {
+        Self {
+            flow_control_id: FlowControls::generate_flow_control_id(),
+            peer_idle_timeout: DEFAULT_PEER_IDLE_TIMEOUT,
+            max_peers: DEFAULT_MAX_PEERS,
+        }
+    }
+
+    /// The sender for the replies to a peer is stopped once nothing was received from the
+    /// peer for this long. Later replies to it are undeliverable.
+    pub fn with_peer_idle_timeout(mut self, peer_idle_timeout: Duration) -> Self {
+        self.peer_idle_timeout = peer_idle_timeout;
+        self
+    }
+
+    /// Beyond this many peers, the sender of the one that was heard from the longest ago is
+    /// stopped.
+    pub fn with_max_peers(mut self, max_peers: usize) -> Self {
+        self.max_peers = max_peers.max(1);
+        self
+    }
+}
+
+/// Each [UdpConnectionOptions::new] gets its own flow control id, like
+/// `TcpConnectionOptions`. The workers that send over the connection may receive the
+/// replies.
+#[derive(Debug, Clone)]
+pub struct UdpConnectionOptions {
+    flow_control_id: FlowControlId,
+}
+
+impl Default for UdpConnectionOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UdpConnectionOptions {
+    pub fn new() -> Self {
+        Self {
+            flow_control_id: FlowControls::generate_flow_control_id(),
+        }
+    }
+}
+
+/// A UDP socket that receives messages from any peer. The replies to a peer go back
+/// through a sender worker that is created for that peer.
+#[derive(Debug, Clone)]
+pub struct UdpListener {
+    socket_address: SocketAddr,
+    processor_address: Address,
+    flow_control_id: FlowControlId,
+}
+
+impl UdpListener {
+    pub fn socket_address(&self) -> &SocketAddr {
+        &self.socket_address
+    }
+
+    /// The address of the processor that receives the datagrams, eg: to stop it.
+    pub fn processor_address(&self) -> &Address {
+        &self.processor_address
+    }
+
+    pub fn flow_control_id(&self) -> &FlowControlId {
+        &self.flow_control_id
+    }
+}
+
+/// A UDP socket that sends messages to a single peer. Like a `TcpConnection`, it is used
+/// as the first hop of a route.
+#[derive(Debug, Clone)]
+pub struct UdpConnection {
+    peer: SocketAddr,
+    sender_address: Address,
+    receiver_address: Address,
+    flow_control_id: FlowControlId,
+}
+
+impl UdpConnection {
+    pub fn peer(&self) -> &SocketAddr {
+        &self.peer
+    }
+
+    pub fn sender_address(&self) -> &Address {
+        &self.sender_address
+    }
+
+    /// The address of the processor that receives the replies, eg: to stop it.
+    pub fn receiver_address(&self) -> &Address {
+        &self.receiver_address
+    }
+
+    pub fn flow_control_id(&self) -> &FlowControlId {
+        &self.flow_control_id
+    }
+}
+
+impl From<UdpConnection> for Address {
+    fn from(connection: UdpConnection) -> Self {
+        connection.sender_address
+    }
+}
+
+impl From<&UdpConnection> for Address {
+    fn from(connection: &UdpConnection) -> Self {
+        connection.sender_address.clone()
+    }
+}
+
+/// Sends & receives messages as UDP datagrams, w/ the same flow control wiring as the TCP
+/// transport, so that eg: a [crate::Forwarder] works the same over both. UDP doesn't
+/// retry, so a lost datagram is a lost message.
+pub struct UdpTransport {
+    /// Only starts the senders & receivers, which get their own contexts w/ the access
+    /// control of their flow control id, so this one never sends or receives.
+    ctx: Context,
+}
+
+impl UdpTransport {
+    pub async fn create(ctx: &Context) -> Result<Self> {
+        Ok(Self {
+            ctx: ctx.async_try_clone().await?,
+        })
+    }
+
+    /// Eg: `udp_transport.listen("127.0.0.1:4000", UdpListenerOptions::new())`.
+    pub async fn listen(
+        &self,
+        bind_address: impl AsRef<str>,
+        options: UdpListenerOptions,
+    ) -> Result<UdpListener> {
+        let socket = UdpSocket::bind(resolve(bind_address.as_ref()).await?)
+            .await
+            .map_err(transport_io_error)?;
+        let socket_address = socket.local_addr().map_err(transport_io_error)?;
+        let processor_address = Address::random_tagged("UdpListener.receiver");
+
+        self.ctx.flow_controls().add_producer(
+            processor_address.clone(),
+            &options.flow_control_id,
+            None,
+            vec![],
+        );
+        let receiver = UdpReceiver {
+            socket: Arc::new(socket),
+            peer: None,
+            flow_control_id: options.flow_control_id.clone(),
+            reply_senders: BTreeMap::new(),
+            peer_idle_timeout: options.peer_idle_timeout,
+            max_peers: options.max_peers,
+        };
+        self.start_receiver(
+            processor_address.clone(),
+            receiver,
+            &options.flow_control_id,
+        )
+        .await?;
+
+        Ok(UdpListener {
+            socket_address,
+            processor_address,
+            flow_control_id: options.flow_control_id,
+        })
+    }
+
+    /// Eg: `udp_transport.connect("localhost:4000", UdpConnectionOptions::new())`.
+    pub async fn connect(
+        &self,
+        peer: impl AsRef<str>,
+        options: UdpConnectionOptions,
+    ) -> Result<UdpConnection> {
+        let peer = resolve(peer.as_ref()).await?;
+        let bind_address = if peer.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = Arc::new(
+            UdpSocket::bind(bind_address)
+                .await
+                .map_err(transport_io_error)?,
+        );
+        let sender_address = Address::random_tagged("UdpConnection.sender");
+        let receiver_address = Address::random_tagged("UdpConnection.receiver");
+
+        // Like a TCP connection, the sender is an additional address of the producer, so
+        // that the workers that send to it are allowed to receive the replies.
+        self.ctx.flow_controls().add_producer(
+            receiver_address.clone(),
+            &options.flow_control_id,
+            None,
+            vec![sender_address.clone()],
+        );
+        let sender = UdpSender {
+            socket: socket.clone(),
+            peer,
+        };
+        self.ctx
+            .start_worker(sender_address.clone(), sender)
+            .await?;
+        let receiver = UdpReceiver {
+            socket,
+            peer: Some((peer, sender_address.clone())),
+            flow_control_id: options.flow_control_id.clone(),
+            reply_senders: BTreeMap::new(),
+            peer_idle_timeout: DEFAULT_PEER_IDLE_TIMEOUT,
+            max_peers: DEFAULT_MAX_PEERS,
+        };
+        self.start_receiver(receiver_address.clone(), receiver, &options.flow_control_id)
+            .await?;
+
+        Ok(UdpConnection {
+            peer,
+            sender_address,
+            receiver_address,
+            flow_control_id: options.flow_control_id,
+        })
+    }
+
+    /// Like the receiver of a TCP connection, it only forwards messages to the consumers of
+    /// its flow control id.
+    async fn start_receiver(
+        &self,
+        address: Address,
+        receiver: UdpReceiver,
+        flow_control_id: &FlowControlId,
+    ) -> Result<()> {
+        let outgoing = FlowControlOutgoingAccessControl::new(
+            self.ctx.flow_controls(),
+            flow_control_id.clone(),
+            None,
+        );
+        self.ctx
+            .start_processor_with_access_control(address, receiver, DenyAll, outgoing)
+            .await
+    }
+}
+
+/// Sends each message that it receives to `peer`, w/out its own address on the onward
+/// route.
+struct UdpSender {
+    socket: Arc<UdpSocket>,
+    peer: SocketAddr,
+}
+
+#[ockam::worker]
+impl Worker for UdpSender {
+    type Context = Context;
+    type Message = Any;
+
+    async fn handle_message(&mut self, _ctx: &mut Context, msg: Routed<Any>) -> Result<()> {
+        let mut transport_message = msg.into_local_message().into_transport_message();
+        transport_message.onward_route.step()?;
+        let datagram = Datagram {
+            onward_route: transport_message.onward_route,
+            return_route: transport_message.return_route,
+            payload: transport_message.payload,
+        };
+        let bytes = datagram.encode()?;
+        if bytes.len() > MAX_DATAGRAM_SIZE {
+            return Err(ockam::Error::new(
+                Origin::Transport,
+                Kind::Invalid,
+                format!(
+                    "the message is {} bytes, UDP datagrams can't be bigger than {}",
+                    bytes.len(),
+                    MAX_DATAGRAM_SIZE
+                ),
+            ));
+        }
+        self.socket
+            .send_to(&bytes, self.peer)
+            .await
+            .map_err(transport_io_error)?;
+        Ok(())
+    }
+}
+
+/// Receives the datagrams on a socket, and forwards them w/ the address of a [UdpSender]
+/// back to their peer prepended to the return route. A connection's receiver replies via
+/// the connection's sender, a listener's receiver starts a sender for each new peer, and
+/// stops the ones of the peers that went quiet.
+struct UdpReceiver {
+    socket: Arc<UdpSocket>,
+    /// Only set for connections.
+    peer: Option<(SocketAddr, Address)>,
+    flow_control_id: FlowControlId,
+    /// The sender for each peer, & when the peer was last heard from.
+    reply_senders: BTreeMap<SocketAddr, (Address, Instant)>,
+    peer_idle_timeout: Duration,
+    max_peers: usize,
+}
+
+impl UdpReceiver {
+    async fn reply_sender(&mut self, ctx: &Context, from: SocketAddr) -> Result<Address> {
+        if let Some((_, sender_address)) = &self.peer {
+            return Ok(sender_address.clone());
+        }
+        if let Some((sender_address, last_heard)) = self.reply_senders.get_mut(&from) {
+            *last_heard = Instant::now();
+            return Ok(sender_address.clone());
+        }
+
+        if self.reply_senders.len() >= self.max_peers {
+            let quietest = self
+                .reply_senders
+                .iter()
+                .min_by_key(|(_, (_, last_heard))| *last_heard)
+                .map(|(peer, _)| *peer);
+            if let Some(peer) = quietest {
+                self.stop_reply_sender(ctx, peer).await;
+            }
+        }
+        let sender_address = Address::random_tagged("UdpListener.sender");
+        // So that a [crate::Forwarder] can find the flow control of the peer's messages.
+        ctx.flow_controls().add_producer(
+            sender_address.clone(),
+            &self.flow_control_id,
+            None,
+            vec![],
+        );
+        let sender = UdpSender {
+            socket: self.socket.clone(),
+            peer: from,
+        };
+        ctx.start_worker(sender_address.clone(), sender).await?;
+        self.reply_senders
+            .insert(from, (sender_address.clone(), Instant::now()));
+        Ok(sender_address)
+    }
+
+    async fn stop_idle_reply_senders(&mut self, ctx: &Context) {
+        let idle: Vec<SocketAddr> = self
+            .reply_senders
+            .iter()
+            .filter(|(_, (_, last_heard))| last_heard.elapsed() >= self.peer_idle_timeout)
+            .map(|(peer, _)| *peer)
+            .collect();
+        for peer in idle {
+            self.stop_reply_sender(ctx, peer).await;
+        }
+    }
+
+    async fn stop_reply_sender(&mut self, ctx: &Context, peer: SocketAddr) {
+        if let Some((sender_address, _)) = self.reply_senders.remove(&peer) {
+            let _ = ctx.stop_worker(sender_address.clone()).await;
+            ctx.flow_controls().cleanup_address(&sender_address);
+        }
+    }
+}
+
+#[async_trait]
+impl Processor for UdpReceiver {
+    type Context = Context;
+
+    async fn process(&mut self, ctx: &mut Context) -> Result<bool> {
+        let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+        let received =
+            tokio::time::timeout(RECEIVE_POLL_INTERVAL, self.socket.recv_from(&mut buffer)).await;
+        let (length, from) = match received {
+            Ok(Ok(it)) => it,
+            Ok(Err(error)) => return Err(transport_io_error(error)),
+            // Nothing yet, give the processor a chance to be stopped.
+            Err(_) => {
+                self.stop_idle_reply_senders(ctx).await;
+                return Ok(true);
+            }
+        };
+        if let Some((peer, _)) = &self.peer {
+            if from != *peer {
+                return Ok(true);
+            }
+        }
+        // Drop what isn't a message, like the network would.
+        let Ok(datagram) = Datagram::decode(&buffer[..length]) else {
+            return Ok(true);
+        };
+
+        self.stop_idle_reply_senders(ctx).await;
+        let reply_sender = self.reply_sender(ctx, from).await?;
+        let mut return_route = datagram.return_route;
+        return_route.modify().prepend(reply_sender);
+        let transport_message =
+            TransportMessage::v1(datagram.onward_route, return_route, datagram.payload);
+        ctx.forward(LocalMessage::new(transport_message, vec![]))
+            .await?;
+        Ok(true)
+    }
+}
+
+async fn resolve(address: &str) -> Result<SocketAddr> {
+    tokio::net::lookup_host(address)
+        .await
+        .map_err(transport_io_error)?
+        .next()
+        .ok_or_else(|| {
+            ockam::Error::new(
+                Origin::Transport,
+                Kind::NotFound,
+                format!("can't resolve '{}'", address),
+            )
+        })
+}
@@ -0,0 +1,524 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use crate::{emit_event, transport_io_error, Event};
+use async_trait::async_trait;
+use ockam::access_control::DenyAll;
+use ockam::errcode::{Kind, Origin};
+use ockam::flow_control::{FlowControlId, FlowControlOutgoingAccessControl, FlowControls};
+use ockam::{
+    Address, Any, AsyncTryClone, Context, LocalMessage, Node, Processor, Result, Route, Routed,
+    TransportMessage, Worker,
+};
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// The biggest frame that is sent or received. A bigger one closes the connection.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// How often the processors check whether they have been asked to stop.
+const RECEIVE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Only the user that runs the node may connect, unless [UnixListenerOptions::with_mode]
+/// says otherwise.
+const DEFAULT_SOCKET_MODE: u32 = 0o600;
+
+/// Where a listener's socket is bound, before it is locked down, see [bind_private].
+const PRIVATE_DIRECTORY_MODE: u32 = 0o700;
+
+/// What goes in each frame (after its length, as a big endian u32): a message w/ the
+/// routes that it had when it was sent.
+#[derive(Serialize, Deserialize)]
+struct Frame {
+    onward_route: Route,
+    return_route: Route,
+    payload: Vec<u8>,
+}
+
+/// Like `TcpTransportExtension`, eg: `let unix_transport = node.create_unix_transport().await?`.
+#[async_trait]
+pub trait UnixTransportExtension {
+    async fn create_unix_transport(&self) -> Result<UnixTransport>;
+}
+
+#[async_trait]
+impl UnixTransportExtension for Node {
+    async fn create_unix_transport(&self) -> Result<UnixTransport> {
+        UnixTransport::create(self.context()).await
+    }
+}
+
+/// Each [UnixListenerOptions::new] gets its own flow control id, like `TcpListenerOptions`.
+/// Add the workers that may receive messages from the listener as its consumers.
+///
+/// Who may connect is decided by the permissions of the socket file (0o600 by default), and
+/// optionally by the user id of the peer process, see [UnixListenerOptions::with_allowed_uids].
+#[derive(Debug, Clone)]
+pub struct UnixListenerOptions {
+    flow_control_id: FlowControlId,
+    mode: u32,
+    allowed_uids: Option<Vec<u32>>,
+}
+
+impl Default for UnixListenerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UnixListenerOptions {
+    pub fn new() -> Self {
+        Self {
+            flow_control_id: FlowControls::generate_flow_control_id(),
+            mode: DEFAULT_SOCKET_MODE,
+            allowed_uids: None,
+        }
+    }
+
+    /// The permissions of the socket file, eg: `0o660` to let the group connect too.
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Only accept connections from processes that run as one of `uids`, whatever the
+    /// permissions of the socket file are. The others are closed right away.
+    pub fn with_allowed_uids(mut self, uids: impl IntoIterator<Item = u32>) -> Self {
+        self.allowed_uids = Some(uids.into_iter().collect());
+        self
+    }
+
+    pub fn flow_control_id(&self) -> &FlowControlId {
+        &self.flow_control_id
+    }
+}
+
+/// Each [UnixConnectionOptions::new] gets its own flow control id, like
+/// `TcpConnectionOptions`. The workers that send over the connection may receive the
+/// replies.
+#[derive(Debug, Clone)]
+pub struct UnixConnectionOptions {
+    flow_control_id: FlowControlId,
+}
+
+impl Default for UnixConnectionOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UnixConnectionOptions {
+    pub fn new() -> Self {
+        Self {
+            flow_control_id: FlowControls::generate_flow_control_id(),
+        }
+    }
+
+    pub fn flow_control_id(&self) -> &FlowControlId {
+        &self.flow_control_id
+    }
+}
+
+/// A socket file that accepts connections. Each accepted connection gets its own sender &
+/// receiver, like a TCP listener's.
+#[derive(Debug, Clone)]
+pub struct UnixListener {
+    path: PathBuf,
+    processor_address: Address,
+    flow_control_id: FlowControlId,
+}
+
+impl UnixListener {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The address of the processor that accepts the connections, eg: to stop it. The
+    /// socket file is removed when it stops.
+    pub fn processor_address(&self) -> &Address {
+        &self.processor_address
+    }
+
+    pub fn flow_control_id(&self) -> &FlowControlId {
+        &self.flow_control_id
+    }
+}
+
+/// A connection to a socket file. Like a `TcpConnection`, it is used as the first hop of a
+/// route.
+#[derive(Debug, Clone)]
+pub struct UnixConnection {
+    path: PathBuf,
+    sender_address: Address,
+    receiver_address: Address,
+    flow_control_id: FlowControlId,
+}
+
+impl UnixConnection {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn sender_address(&self) -> &Address {
+        &self.sender_address
+    }
+
+    /// The address of the processor that receives the replies, eg: to stop it.
+    pub fn receiver_address(&self) -> &Address {
+        &self.receiver_address
+    }
+
+    pub fn flow_control_id(&self) -> &FlowControlId {
+        &self.flow_control_id
+    }
+}
+
+impl From<UnixConnection> for Address {
+    fn from(connection: UnixConnection) -> Self {
+        connection.sender_address
+    }
+}
+
+impl From<&UnixConnection> for Address {
+    fn from(connection: &UnixConnection) -> Self {
+        connection.sender_address.clone()
+    }
+}
+
+/// Sends & receives messages over Unix domain sockets, for nodes on the same host that
+/// shouldn't open TCP ports. It has the same flow control wiring as the TCP transport, so
+/// that eg: a [crate::Forwarder] works the same over both.
+pub struct UnixTransport {
+    /// Only starts the acceptors, senders & receivers, which get their own contexts w/ the
+    /// access control of their flow control id, so this one never sends or receives.
+    ctx: Context,
+}
+
+impl UnixTransport {
+    pub async fn create(ctx: &Context) -> Result<Self> {
+        Ok(Self {
+            ctx: ctx.async_try_clone().await?,
+        })
+    }
+
+    /// Eg: `unix_transport.listen("/tmp/bob.sock", UnixListenerOptions::new())`. A socket
+    /// file that is left over from a node that didn't stop cleanly is replaced, but one
+    /// that is still accepted on is an error.
+    pub async fn listen(
+        &self,
+        path: impl AsRef<Path>,
+        options: UnixListenerOptions,
+    ) -> Result<UnixListener> {
+        let path = path.as_ref().to_path_buf();
+        remove_stale_socket(&path).await?;
+        let listener = bind_private(&path, options.mode)?;
+        let processor_address = Address::random_tagged("UnixListener.acceptor");
+
+        let acceptor = UnixAcceptor {
+            listener,
+            path: path.clone(),
+            allowed_uids: options.allowed_uids,
+            flow_control_id: options.flow_control_id.clone(),
+        };
+        self.ctx
+            .start_processor(processor_address.clone(), acceptor)
+            .await?;
+
+        Ok(UnixListener {
+            path,
+            processor_address,
+            flow_control_id: options.flow_control_id,
+        })
+    }
+
+    /// Eg: `unix_transport.connect("/tmp/bob.sock", UnixConnectionOptions::new())`.
+    pub async fn connect(
+        &self,
+        path: impl AsRef<Path>,
+        options: UnixConnectionOptions,
+    ) -> Result<UnixConnection> {
+        let path = path.as_ref().to_path_buf();
+        let stream = UnixStream::connect(&path)
+            .await
+            .map_err(transport_io_error)?;
+        let (sender_address, receiver_address) = start_stream(
+            &self.ctx,
+            stream,
+            &options.flow_control_id,
+            "UnixConnection",
+        )
+        .await?;
+
+        Ok(UnixConnection {
+            path,
+            sender_address,
+            receiver_address,
+            flow_control_id: options.flow_control_id,
+        })
+    }
+}
+
+/// Start the sender & receiver of a connection. Like a TCP connection, the receiver is the
+/// producer, and the sender is an additional address of it, so that the workers that send
+/// to the sender are allowed to receive the replies (and a [crate::Forwarder] can find the
+/// flow control of either). The receiver only forwards messages to the consumers of the
+/// flow control id.
+async fn start_stream(
+    ctx: &Context,
+    stream: UnixStream,
+    flow_control_id: &FlowControlId,
+    tag: &str,
+) -> Result<(Address, Address)> {
+    let sender_address = Address::random_tagged(&format!("{}.sender", tag));
+    let receiver_address = Address::random_tagged(&format!("{}.receiver", tag));
+    ctx.flow_controls().add_producer(
+        receiver_address.clone(),
+        flow_control_id,
+        None,
+        vec![sender_address.clone()],
+    );
+
+    let (read_half, write_half) = stream.into_split();
+    ctx.start_worker(sender_address.clone(), UnixSender { write_half })
+        .await?;
+    let (frames_tx, frames_rx) = mpsc::channel(64);
+    let receiver = UnixReceiver {
+        frames: frames_rx,
+        reader: tokio::spawn(read_frames(read_half, frames_tx)),
+        sender_address: sender_address.clone(),
+    };
+    let outgoing =
+        FlowControlOutgoingAccessControl::new(ctx.flow_controls(), flow_control_id.clone(), None);
+    ctx.start_processor_with_access_control(receiver_address.clone(), receiver, DenyAll, outgoing)
+        .await?;
+
+    Ok((sender_address, receiver_address))
+}
+
+/// Accepts the connections on a socket file, and starts a sender & receiver for each one
+/// that is allowed.
+struct UnixAcceptor {
+    listener: tokio::net::UnixListener,
+    path: PathBuf,
+    allowed_uids: Option<Vec<u32>>,
+    flow_control_id: FlowControlId,
+}
+
+#[async_trait]
+impl Processor for UnixAcceptor {
+    type Context = Context;
+
+    async fn process(&mut self, ctx: &mut Context) -> Result<bool> {
+        let accepted = tokio::time::timeout(RECEIVE_POLL_INTERVAL, self.listener.accept()).await;
+        let stream = match accepted {
+            Ok(Ok((stream, _))) => stream,
+            Ok(Err(error)) => return Err(transport_io_error(error)),
+            // Nothing yet, give the processor a chance to be stopped.
+            Err(_) => return Ok(true),
+        };
+
+        if let Some(allowed_uids) = &self.allowed_uids {
+            let uid = stream.peer_cred().map_err(transport_io_error)?.uid();
+            if !allowed_uids.contains(&uid) {
+                emit_event(
+                    ctx.address().address(),
+                    Event::AccessDenied {
+                        destination: self.path.display().to_string(),
+                        from: format!("uid {}", uid),
+                        their_identity: None,
+                    },
+                );
+                return Ok(true);
+            }
+        }
+
+        start_stream(ctx, stream, &self.flow_control_id, "UnixListener").await?;
+        Ok(true)
+    }
+
+    async fn shutdown(&mut self, _ctx: &mut Context) -> Result<()> {
+        let _ = std::fs::remove_file(&self.path);
+        Ok(())
+    }
+}
+
+/// Writes each message that it receives as a frame, w/out its own address on the onward
+/// route.
+struct UnixSender {
+    write_half: OwnedWriteHalf,
+}
+
+#[ockam::worker]
+impl Worker for UnixSender {
+    type Context = Context;
+    type Message = Any;
+
+    async fn handle_message(&mut self, _ctx: &mut Context, msg: Routed<Any>) -> Result<()> {
+        let mut transport_message = msg.into_local_message().into_transport_message();
+        transport_message.onward_route.step()?;
+        let frame = Frame {
+            onward_route: transport_message.onward_route,
+            return_route: transport_message.return_route,
+            payload: transport_message.payload,
+        };
+        let bytes = serde_json::to_vec(&frame)
+            .map_err(|error| ockam::Error::new(Origin::Transport, Kind::Serialization, error))?;
+        if bytes.len() > MAX_FRAME_SIZE {
+            return Err(ockam::Error::new(
+                Origin::Transport,
+                Kind::Invalid,
+                format!(
+                    "the message is {} bytes, frames can't be bigger than {}",
+                    bytes.len(),
+                    MAX_FRAME_SIZE
+                ),
+            ));
+        }
+        self.write_half
+            .write_u32(bytes.len() as u32)
+            .await
+            .map_err(transport_io_error)?;
+        self.write_half
+            .write_all(&bytes)
+            .await
+            .map_err(transport_io_error)?;
+        Ok(())
+    }
+}
+
+/// Forwards the frames that [read_frames] reads w/ the address of the connection's
+/// [UnixSender] prepended to the return route. When the peer closes the connection, the
+/// sender & receiver are stopped.
+struct UnixReceiver {
+    frames: mpsc::Receiver<Frame>,
+    reader: JoinHandle<()>,
+    sender_address: Address,
+}
+
+#[async_trait]
+impl Processor for UnixReceiver {
+    type Context = Context;
+
+    async fn process(&mut self, ctx: &mut Context) -> Result<bool> {
+        let frame = match tokio::time::timeout(RECEIVE_POLL_INTERVAL, self.frames.recv()).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
+                let _ = ctx.stop_worker(self.sender_address.clone()).await;
+                return Ok(false);
+            }
+            // Nothing yet, give the processor a chance to be stopped.
+            Err(_) => return Ok(true),
+        };
+
+        let mut return_route = frame.return_route;
+        return_route.modify().prepend(self.sender_address.clone());
+        let transport_message =
+            TransportMessage::v1(frame.onward_route, return_route, frame.payload);
+        ctx.forward(LocalMessage::new(transport_message, vec![]))
+            .await?;
+        Ok(true)
+    }
+
+    async fn shutdown(&mut self, _ctx: &mut Context) -> Result<()> {
+        self.reader.abort();
+        Ok(())
+    }
+}
+
+/// Reads frames until the peer closes the connection, or sends something that isn't a
+/// frame. Reads aren't cancel safe, so this runs in its own task instead of in
+/// [UnixReceiver::process].
+async fn read_frames(mut read_half: OwnedReadHalf, frames: mpsc::Sender<Frame>) {
+    loop {
+        let Ok(length) = read_half.read_u32().await else {
+            return;
+        };
+        if length as usize > MAX_FRAME_SIZE {
+            return;
+        }
+        let mut bytes = vec![0; length as usize];
+        if read_half.read_exact(&mut bytes).await.is_err() {
+            return;
+        }
+        let Ok(frame) = serde_json::from_slice::<Frame>(&bytes) else {
+            return;
+        };
+        if frames.send(frame).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Bind a socket at `path` that has the permissions `mode` before anyone can connect to it.
+/// A socket gets the umask's permissions when it is bound, so it is bound in a private
+/// directory, locked down there, & only then linked to `path`.
+fn bind_private(path: &Path, mode: u32) -> Result<tokio::net::UnixListener> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // Short, since the path of a socket can't be longer than ~100 bytes.
+    let random = Address::random_local();
+    let binding_dir = parent.join(format!(".uds-{}", &random.address()[..8]));
+    std::fs::DirBuilder::new()
+        .mode(PRIVATE_DIRECTORY_MODE)
+        .create(&binding_dir)
+        .map_err(transport_io_error)?;
+    let binding_path = binding_dir.join("s");
+
+    let result = tokio::net::UnixListener::bind(&binding_path)
+        .and_then(|listener| {
+            std::fs::set_permissions(&binding_path, std::fs::Permissions::from_mode(mode))?;
+            // Unlike a rename, this doesn't replace a socket that was bound at `path` since
+            // it was checked.
+            std::fs::hard_link(&binding_path, path)?;
+            Ok(listener)
+        })
+        .map_err(transport_io_error);
+    let _ = std::fs::remove_file(&binding_path);
+    let _ = std::fs::remove_dir(&binding_dir);
+    result
+}
+
+/// Remove `path` if it is a socket file that nothing accepts on anymore.
+async fn remove_stale_socket(path: &Path) -> Result<()> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(ockam::Error::new(
+            Origin::Transport,
+            Kind::AlreadyExists,
+            format!("'{}' exists and isn't a socket", path.display()),
+        ));
+    }
+    if UnixStream::connect(path).await.is_ok() {
+        return Err(ockam::Error::new(
+            Origin::Transport,
+            Kind::AlreadyExists,
+            format!("'{}' is already being listened on", path.display()),
+        ));
+    }
+    std::fs::remove_file(path).map_err(transport_io_error)
+}
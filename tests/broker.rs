@@ -0,0 +1,150 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{loopback, start_tcp_middle};
+use hello_ockam::{Broker, BrokerClient, BrokerRequest, Subscriber, SubscriberEvent};
+use ockam::{
+    route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+
+/// Wait for `count` deliveries, and return their payloads.
+async fn receive_deliveries(ctx: &mut Context, count: usize) -> Result<Vec<String>> {
+    let mut payloads = vec![];
+    while payloads.len() < count {
+        if let SubscriberEvent::Delivery(message) = ctx.receive::<SubscriberEvent>().await?.body() {
+            payloads.push(message.payload);
+        }
+    }
+    Ok(payloads)
+}
+
+#[ockam::test]
+async fn fans_out_to_every_subscriber_of_the_topic(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("broker", Broker::new()).await?;
+    // A context from `async_try_clone` denies all messages, so the subscribers forward
+    // their events to this one.
+    let address = ctx.address();
+
+    for (name, topic) in [("s1", "news"), ("s2", "news"), ("s3", "weather")] {
+        let subscriber = Subscriber::new(route!["broker"], topic).with_forward_to(address.clone());
+        ctx.start_worker(name, subscriber).await?;
+        assert!(matches!(
+            ctx.receive::<SubscriberEvent>().await?.body(),
+            SubscriberEvent::Subscribed { .. }
+        ));
+    }
+
+    let broker = BrokerClient::new(route!["broker"]);
+    let receipt = broker.publish(ctx, "news", "Hello Ockam!").await?;
+    assert_eq!((receipt.sequence, receipt.delivered), (1, 2));
+    let receipt = broker.publish(ctx, "news", "Hello again!").await?;
+    assert_eq!((receipt.sequence, receipt.delivered), (2, 2));
+
+    let mut payloads = receive_deliveries(ctx, 4).await?;
+    payloads.sort();
+    assert_eq!(
+        payloads,
+        [
+            "Hello Ockam!",
+            "Hello Ockam!",
+            "Hello again!",
+            "Hello again!"
+        ]
+    );
+
+    // Nobody subscribed to this one.
+    let receipt = broker.publish(ctx, "sports", "Goal!").await?;
+    assert_eq!((receipt.sequence, receipt.delivered), (1, 0));
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn prunes_subscribers_that_stop_acknowledging(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("broker", Broker::new().with_max_unacked(2))
+        .await?;
+
+    // Subscribes, but never acknowledges anything, like a subscriber whose node is gone.
+    ctx.send(
+        route!["broker"],
+        BrokerRequest::Subscribe {
+            topic: "news".to_string(),
+        },
+    )
+    .await?;
+    assert!(matches!(
+        ctx.receive::<SubscriberEvent>().await?.body(),
+        SubscriberEvent::Subscribed { .. }
+    ));
+
+    let broker = BrokerClient::new(route!["broker"]);
+    for _ in 0..2 {
+        let receipt = broker.publish(ctx, "news", "Hello Ockam!").await?;
+        assert_eq!((receipt.delivered, receipt.pruned), (1, 0));
+    }
+    let receipt = broker.publish(ctx, "news", "Hello Ockam!").await?;
+    assert_eq!((receipt.delivered, receipt.pruned), (0, 1));
+    let receipt = broker.publish(ctx, "news", "Hello Ockam!").await?;
+    assert_eq!((receipt.delivered, receipt.pruned), (0, 0));
+
+    ctx.stop().await
+}
+
+/// Like examples/10-pub-sub.rs, w/ the subscriber & the publisher two TCP hops away.
+#[ockam::test]
+async fn delivers_to_subscribers_across_tcp_hops(ctx: &mut Context) -> Result<()> {
+    let node_responder = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_responder.create_tcp_transport().await?;
+    node_responder.start_worker("broker", Broker::new()).await?;
+    let listener = tcp_transport
+        .listen(loopback(4601), TcpListenerOptions::new())
+        .await?;
+    node_responder
+        .flow_controls()
+        .add_consumer("broker", listener.flow_control_id());
+    let _node_middle = start_tcp_middle(ctx, "forward_to_broker", 3601, 4601).await?;
+
+    let node_subscriber = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_subscriber.create_tcp_transport().await?;
+    let connection = tcp_transport
+        .connect(loopback(3601), TcpConnectionOptions::new())
+        .await?;
+    // Replies arrive over the connection, so the subscriber has to be one of its consumers.
+    node_subscriber
+        .flow_controls()
+        .add_consumer("subscriber", connection.flow_control_id());
+    let broker_route = route![connection, "forward_to_broker", "broker"];
+    let subscriber = Subscriber::new(broker_route.clone(), "news").with_forward_to(ctx.address());
+    node_subscriber
+        .start_worker("subscriber", subscriber)
+        .await?;
+    assert!(matches!(
+        ctx.receive::<SubscriberEvent>().await?.body(),
+        SubscriberEvent::Subscribed { .. }
+    ));
+
+    let receipt = BrokerClient::new(broker_route)
+        .publish(ctx, "news", "Hello Ockam!")
+        .await?;
+    assert_eq!(receipt.delivered, 1);
+    assert_eq!(receive_deliveries(ctx, 1).await?, ["Hello Ockam!"]);
+
+    ctx.stop().await
+}
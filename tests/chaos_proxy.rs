@@ -0,0 +1,171 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{
+    loopback, send_hello, start_secure_responder, start_tcp_responder, ECHO_REPLY, NO_REPLY_TIMEOUT,
+};
+use hello_ockam::{ChaosProxy, ChaosSettings, Forwarder, RouteResolver, RouteSpec};
+use ockam::{
+    route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+use std::time::{Duration, Instant};
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[ockam::test]
+async fn delays_and_throttles_both_ways(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_tcp_responder(ctx, 4951).await?;
+    let proxy = ChaosProxy::start("127.0.0.1:0", &loopback(4951)).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection = tcp_transport
+        .connect(
+            proxy.listen_address().to_string(),
+            TcpConnectionOptions::new(),
+        )
+        .await?;
+    let route = route![connection, "echoer"];
+    assert_eq!(
+        send_hello(ctx, route.clone(), REPLY_TIMEOUT).await?,
+        ECHO_REPLY
+    );
+
+    let delay = Duration::from_millis(300);
+    proxy.set_settings(ChaosSettings::normal().with_delay(delay));
+    let started = Instant::now();
+    assert_eq!(
+        send_hello(ctx, route.clone(), REPLY_TIMEOUT).await?,
+        ECHO_REPLY
+    );
+    assert!(started.elapsed() >= delay * 2, "{:?}", started.elapsed());
+
+    // The message & its reply are less than 200 bytes each.
+    proxy.set_settings(ChaosSettings::normal().with_bandwidth(200));
+    let started = Instant::now();
+    assert_eq!(send_hello(ctx, route, REPLY_TIMEOUT).await?, ECHO_REPLY);
+    assert!(
+        started.elapsed() >= Duration::from_millis(300),
+        "{:?}",
+        started.elapsed()
+    );
+
+    let stats = proxy.stats();
+    assert_eq!((stats.accepted, stats.active), (1, 1));
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn blackholes_until_healed(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_tcp_responder(ctx, 4952).await?;
+    let proxy = ChaosProxy::start("127.0.0.1:0", &loopback(4952)).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection = tcp_transport
+        .connect(
+            proxy.listen_address().to_string(),
+            TcpConnectionOptions::new(),
+        )
+        .await?;
+    let route = route![connection, "echoer"];
+
+    proxy.blackhole();
+    let forwarded_bytes = proxy.stats().forwarded_bytes;
+    assert!(send_hello(ctx, route.clone(), NO_REPLY_TIMEOUT)
+        .await
+        .is_err());
+    assert_eq!(proxy.stats().forwarded_bytes, forwarded_bytes);
+
+    // The connection stayed open the whole time, so it works again right away, & the
+    // message that was held back goes through too.
+    proxy.heal();
+    assert_eq!(send_hello(ctx, route, REPLY_TIMEOUT).await?, ECHO_REPLY);
+    let stats = proxy.stats();
+    assert!(stats.forwarded_bytes > forwarded_bytes);
+    assert_eq!(stats.reset, 0);
+
+    ctx.stop().await
+}
+
+/// Like examples/15-chaos.rs: the proxy is between the middle node and the responder.
+#[ockam::test]
+async fn resets_break_the_forwarder_but_not_new_connections(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_secure_responder(ctx, 4953, true).await?;
+    let proxy = ChaosProxy::start("127.0.0.1:0", &loopback(4953)).await?;
+
+    let node_middle = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_middle.create_tcp_transport().await?;
+    let connection_to_proxy = tcp_transport
+        .connect(
+            proxy.listen_address().to_string(),
+            TcpConnectionOptions::new(),
+        )
+        .await?;
+    node_middle
+        .start_worker(
+            "forward_to_bob",
+            Forwarder {
+                address: connection_to_proxy.into(),
+            },
+        )
+        .await?;
+    let listener = tcp_transport
+        .listen(loopback(3953), TcpListenerOptions::new())
+        .await?;
+    node_middle
+        .flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let initiator_tcp_transport = node_initiator.create_tcp_transport().await?;
+    let resolver =
+        RouteResolver::new(&node_initiator, &initiator_tcp_transport).with_identity(&id_alice);
+    let spec: RouteSpec =
+        "secure(tcp:127.0.0.1:3953 / forward_to_bob / bob_listener) / echoer".parse()?;
+    let resolved = resolver.resolve(&spec).await?;
+    assert_eq!(
+        send_hello(ctx, resolved.route.clone(), REPLY_TIMEOUT).await?,
+        ECHO_REPLY
+    );
+
+    proxy.reset_connections();
+    assert!(send_hello(ctx, resolved.route, NO_REPLY_TIMEOUT)
+        .await
+        .is_err());
+    let stats = proxy.stats();
+    assert_eq!((stats.reset, stats.active), (1, 0));
+
+    // The proxy still accepts new connections.
+    let spec: RouteSpec = format!(
+        "secure(tcp:{} / bob_listener) / echoer",
+        proxy.listen_address()
+    )
+    .parse()?;
+    let resolved = resolver.resolve(&spec).await?;
+    assert_eq!(
+        send_hello(ctx, resolved.route, REPLY_TIMEOUT).await?,
+        ECHO_REPLY
+    );
+
+    ctx.stop().await
+}
@@ -0,0 +1,140 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! Helpers shared by the integration tests. Each test binds its own loopback ports so that
+//! the tests can run in parallel.
+
+#![allow(dead_code)]
+
+use hello_ockam::{DeadLetter, DeadLetterClient, Echoer, Forwarder};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    AsyncTryClone, Context, MessageSendReceiveOptions, Node, Result, Route, TcpConnectionOptions,
+    TcpListenerOptions, TcpTransportExtension,
+};
+use std::time::Duration;
+
+pub const HELLO: &str = "Hello Ockam!";
+pub const ECHO_REPLY: &str = "👈 echo back: Hello Ockam!";
+
+/// How long to wait for a reply that is expected to never arrive.
+pub const NO_REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn loopback(port: u16) -> String {
+    format!("127.0.0.1:{}", port)
+}
+
+/// Send [HELLO] over `route` and return the reply, or an error if none arrives within
+/// `timeout`.
+pub async fn send_hello(
+    ctx: &Context,
+    route: impl Into<Route>,
+    timeout: Duration,
+) -> Result<String> {
+    let reply = ctx
+        .send_and_receive_extended::<String>(
+            route,
+            HELLO.to_string(),
+            MessageSendReceiveOptions::new().with_timeout(timeout),
+        )
+        .await?;
+    Ok(reply.body())
+}
+
+/// Start an echoer that is reachable via TCP on `port`, like `node_responder` in
+/// examples/04-routing-over-transport.rs.
+pub async fn start_tcp_responder(ctx: &Context, port: u16) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    node.start_worker("echoer", Echoer).await?;
+    let listener = tcp_transport
+        .listen(loopback(port), TcpListenerOptions::new())
+        .await?;
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    Ok(node)
+}
+
+/// Start a forwarder that listens on `listen_port` and forwards to `responder_port`, like
+/// `node_middle` in examples/04-routing-over-two-transport-hops.rs.
+pub async fn start_tcp_middle(
+    ctx: &Context,
+    forwarder_address: &str,
+    listen_port: u16,
+    responder_port: u16,
+) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    let connection_to_responder = tcp_transport
+        .connect(loopback(responder_port), TcpConnectionOptions::new())
+        .await?;
+    node.start_worker(
+        forwarder_address,
+        Forwarder {
+            address: connection_to_responder.into(),
+        },
+    )
+    .await?;
+    let listener = tcp_transport
+        .listen(loopback(listen_port), TcpListenerOptions::new())
+        .await?;
+    node.flow_controls()
+        .add_consumer(forwarder_address, listener.flow_control_id());
+    Ok(node)
+}
+
+/// Start `bob` behind a TCP listener on `port`, like `node_responder` in
+/// examples/05-secure-channel-over-two-transport-hops-responder.rs. When
+/// `allow_echoer` is false the echoer isn't a consumer of the secure channel listener.
+pub async fn start_secure_responder(ctx: &Context, port: u16, allow_echoer: bool) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    node.start_worker("echoer", Echoer).await?;
+    let id_bob = node.create_identity().await?;
+    let listener = tcp_transport
+        .listen(loopback(port), TcpListenerOptions::new())
+        .await?;
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await?;
+    if allow_echoer {
+        node.flow_controls()
+            .add_consumer("echoer", secure_channel_listener.flow_control_id());
+    }
+    Ok(node)
+}
+
+/// The dead letter from the worker (or link) at `failed_at`, from the queue that `client`
+/// talks to. They're sent to the queue after the message failed, so poll for it.
+pub async fn wait_for_dead_letter(
+    ctx: &Context,
+    client: &DeadLetterClient,
+    failed_at: &str,
+) -> Result<DeadLetter> {
+    for _ in 0..50 {
+        let letters = client.list(ctx).await?;
+        if let Some(letter) = letters.into_iter().find(|it| it.failed_at == failed_at) {
+            return Ok(letter);
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("no dead letter from '{}'", failed_at);
+}
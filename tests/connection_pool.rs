@@ -0,0 +1,249 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{
+    loopback, start_secure_responder, start_tcp_middle, start_tcp_responder, ECHO_REPLY, HELLO,
+    NO_REPLY_TIMEOUT,
+};
+use hello_ockam::{ChaosProxy, ConnectionPool, RouteSpec};
+use ockam::errcode::Kind;
+use ockam::{AsyncTryClone, Context, Result, TcpTransportExtension};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fails to compile unless `future` can be moved between threads, eg: to a spawned task.
+fn assert_send<F: Future + Send>(future: F) -> F {
+    future
+}
+
+/// examples/16-connection-pool.rs
+#[ockam::test]
+async fn reuses_connections_and_channels_per_identity(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_secure_responder(ctx, 4961, true).await?;
+    let _node_middle = start_tcp_middle(ctx, "forward_to_bob", 3961, 4961).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let id_carol = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let pool = ConnectionPool::new(&node_initiator, &tcp_transport);
+    let spec: RouteSpec =
+        "secure(tcp:127.0.0.1:3961 / forward_to_bob / bob_listener) / echoer".parse()?;
+
+    for _ in 0..5 {
+        let reply: String = pool
+            .send_and_receive(
+                ctx,
+                &spec,
+                Some(&id_alice),
+                HELLO.to_string(),
+                REPLY_TIMEOUT,
+            )
+            .await?;
+        assert_eq!(reply, ECHO_REPLY);
+    }
+    let stats = pool.stats().await;
+    assert_eq!(stats.tcp_connections_created, 1);
+    assert_eq!(stats.secure_channels_created, 1);
+    assert_eq!(stats.secure_channels_reused, 4);
+
+    // Another identity gets its own channel, over the same connection.
+    let reply: String = pool
+        .send_and_receive(
+            ctx,
+            &spec,
+            Some(&id_carol),
+            HELLO.to_string(),
+            REPLY_TIMEOUT,
+        )
+        .await?;
+    assert_eq!(reply, ECHO_REPLY);
+    let stats = pool.stats().await;
+    assert_eq!(stats.tcp_connections_created, 1);
+    assert_eq!(stats.tcp_connections_reused, 1);
+    assert_eq!(stats.secure_channels_created, 2);
+    assert_eq!(stats.retried, 0);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn closes_idle_connections_and_channels(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_secure_responder(ctx, 4962, true).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let pool = ConnectionPool::new(&node_initiator, &tcp_transport)
+        .with_idle_timeout(Duration::from_millis(300));
+    let spec: RouteSpec = "secure(tcp:127.0.0.1:4962 / bob_listener) / echoer".parse()?;
+
+    for _ in 0..2 {
+        let reply: String = pool
+            .send_and_receive(
+                ctx,
+                &spec,
+                Some(&id_alice),
+                HELLO.to_string(),
+                REPLY_TIMEOUT,
+            )
+            .await?;
+        assert_eq!(reply, ECHO_REPLY);
+        tokio::time::sleep(Duration::from_millis(600)).await;
+    }
+    pool.close_idle().await;
+
+    let stats = pool.stats().await;
+    assert_eq!(stats.tcp_connections_created, 2);
+    assert_eq!(stats.secure_channels_created, 2);
+    assert_eq!(stats.closed_idle, 4);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn reestablishes_broken_connections_and_channels(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_secure_responder(ctx, 4963, true).await?;
+    let proxy = ChaosProxy::start("127.0.0.1:0", "127.0.0.1:4963").await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let pool = ConnectionPool::new(&node_initiator, &tcp_transport)
+        .with_health_check_interval(Duration::ZERO);
+    let spec: RouteSpec = format!(
+        "secure(tcp:{} / bob_listener) / echoer",
+        proxy.listen_address()
+    )
+    .parse()?;
+
+    let reply: String = pool
+        .send_and_receive(
+            ctx,
+            &spec,
+            Some(&id_alice),
+            HELLO.to_string(),
+            REPLY_TIMEOUT,
+        )
+        .await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    proxy.reset_connections();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let reply: String = pool
+        .send_and_receive(
+            ctx,
+            &spec,
+            Some(&id_alice),
+            HELLO.to_string(),
+            REPLY_TIMEOUT,
+        )
+        .await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    let stats = pool.stats().await;
+    assert_eq!(stats.tcp_connections_created, 2);
+    assert_eq!(stats.secure_channels_created, 2);
+    assert!(stats.evicted_unhealthy >= 1, "{:?}", stats);
+    assert_eq!(proxy.stats().accepted, 2);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn retries_only_the_requests_that_werent_sent(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_tcp_responder(ctx, 4964).await?;
+    let proxy = ChaosProxy::start("127.0.0.1:0", &loopback(4964)).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    // The connection isn't checked before it's reused.
+    let pool = ConnectionPool::new(&node_initiator, &tcp_transport);
+    let spec: RouteSpec = format!("tcp:{} / echoer", proxy.listen_address()).parse()?;
+
+    let reply: String =
+        assert_send(pool.send_and_receive(ctx, &spec, None, HELLO.to_string(), REPLY_TIMEOUT))
+            .await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    // The pooled connection is gone by the time it's used, so nothing was sent over it.
+    proxy.reset_connections();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let reply: String = pool
+        .send_and_receive(ctx, &spec, None, HELLO.to_string(), REPLY_TIMEOUT)
+        .await?;
+    assert_eq!(reply, ECHO_REPLY);
+    assert_eq!(pool.stats().await.retried, 1);
+
+    // This one went out, but no reply came back, so it may have been handled.
+    proxy.blackhole();
+    let error = pool
+        .send_and_receive::<String, _>(ctx, &spec, None, HELLO.to_string(), NO_REPLY_TIMEOUT)
+        .await
+        .unwrap_err();
+    assert_eq!(error.code().kind, Kind::Timeout);
+    let stats = pool.stats().await;
+    assert_eq!(stats.retried, 1);
+    assert_eq!(stats.tcp_connections_created, 2);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn handshakes_dont_hold_up_the_other_routes(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_tcp_responder(ctx, 4965).await?;
+    // Nothing gets through, so the handshake never gets a reply.
+    let proxy = ChaosProxy::start("127.0.0.1:0", &loopback(4965)).await?;
+    proxy.blackhole();
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let pool = ConnectionPool::new(&node_initiator, &tcp_transport);
+    let stalled_spec: RouteSpec = format!(
+        "secure(tcp:{} / bob_listener) / echoer",
+        proxy.listen_address()
+    )
+    .parse()?;
+    let spec: RouteSpec = format!("tcp:{} / echoer", loopback(4965)).parse()?;
+
+    // The other route is resolved & used while the handshake is stuck.
+    let other = async {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let started = Instant::now();
+        let reply: Result<String> = pool
+            .send_and_receive(ctx, &spec, None, HELLO.to_string(), REPLY_TIMEOUT)
+            .await;
+        (reply, started.elapsed())
+    };
+    tokio::select! {
+        _ = pool.resolve(&stalled_spec, Some(&id_alice)) => panic!("the handshake went through"),
+        (reply, elapsed) = other => {
+            assert_eq!(reply?, ECHO_REPLY);
+            assert!(elapsed < Duration::from_secs(2), "took {:?}", elapsed);
+        }
+    }
+    let stats = pool.stats().await;
+    assert_eq!(stats.tcp_connections_created, 2);
+    assert_eq!(stats.secure_channels_created, 0);
+
+    ctx.stop().await
+}
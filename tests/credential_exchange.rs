@@ -0,0 +1,229 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{loopback, send_hello, ECHO_REPLY, NO_REPLY_TIMEOUT};
+use hello_ockam::Echoer;
+use ockam::abac::AbacAccessControl;
+use ockam::access_control::{AllowAll, IdentityIdAccessControl};
+use ockam::identity::{
+    AuthorityService, Credential, CredentialsIssuer, CredentialsIssuerClient, Identity,
+    IdentityIdentifier, SecureChannelListenerOptions, SecureChannelOptions, TrustContext,
+};
+use ockam::{
+    route, AsyncTryClone, Context, Node, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+use std::time::Duration;
+
+// The identities below are the same ones used in examples/06-credential-exchange.rs.
+const ISSUER_IDENTITY: &str = "0180370b91c5d0aa4af34580a9ab4b8fb2a28351bed061525c96b4f07e75c0ee18000547c93239ba3d818ec26c9cdadd2a35cbdf1fa3b6d1a731e06164b1079fb7b8084f434b414d5f524b03012000000020236f79490d3f683e0c3bf458a7381c366c99a8f2b2ac406db1ef8c130111f12703010140b23fddceb11cea25602aa681b6ef6abda036722c27a6dee291f1d6b2234a127af21cc79de2252201f27e7e34e0bf5064adbf3d01eb355aff4bf5c90b8f1fd80a";
+const ISSUER_SECRET: &str = "9278735d525efceef16bfd9143d3534759f3d388e460e6002134b9541e06489f";
+const SERVER_CHANGE_HISTORY: &str = "01ed8a5b1303f975c1296c990d1bd3c1946cfef328de20531e3511ec5604ce0dd9000547c93239ba3d818ec26c9cdadd2a35cbdf1fa3b6d1a731e06164b1079fb7b8084f434b414d5f524b03012000000020e8c328bc0cc07a374762091d037e69c36fdd4d2e1a651abd4d43a1362d3f800503010140a349968063d7337d0c965969fa9c640824c01a6d37fe130d4ab963b0271b9d5bbf0923faa5e27f15359554f94f08676df01b99d997944e4feaf0caaa1189480e";
+const SERVER_SECRET: &str = "5b2b3f2abbd1787704d8f8b363529f8e2d8f423b6dd4b96a2c462e4f0e04ee18";
+const CLIENT_CHANGE_HISTORY: &str = "01dcf392551f796ef1bcb368177e53f9a5875a962f67279259207d24a01e690721000547c93239ba3d818ec26c9cdadd2a35cbdf1fa3b6d1a731e06164b1079fb7b8084f434b414d5f524b03012000000020a0d205f09cab9a9467591fcee560429aab1215d8136e5c985a6b7dc729e6f08203010140b098463a727454c0e5292390d8f4cbd4dd0cae5db95606832f3d0a138936487e1da1489c40d8a0995fce71cc1948c6bcfd67186467cdd78eab7e95c080141505";
+const CLIENT_SECRET: &str = "41b6873b20d95567bf958e6bab2808e9157720040882630b1bb37a72f4015cd2";
+const KNOWN_IDENTIFIERS: [&str; 2] = [
+    "Pe92f183eb4c324804ef4d62962dea94cf095a265d4d28500c34e1a4e0d5ef638",
+    "Pada09e0f96e56580f6a0cb54f55ecbde6c973db6732e30dfb39b178760aed041",
+];
+
+async fn start_issuer(ctx: &Context, port: u16) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let issuer = node
+        .import_private_identity(ISSUER_IDENTITY, ISSUER_SECRET)
+        .await?;
+
+    let mut known_identifiers: Vec<IdentityIdentifier> = vec![];
+    for identifier in KNOWN_IDENTIFIERS {
+        known_identifiers.push(identifier.try_into()?);
+    }
+    let credential_issuer = CredentialsIssuer::new(
+        node.identities(),
+        issuer.identifier(),
+        "trust_context".into(),
+    )
+    .await?;
+    for identifier in known_identifiers.iter() {
+        node.identities()
+            .repository()
+            .put_attribute_value(identifier, "cluster", "production")
+            .await?;
+    }
+
+    let tcp_listener_options = TcpListenerOptions::new();
+    let sc_listener_options = SecureChannelListenerOptions::new()
+        .as_consumer(&tcp_listener_options.spawner_flow_control_id());
+    let sc_listener_flow_control_id = sc_listener_options.spawner_flow_control_id();
+    node.create_secure_channel_listener(&issuer.identifier(), "secure-issuer", sc_listener_options)
+        .await?;
+
+    let allow_known = IdentityIdAccessControl::new(known_identifiers);
+    node.flow_controls()
+        .add_consumer("issuer", &sc_listener_flow_control_id);
+    node.start_worker_with_access_control("issuer", credential_issuer, allow_known, AllowAll)
+        .await?;
+
+    let tcp_transport = node.create_tcp_transport().await?;
+    tcp_transport
+        .listen(loopback(port), tcp_listener_options)
+        .await?;
+    Ok(node)
+}
+
+/// Authenticate `identity` with the issuer listening on `issuer_port`, retrieve its
+/// credential, and build a trust context rooted at the issuer.
+async fn retrieve_credential(
+    node: &Node,
+    identity: &Identity,
+    issuer_port: u16,
+) -> Result<(Credential, TrustContext)> {
+    let tcp_transport = node.create_tcp_transport().await?;
+    let issuer_connection = tcp_transport
+        .connect(loopback(issuer_port), TcpConnectionOptions::new())
+        .await?;
+    let issuer_channel = node
+        .create_secure_channel(
+            &identity.identifier(),
+            route![issuer_connection, "secure-issuer"],
+            SecureChannelOptions::new(),
+        )
+        .await?;
+    let issuer_client =
+        CredentialsIssuerClient::new(route![issuer_channel, "issuer"], node.context()).await?;
+    let credential = issuer_client.credential().await?;
+
+    let issuer = node.import_identity_hex(ISSUER_IDENTITY).await?;
+    node.credentials()
+        .verify_credential(
+            &identity.identifier(),
+            std::slice::from_ref(&issuer),
+            credential.clone(),
+        )
+        .await?;
+    Ok((credential, issuer_trust_context(node).await?))
+}
+
+/// A trust context rooted at the issuer, to verify the credentials that peers present. The
+/// issuer's identity is stored, so that nodes that never talked to it can use it too.
+async fn issuer_trust_context(node: &Node) -> Result<TrustContext> {
+    let issuer = node.import_identity_hex(ISSUER_IDENTITY).await?;
+    node.identities()
+        .repository()
+        .update_identity(&issuer)
+        .await?;
+    Ok(TrustContext::new(
+        "trust_context_id".to_string(),
+        Some(AuthorityService::new(
+            node.identities().identities_reader(),
+            node.credentials(),
+            issuer.identifier(),
+            None,
+        )),
+    ))
+}
+
+async fn start_server(ctx: &Context, port: u16, issuer_port: u16) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let server = node
+        .import_private_identity(SERVER_CHANGE_HISTORY, SERVER_SECRET)
+        .await?;
+    let (credential, trust_context) = retrieve_credential(&node, &server, issuer_port).await?;
+
+    let tcp_listener_options = TcpListenerOptions::new();
+    let sc_listener_options = SecureChannelListenerOptions::new()
+        .with_trust_context(trust_context)
+        .with_credential(credential)
+        .as_consumer(&tcp_listener_options.spawner_flow_control_id());
+    node.flow_controls()
+        .add_consumer("echoer", &sc_listener_options.spawner_flow_control_id());
+    let allow_production = AbacAccessControl::create(node.repository(), "cluster", "production");
+    node.start_worker_with_access_control("echoer", Echoer, allow_production, AllowAll)
+        .await?;
+    node.create_secure_channel_listener(&server.identifier(), "secure-server", sc_listener_options)
+        .await?;
+
+    let tcp_transport = node.create_tcp_transport().await?;
+    tcp_transport
+        .listen(loopback(port), tcp_listener_options)
+        .await?;
+    Ok(node)
+}
+
+/// examples/06-credential-exchange.rs
+#[ockam::test]
+async fn allowed_client_gets_a_reply_from_the_echoer(ctx: &mut Context) -> Result<()> {
+    let _node_issuer = start_issuer(ctx, 5301).await?;
+    let _node_server = start_server(ctx, 4301, 5301).await?;
+
+    let node_client = ockam::node(ctx.async_try_clone().await?);
+    let client = node_client
+        .import_private_identity(CLIENT_CHANGE_HISTORY, CLIENT_SECRET)
+        .await?;
+    let (credential, trust_context) = retrieve_credential(&node_client, &client, 5301).await?;
+
+    let tcp_transport = node_client.create_tcp_transport().await?;
+    let server_connection = tcp_transport
+        .connect(loopback(4301), TcpConnectionOptions::new())
+        .await?;
+    let channel = node_client
+        .create_secure_channel(
+            &client.identifier(),
+            route![server_connection, "secure-server"],
+            SecureChannelOptions::new()
+                .with_trust_context(trust_context)
+                .with_credential(credential),
+        )
+        .await?;
+
+    let reply = send_hello(ctx, route![channel, "echoer"], Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+/// A client whose identifier isn't known to the issuer has no credential, so it can open
+/// a secure channel to the server, but the echoer's ABAC policy denies its messages.
+#[ockam::test]
+async fn denied_client_gets_no_reply_from_the_echoer(ctx: &mut Context) -> Result<()> {
+    let _node_issuer = start_issuer(ctx, 5302).await?;
+    let _node_server = start_server(ctx, 4302, 5302).await?;
+
+    let node_client = ockam::node(ctx.async_try_clone().await?);
+    let client = node_client.create_identity().await?;
+
+    // The client can't get a credential, but it still has to verify the server's one,
+    // otherwise the handshake fails.
+    let tcp_transport = node_client.create_tcp_transport().await?;
+    let server_connection = tcp_transport
+        .connect(loopback(4302), TcpConnectionOptions::new())
+        .await?;
+    let channel = node_client
+        .create_secure_channel(
+            &client,
+            route![server_connection, "secure-server"],
+            SecureChannelOptions::new()
+                .with_trust_context(issuer_trust_context(&node_client).await?),
+        )
+        .await?;
+
+    let result = send_hello(ctx, route![channel, "echoer"], NO_REPLY_TIMEOUT).await;
+    assert!(result.is_err());
+
+    ctx.stop().await
+}
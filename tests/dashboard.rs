@@ -0,0 +1,78 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use hello_ockam::{Dashboard, NodeRole, Topology};
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+
+/// The text on each row of the test terminal.
+fn rows(terminal: &Terminal<TestBackend>) -> Vec<String> {
+    let buffer = terminal.backend().buffer();
+    buffer
+        .content
+        .chunks(buffer.area.width as usize)
+        .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect())
+        .collect()
+}
+
+#[test]
+fn draws_a_panel_for_each_node() {
+    let mut topology = Topology::new();
+    topology
+        .node("node_initiator", NodeRole::Initiator)
+        .worker("app");
+    topology
+        .node("node_responder", NodeRole::Responder)
+        .worker("echoer");
+
+    let dashboard = Dashboard::in_memory();
+    dashboard.show_topology(&topology);
+    dashboard.add_flow_control("node_responder", "listener → echoer");
+    dashboard.add_secure_channel("node_initiator", "'alice' → 'bob'");
+
+    let mut terminal = Terminal::new(TestBackend::new(80, 16)).unwrap();
+    dashboard.draw_to(&mut terminal).unwrap();
+    assert_eq!(
+        rows(&terminal),
+        [
+            "                                                                                ",
+            "┌ node_initiator (initiator) ──────────┐┌ node_responder (responder) ──────────┐",
+            "│worker      app                       ││worker      echoer                    │",
+            "│secure      'alice' → 'bob'           ││flow ctrl   listener → echoer         │",
+            "│received 0  sent 0  log lines 0       ││received 0  sent 0  log lines 0       │",
+            "└──────────────────────────────────────┘└──────────────────────────────────────┘",
+            "┌ Log ─────────────────────────────────┐┌ Log ─────────────────────────────────┐",
+            "│                                      ││                                      │",
+            "│                                      ││                                      │",
+            "│                                      ││                                      │",
+            "│                                      ││                                      │",
+            "│                                      ││                                      │",
+            "│                                      ││                                      │",
+            "│                                      ││                                      │",
+            "└──────────────────────────────────────┘└──────────────────────────────────────┘",
+            "q: quit, ↑/↓: scroll the logs, End: follow the logs                             ",
+        ]
+    );
+}
+
+#[test]
+fn a_disabled_dashboard_draws_nothing() {
+    let dashboard = Dashboard::disabled();
+    let mut terminal = Terminal::new(TestBackend::new(20, 4)).unwrap();
+    dashboard.draw_to(&mut terminal).unwrap();
+    assert!(rows(&terminal).iter().all(|row| row.trim().is_empty()));
+}
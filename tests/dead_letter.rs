@@ -0,0 +1,83 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{wait_for_dead_letter, ECHO_REPLY, HELLO};
+use hello_ockam::{
+    DeadLetterClient, DeadLetterQueue, DeadLetterReason, Echoer, Forwarder, Hopper,
+    DEAD_LETTER_ADDRESS,
+};
+use ockam::errcode::Kind;
+use ockam::{route, Context, Result};
+
+#[ockam::test]
+async fn keeps_and_replays_messages_to_an_unknown_address(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker(DEAD_LETTER_ADDRESS, DeadLetterQueue::new())
+        .await?;
+    ctx.start_worker("h_unknown", Hopper).await?;
+    let client = DeadLetterClient::new(route![DEAD_LETTER_ADDRESS]);
+
+    ctx.send(route!["h_unknown", "late_echoer"], HELLO.to_string())
+        .await?;
+
+    let letter = wait_for_dead_letter(ctx, &client, "h_unknown").await?;
+    assert_eq!(letter.reason, DeadLetterReason::UnknownAddress);
+    assert_eq!(letter.onward_route, route!["late_echoer"]);
+
+    // Once the next hop exists, the replayed message (& the reply to it) go through.
+    ctx.start_worker("late_echoer", Echoer).await?;
+    client.replay(ctx, letter.id, None).await?;
+    assert_eq!(ctx.receive::<String>().await?.body(), ECHO_REPLY);
+
+    // A dead letter is only replayed once.
+    let error = client.replay(ctx, letter.id, None).await.unwrap_err();
+    assert_eq!(error.code().kind, Kind::NotFound);
+    let letters = client.list(ctx).await?;
+    assert!(letters.iter().all(|it| it.id != letter.id));
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn replays_to_another_route_when_the_connection_is_gone(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker(DEAD_LETTER_ADDRESS, DeadLetterQueue::new())
+        .await?;
+    ctx.start_worker("echoer", Echoer).await?;
+    ctx.start_worker(
+        "forward_to_gone",
+        Forwarder {
+            address: "gone".into(),
+        },
+    )
+    .await?;
+    let client = DeadLetterClient::new(route![DEAD_LETTER_ADDRESS]);
+
+    ctx.send(route!["forward_to_gone", "echoer"], HELLO.to_string())
+        .await?;
+
+    let letter = wait_for_dead_letter(ctx, &client, "forward_to_gone").await?;
+    assert_eq!(letter.reason, DeadLetterReason::ConnectionClosed);
+    assert_eq!(letter.onward_route, route!["gone", "echoer"]);
+
+    client
+        .replay(ctx, letter.id, Some(route!["echoer"]))
+        .await?;
+    assert_eq!(ctx.receive::<String>().await?.body(), ECHO_REPLY);
+
+    ctx.stop().await
+}
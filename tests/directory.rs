@@ -0,0 +1,140 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{send_hello, ECHO_REPLY};
+use hello_ockam::{
+    DirectoryClient, DirectoryRequest, DirectoryResponse, Echoer, RouteResolver, RouteSpec,
+    ServiceDirectory, ServiceHealth, MAX_SERVICE_TTL,
+};
+use ockam::errcode::Kind;
+use ockam::{route, AsyncTryClone, Context, Result, TcpTransportExtension};
+use std::time::Duration;
+
+/// Any peer can send a TTL, so a huge one mustn't crash the directory.
+#[ockam::test]
+async fn caps_huge_ttls(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("directory", ServiceDirectory::new())
+        .await?;
+    let request = DirectoryRequest::Register {
+        name: "forever-service".to_string(),
+        route: "echoer".to_string(),
+        ttl_ms: u64::MAX,
+        health: ServiceHealth::Healthy,
+    };
+    let response: DirectoryResponse = ctx.send_and_receive(route!["directory"], request).await?;
+    let DirectoryResponse::Registered(record) = response else {
+        panic!("unexpected response: {:?}", response);
+    };
+    assert!(record.expires_in_ms > 0);
+    assert!(record.expires_in_ms <= MAX_SERVICE_TTL.as_millis() as u64);
+
+    // The client doesn't wrap a huge TTL around into a short one.
+    let directory = DirectoryClient::new(route!["directory"]);
+    let record = directory
+        .register(
+            ctx,
+            "forever-service",
+            &"echoer".parse()?,
+            Duration::MAX,
+            ServiceHealth::Healthy,
+        )
+        .await?;
+    assert!(record.expires_in_ms > MAX_SERVICE_TTL.as_millis() as u64 - 60_000);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn resolves_a_registered_service_by_name(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("directory", ServiceDirectory::new())
+        .await?;
+    ctx.start_worker("echoer", Echoer).await?;
+    let directory = DirectoryClient::new(route!["directory"]);
+
+    let route: RouteSpec = "echoer".parse()?;
+    directory
+        .register(
+            ctx,
+            "echo-service",
+            &route,
+            Duration::from_secs(60),
+            ServiceHealth::Healthy,
+        )
+        .await?;
+
+    let record = directory.resolve(ctx, "echo-service").await?;
+    assert_eq!(record.route, "echoer");
+    assert_eq!(record.health, ServiceHealth::Healthy);
+    assert!(record.expires_in_ms > 0);
+
+    // Send to the service w/out knowing its address.
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    let resolved = RouteResolver::new(&node, &tcp_transport)
+        .resolve(&record.route_spec()?)
+        .await?;
+    let reply = send_hello(ctx, resolved.route, Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn registrations_expire_and_can_be_removed(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("directory", ServiceDirectory::new())
+        .await?;
+    let directory = DirectoryClient::new(route!["directory"]);
+
+    let short_lived: RouteSpec = "tcp:127.0.0.1:4000 / echoer".parse()?;
+    directory
+        .register(
+            ctx,
+            "short-lived",
+            &short_lived,
+            Duration::from_millis(50),
+            ServiceHealth::Healthy,
+        )
+        .await?;
+    let long_lived: RouteSpec = "h1 / echoer".parse()?;
+    directory
+        .register(
+            ctx,
+            "long-lived",
+            &long_lived,
+            Duration::from_secs(60),
+            ServiceHealth::Unhealthy,
+        )
+        .await?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let error = directory.resolve(ctx, "short-lived").await.unwrap_err();
+    assert_eq!(error.code().kind, Kind::NotFound);
+
+    let records = directory.list(ctx).await?;
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].name, "long-lived");
+    assert_eq!(records[0].route, "h1 / echoer");
+    assert_eq!(records[0].health, ServiceHealth::Unhealthy);
+
+    assert!(directory.deregister(ctx, "long-lived").await?);
+    assert!(!directory.deregister(ctx, "long-lived").await?);
+    assert!(directory.list(ctx).await?.is_empty());
+
+    ctx.stop().await
+}
@@ -0,0 +1,164 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use hello_ockam::{NodeError, NodeRole, NodeScope, ResultExt};
+use ockam::errcode::{Kind, Origin};
+use ockam::{Context, Result, TcpConnectionOptions, TcpListenerOptions, TcpTransport};
+
+fn scope() -> NodeScope {
+    NodeScope::new(NodeRole::Initiator, "node_initiator")
+}
+
+fn node_error(origin: Origin, kind: Kind, message: &str, step: &str) -> NodeError {
+    let result: ockam::Result<()> = Err(ockam::Error::new(origin, kind, message.to_string()));
+    result.during(&scope(), step).unwrap_err()
+}
+
+#[ockam::test]
+async fn address_in_use_suggests_stopping_the_other_process(ctx: &mut Context) -> Result<()> {
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = taken.local_addr().unwrap().to_string();
+    let tcp_transport = TcpTransport::create(ctx).await?;
+
+    let error = tcp_transport
+        .listen(&address, TcpListenerOptions::new())
+        .await
+        .during(&scope(), format!("listen on {}", address))
+        .unwrap_err();
+    let hints = error.suggestions();
+    assert_eq!(hints.len(), 1, "{}", error);
+    assert!(hints[0].starts_with("The port is already in use."));
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn connection_refused_suggests_starting_the_listener_first(ctx: &mut Context) -> Result<()> {
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = closed.local_addr().unwrap().to_string();
+    drop(closed);
+    let tcp_transport = TcpTransport::create(ctx).await?;
+
+    let error = tcp_transport
+        .connect(&address, TcpConnectionOptions::new())
+        .await
+        .during(&scope(), format!("connect to {}", address))
+        .unwrap_err();
+    let hints = error.suggestions();
+    assert_eq!(hints.len(), 1, "{}", error);
+    assert!(hints[0].starts_with("Nothing is listening on that port."));
+
+    ctx.stop().await
+}
+
+#[test]
+fn timeout_kind_suggests_flow_controls_and_route_addresses() {
+    let error = node_error(
+        Origin::Node,
+        Kind::Timeout,
+        "no reply",
+        "send a message to echoer",
+    );
+    let hints = error.suggestions();
+    assert_eq!(hints.len(), 2);
+    assert!(hints[0].starts_with("No reply arrived."));
+    assert!(hints[1].starts_with("Check that every address in the route"));
+}
+
+#[test]
+fn timeout_in_the_message_is_treated_like_the_timeout_kind() {
+    let error = node_error(
+        Origin::Application,
+        Kind::Other,
+        "receive timeout",
+        "send a message to echoer",
+    );
+    assert!(error.suggestions()[0].starts_with("No reply arrived."));
+}
+
+#[test]
+fn credential_steps_suggest_checking_the_issuer() {
+    let error = node_error(
+        Origin::Identity,
+        Kind::Invalid,
+        "invalid signature",
+        "retrieve a credential",
+    );
+    let hints = error.suggestions();
+    assert_eq!(hints.len(), 1);
+    assert!(hints[0].starts_with("Check that the issuer identity"));
+}
+
+#[test]
+fn secure_channel_steps_suggest_flow_control_consumers() {
+    let error = node_error(
+        Origin::Channel,
+        Kind::Invalid,
+        "handshake failed",
+        "create a secure channel",
+    );
+    let hints = error.suggestions();
+    assert_eq!(hints.len(), 1);
+    assert!(hints[0].starts_with("Check that the secure channel listener is a consumer"));
+}
+
+#[test]
+fn secure_channel_timeouts_only_suggest_the_timeout_fixes() {
+    let error = node_error(
+        Origin::Channel,
+        Kind::Timeout,
+        "handshake timed out",
+        "create a secure channel",
+    );
+    let hints = error.suggestions();
+    assert_eq!(hints.len(), 2);
+    assert!(hints.iter().all(|it| !it.contains("as_consumer")));
+}
+
+#[test]
+fn other_errors_have_no_suggestions() {
+    let error = node_error(
+        Origin::Application,
+        Kind::Internal,
+        "something else",
+        "start worker echoer",
+    );
+    assert!(error.suggestions().is_empty());
+}
+
+#[test]
+fn display_names_the_node_step_cause_and_hints() {
+    colored::control::set_override(false);
+    let error = node_error(
+        Origin::Transport,
+        Kind::Misuse,
+        "connection peer was not found",
+        "connect to 127.0.0.1:4000",
+    );
+    let output = error.to_string();
+    assert!(output.starts_with("❌ node_initiator (initiator) failed to connect to 127.0.0.1:4000"));
+    assert!(output.contains("cause: "));
+    assert!(output.contains("hint: Nothing is listening on that port."));
+}
+
+#[test]
+fn converting_to_an_ockam_error_keeps_the_kind() {
+    let error = node_error(Origin::Node, Kind::Timeout, "no reply", "send");
+    let error: ockam::Error = error.into();
+    assert_eq!(error.code().kind, Kind::Timeout);
+    assert_eq!(error.code().origin, Origin::Application);
+}
@@ -0,0 +1,84 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{send_hello, ECHO_REPLY};
+use hello_ockam::{Echoer, Event, EventLog, Hopper, NodeRole};
+use ockam::{route, Context, Result};
+use serde_json::Value;
+use std::time::Duration;
+
+/// The keys (& their order) that CI relies on.
+#[test]
+fn json_line_has_the_stable_schema() {
+    let line = Event::NodeStarted {
+        role: NodeRole::Responder,
+    }
+    .to_json_line("server", 1690000000000);
+    assert_eq!(
+        line,
+        r#"{"data":{"role":"responder"},"kind":"node_started","schema":1,"source":"server","timestamp_ms":1690000000000}"#
+    );
+}
+
+/// examples/03-routing-many-hops.rs w/ an event log.
+#[ockam::test]
+async fn workers_emit_events_to_the_global_event_log(ctx: &mut Context) -> Result<()> {
+    let path =
+        std::env::temp_dir().join(format!("hello_ockam_events_{}.jsonl", std::process::id()));
+    std::fs::remove_file(&path).ok();
+    EventLog::init_global(EventLog::open(&path).unwrap()).unwrap();
+    assert_eq!(EventLog::global().path(), Some(path.as_path()));
+
+    ctx.start_worker("echoer", Echoer).await?;
+    ctx.start_worker("h1", Hopper).await?;
+    ctx.start_worker("h2", Hopper).await?;
+    let reply = send_hello(ctx, route!["h1", "h2", "echoer"], Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    let events: Vec<Value> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|it| serde_json::from_str(it).unwrap())
+        .collect();
+    let kinds: Vec<(&str, &str)> = events
+        .iter()
+        .map(|it| (it["source"].as_str().unwrap(), it["kind"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("h1", "message_forwarded"),
+            ("h2", "message_forwarded"),
+            ("echoer", "message_received"),
+            ("echoer", "message_sent"),
+            ("h2", "message_forwarded"),
+            ("h1", "message_forwarded"),
+        ]
+    );
+    assert_eq!(events[0]["data"]["to"], "h2");
+    assert_eq!(events[1]["data"]["from"], "h1");
+    assert_eq!(events[2]["data"]["from"], "h2");
+    assert_eq!(events[3]["data"]["payload"], ECHO_REPLY);
+    assert_eq!(events[4]["data"]["from"], "echoer");
+    assert_eq!(events[4]["data"]["to"], "h1");
+    assert_eq!(events[5]["data"]["from"], "h2");
+    std::fs::remove_file(&path).ok();
+
+    ctx.stop().await
+}
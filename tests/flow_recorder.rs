@@ -0,0 +1,76 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{send_hello, ECHO_REPLY, HELLO};
+use hello_ockam::{Echoer, FlowRecorder, Hopper};
+use ockam::{route, Context, Result};
+use std::time::Duration;
+
+/// examples/03-routing-many-hops.rs w/ the recorder enabled.
+#[ockam::test]
+async fn records_each_hop_and_renders_a_sequence_diagram(ctx: &mut Context) -> Result<()> {
+    let recorder = FlowRecorder::global();
+    recorder.enable();
+
+    ctx.start_worker("echoer", Echoer).await?;
+    ctx.start_worker("h1", Hopper).await?;
+    ctx.start_worker("h2", Hopper).await?;
+
+    let route = route!["h1", "h2", "echoer"];
+    let app_address = ctx.address();
+    recorder.record_send(&app_address, &route, HELLO);
+    let reply = send_hello(ctx, route.clone(), Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+    recorder.record_reply(&app_address, &route, &reply);
+
+    // The initiator records the send & the reply, and each worker records what it receives,
+    // on the way to the echoer and back. The workers see the message coming from
+    // send_and_receive's temporary address, which is random.
+    let events = recorder.events();
+    let hops: Vec<(&str, &str)> = events
+        .iter()
+        .map(|it| (it.sender.as_str(), it.receiver.as_str()))
+        .collect();
+    let app = app_address.address().to_string();
+    let temporary = events[1].sender.as_str();
+    assert_ne!(temporary, app);
+    assert_eq!(
+        hops,
+        vec![
+            (app.as_str(), "h1"),
+            (temporary, "h1"),
+            ("h1", "h2"),
+            ("h2", "echoer"),
+            ("echoer", "h2"),
+            ("h2", "h1"),
+            ("echoer", app.as_str()),
+        ]
+    );
+    assert_eq!(events[3].payload, HELLO);
+    assert_eq!(events[3].return_route.split(" → ").next(), Some("[h2"));
+    assert_eq!(events[5].payload, ECHO_REPLY);
+
+    let diagram = recorder.render_sequence_diagram();
+    assert!(diagram.lines().next().unwrap().contains("echoer"));
+    assert!(diagram.contains("├"));
+    assert!(diagram.contains("◄"));
+    assert_eq!(recorder.flow_steps().len(), 7);
+
+    ctx.stop().await
+}
@@ -0,0 +1,236 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{loopback, send_hello, wait_for_dead_letter, ECHO_REPLY, NO_REPLY_TIMEOUT};
+use hello_ockam::{
+    ChaosProxy, DeadLetterClient, DeadLetterQueue, DeadLetterReason, Echoer, Forwarder, Heartbeat,
+    HeartbeatMonitor, HeartbeatResponder, LinkEvent, LinkHealth, LinkStatus, DEAD_LETTER_ADDRESS,
+    HEARTBEAT_ADDRESS,
+};
+use ockam::{
+    route, AsyncTryClone, Context, Node, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const INTERVAL: Duration = Duration::from_millis(100);
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long to wait for a link to change its status.
+const LINK_EVENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Start an echoer & a heartbeat responder that are reachable via TCP on `port`.
+async fn start_heartbeat_responder(ctx: &Context, port: u16) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    node.start_worker("echoer", Echoer).await?;
+    node.start_worker(HEARTBEAT_ADDRESS, HeartbeatResponder)
+        .await?;
+    let listener = tcp_transport
+        .listen(loopback(port), TcpListenerOptions::new())
+        .await?;
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer(HEARTBEAT_ADDRESS, listener.flow_control_id());
+    Ok(node)
+}
+
+/// Wait for the next event of `link` (the other tests' links share [LinkHealth::global]).
+async fn next_link_event(events: &mut broadcast::Receiver<LinkEvent>, link: &str) -> LinkEvent {
+    let next = async {
+        loop {
+            if let Ok(event) = events.recv().await {
+                if event.link == link {
+                    return event;
+                }
+            }
+        }
+    };
+    tokio::time::timeout(LINK_EVENT_TIMEOUT, next)
+        .await
+        .unwrap_or_else(|_| panic!("no event for link '{}'", link))
+}
+
+#[ockam::test]
+async fn link_goes_down_when_blackholed_and_up_when_healed(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_heartbeat_responder(ctx, 5001).await?;
+    let proxy = ChaosProxy::start("127.0.0.1:0", &loopback(5001)).await?;
+
+    let node_monitor = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_monitor.create_tcp_transport().await?;
+    let connection = tcp_transport
+        .connect(
+            proxy.listen_address().to_string(),
+            TcpConnectionOptions::new(),
+        )
+        .await?;
+
+    let link = "monitor→responder (5001)";
+    let mut events = LinkHealth::global().subscribe();
+    let monitor = HeartbeatMonitor::new(link, route![connection, HEARTBEAT_ADDRESS])
+        .with_interval(INTERVAL)
+        .with_timeout(TIMEOUT)
+        .start(ctx)
+        .await?;
+
+    let event = next_link_event(&mut events, link).await;
+    assert_eq!(event.status, LinkStatus::Up);
+    assert_eq!(LinkHealth::global().status(link), Some(LinkStatus::Up));
+
+    proxy.blackhole();
+    let event = next_link_event(&mut events, link).await;
+    assert_eq!(event.status, LinkStatus::Down);
+    assert!(event.silent_for >= TIMEOUT, "{:?}", event.silent_for);
+    assert_eq!(LinkHealth::global().status(link), Some(LinkStatus::Down));
+
+    proxy.heal();
+    let event = next_link_event(&mut events, link).await;
+    assert_eq!(event.status, LinkStatus::Up);
+
+    ctx.stop_worker(monitor).await?;
+    tokio::time::sleep(INTERVAL).await;
+    assert_eq!(LinkHealth::global().status(link), None);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn ignores_the_acks_of_beats_it_didnt_send(ctx: &mut Context) -> Result<()> {
+    let link = "monitor→nowhere";
+    let mut events = LinkHealth::global().subscribe();
+    let monitor = HeartbeatMonitor::new(link, route!["nowhere", HEARTBEAT_ADDRESS])
+        .with_interval(INTERVAL)
+        .with_timeout(TIMEOUT)
+        .start(ctx)
+        .await?;
+
+    for sequence in [0, 99, u64::MAX] {
+        ctx.send(monitor.clone(), Heartbeat::Ack { sequence })
+            .await?;
+    }
+    let event = next_link_event(&mut events, link).await;
+    assert_eq!(event.status, LinkStatus::Down);
+
+    ctx.send(monitor.clone(), Heartbeat::Ack { sequence: 99 })
+        .await?;
+    tokio::time::sleep(TIMEOUT).await;
+    assert_eq!(LinkHealth::global().status(link), Some(LinkStatus::Down));
+
+    ctx.stop_worker(monitor).await?;
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn forwarder_keeps_messages_as_dead_letters_while_the_link_is_down(
+    ctx: &mut Context,
+) -> Result<()> {
+    let _node_responder = start_heartbeat_responder(ctx, 5002).await?;
+    let proxy = ChaosProxy::start("127.0.0.1:0", &loopback(5002)).await?;
+
+    // Like `node_middle` in examples/04-routing-over-two-transport-hops.rs, but w/ a
+    // heartbeat monitor on its connection to the responder.
+    let node_middle = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_middle.create_tcp_transport().await?;
+    let connection = tcp_transport
+        .connect(
+            proxy.listen_address().to_string(),
+            TcpConnectionOptions::new(),
+        )
+        .await?;
+    let connection_address = connection.sender_address().clone();
+    node_middle
+        .start_worker(
+            "forward_to_responder_5002",
+            Forwarder {
+                address: connection.into(),
+            },
+        )
+        .await?;
+    node_middle
+        .start_worker(DEAD_LETTER_ADDRESS, DeadLetterQueue::new())
+        .await?;
+    let listener = tcp_transport
+        .listen(loopback(3002), TcpListenerOptions::new())
+        .await?;
+    node_middle
+        .flow_controls()
+        .add_consumer("forward_to_responder_5002", listener.flow_control_id());
+    node_middle
+        .flow_controls()
+        .add_consumer(DEAD_LETTER_ADDRESS, listener.flow_control_id());
+
+    let link = "middle→responder (5002)";
+    let mut events = LinkHealth::global().subscribe();
+    HeartbeatMonitor::new(link, route![connection_address.clone(), HEARTBEAT_ADDRESS])
+        .with_interval(INTERVAL)
+        .with_timeout(TIMEOUT)
+        .start(ctx)
+        .await?;
+    assert_eq!(
+        next_link_event(&mut events, link).await.status,
+        LinkStatus::Up
+    );
+    assert_eq!(
+        LinkHealth::global().status_of_next_hop(&connection_address),
+        Some(LinkStatus::Up)
+    );
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection_to_middle = tcp_transport
+        .connect(loopback(3002), TcpConnectionOptions::new())
+        .await?;
+    let connection_to_middle = connection_to_middle.sender_address().clone();
+    let route = route![
+        connection_to_middle.clone(),
+        "forward_to_responder_5002",
+        "echoer"
+    ];
+    assert_eq!(
+        send_hello(ctx, route.clone(), LINK_EVENT_TIMEOUT).await?,
+        ECHO_REPLY
+    );
+
+    proxy.blackhole();
+    assert_eq!(
+        next_link_event(&mut events, link).await.status,
+        LinkStatus::Down
+    );
+    assert!(send_hello(ctx, route.clone(), NO_REPLY_TIMEOUT)
+        .await
+        .is_err());
+    // The middle node's dead letters can be listed from the initiator.
+    let client = DeadLetterClient::new(route![connection_to_middle, DEAD_LETTER_ADDRESS]);
+    let letter = wait_for_dead_letter(ctx, &client, "forward_to_responder_5002").await?;
+    assert_eq!(letter.reason, DeadLetterReason::LinkDown);
+
+    proxy.heal();
+    assert_eq!(
+        next_link_event(&mut events, link).await.status,
+        LinkStatus::Up
+    );
+    assert_eq!(
+        send_hello(ctx, route, LINK_EVENT_TIMEOUT).await?,
+        ECHO_REPLY
+    );
+
+    ctx.stop().await
+}
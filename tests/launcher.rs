@@ -0,0 +1,139 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+//! The roles are small `sh` scripts instead of the credential exchange example, so that
+//! each test can make a role become ready, exit, or hang when it needs to.
+
+#![cfg(unix)]
+
+use colored::Color;
+use hello_ockam::{ChildRole, Launcher};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Long running roles sleep for this long, the tests must finish well before.
+const SLEEP_FOREVER: &str = "sleep 60";
+
+fn script_role(name: &str, script: &str) -> ChildRole {
+    ChildRole::new(name, Color::BrightBlue, "sh")
+        .arg("-c")
+        .arg(script)
+}
+
+fn long_running_role(name: &str, script: &str) -> ChildRole {
+    script_role(name, script).ready_when_stdout_contains("ready")
+}
+
+/// Whether `pid` is gone (or a zombie that nobody has reaped yet).
+fn is_gone(pid: u32) -> bool {
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => stat
+            .rsplit(')')
+            .next()
+            .is_some_and(|it| it.trim_start().starts_with('Z')),
+        Err(_) => unsafe { libc::kill(pid as libc::pid_t, 0) != 0 },
+    }
+}
+
+#[tokio::test]
+async fn returns_the_last_roles_exit_code_and_stops_the_others() -> std::io::Result<()> {
+    let started = Instant::now();
+    let exit_code = Launcher::new()
+        .role(long_running_role(
+            "issuer",
+            &format!("echo ready; {}", SLEEP_FOREVER),
+        ))
+        .role(long_running_role(
+            "server",
+            &format!("echo ready; {}", SLEEP_FOREVER),
+        ))
+        .role(script_role("client", "echo done; exit 3"))
+        .with_stop_grace_period(Duration::from_secs(2))
+        .run()
+        .await?;
+
+    assert_eq!(exit_code, 3);
+    assert!(started.elapsed() < Duration::from_secs(10));
+    Ok(())
+}
+
+#[tokio::test]
+async fn stops_everything_when_a_long_running_role_dies_later() -> std::io::Result<()> {
+    let started = Instant::now();
+    let exit_code = Launcher::new()
+        .role(long_running_role("issuer", "echo ready; sleep 1; exit 7"))
+        .role(long_running_role(
+            "server",
+            &format!("echo ready; {}", SLEEP_FOREVER),
+        ))
+        .role(script_role("client", SLEEP_FOREVER))
+        .with_stop_grace_period(Duration::from_secs(2))
+        .run()
+        .await?;
+
+    assert_eq!(exit_code, 7);
+    assert!(started.elapsed() < Duration::from_secs(10));
+    Ok(())
+}
+
+#[tokio::test]
+async fn gives_up_on_a_role_that_never_becomes_ready() -> std::io::Result<()> {
+    let exit_code = Launcher::new()
+        .role(long_running_role("issuer", SLEEP_FOREVER))
+        .role(script_role("client", "exit 0"))
+        .with_ready_timeout(Duration::from_millis(500))
+        .with_stop_grace_period(Duration::from_secs(2))
+        .run()
+        .await?;
+
+    assert_eq!(exit_code, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn stops_the_processes_that_a_role_started() -> std::io::Result<()> {
+    let pid_file = std::env::temp_dir().join(format!(
+        "hello_ockam_launcher_test_{}.pid",
+        std::process::id()
+    ));
+    let script = format!(
+        "{} & echo $! > {}; echo ready; wait",
+        SLEEP_FOREVER,
+        pid_file.display()
+    );
+    let exit_code = Launcher::new()
+        .role(long_running_role("issuer", &script))
+        .role(script_role("client", "exit 0"))
+        .with_stop_grace_period(Duration::from_secs(2))
+        .run()
+        .await?;
+    assert_eq!(exit_code, 0);
+
+    let pid: u32 = std::fs::read_to_string(&pid_file)?.trim().parse().unwrap();
+    std::fs::remove_file(PathBuf::from(&pid_file))?;
+    // The signal reaches the grandchild asynchronously.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !is_gone(pid) && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(
+        is_gone(pid),
+        "the role's `sleep` ({}) is still running",
+        pid
+    );
+    Ok(())
+}
@@ -0,0 +1,79 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use hello_ockam::{Echoer, Hopper, LoadGenerator, LoadReport};
+use ockam::{route, Context, Result};
+use std::time::Duration;
+
+#[ockam::test]
+async fn sends_every_message_over_a_hop_chain(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("echoer", Echoer).await?;
+    ctx.start_worker("h1", Hopper).await?;
+    ctx.start_worker("h2", Hopper).await?;
+
+    let report = LoadGenerator::new(route!["h1", "h2", "echoer"])
+        .with_concurrency(4)
+        .with_message_size(100)
+        .with_messages(50)
+        .run(ctx)
+        .await?;
+    assert_eq!((report.sent, report.received(), report.errors), (50, 50, 0));
+    assert!(report.throughput() > 0.0);
+    let counted: u64 = report.histogram().iter().map(|it| it.1).sum();
+    assert_eq!(counted, 50);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn messages_w_out_a_reply_are_errors(ctx: &mut Context) -> Result<()> {
+    let report = LoadGenerator::new(route!["no_such_echoer"])
+        .with_concurrency(2)
+        .with_messages(4)
+        .with_timeout(Duration::from_millis(100))
+        .run(ctx)
+        .await?;
+    assert_eq!((report.received(), report.errors), (0, 4));
+    assert!(report.histogram().is_empty());
+
+    ctx.stop().await
+}
+
+#[test]
+fn histogram_buckets_double_in_width() {
+    let report = LoadReport {
+        concurrency: 1,
+        message_size: 0,
+        sent: 4,
+        errors: 0,
+        elapsed: Duration::from_secs(1),
+        latencies: [300, 400, 512, 1500]
+            .into_iter()
+            .map(Duration::from_micros)
+            .collect(),
+    };
+    assert_eq!(
+        report.histogram(),
+        vec![
+            (Duration::from_micros(512), 3),
+            (Duration::from_micros(1024), 0),
+            (Duration::from_micros(2048), 1),
+        ]
+    );
+    assert_eq!(report.percentile(50.0), Some(Duration::from_micros(400)));
+    assert_eq!(report.throughput(), 4.0);
+}
@@ -0,0 +1,67 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{send_hello, ECHO_REPLY, HELLO, NO_REPLY_TIMEOUT};
+use hello_ockam::{Echoer, Hopper};
+use ockam::{route, Context, Result};
+use std::time::Duration;
+
+/// examples/02-worker.rs
+#[ockam::test]
+async fn echoer_replies_on_the_return_route(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("echoer", Echoer).await?;
+
+    ctx.send("echoer", HELLO.to_string()).await?;
+    let reply = ctx.receive::<String>().await?;
+    assert_eq!(reply.body(), ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+/// examples/03-routing.rs & examples/03-routing-many-hops.rs
+#[ockam::test]
+async fn echoer_replies_over_a_chain_of_hoppers(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("echoer", Echoer).await?;
+    ctx.start_worker("hopper1", Hopper).await?;
+    ctx.start_worker("hopper2", Hopper).await?;
+    ctx.start_worker("hopper3", Hopper).await?;
+
+    let reply = send_hello(ctx, route!["hopper1", "echoer"], Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    let reply = send_hello(
+        ctx,
+        route!["hopper1", "hopper2", "hopper3", "echoer"],
+        Duration::from_secs(5),
+    )
+    .await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn no_reply_when_the_route_ends_at_an_unknown_address(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("hopper1", Hopper).await?;
+
+    let result = send_hello(ctx, route!["hopper1", "no_such_worker"], NO_REPLY_TIMEOUT).await;
+    assert!(result.is_err());
+
+    ctx.stop().await
+}
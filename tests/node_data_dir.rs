@@ -0,0 +1,175 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{loopback, send_hello, ECHO_REPLY};
+use hello_ockam::{Echoer, IdentityOrigin, NodeDataDir, NODE_DATA_DIR_ENV};
+use ockam::identity::{SecureChannelListenerOptions, SecureChannelOptions};
+use ockam::{
+    route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+use std::path::Path;
+use std::time::Duration;
+
+/// A data directory that no other test (or earlier run) uses.
+fn fresh_data_dir(name: &str) -> Result<NodeDataDir> {
+    let data_dir = NodeDataDir::new(std::env::temp_dir().join(format!(
+        "hello_ockam_test_{}_{}",
+        name,
+        std::process::id()
+    )));
+    data_dir.remove()?;
+    Ok(data_dir)
+}
+
+#[ockam::test]
+async fn identities_keep_their_identifiers_across_restarts(ctx: &mut Context) -> Result<()> {
+    let data_dir = fresh_data_dir("restarts")?;
+
+    let node = data_dir.create_node(ctx.async_try_clone().await?).await?;
+    let (id_bob, origin) = data_dir.identity(&node, "bob").await?;
+    assert_eq!(origin, IdentityOrigin::Created);
+    assert!(data_dir.identity_path("bob").exists());
+    assert!(data_dir.vault_path().exists());
+
+    // Like the next run of the node.
+    let node = data_dir.create_node(ctx.async_try_clone().await?).await?;
+    let (id_bob_again, origin) = data_dir.identity(&node, "bob").await?;
+    assert_eq!(origin, IdentityOrigin::Loaded);
+    assert_eq!(id_bob_again, id_bob);
+
+    let (id_carol, origin) = data_dir.identity(&node, "carol").await?;
+    assert_eq!(origin, IdentityOrigin::Created);
+    assert_ne!(id_carol, id_bob);
+
+    data_dir.remove()?;
+    assert!(!data_dir.path().exists());
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn refuses_identities_whose_secret_key_isnt_in_the_vault(ctx: &mut Context) -> Result<()> {
+    let data_dir = fresh_data_dir("lost_vault")?;
+    let node = data_dir.create_node(ctx.async_try_clone().await?).await?;
+    data_dir.identity(&node, "bob").await?;
+
+    std::fs::remove_file(data_dir.vault_path()).unwrap();
+    let node = data_dir.create_node(ctx.async_try_clone().await?).await?;
+    let error = data_dir.identity(&node, "bob").await.unwrap_err();
+    assert_eq!(error.code().kind, ockam::errcode::Kind::NotFound);
+
+    data_dir.remove()?;
+    ctx.stop().await
+}
+
+/// Stable identifiers need a directory that survives reboots, so it isn't the temp one.
+#[test]
+fn nodes_keep_their_data_in_the_users_data_directory() {
+    std::env::remove_var(NODE_DATA_DIR_ENV);
+    std::env::set_var("XDG_DATA_HOME", "/home/alice/.data");
+    assert_eq!(
+        NodeDataDir::for_node("node_responder").path(),
+        Path::new("/home/alice/.data/hello_ockam/node_responder")
+    );
+
+    std::env::set_var(NODE_DATA_DIR_ENV, "/srv/nodes");
+    assert_eq!(
+        NodeDataDir::for_node("node_responder").path(),
+        Path::new("/srv/nodes/node_responder")
+    );
+    std::env::remove_var(NODE_DATA_DIR_ENV);
+}
+
+/// The reloaded identity's secret key comes from the vault on disk, so it can still run a
+/// secure channel listener, like `bob` in
+/// examples/05-secure-channel-over-two-transport-hops-responder.rs.
+#[ockam::test]
+async fn reloaded_identities_can_run_secure_channel_listeners(ctx: &mut Context) -> Result<()> {
+    let data_dir = fresh_data_dir("secure_channel")?;
+    let node = data_dir.create_node(ctx.async_try_clone().await?).await?;
+    let (id_bob, _) = data_dir.identity(&node, "bob").await?;
+
+    let node_responder = data_dir.create_node(ctx.async_try_clone().await?).await?;
+    let (id_bob_again, origin) = data_dir.identity(&node_responder, "bob").await?;
+    assert_eq!(
+        (id_bob_again.clone(), origin),
+        (id_bob, IdentityOrigin::Loaded)
+    );
+    let tcp_transport = node_responder.create_tcp_transport().await?;
+    node_responder.start_worker("echoer", Echoer).await?;
+    let listener = tcp_transport
+        .listen(loopback(5101), TcpListenerOptions::new())
+        .await?;
+    let secure_channel_listener = node_responder
+        .create_secure_channel_listener(
+            &id_bob_again,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await?;
+    node_responder
+        .flow_controls()
+        .add_consumer("echoer", secure_channel_listener.flow_control_id());
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection = tcp_transport
+        .connect(loopback(5101), TcpConnectionOptions::new())
+        .await?;
+    let channel = node_initiator
+        .create_secure_channel(
+            &id_alice,
+            route![connection, "bob_listener"],
+            SecureChannelOptions::new(),
+        )
+        .await?;
+    let reply = send_hello(ctx, route![channel, "echoer"], Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    data_dir.remove()?;
+    ctx.stop().await
+}
+
+#[cfg(unix)]
+#[ockam::test]
+async fn refuses_files_that_other_users_can_read(ctx: &mut Context) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let data_dir = fresh_data_dir("permissions")?;
+    let node = data_dir.create_node(ctx.async_try_clone().await?).await?;
+    data_dir.identity(&node, "bob").await?;
+
+    let mode =
+        |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(data_dir.path()), 0o700);
+    assert_eq!(mode(&data_dir.identity_path("bob")), 0o600);
+    assert_eq!(mode(&data_dir.vault_path()), 0o600);
+
+    std::fs::set_permissions(
+        data_dir.identity_path("bob"),
+        std::fs::Permissions::from_mode(0o644),
+    )
+    .unwrap();
+    let error = data_dir.identity(&node, "bob").await.unwrap_err();
+    assert_eq!(error.code().kind, ockam::errcode::Kind::Misuse);
+
+    data_dir.remove()?;
+    ctx.stop().await
+}
@@ -0,0 +1,129 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::loopback;
+use hello_ockam::{Forwarder, PingClient, PingReport, Pinger};
+use ockam::{
+    route, Any, AsyncTryClone, Context, Result, Routed, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension, Worker,
+};
+use std::time::Duration;
+
+#[test]
+fn report_has_ping_style_statistics() {
+    let report = PingReport {
+        transmitted: 4,
+        rtts: [3, 1, 2].into_iter().map(Duration::from_millis).collect(),
+    };
+    assert_eq!(report.received(), 3);
+    assert_eq!(report.loss_percent(), 25.0);
+    assert_eq!(report.min(), Some(Duration::from_millis(1)));
+    assert_eq!(report.avg(), Some(Duration::from_millis(2)));
+    assert_eq!(report.max(), Some(Duration::from_millis(3)));
+    assert_eq!(report.percentile(99.0), Some(Duration::from_millis(3)));
+    assert_eq!(report.percentile(50.0), Some(Duration::from_millis(2)));
+    assert!(report
+        .to_string()
+        .contains("4 probes transmitted, 3 received, 25.0% loss"));
+    assert!(report
+        .to_string()
+        .contains("rtt min/avg/max/p99 = 1.000/2.000/3.000/3.000 ms"));
+}
+
+#[ockam::test]
+async fn pings_a_local_pinger(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("pinger", Pinger).await?;
+
+    let report = PingClient::new(route!["pinger"])
+        .with_count(3)
+        .with_interval(Duration::ZERO)
+        .with_payload_size(64)
+        .run(ctx)
+        .await?;
+    assert_eq!(report.transmitted, 3);
+    assert_eq!(report.received(), 3);
+    assert_eq!(report.loss_percent(), 0.0);
+
+    ctx.stop().await
+}
+
+/// Over a TCP hop & a forwarder, like examples/07-ping.rs w/out the secure channel.
+#[ockam::test]
+async fn pings_over_tcp_and_a_forwarder(ctx: &mut Context) -> Result<()> {
+    let node_responder = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_responder.create_tcp_transport().await?;
+    node_responder.start_worker("pinger", Pinger).await?;
+    let listener = tcp_transport
+        .listen(loopback(4501), TcpListenerOptions::new())
+        .await?;
+    node_responder
+        .flow_controls()
+        .add_consumer("pinger", listener.flow_control_id());
+
+    let connection = tcp_transport
+        .connect(loopback(4501), TcpConnectionOptions::new())
+        .await?;
+    ctx.start_worker(
+        "forward_to_pinger",
+        Forwarder {
+            address: connection.into(),
+        },
+    )
+    .await?;
+
+    let report = PingClient::new(route!["forward_to_pinger", "pinger"])
+        .with_count(2)
+        .with_interval(Duration::ZERO)
+        .run(ctx)
+        .await?;
+    assert_eq!(report.received(), 2);
+
+    ctx.stop().await
+}
+
+/// Drops every message, so that each probe times out.
+struct BlackHole;
+
+#[ockam::worker]
+impl Worker for BlackHole {
+    type Context = Context;
+    type Message = Any;
+
+    async fn handle_message(&mut self, _ctx: &mut Context, _msg: Routed<Any>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[ockam::test]
+async fn unanswered_probes_count_as_lost(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("black_hole", BlackHole).await?;
+
+    let report = PingClient::new(route!["black_hole"])
+        .with_count(2)
+        .with_interval(Duration::ZERO)
+        .with_timeout(Duration::from_millis(200))
+        .run(ctx)
+        .await?;
+    assert_eq!(report.transmitted, 2);
+    assert_eq!(report.received(), 0);
+    assert_eq!(report.loss_percent(), 100.0);
+    assert!(!report.to_string().contains("rtt"));
+
+    ctx.stop().await
+}
@@ -0,0 +1,117 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{loopback, start_tcp_middle, NO_REPLY_TIMEOUT};
+use hello_ockam::{http_get, HttpTarget, RouteResolver, RouteSpec};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    AsyncTryClone, Context, Node, Result, TcpInletOptions, TcpListenerOptions, TcpOutletOptions,
+    TcpTransportExtension,
+};
+
+/// Like `start_secure_responder`, w/ an outlet to `target` instead of an echoer. The outlet
+/// is only reachable via secure channels.
+async fn start_outlet_responder(ctx: &Context, port: u16, target: &HttpTarget) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    let id_bob = node.create_identity().await?;
+    let listener = tcp_transport
+        .listen(loopback(port), TcpListenerOptions::new())
+        .await?;
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await?;
+    tcp_transport
+        .create_outlet(
+            "outlet",
+            target.socket_address().to_string(),
+            TcpOutletOptions::new().as_consumer(secure_channel_listener.flow_control_id()),
+        )
+        .await?;
+    Ok(node)
+}
+
+/// Start an inlet on a free port that tunnels to the end of `route`, and return its
+/// socket address.
+async fn start_inlet(node: &Node, route: &str) -> Result<String> {
+    let id_alice = node.create_identity().await?;
+    let tcp_transport = node.create_tcp_transport().await?;
+    let spec: RouteSpec = route.parse()?;
+    let resolved = RouteResolver::new(node, &tcp_transport)
+        .with_identity(&id_alice)
+        .resolve(&spec)
+        .await?;
+    let (socket_address, _) = tcp_transport
+        .create_inlet("127.0.0.1:0", resolved.route, TcpInletOptions::new())
+        .await?;
+    Ok(socket_address.to_string())
+}
+
+/// examples/14-portal.rs
+#[ockam::test]
+async fn http_goes_through_the_portal_over_a_forwarder(ctx: &mut Context) -> Result<()> {
+    // Big enough to take many messages through the portal.
+    let body = "🌍 hello over the portal\n".repeat(10_000);
+    let target = HttpTarget::start("127.0.0.1:0", body.clone()).await?;
+    let _node_responder = start_outlet_responder(ctx, 4901, &target).await?;
+    let _node_middle = start_tcp_middle(ctx, "forward_to_bob", 3901, 4901).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let inlet = start_inlet(
+        &node_initiator,
+        "secure(tcp:127.0.0.1:3901 / forward_to_bob / bob_listener) / outlet",
+    )
+    .await?;
+
+    // Each request is a new TCP connection, and so a new stream through the portal.
+    for _ in 0..3 {
+        let response = http_get(&inlet, "/").await?;
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, body);
+    }
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn outlet_isnt_reachable_w_out_a_secure_channel(ctx: &mut Context) -> Result<()> {
+    let target = HttpTarget::start("127.0.0.1:0", "secret").await?;
+    let _node_responder = start_outlet_responder(ctx, 4902, &target).await?;
+    let _node_middle = start_tcp_middle(ctx, "forward_to_bob", 3902, 4902).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let inlet = start_inlet(
+        &node_initiator,
+        "tcp:127.0.0.1:3902 / forward_to_bob / outlet",
+    )
+    .await?;
+
+    let result = tokio::time::timeout(NO_REPLY_TIMEOUT, http_get(&inlet, "/")).await;
+    assert!(
+        !matches!(&result, Ok(Ok(response)) if response.body == "secret"),
+        "{:?}",
+        result
+    );
+
+    ctx.stop().await
+}
@@ -0,0 +1,97 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use hello_ockam::{
+    is_output_redirected, redirect_output, restore_output, strip_ansi, terminal_width, wrap,
+    NodeRole, Presenter, Theme,
+};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn wrap_splits_long_lines_into_chunks_of_the_given_width() {
+    assert_eq!(wrap("abcdefgh", 3), vec!["abc", "def", "gh"]);
+    assert_eq!(wrap("abcdef", 3), vec!["abc", "def"]);
+}
+
+#[test]
+fn wrap_keeps_short_and_empty_lines_as_they_are() {
+    assert_eq!(wrap("abc", 10), vec!["abc"]);
+    assert_eq!(wrap("", 10), vec![""]);
+}
+
+#[test]
+fn wrap_counts_characters_not_bytes() {
+    assert_eq!(wrap("👉👈🔐✅", 2), vec!["👉👈", "🔐✅"]);
+}
+
+#[test]
+fn wrap_doesnt_count_or_split_ansi_escapes() {
+    let line = "\x1b[31mabcd\x1b[0mef";
+    assert_eq!(wrap(line, 4), vec!["\x1b[31mabcd\x1b[0m", "ef"]);
+    assert_eq!(wrap(line, 3), vec!["\x1b[31mabc", "d\x1b[0mef"]);
+}
+
+#[test]
+fn terminal_width_leaves_room_for_the_prefix_and_some_text() {
+    std::env::set_var("COLUMNS", "19");
+    assert_eq!(terminal_width(), 40);
+    std::env::set_var("COLUMNS", "100");
+    assert_eq!(terminal_width(), 100);
+    std::env::remove_var("COLUMNS");
+}
+
+#[test]
+fn theme_for_name_is_stable() {
+    assert_eq!(Theme::for_name("echoer"), Theme::for_name("echoer"));
+}
+
+#[test]
+fn strip_ansi_removes_color_escapes() {
+    assert_eq!(strip_ansi("\x1b[31mred\x1b[0m"), "red");
+    assert_eq!(strip_ansi("a\x1b[1;32mb\x1b[0mc"), "abc");
+}
+
+#[test]
+fn strip_ansi_keeps_plain_text_as_it_is() {
+    assert_eq!(strip_ansi("plain 👉 text"), "plain 👉 text");
+    assert_eq!(strip_ansi(""), "");
+}
+
+/// The sink is called w/out holding the lock, so it can use the output functions too.
+#[test]
+fn redirected_output_goes_to_the_sink_without_colors() {
+    let lines = Arc::new(Mutex::new(vec![]));
+    let sink_lines = lines.clone();
+    redirect_output(move |name, line| {
+        assert!(is_output_redirected());
+        sink_lines
+            .lock()
+            .unwrap()
+            .push((name.to_string(), line.to_string()));
+    });
+    Presenter::for_role(NodeRole::Responder, "node_responder").println("one\ntwo");
+    restore_output();
+
+    assert!(!is_output_redirected());
+    assert_eq!(
+        *lines.lock().unwrap(),
+        vec![
+            ("node_responder".to_string(), "one".to_string()),
+            ("node_responder".to_string(), "two".to_string()),
+        ]
+    );
+}
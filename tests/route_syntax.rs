@@ -0,0 +1,143 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{send_hello, start_secure_responder, start_tcp_middle, ECHO_REPLY};
+use hello_ockam::{RouteHop, RouteResolver, RouteSpec};
+use ockam::errcode::Kind;
+use ockam::{AsyncTryClone, Context, Result, TcpTransportExtension};
+use std::future::Future;
+use std::time::Duration;
+
+/// Fails to compile unless `future` can be moved between threads, eg: to a spawned task.
+fn assert_send<F: Future + Send>(future: F) -> F {
+    future
+}
+
+#[test]
+fn parses_transport_and_worker_hops() {
+    let spec: RouteSpec = "tcp:127.0.0.1:3000/forward_to_responder /  echoer"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        spec.hops,
+        vec![
+            RouteHop::Tcp("127.0.0.1:3000".to_string()),
+            RouteHop::Worker("forward_to_responder".to_string()),
+            RouteHop::Worker("echoer".to_string()),
+        ]
+    );
+    assert_eq!(
+        spec.to_string(),
+        "tcp:127.0.0.1:3000 / forward_to_responder / echoer"
+    );
+}
+
+#[test]
+fn parses_secure_hops_and_prints_them_back() {
+    let text = "secure(tcp:localhost:4000 / secure-server) / echoer";
+    let spec: RouteSpec = text.parse().unwrap();
+    assert_eq!(
+        spec.hops,
+        vec![
+            RouteHop::Secure(RouteSpec::new(vec![
+                RouteHop::Tcp("localhost:4000".to_string()),
+                RouteHop::Worker("secure-server".to_string()),
+            ])),
+            RouteHop::Worker("echoer".to_string()),
+        ]
+    );
+    assert_eq!(spec.to_string(), text);
+    assert_eq!(spec.to_string().parse::<RouteSpec>().unwrap(), spec);
+}
+
+#[test]
+fn parses_unix_hops_w_slashes_in_their_path() {
+    let text = "secure(unix(/tmp/bob.sock) / bob_listener) / echoer";
+    let spec: RouteSpec = text.parse().unwrap();
+    assert_eq!(
+        spec.hops,
+        vec![
+            RouteHop::Secure(RouteSpec::new(vec![
+                RouteHop::Unix("/tmp/bob.sock".to_string()),
+                RouteHop::Worker("bob_listener".to_string()),
+            ])),
+            RouteHop::Worker("echoer".to_string()),
+        ]
+    );
+    assert_eq!(spec.to_string(), text);
+}
+
+#[test]
+fn reports_where_the_syntax_is_wrong() {
+    for (text, position) in [
+        ("", 0),
+        ("echoer /", 8),
+        ("tcp: / echoer", 4),
+        ("secure(tcp:localhost:4000 / bob", 31),
+        ("quic:localhost:4000", 0),
+        ("echoer) / h1", 6),
+        ("unix() / echoer", 5),
+        ("unix(/tmp/bob.sock", 18),
+    ] {
+        let error = text.parse::<RouteSpec>().unwrap_err();
+        assert_eq!(error.position, position, "{}", error);
+    }
+}
+
+#[test]
+fn syntax_errors_are_invalid_input() {
+    let error: ockam::Error = "echoer /".parse::<RouteSpec>().unwrap_err().into();
+    assert_eq!(error.code().kind, Kind::Invalid);
+}
+
+/// examples/05-secure-channel-over-two-transport-hops-responder.rs w/ its default route.
+#[ockam::test]
+async fn resolves_tcp_and_secure_hops(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_secure_responder(ctx, 4401, true).await?;
+    let _node_middle = start_tcp_middle(ctx, "forward_to_bob", 3401, 4401).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let spec: RouteSpec =
+        "secure(tcp:127.0.0.1:3401 / forward_to_bob / bob_listener) / echoer".parse()?;
+    let resolver = RouteResolver::new(&node_initiator, &tcp_transport).with_identity(&id_alice);
+    let resolved = assert_send(resolver.resolve(&spec)).await?;
+    assert_eq!(resolved.tcp_connections.len(), 1);
+    assert_eq!(resolved.secure_channels.len(), 1);
+    assert_eq!(resolved.flow_control_ids.len(), 2);
+
+    let reply = send_hello(ctx, resolved.route, Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn secure_hops_need_an_identity(ctx: &mut Context) -> Result<()> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    let spec: RouteSpec = "secure(bob_listener) / echoer".parse()?;
+    assert!(RouteResolver::new(&node, &tcp_transport)
+        .resolve(&spec)
+        .await
+        .is_err());
+
+    ctx.stop().await
+}
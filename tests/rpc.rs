@@ -0,0 +1,177 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use async_trait::async_trait;
+use hello_ockam::{
+    EchoService, RetryPolicy, RpcCallError, RpcClient, RpcError, RpcErrorCode, RpcRequest,
+    RpcResponse, RpcResult, RpcServer, RpcService,
+};
+use ockam::{route, Context, Result};
+use std::time::Duration;
+
+/// Doesn't reply to the first `ignore` requests, to exercise the client's retries.
+struct Flaky {
+    ignore: u32,
+    seen: Vec<u64>,
+}
+
+#[ockam::worker]
+impl ockam::Worker for Flaky {
+    type Context = Context;
+    type Message = RpcRequest;
+
+    async fn handle_message(
+        &mut self,
+        ctx: &mut Context,
+        msg: ockam::Routed<RpcRequest>,
+    ) -> Result<()> {
+        let return_route = msg.return_route();
+        let request = msg.body();
+        self.seen.push(request.correlation_id);
+        if self.ignore > 0 {
+            self.ignore -= 1;
+            return Ok(());
+        }
+        // Every attempt of a call must carry the same correlation id.
+        let body = serde_json::to_vec(&self.seen).unwrap();
+        let response = RpcResponse {
+            correlation_id: request.correlation_id,
+            result: RpcResult::Ok(body),
+        };
+        ctx.send(return_route, response).await
+    }
+}
+
+/// Adds the two numbers, and rejects overflows w/ an error reply.
+struct Adder;
+
+#[async_trait]
+impl RpcService for Adder {
+    type Request = (u8, u8);
+    type Response = u8;
+
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context,
+        (a, b): (u8, u8),
+    ) -> std::result::Result<u8, RpcError> {
+        a.checked_add(b)
+            .ok_or_else(|| RpcError::invalid_request(format!("{} + {} overflows", a, b)))
+    }
+}
+
+#[ockam::test]
+async fn typed_calls_get_typed_replies_and_errors(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("adder", RpcServer::new(Adder)).await?;
+    let adder = RpcClient::<(u8, u8), u8>::new(route!["adder"]);
+
+    assert_eq!(adder.call(ctx, &(1, 2)).await.unwrap(), 3);
+    match adder.call(ctx, &(200, 100)).await {
+        Err(RpcCallError::Remote(error)) => {
+            assert_eq!(error.code, RpcErrorCode::InvalidRequest);
+            assert_eq!(error.message, "200 + 100 overflows");
+        }
+        other => panic!("expected an error reply, got {:?}", other),
+    }
+
+    // A request that doesn't decode as the service's request type.
+    let mismatched = RpcClient::<String, u8>::new(route!["adder"]);
+    match mismatched.call(ctx, &"1 + 2".to_string()).await {
+        Err(RpcCallError::Remote(error)) => assert_eq!(error.code, RpcErrorCode::InvalidRequest),
+        other => panic!("expected an error reply, got {:?}", other),
+    }
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn echo_service_replies_like_the_echoer(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("echo_rpc", RpcServer::new(EchoService))
+        .await?;
+    let echo = RpcClient::<String, String>::new(route!["echo_rpc"]);
+
+    let reply = echo.call(ctx, &"Hello Ockam!".to_string()).await.unwrap();
+    assert_eq!(reply, common::ECHO_REPLY);
+    assert!(matches!(
+        echo.call(ctx, &String::new()).await,
+        Err(RpcCallError::Remote(_))
+    ));
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn timed_out_calls_are_retried_w_the_same_correlation_id(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker(
+        "flaky",
+        Flaky {
+            ignore: 2,
+            seen: vec![],
+        },
+    )
+    .await?;
+    let client = RpcClient::<(), Vec<u64>>::new(route!["flaky"])
+        .with_timeout(Duration::from_millis(200))
+        .with_retry_policy(RetryPolicy::fixed(3, Duration::from_millis(10)));
+
+    let seen = client.call(ctx, &()).await.unwrap();
+    assert_eq!(seen.len(), 3);
+    assert!(seen.iter().all(|it| *it == seen[0]));
+
+    // The next call has a new correlation id, and gets a reply on the first attempt.
+    let seen = client.call(ctx, &()).await.unwrap();
+    assert_eq!(seen.len(), 4);
+    assert_ne!(seen[3], seen[0]);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn gives_up_after_the_last_attempt(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker(
+        "flaky",
+        Flaky {
+            ignore: u32::MAX,
+            seen: vec![],
+        },
+    )
+    .await?;
+    let client = RpcClient::<(), Vec<u64>>::new(route!["flaky"])
+        .with_timeout(Duration::from_millis(100))
+        .with_retry_policy(RetryPolicy::exponential(2, Duration::from_millis(10)));
+
+    assert!(matches!(
+        client.call(ctx, &()).await,
+        Err(RpcCallError::Timeout { attempts: 2 })
+    ));
+
+    ctx.stop().await
+}
+
+#[test]
+fn exponential_backoff_doubles_after_each_attempt() {
+    let policy = RetryPolicy::exponential(4, Duration::from_millis(100));
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(3), Duration::from_millis(400));
+    assert_eq!(
+        RetryPolicy::fixed(4, Duration::from_millis(50)).backoff(3),
+        Duration::from_millis(50)
+    );
+}
@@ -0,0 +1,105 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{
+    loopback, send_hello, start_secure_responder, start_tcp_middle, ECHO_REPLY, NO_REPLY_TIMEOUT,
+};
+use ockam::identity::SecureChannelOptions;
+use ockam::{route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpTransportExtension};
+use std::time::Duration;
+
+/// examples/05-secure-channel-over-two-transport-hops-responder.rs
+#[ockam::test]
+async fn echoer_replies_over_a_secure_channel_across_a_forwarder(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_secure_responder(ctx, 4201, true).await?;
+    let _node_middle = start_tcp_middle(ctx, "forward_to_bob", 3201, 4201).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection_to_middle_node = tcp_transport
+        .connect(loopback(3201), TcpConnectionOptions::new())
+        .await?;
+    let channel = node_initiator
+        .create_secure_channel(
+            &id_alice,
+            route![connection_to_middle_node, "forward_to_bob", "bob_listener"],
+            SecureChannelOptions::new(),
+        )
+        .await?;
+
+    let reply = send_hello(ctx, route![channel, "echoer"], Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn no_reply_when_the_echoer_is_not_a_secure_channel_consumer(
+    ctx: &mut Context,
+) -> Result<()> {
+    let _node_responder = start_secure_responder(ctx, 4202, false).await?;
+    let _node_middle = start_tcp_middle(ctx, "forward_to_bob", 3202, 4202).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection_to_middle_node = tcp_transport
+        .connect(loopback(3202), TcpConnectionOptions::new())
+        .await?;
+    let channel = node_initiator
+        .create_secure_channel(
+            &id_alice,
+            route![connection_to_middle_node, "forward_to_bob", "bob_listener"],
+            SecureChannelOptions::new(),
+        )
+        .await?;
+
+    let result = send_hello(ctx, route![channel, "echoer"], NO_REPLY_TIMEOUT).await;
+    assert!(result.is_err());
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn secure_channel_fails_when_the_listener_address_is_wrong(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_secure_responder(ctx, 4203, true).await?;
+    let _node_middle = start_tcp_middle(ctx, "forward_to_bob", 3203, 4203).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection_to_middle_node = tcp_transport
+        .connect(loopback(3203), TcpConnectionOptions::new())
+        .await?;
+    let result = node_initiator
+        .create_secure_channel(
+            &id_alice,
+            route![
+                connection_to_middle_node,
+                "forward_to_bob",
+                "no_such_listener"
+            ],
+            SecureChannelOptions::new().with_timeout(NO_REPLY_TIMEOUT),
+        )
+        .await;
+    assert!(result.is_err());
+
+    ctx.stop().await
+}
@@ -0,0 +1,124 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use hello_ockam::{Echoer, Hopper, NodeRole, ShutdownCoordinator};
+use ockam::{
+    route, Address, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+
+#[ockam::test]
+async fn stops_the_registered_workers_role_by_role(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("echoer", Echoer).await?;
+    ctx.start_worker("h1", Hopper).await?;
+    ctx.start_worker("h2", Hopper).await?;
+    let workers = ctx.list_workers().await?;
+    for address in ["echoer", "h1", "h2"] {
+        assert!(
+            workers.contains(&address.into()),
+            "{} isn't running",
+            address
+        );
+    }
+
+    // Registered out of order, they are stopped in [NodeRole::SHUTDOWN_ORDER].
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+    coordinator
+        .register_worker(NodeRole::Responder, "node_responder", "echoer")
+        .register_worker(NodeRole::Initiator, "node_initiator", "h1")
+        .register_worker(NodeRole::Forwarder, "node_middle", "h2");
+    let report = coordinator.shutdown().await?;
+
+    assert!(report.is_clean(), "{}", report);
+    let stopped: Vec<(NodeRole, Address)> = report
+        .stopped
+        .into_iter()
+        .map(|(role, _, address)| (role, address))
+        .collect();
+    assert_eq!(
+        stopped,
+        vec![
+            (NodeRole::Initiator, "h1".into()),
+            (NodeRole::Forwarder, "h2".into()),
+            (NodeRole::Responder, "echoer".into()),
+        ]
+    );
+
+    // The coordinator stopped the router too.
+    Ok(())
+}
+
+#[ockam::test]
+async fn reports_workers_that_dont_stop(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker("echoer", Echoer).await?;
+
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+    coordinator
+        .register_worker(NodeRole::Responder, "node_responder", "echoer")
+        .register_worker(NodeRole::Responder, "node_responder", "not_started");
+    let report = coordinator.shutdown().await?;
+
+    assert!(!report.is_clean());
+    assert_eq!(report.stopped.len(), 1);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].address, "not_started".into());
+    assert!(report.to_string().contains("❌ node_responder (responder)"));
+
+    Ok(())
+}
+
+#[ockam::test]
+async fn stops_tcp_listeners_and_connections(ctx: &mut Context) -> Result<()> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    node.start_worker("echoer", Echoer).await?;
+    let listener = tcp_transport
+        .listen("127.0.0.1:4801", TcpListenerOptions::new())
+        .await?;
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    let connection = tcp_transport
+        .connect("127.0.0.1:4801", TcpConnectionOptions::new())
+        .await?;
+    let reply = ctx
+        .send_and_receive::<String>(
+            route![connection.clone(), "echoer"],
+            "Hello Ockam!".to_string(),
+        )
+        .await?;
+    assert_eq!(reply, "👈 echo back: Hello Ockam!");
+
+    // Once all the TCP workers are stopped, the router must not wait for them anymore.
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+    coordinator
+        .register_worker(
+            NodeRole::Initiator,
+            "node_initiator",
+            connection.sender_address().clone(),
+        )
+        .register_processor(
+            NodeRole::Responder,
+            "node_responder",
+            listener.processor_address().clone(),
+        )
+        .register_worker(NodeRole::Responder, "node_responder", "echoer");
+    let report = coordinator.shutdown().await?;
+
+    assert!(report.is_clean(), "{}", report);
+    assert_eq!(report.stopped.len(), 3);
+    Ok(())
+}
@@ -0,0 +1,222 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{send_hello, wait_for_dead_letter, ECHO_REPLY, HELLO, NO_REPLY_TIMEOUT};
+use hello_ockam::{
+    DeadLetterClient, DeadLetterQueue, DeadLetterReason, Echoer, Forwarder, LinkSettings,
+    SimNetwork, DEAD_LETTER_ADDRESS,
+};
+use ockam::identity::{SecureChannelListenerOptions, SecureChannelOptions};
+use ockam::{route, AsyncTryClone, Context, Result, Routed, Worker};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Keeps the bodies of the messages that it receives, in order.
+struct Collector(Arc<Mutex<Vec<String>>>);
+
+#[ockam::worker]
+impl Worker for Collector {
+    type Context = Context;
+    type Message = String;
+
+    async fn handle_message(&mut self, _ctx: &mut Context, msg: Routed<String>) -> Result<()> {
+        self.0.lock().unwrap().push(msg.body());
+        Ok(())
+    }
+}
+
+/// Send 100 numbered messages from "a" to a collector on "b" over a lossy link that
+/// reorders, and return what arrived. They're sent in a burst, so the reorder delay is
+/// much longer than the (wall clock) time between them, and the same seed reorders them
+/// the same.
+async fn run_lossy_link(ctx: &Context, seed: u64, collector: &str) -> Result<Vec<String>> {
+    let received = Arc::new(Mutex::new(vec![]));
+    ctx.start_worker(collector, Collector(received.clone()))
+        .await?;
+    let network = SimNetwork::new(seed);
+    let settings = LinkSettings::perfect()
+        .with_latency(Duration::from_millis(5))
+        .with_loss(0.3)
+        .with_reordering(0.2, Duration::from_millis(20));
+    let connection = network.connect(ctx, "a", "b", settings).await?;
+
+    for number in 0..100 {
+        ctx.send(route![connection.clone(), collector], number.to_string())
+            .await?;
+    }
+    let mut stats = network.stats("a", "b");
+    while stats.sent < 100 || stats.in_flight() > 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        stats = network.stats("a", "b");
+    }
+    assert!(stats.lost > 0);
+
+    // The last deliveries may still be in the collector's mailbox.
+    while (received.lock().unwrap().len() as u64) < stats.delivered {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let received = received.lock().unwrap().clone();
+    Ok(received)
+}
+
+#[ockam::test]
+async fn the_same_seed_loses_and_reorders_the_same_messages(ctx: &mut Context) -> Result<()> {
+    let first = run_lossy_link(ctx, 42, "collector_1").await?;
+    let second = run_lossy_link(ctx, 42, "collector_2").await?;
+    let other_seed = run_lossy_link(ctx, 7, "collector_3").await?;
+
+    assert_eq!(first, second);
+    assert_ne!(first, other_seed);
+    let mut sorted = first.clone();
+    sorted.sort_by_key(|it| it.parse::<u32>().unwrap());
+    assert_ne!(first, sorted, "some messages should have been reordered");
+
+    ctx.stop().await
+}
+
+/// examples/04-routing-over-two-transport-hops.rs, w/ simulated links instead of TCP.
+#[ockam::test]
+async fn forwarder_adds_the_latency_of_both_links(ctx: &mut Context) -> Result<()> {
+    let network = SimNetwork::new(1);
+    let settings = LinkSettings::perfect().with_latency(Duration::from_millis(25));
+    ctx.start_worker("echoer", Echoer).await?;
+    let to_responder = network
+        .connect(ctx, "middle", "responder", settings.clone())
+        .await?;
+    ctx.start_worker(
+        "forward_to_responder",
+        Forwarder {
+            address: to_responder.into(),
+        },
+    )
+    .await?;
+    let to_middle = network
+        .connect(ctx, "initiator", "middle", settings)
+        .await?;
+
+    let started = Instant::now();
+    let route = route![to_middle, "forward_to_responder", "echoer"];
+    let reply = send_hello(ctx, route, Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+    // Two links, there & back.
+    assert!(started.elapsed() >= Duration::from_millis(100));
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn no_reply_while_the_link_is_partitioned(ctx: &mut Context) -> Result<()> {
+    let network = SimNetwork::new(1);
+    ctx.start_worker("echoer", Echoer).await?;
+    let connection = network
+        .connect(ctx, "initiator", "responder", LinkSettings::perfect())
+        .await?;
+    let route = route![connection, "echoer"];
+
+    network.partition("initiator", "responder");
+    assert!(send_hello(ctx, route.clone(), NO_REPLY_TIMEOUT)
+        .await
+        .is_err());
+    assert_eq!(network.stats("initiator", "responder").partitioned, 1);
+
+    network.heal("initiator", "responder");
+    let reply = send_hello(ctx, route, Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+/// examples/05-secure-channel-over-two-transport-hops-responder.rs, w/ jittery simulated
+/// links instead of TCP.
+#[ockam::test]
+async fn secure_channel_across_a_forwarder_over_jittery_links(ctx: &mut Context) -> Result<()> {
+    let network = SimNetwork::new(3);
+    let settings = LinkSettings::perfect()
+        .with_latency(Duration::from_millis(5))
+        .with_jitter(Duration::from_millis(10))
+        .with_bandwidth(1_000_000);
+
+    let node_responder = ockam::node(ctx.async_try_clone().await?);
+    let id_bob = node_responder.create_identity().await?;
+    let listener = node_responder
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new(),
+        )
+        .await?;
+    node_responder.start_worker("echoer", Echoer).await?;
+    node_responder
+        .flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+
+    let to_responder = network
+        .connect(ctx, "middle", "responder", settings.clone())
+        .await?;
+    ctx.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: to_responder.into(),
+        },
+    )
+    .await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let to_middle = network
+        .connect(ctx, "initiator", "middle", settings)
+        .await?;
+    let channel = node_initiator
+        .create_secure_channel(
+            &id_alice,
+            route![to_middle, "forward_to_bob", "bob_listener"],
+            SecureChannelOptions::new(),
+        )
+        .await?;
+
+    let reply = send_hello(ctx, route![channel, "echoer"], Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn keeps_a_dead_letter_when_the_next_hop_is_unknown(ctx: &mut Context) -> Result<()> {
+    ctx.start_worker(DEAD_LETTER_ADDRESS, DeadLetterQueue::new())
+        .await?;
+    let network = SimNetwork::new(5);
+    let connection = network
+        .connect(ctx, "initiator", "responder", LinkSettings::perfect())
+        .await?;
+    ctx.send(route![connection.clone(), "nobody"], HELLO.to_string())
+        .await?;
+    let mut stats = network.stats("initiator", "responder");
+    while stats.sent == 0 || stats.in_flight() > 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        stats = network.stats("initiator", "responder");
+    }
+    assert_eq!((stats.delivered, stats.undeliverable), (0, 1));
+    let failed_at = connection.sender_address().address().to_string();
+    let client = DeadLetterClient::new(route![DEAD_LETTER_ADDRESS]);
+    let letter = wait_for_dead_letter(ctx, &client, &failed_at).await?;
+    assert_eq!(letter.reason, DeadLetterReason::UnknownAddress);
+    assert_eq!(letter.onward_route, route!["nobody"]);
+
+    ctx.stop().await
+}
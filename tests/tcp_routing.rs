@@ -0,0 +1,106 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{
+    loopback, send_hello, start_tcp_middle, start_tcp_responder, ECHO_REPLY, NO_REPLY_TIMEOUT,
+};
+use ockam::{route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpTransportExtension};
+use std::time::Duration;
+
+/// examples/04-routing-over-transport.rs
+#[ockam::test]
+async fn echoer_replies_over_one_tcp_hop(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_tcp_responder(ctx, 4101).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection_to_responder = tcp_transport
+        .connect(loopback(4101), TcpConnectionOptions::new())
+        .await?;
+
+    let reply = send_hello(
+        ctx,
+        route![connection_to_responder, "echoer"],
+        Duration::from_secs(5),
+    )
+    .await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+/// examples/04-routing-over-two-transport-hops.rs
+#[ockam::test]
+async fn echoer_replies_over_two_tcp_hops(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_tcp_responder(ctx, 4102).await?;
+    let _node_middle = start_tcp_middle(ctx, "forward_to_responder", 3102, 4102).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection_to_middle_node = tcp_transport
+        .connect(loopback(3102), TcpConnectionOptions::new())
+        .await?;
+
+    let reply = send_hello(
+        ctx,
+        route![connection_to_middle_node, "forward_to_responder", "echoer"],
+        Duration::from_secs(5),
+    )
+    .await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn connect_fails_when_nothing_is_listening(ctx: &mut Context) -> Result<()> {
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+
+    let result = tcp_transport
+        .connect(loopback(4103), TcpConnectionOptions::new())
+        .await;
+    assert!(result.is_err());
+
+    ctx.stop().await
+}
+
+/// The echoer is only a flow control consumer of the responder's TCP listener, so messages
+/// that arrive via the middle node's listener are dropped before they reach it.
+#[ockam::test]
+async fn no_reply_without_a_flow_control_consumer(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_tcp_responder(ctx, 4104).await?;
+    let _node_middle = start_tcp_middle(ctx, "forward_to_responder", 3104, 4104).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let connection_to_middle_node = tcp_transport
+        .connect(loopback(3104), TcpConnectionOptions::new())
+        .await?;
+
+    let result = send_hello(
+        ctx,
+        route![connection_to_middle_node, "echoer"],
+        NO_REPLY_TIMEOUT,
+    )
+    .await;
+    assert!(result.is_err());
+
+    ctx.stop().await
+}
@@ -0,0 +1,136 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{loopback, send_hello, ECHO_REPLY};
+use hello_ockam::{
+    render_ascii_diagram, strip_ansi, Echoer, NodeRole, ShutdownCoordinator, Topology,
+    TopologyElement,
+};
+use ockam::{
+    route, AsyncTryClone, Context, Result, TcpConnectionOptions, TcpListenerOptions,
+    TcpTransportExtension,
+};
+use std::time::Duration;
+
+/// The TCP listener & connection aren't named by hand, they come from the transports.
+#[ockam::test]
+async fn snapshot_reads_tcp_listeners_and_connections_from_the_transports(
+    ctx: &mut Context,
+) -> Result<()> {
+    let mut topology = Topology::new();
+
+    let node_responder = ockam::node(ctx.async_try_clone().await?);
+    let responder_tcp = node_responder.create_tcp_transport().await?;
+    node_responder.start_worker("echoer", Echoer).await?;
+    let listener = responder_tcp
+        .listen(loopback(4911), TcpListenerOptions::new())
+        .await?;
+    node_responder
+        .flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    topology
+        .node("node_responder", NodeRole::Responder)
+        .tcp_transport(&responder_tcp)
+        .worker("echoer");
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let initiator_tcp = node_initiator.create_tcp_transport().await?;
+    let connection = initiator_tcp
+        .connect(loopback(4911), TcpConnectionOptions::new())
+        .await?;
+    ctx.wait_for(connection.sender_address().clone()).await?;
+    topology
+        .node("node_initiator", NodeRole::Initiator)
+        .context(node_initiator.context())
+        .tcp_transport(&initiator_tcp);
+
+    let snapshot = topology.snapshot(ctx).await?;
+    let nodes = snapshot.nodes();
+    assert_eq!(nodes[0].name, "node_initiator");
+    assert!(matches!(
+        nodes[0].elements.as_slice(),
+        [
+            TopologyElement::Worker { .. },
+            TopologyElement::TcpConnection { socket_address, flow_control_id, .. },
+        ] if socket_address.port() == 4911 && flow_control_id == connection.flow_control_id()
+    ));
+    assert_eq!(nodes[1].name, "node_responder");
+    assert!(matches!(
+        nodes[1].elements.as_slice(),
+        [
+            TopologyElement::TcpListener { socket_address, flow_control_id, .. },
+            TopologyElement::Worker { address },
+        ] if socket_address.port() == 4911
+            && flow_control_id == listener.flow_control_id()
+            && address == &"echoer".into()
+    ));
+    let links = snapshot.links();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].from_node, "node_initiator");
+    assert_eq!(links[0].to_node, "node_responder");
+
+    // The initiator's context has a random address (of 32 hex digits).
+    let context_address = node_initiator.context().address().address().to_string();
+    let expected = [
+        "┌──────────────────────────────────────┐    ┌─────────────────────┐",
+        "│node_initiator                        │    │node_responder       │",
+        "├──────────────────────────────────────┤    ├─────────────────────┤",
+        "│ ┌──────────────────────────────────┐ │    │ ┌─────────────────┐ │",
+        "│ │Address:                          │ │    │ │TCP transport    │ │",
+        "│ │'CONTEXT_ADDRESS_________________'│ │    │ │listening on 4911│ │",
+        "│ └─┬──────────────────────────────▲─┘ │    │ └─┬─────────────▲─┘ │",
+        "│ ┌─▼──────────────────────────────┴─┐ │    │ ┌─▼─────────────┴─┐ │",
+        "│ │TCP transport                     │ │───►│ │Address:         │ │",
+        "│ │connect to 4911                   │ │◄───│ │'echoer'         │ │",
+        "│ └──────────────────────────────────┘ │    │ └─────────────────┘ │",
+        "└──────────────────────────────────────┘    └─────────────────────┘",
+        "",
+        "Links:",
+        "  node_initiator ──tcp 127.0.0.1:4911──► node_responder",
+    ]
+    .join("\n")
+    .replace("CONTEXT_ADDRESS_________________", &context_address);
+    assert_eq!(
+        strip_ansi(&render_ascii_diagram(&snapshot)).trim_end(),
+        expected
+    );
+
+    let reply = send_hello(ctx, route![connection, "echoer"], Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    // Everything but the initiator's own context is stopped, in order.
+    let mut coordinator = ShutdownCoordinator::new(ctx.async_try_clone().await?);
+    coordinator.register_topology(&topology);
+    let report = coordinator.shutdown().await?;
+    assert!(report.is_clean(), "{}", report);
+    let stopped: Vec<(NodeRole, String)> = report
+        .stopped
+        .into_iter()
+        .map(|(role, node_name, _)| (role, node_name))
+        .collect();
+    assert_eq!(
+        stopped,
+        vec![
+            (NodeRole::Initiator, "node_initiator".to_string()),
+            (NodeRole::Responder, "node_responder".to_string()),
+            (NodeRole::Responder, "node_responder".to_string()),
+        ]
+    );
+    Ok(())
+}
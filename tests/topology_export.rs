@@ -0,0 +1,97 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+use hello_ockam::{to_dot, to_mermaid, FlowStep, NodeRole, Topology};
+use ockam::{route, Address};
+
+/// An initiator that sends "hi" to an echoer on a responder, via a forwarder.
+fn small_topology() -> (Topology, Vec<FlowStep>) {
+    let mut topology = Topology::new();
+    topology
+        .node("node_initiator", NodeRole::Initiator)
+        .worker("app");
+    topology
+        .node("node_responder", NodeRole::Responder)
+        .worker("forward_to_echoer")
+        .worker("echoer");
+    let flows = FlowStep::request_and_reply(
+        &Address::from_string("app"),
+        &route!["forward_to_echoer", "echoer"],
+        "hi",
+        "echo: hi",
+    );
+    (topology, flows)
+}
+
+#[test]
+fn request_and_reply_has_a_step_per_hop_and_the_reply() {
+    let (_, flows) = small_topology();
+    assert_eq!(
+        flows,
+        vec![
+            FlowStep::new("app", "forward_to_echoer", "hi"),
+            FlowStep::new("forward_to_echoer", "echoer", "hi"),
+            FlowStep::new("echoer", "app", "echo: hi"),
+        ]
+    );
+}
+
+#[test]
+fn mermaid_snapshot() {
+    let (topology, flows) = small_topology();
+    assert_eq!(
+        to_mermaid(&topology, &flows),
+        r#"sequenceDiagram
+    box node_initiator
+    participant app as app
+    end
+    box node_responder
+    participant forward_to_echoer as forward_to_echoer
+    participant echoer as echoer
+    end
+    app->>forward_to_echoer: hi
+    forward_to_echoer->>echoer: hi
+    echoer->>app: echo: hi
+"#
+    );
+}
+
+#[test]
+fn dot_snapshot() {
+    let (topology, flows) = small_topology();
+    assert_eq!(
+        to_dot(&topology, &flows),
+        r#"digraph topology {
+    rankdir=LR;
+    node [shape=box, fontname="monospace"];
+    subgraph cluster_0 {
+        label="node_initiator";
+        node_initiator_0 [label="Address:\n'app'"];
+    }
+    subgraph cluster_1 {
+        label="node_responder";
+        node_responder_0 [label="Address:\n'forward_to_echoer'"];
+        node_responder_1 [label="Address:\n'echoer'"];
+        node_responder_0 -> node_responder_1;
+    }
+    node_initiator_0 -> node_responder_0 [label="1: hi", style=dashed, constraint=false];
+    node_responder_0 -> node_responder_1 [label="2: hi", style=dashed, constraint=false];
+    node_responder_1 -> node_initiator_0 [label="3: echo: hi", style=dashed, constraint=false];
+}
+"#
+    );
+}
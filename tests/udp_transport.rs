@@ -0,0 +1,242 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+mod common;
+
+use common::{loopback, send_hello, start_tcp_responder, ECHO_REPLY};
+use hello_ockam::{
+    start_forwarder, Echoer, NodeTransports, RouteHop, RouteResolver, RouteSpec,
+    UdpConnectionOptions, UdpListenerOptions, UdpTransportExtension,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    route, AsyncTryClone, Context, MessageReceiveOptions, Node, Result, TcpTransportExtension,
+};
+use std::time::Duration;
+
+/// Like `start_secure_responder`, w/ a UDP listener on `port` instead of a TCP one.
+async fn start_udp_responder(ctx: &Context, port: u16) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let transports = NodeTransports::create(&node).await?;
+    node.start_worker("echoer", Echoer).await?;
+    let id_bob = node.create_identity().await?;
+    let listener = transports.listen(&RouteHop::Udp(loopback(port))).await?;
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await?;
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("echoer", secure_channel_listener.flow_control_id());
+    Ok(node)
+}
+
+/// Like `start_tcp_middle`, but w/ `forward_to_bob` listening on `listen_hop` and
+/// connecting to `responder_hop`, over either transport.
+async fn start_middle(
+    ctx: &Context,
+    listen_hop: RouteHop,
+    responder_hop: RouteHop,
+) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let transports = NodeTransports::create(&node).await?;
+    let connection_to_responder = transports.connect(&responder_hop).await?;
+    let listener = transports.listen(&listen_hop).await?;
+    start_forwarder(&node, "forward_to_bob", &listener, &connection_to_responder).await?;
+    Ok(node)
+}
+
+/// The number of workers on the router, once it has settled on `expected` (the workers
+/// are stopped in the background).
+async fn settled_worker_count(ctx: &Context, expected: usize) -> Result<usize> {
+    let mut count = ctx.list_workers().await?.len();
+    for _ in 0..20 {
+        if count == expected {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        count = ctx.list_workers().await?.len();
+    }
+    Ok(count)
+}
+
+/// examples/12-udp.rs
+#[ockam::test]
+async fn echoer_replies_over_two_udp_hops_and_a_secure_channel(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_udp_responder(ctx, 4701).await?;
+    let _node_middle = start_middle(
+        ctx,
+        RouteHop::Udp(loopback(3701)),
+        RouteHop::Udp(loopback(4701)),
+    )
+    .await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let udp_transport = node_initiator.create_udp_transport().await?;
+    let resolver = RouteResolver::new(&node_initiator, &tcp_transport)
+        .with_udp_transport(&udp_transport)
+        .with_identity(&id_alice);
+
+    for text in [
+        "udp:127.0.0.1:3701 / forward_to_bob / echoer",
+        "secure(udp:127.0.0.1:3701 / forward_to_bob / bob_listener) / echoer",
+    ] {
+        let spec: RouteSpec = text.parse()?;
+        let resolved = resolver.resolve(&spec).await?;
+        assert_eq!(resolved.udp_connections.len(), 1);
+        assert!(!resolved.flow_control_ids.is_empty());
+        let reply = send_hello(ctx, resolved.route, Duration::from_secs(5)).await?;
+        assert_eq!(reply, ECHO_REPLY, "over {}", text);
+    }
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn udp_hops_need_a_udp_transport(ctx: &mut Context) -> Result<()> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    let spec: RouteSpec = "udp:127.0.0.1:3702 / echoer".parse()?;
+    let error = RouteResolver::new(&node, &tcp_transport)
+        .resolve(&spec)
+        .await
+        .unwrap_err();
+    assert_eq!(error.code().kind, ockam::errcode::Kind::Misuse);
+
+    // The listener isn't needed for the connection, UDP doesn't have a handshake.
+    let udp_transport = node.create_udp_transport().await?;
+    let connection = udp_transport
+        .connect(loopback(3702), UdpConnectionOptions::new())
+        .await?;
+    let result = send_hello(
+        ctx,
+        route![connection, "echoer"],
+        Duration::from_millis(500),
+    )
+    .await;
+    assert!(result.is_err());
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn forwards_from_udp_to_tcp(ctx: &mut Context) -> Result<()> {
+    let _node_responder = start_tcp_responder(ctx, 4703).await?;
+    let _node_middle = start_middle(
+        ctx,
+        RouteHop::Udp(loopback(3703)),
+        RouteHop::Tcp(loopback(4703)),
+    )
+    .await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let transports = NodeTransports::create(&node_initiator).await?;
+    let connection = transports.connect(&RouteHop::Udp(loopback(3703))).await?;
+    let reply = send_hello(
+        ctx,
+        route![&connection, "forward_to_bob", "echoer"],
+        Duration::from_secs(5),
+    )
+    .await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    // Unix socket hops aren't supported by the helpers.
+    let error = transports
+        .listen(&RouteHop::Unix("/tmp/nobody.sock".into()))
+        .await
+        .unwrap_err();
+    assert_eq!(error.code().kind, ockam::errcode::Kind::Unsupported);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn binary_payloads_fit_in_a_datagram(ctx: &mut Context) -> Result<()> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let udp_transport = node.create_udp_transport().await?;
+    let listener = udp_transport
+        .listen(loopback(3704), UdpListenerOptions::new())
+        .await?;
+    node.flow_controls()
+        .add_consumer(ctx.address(), listener.flow_control_id());
+    let connection = udp_transport
+        .connect(loopback(3704), UdpConnectionOptions::new())
+        .await?;
+
+    // Each byte is one byte in the datagram, not a number in a JSON array.
+    let payload = vec![255u8; 30_000];
+    ctx.send(route![connection, ctx.address()], payload.clone())
+        .await?;
+    let received = ctx
+        .receive_extended::<Vec<u8>>(
+            MessageReceiveOptions::new().with_timeout(Duration::from_secs(5)),
+        )
+        .await?;
+    assert_eq!(received.body(), payload);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn listeners_stop_the_senders_of_quiet_peers(ctx: &mut Context) -> Result<()> {
+    let node_responder = ockam::node(ctx.async_try_clone().await?);
+    let udp_transport = node_responder.create_udp_transport().await?;
+    node_responder.start_worker("echoer", Echoer).await?;
+    let listener = udp_transport
+        .listen(
+            loopback(4705),
+            UdpListenerOptions::new()
+                .with_max_peers(1)
+                .with_peer_idle_timeout(Duration::from_millis(500)),
+        )
+        .await?;
+    node_responder
+        .flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let udp_transport = node_initiator.create_udp_transport().await?;
+    let connection_1 = udp_transport
+        .connect(loopback(4705), UdpConnectionOptions::new())
+        .await?;
+    let connection_2 = udp_transport
+        .connect(loopback(4705), UdpConnectionOptions::new())
+        .await?;
+    let workers = ctx.list_workers().await?.len();
+
+    // The listener starts a sender for the replies to the first peer...
+    let reply = send_hello(ctx, route![connection_1, "echoer"], Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+    assert_eq!(settled_worker_count(ctx, workers + 1).await?, workers + 1);
+
+    // ... which is stopped for the second one, since it only keeps one.
+    let reply = send_hello(ctx, route![connection_2, "echoer"], Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+    assert_eq!(settled_worker_count(ctx, workers + 1).await?, workers + 1);
+
+    // And the second one's is stopped once it's quiet for the idle timeout.
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    assert_eq!(settled_worker_count(ctx, workers).await?, workers);
+
+    ctx.stop().await
+}
@@ -0,0 +1,263 @@
+/*
+ *   Copyright (c) 2023 Nazmul Idris
+ *   All rights reserved.
+ *
+ *   Licensed under the Apache License, Version 2.0 (the "License");
+ *   you may not use this file except in compliance with the License.
+ *   You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *   Unless required by applicable law or agreed to in writing, software
+ *   distributed under the License is distributed on an "AS IS" BASIS,
+ *   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *   See the License for the specific language governing permissions and
+ *   limitations under the License.
+ */
+
+#![cfg(unix)]
+
+mod common;
+
+use common::{loopback, send_hello, ECHO_REPLY, NO_REPLY_TIMEOUT};
+use hello_ockam::{
+    Echoer, Forwarder, RouteResolver, RouteSpec, UnixConnectionOptions, UnixListenerOptions,
+    UnixTransportExtension,
+};
+use ockam::identity::SecureChannelListenerOptions;
+use ockam::{
+    route, AsyncTryClone, Context, Node, Result, TcpListenerOptions, TcpTransportExtension,
+};
+use std::collections::BTreeSet;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How many times [socket_file_is_locked_down_from_the_start] starts a listener.
+const LISTEN_TRIES: usize = 50;
+
+/// A socket file that no other test (or test run) uses.
+fn socket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("hello_ockam_{}_{}.sock", name, std::process::id()))
+}
+
+/// Like `start_secure_responder`, w/ a Unix socket listener instead of a TCP one.
+async fn start_unix_responder(
+    ctx: &Context,
+    path: &PathBuf,
+    options: UnixListenerOptions,
+) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let unix_transport = node.create_unix_transport().await?;
+    node.start_worker("echoer", Echoer).await?;
+    let id_bob = node.create_identity().await?;
+    let listener = unix_transport.listen(path, options).await?;
+    let secure_channel_listener = node
+        .create_secure_channel_listener(
+            &id_bob,
+            "bob_listener",
+            SecureChannelListenerOptions::new().as_consumer(listener.flow_control_id()),
+        )
+        .await?;
+    node.flow_controls()
+        .add_consumer("echoer", listener.flow_control_id());
+    node.flow_controls()
+        .add_consumer("echoer", secure_channel_listener.flow_control_id());
+    Ok(node)
+}
+
+/// Like `start_tcp_middle`, but it forwards over a Unix socket.
+async fn start_unix_middle(ctx: &Context, listen_port: u16, path: &PathBuf) -> Result<Node> {
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node.create_tcp_transport().await?;
+    let unix_transport = node.create_unix_transport().await?;
+    let connection_to_responder = unix_transport
+        .connect(path, UnixConnectionOptions::new())
+        .await?;
+    node.start_worker(
+        "forward_to_bob",
+        Forwarder {
+            address: connection_to_responder.into(),
+        },
+    )
+    .await?;
+    let listener = tcp_transport
+        .listen(loopback(listen_port), TcpListenerOptions::new())
+        .await?;
+    node.flow_controls()
+        .add_consumer("forward_to_bob", listener.flow_control_id());
+    Ok(node)
+}
+
+/// examples/13-unix-socket.rs
+#[ockam::test]
+async fn echoer_replies_behind_a_unix_socket(ctx: &mut Context) -> Result<()> {
+    let path = socket_path("forwarder");
+    let _node_responder = start_unix_responder(ctx, &path, UnixListenerOptions::new()).await?;
+    let _node_middle = start_unix_middle(ctx, 3801, &path).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let id_alice = node_initiator.create_identity().await?;
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let resolver = RouteResolver::new(&node_initiator, &tcp_transport).with_identity(&id_alice);
+
+    for text in [
+        "tcp:127.0.0.1:3801 / forward_to_bob / echoer",
+        "secure(tcp:127.0.0.1:3801 / forward_to_bob / bob_listener) / echoer",
+    ] {
+        let spec: RouteSpec = text.parse()?;
+        let resolved = resolver.resolve(&spec).await?;
+        let reply = send_hello(ctx, resolved.route, Duration::from_secs(5)).await?;
+        assert_eq!(reply, ECHO_REPLY, "over {}", text);
+    }
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn resolves_unix_hops(ctx: &mut Context) -> Result<()> {
+    let path = socket_path("resolver");
+    let _node_responder = start_unix_responder(ctx, &path, UnixListenerOptions::new()).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let tcp_transport = node_initiator.create_tcp_transport().await?;
+    let unix_transport = node_initiator.create_unix_transport().await?;
+    let spec: RouteSpec = format!("unix({}) / echoer", path.display()).parse()?;
+
+    let error = RouteResolver::new(&node_initiator, &tcp_transport)
+        .resolve(&spec)
+        .await
+        .unwrap_err();
+    assert_eq!(error.code().kind, ockam::errcode::Kind::Misuse);
+
+    let resolved = RouteResolver::new(&node_initiator, &tcp_transport)
+        .with_unix_transport(&unix_transport)
+        .resolve(&spec)
+        .await?;
+    assert_eq!(resolved.unix_connections.len(), 1);
+    let reply = send_hello(ctx, resolved.route, Duration::from_secs(5)).await?;
+    assert_eq!(reply, ECHO_REPLY);
+
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn socket_file_is_locked_down_and_replaces_a_stale_one(ctx: &mut Context) -> Result<()> {
+    let path = socket_path("permissions");
+    // Left over from a listener that didn't stop cleanly.
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let unix_transport = node.create_unix_transport().await?;
+    let listener = unix_transport
+        .listen(&path, UnixListenerOptions::new())
+        .await?;
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // The socket is still being accepted on, so it can't be replaced.
+    assert!(unix_transport
+        .listen(&path, UnixListenerOptions::new())
+        .await
+        .is_err());
+
+    ctx.stop_processor(listener.processor_address().clone())
+        .await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!path.exists());
+
+    ctx.stop().await
+}
+
+/// The socket must never be accessible w/ the umask's permissions, not even between the
+/// bind & the first accept.
+#[ockam::test]
+async fn socket_file_is_locked_down_from_the_start(ctx: &mut Context) -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("hello_ockam_umask_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir(&dir).unwrap();
+    let path = dir.join("bob.sock");
+
+    // Record every mode that the socket file has while listeners start & stop. The window
+    // between a bind & a chmod is short, so it takes a few tries.
+    let done = Arc::new(AtomicBool::new(false));
+    let watcher = {
+        let (path, done) = (path.clone(), done.clone());
+        std::thread::spawn(move || {
+            let mut modes = BTreeSet::new();
+            while !done.load(Ordering::Relaxed) {
+                if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+                    modes.insert(metadata.permissions().mode() & 0o777);
+                }
+            }
+            modes
+        })
+    };
+
+    let node = ockam::node(ctx.async_try_clone().await?);
+    let unix_transport = node.create_unix_transport().await?;
+    // SAFETY: umask() can't fail, and doesn't touch any memory.
+    let umask = unsafe { libc::umask(0) };
+    let mut result = Ok(());
+    for _ in 0..LISTEN_TRIES {
+        match unix_transport
+            .listen(&path, UnixListenerOptions::new())
+            .await
+        {
+            Ok(listener) => {
+                ctx.stop_processor(listener.processor_address().clone())
+                    .await?;
+                while path.exists() {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+            }
+            Err(error) => {
+                result = Err(error);
+                break;
+            }
+        }
+    }
+    unsafe { libc::umask(umask) };
+    result?;
+    let listener = unix_transport
+        .listen(&path, UnixListenerOptions::new())
+        .await?;
+    done.store(true, Ordering::Relaxed);
+
+    let modes = watcher.join().unwrap();
+    assert!(modes.iter().all(|it| *it == 0o600), "{:?}", modes);
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    // The directory that the socket was bound in is gone.
+    let entries: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|it| it.unwrap().file_name())
+        .collect();
+    assert_eq!(entries, vec!["bob.sock"]);
+
+    ctx.stop_processor(listener.processor_address().clone())
+        .await?;
+    let _ = std::fs::remove_dir_all(&dir);
+    ctx.stop().await
+}
+
+#[ockam::test]
+async fn peers_w_other_uids_are_turned_away(ctx: &mut Context) -> Result<()> {
+    let path = socket_path("uids");
+    // SAFETY: getuid() can't fail, and doesn't touch any memory.
+    let my_uid = unsafe { libc::getuid() };
+    let options = UnixListenerOptions::new().with_allowed_uids([my_uid.wrapping_add(1)]);
+    let _node_responder = start_unix_responder(ctx, &path, options).await?;
+
+    let node_initiator = ockam::node(ctx.async_try_clone().await?);
+    let unix_transport = node_initiator.create_unix_transport().await?;
+    let connection = unix_transport
+        .connect(&path, UnixConnectionOptions::new())
+        .await?;
+    let result = send_hello(ctx, route![connection, "echoer"], NO_REPLY_TIMEOUT).await;
+    assert!(result.is_err());
+
+    ctx.stop().await
+}